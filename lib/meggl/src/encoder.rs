@@ -0,0 +1,480 @@
+//! Image Encoders
+
+use super::*;
+use alloc::{vec, vec::Vec};
+
+/// File formats to save the images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// PNG, 32bit RGBA compressed with the fixed Huffman codes
+    Png,
+    /// The Quite OK Image format, 32bit RGBA
+    Qoi,
+}
+
+impl ImageFormat {
+    /// Returns the format of the file name from the extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, ext) = path.rsplit_once('.')?;
+        if ext.eq_ignore_ascii_case("png") {
+            Some(Self::Png)
+        } else if ext.eq_ignore_ascii_case("qoi") {
+            Some(Self::Qoi)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Qoi => "qoi",
+        }
+    }
+
+    pub fn encode<T>(&self, image: &T) -> Vec<u8>
+    where
+        T: RasterImage<ColorType = TrueColor>,
+    {
+        match self {
+            Self::Png => PngEncoder::encode(image),
+            Self::Qoi => QoiEncoder::encode(image),
+        }
+    }
+}
+
+/// Returns the pixels of the image in RGBA byte order, row by row
+fn rgba_rows<T>(image: &T) -> impl Iterator<Item = impl Iterator<Item = [u8; 4]> + '_> + '_
+where
+    T: RasterImage<ColorType = TrueColor>,
+{
+    let width = image.width();
+    let stride = image.stride();
+    (0..image.height()).map(move |y| {
+        image.slice()[y * stride..y * stride + width]
+            .iter()
+            .map(|pixel| {
+                let [b, g, r, a] = pixel.argb().to_le_bytes();
+                [r, g, b, a]
+            })
+    })
+}
+
+pub struct PngEncoder;
+
+impl PngEncoder {
+    const SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1A\n";
+    const BYTES_PER_PIXEL: usize = 4;
+
+    pub fn encode<T>(image: &T) -> Vec<u8>
+    where
+        T: RasterImage<ColorType = TrueColor>,
+    {
+        let width = image.width();
+        let height = image.height();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8bit depth, RGBA, deflate, adaptive filters, no interlace
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        // Each row begins with the type of the filter that makes the smallest sum of the bytes
+        let row_len = width * Self::BYTES_PER_PIXEL;
+        let mut raw = Vec::with_capacity((row_len + 1) * height);
+        let mut prev = vec![0u8; row_len];
+        let mut line = Vec::with_capacity(row_len);
+        let mut filtered = [(); 5].map(|_| Vec::with_capacity(row_len));
+        for row in rgba_rows(image) {
+            line.clear();
+            row.for_each(|v| line.extend_from_slice(&v));
+            for (kind, out) in filtered.iter_mut().enumerate() {
+                Self::filter(kind as u8, &line, &prev, out);
+            }
+            let (kind, best) = filtered
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, v)| {
+                    v.iter()
+                        .map(|&v| (v as i8).unsigned_abs() as usize)
+                        .sum::<usize>()
+                })
+                .unwrap();
+            raw.push(kind as u8);
+            raw.extend_from_slice(best);
+            core::mem::swap(&mut prev, &mut line);
+        }
+
+        let mut zlib = Vec::with_capacity(raw.len() / 2);
+        // Deflate with the 32K window, no preset dictionary
+        zlib.extend_from_slice(&[0x78, 0x01]);
+        Deflate::compress(&raw, &mut zlib);
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut png = Vec::with_capacity(zlib.len() + 64);
+        png.extend_from_slice(&Self::SIGNATURE);
+        Self::write_chunk(&mut png, b"IHDR", &header);
+        Self::write_chunk(&mut png, b"IDAT", &zlib);
+        Self::write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    fn filter(kind: u8, line: &[u8], prev: &[u8], out: &mut Vec<u8>) {
+        const BPP: usize = PngEncoder::BYTES_PER_PIXEL;
+        out.clear();
+        for (i, &value) in line.iter().enumerate() {
+            let a = if i >= BPP { line[i - BPP] } else { 0 };
+            let b = prev[i];
+            let c = if i >= BPP { prev[i - BPP] } else { 0 };
+            let prediction = match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                _ => Self::paeth(a, b, c),
+            };
+            out.push(value.wrapping_sub(prediction));
+        }
+    }
+
+    #[inline]
+    fn paeth(a: u8, b: u8, c: u8) -> u8 {
+        let p = a as i16 + b as i16 - c as i16;
+        let pa = (p - a as i16).abs();
+        let pb = (p - b as i16).abs();
+        let pc = (p - c as i16).abs();
+        if pa <= pb && pa <= pc {
+            a
+        } else if pb <= pc {
+            b
+        } else {
+            c
+        }
+    }
+
+    fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(chunk_type);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+}
+
+pub struct QoiEncoder;
+
+impl QoiEncoder {
+    const OP_INDEX: u8 = 0x00;
+    const OP_DIFF: u8 = 0x40;
+    const OP_LUMA: u8 = 0x80;
+    const OP_RUN: u8 = 0xC0;
+    const OP_RGB: u8 = 0xFE;
+    const OP_RGBA: u8 = 0xFF;
+    const MAX_RUN: u8 = 62;
+    const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+    pub fn encode<T>(image: &T) -> Vec<u8>
+    where
+        T: RasterImage<ColorType = TrueColor>,
+    {
+        let mut qoi = Vec::with_capacity(22 + image.width() * image.height());
+        qoi.extend_from_slice(b"qoif");
+        qoi.extend_from_slice(&(image.width() as u32).to_be_bytes());
+        qoi.extend_from_slice(&(image.height() as u32).to_be_bytes());
+        // RGBA, sRGB with linear alpha
+        qoi.extend_from_slice(&[4, 0]);
+
+        let mut index = [[0u8; 4]; 64];
+        let mut prev = [0, 0, 0, 0xFF];
+        let mut run = 0;
+        for pixel in rgba_rows(image).flatten() {
+            if pixel == prev {
+                run += 1;
+                if run == Self::MAX_RUN {
+                    qoi.push(Self::OP_RUN | (run - 1));
+                    run = 0;
+                }
+                continue;
+            }
+            if run > 0 {
+                qoi.push(Self::OP_RUN | (run - 1));
+                run = 0;
+            }
+
+            let [r, g, b, a] = pixel;
+            let hash = (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64;
+            if index[hash] == pixel {
+                qoi.push(Self::OP_INDEX | hash as u8);
+            } else if a == prev[3] {
+                let dr = r.wrapping_sub(prev[0]) as i8;
+                let dg = g.wrapping_sub(prev[1]) as i8;
+                let db = b.wrapping_sub(prev[2]) as i8;
+                let dr_dg = dr.wrapping_sub(dg);
+                let db_dg = db.wrapping_sub(dg);
+                if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                    qoi.push(
+                        Self::OP_DIFF
+                            | ((dr + 2) as u8) << 4
+                            | ((dg + 2) as u8) << 2
+                            | (db + 2) as u8,
+                    );
+                } else if (-32..=31).contains(&dg)
+                    && (-8..=7).contains(&dr_dg)
+                    && (-8..=7).contains(&db_dg)
+                {
+                    qoi.push(Self::OP_LUMA | (dg + 32) as u8);
+                    qoi.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    qoi.extend_from_slice(&[Self::OP_RGB, r, g, b]);
+                }
+            } else {
+                qoi.extend_from_slice(&[Self::OP_RGBA, r, g, b, a]);
+            }
+            index[hash] = pixel;
+            prev = pixel;
+        }
+        if run > 0 {
+            qoi.push(Self::OP_RUN | (run - 1));
+        }
+        qoi.extend_from_slice(&Self::END_MARKER);
+        qoi
+    }
+}
+
+/// Deflate compressor with the greedy LZ77 matching and the fixed Huffman codes
+struct Deflate<'a> {
+    out: &'a mut Vec<u8>,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl Deflate<'_> {
+    const LENGTH_BASE: [u16; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u8; 29] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+    ];
+    const DISTANCE_BASE: [u16; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+        2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u8; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12,
+        13, 13,
+    ];
+    const END_OF_BLOCK: u16 = 256;
+
+    /// Compresses the data into a single final block
+    fn compress(data: &[u8], out: &mut Vec<u8>) {
+        let mut deflate = Deflate {
+            out,
+            bits: 0,
+            bit_count: 0,
+        };
+        // BFINAL, BTYPE=01 (fixed Huffman codes)
+        deflate.write_bits(1, 1);
+        deflate.write_bits(1, 2);
+
+        let mut matcher = Matcher::new(data);
+        let mut position = 0;
+        while position < data.len() {
+            match matcher.longest_match(position) {
+                Some((length, distance)) => {
+                    deflate.write_match(length, distance);
+                    for p in position..position + length {
+                        matcher.insert(p);
+                    }
+                    position += length;
+                }
+                None => {
+                    deflate.write_symbol(data[position] as u16);
+                    matcher.insert(position);
+                    position += 1;
+                }
+            }
+        }
+
+        deflate.write_symbol(Self::END_OF_BLOCK);
+        deflate.flush();
+    }
+
+    /// Writes the bits from the least significant bit
+    #[inline]
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Writes the Huffman code from the most significant bit
+    #[inline]
+    fn write_code(&mut self, code: u16, count: u32) {
+        let reversed = (code.reverse_bits() >> (16 - count)) as u32;
+        self.write_bits(reversed, count);
+    }
+
+    /// Writes the literal or the length symbol with the fixed Huffman codes
+    fn write_symbol(&mut self, symbol: u16) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = Self::LENGTH_BASE
+            .iter()
+            .rposition(|&v| v as usize <= length)
+            .unwrap();
+        self.write_symbol(257 + index as u16);
+        self.write_bits(
+            (length - Self::LENGTH_BASE[index] as usize) as u32,
+            Self::LENGTH_EXTRA[index] as u32,
+        );
+
+        let index = Self::DISTANCE_BASE
+            .iter()
+            .rposition(|&v| v as usize <= distance)
+            .unwrap();
+        self.write_code(index as u16, 5);
+        self.write_bits(
+            (distance - Self::DISTANCE_BASE[index] as usize) as u32,
+            Self::DISTANCE_EXTRA[index] as u32,
+        );
+    }
+
+    fn flush(&mut self) {
+        if self.bit_count > 0 {
+            self.out.push(self.bits as u8);
+            self.bits = 0;
+            self.bit_count = 0;
+        }
+    }
+}
+
+/// Finds the previous occurrences of the data with the hash chains
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> Matcher<'a> {
+    const WINDOW_SIZE: usize = 32768;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 258;
+    const HASH_BITS: usize = 15;
+    /// Number of the previous positions to try for each match
+    const MAX_CHAIN: usize = 64;
+    const NIL: usize = usize::MAX;
+
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            head: vec![Self::NIL; 1 << Self::HASH_BITS],
+            prev: vec![Self::NIL; Self::WINDOW_SIZE],
+        }
+    }
+
+    #[inline]
+    fn hash(&self, position: usize) -> usize {
+        let data = &self.data[position..];
+        ((data[0] as usize) << 10 ^ (data[1] as usize) << 5 ^ data[2] as usize)
+            & ((1 << Self::HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, position: usize) {
+        if position + Self::MIN_MATCH <= self.data.len() {
+            let hash = self.hash(position);
+            self.prev[position % Self::WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = position;
+        }
+    }
+
+    /// Returns the length and the distance of the longest match at the position
+    fn longest_match(&self, position: usize) -> Option<(usize, usize)> {
+        if position + Self::MIN_MATCH > self.data.len() {
+            return None;
+        }
+        let max_len = Self::MAX_MATCH.min(self.data.len() - position);
+        let current = &self.data[position..position + max_len];
+        let mut best = None;
+        let mut best_len = Self::MIN_MATCH - 1;
+        let mut candidate = self.head[self.hash(position)];
+        for _ in 0..Self::MAX_CHAIN {
+            // The chain may contain the stale positions, which are verified by the comparison
+            if candidate == Self::NIL
+                || candidate >= position
+                || position - candidate > Self::WINDOW_SIZE
+            {
+                break;
+            }
+            let len = current
+                .iter()
+                .zip(&self.data[candidate..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best_len {
+                best_len = len;
+                best = Some((len, position - candidate));
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate % Self::WINDOW_SIZE];
+        }
+        best
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &v| {
+        CRC32_TABLE[((crc ^ v as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    // The largest number of bytes that does not overflow before the modulo
+    const CHUNK_SIZE: usize = 5552;
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(CHUNK_SIZE) {
+        for &v in chunk {
+            a += v as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
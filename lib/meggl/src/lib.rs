@@ -8,10 +8,12 @@ mod bitmap;
 mod color;
 mod coords;
 mod drawable;
+mod encoder;
 pub use bitmap::*;
 pub use color::*;
 pub use coords::*;
 pub use drawable::*;
+pub use encoder::*;
 
 #[cfg(test)]
 pub mod tests;
@@ -285,3 +285,57 @@ fn one_bit_colors() {
     assert_eq!(canvas.get(6), Monochrome::Zero);
     assert_eq!(canvas.get(7), Monochrome::One);
 }
+
+/// Gradient with the translucent pixels, the runs and the noise
+fn encoder_test_image() -> OwnedBitmap32 {
+    let size = Size::new(97, 61);
+    let mut seed = 0x2545_F491u32;
+    let pixels = (0..size.width() * size.height())
+        .map(|i| {
+            let (x, y) = ((i % size.width()) as u32, (i / size.width()) as u32);
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            ARGB8888(match (x / 16 + y / 16) % 4 {
+                0 => 0xFF00_0000 | x << 16 | y << 8 | (x + y),
+                1 => 0x80FF_FFFF,
+                2 => seed,
+                _ => 0xFF33_3333,
+            })
+        })
+        .collect::<alloc::vec::Vec<_>>();
+    OwnedBitmap32::from_vec(pixels, size)
+}
+
+#[test]
+fn png_encoder() {
+    let image = encoder_test_image();
+    let png = ImageFormat::Png.encode(image.as_ref());
+
+    let (header, pixels) = png_decoder::decode(&png).unwrap();
+    assert_eq!(header.width as usize, image.width());
+    assert_eq!(header.height as usize, image.height());
+    let decoded = OwnedBitmap32::from_vec_rgba(pixels, image.size());
+    assert_eq!(decoded.slice(), image.slice());
+}
+
+#[test]
+fn qoi_encoder() {
+    let image = encoder_test_image();
+    let qoi = ImageFormat::Qoi.encode(image.as_ref());
+
+    let (header, pixels) = rapid_qoi::Qoi::decode_alloc(&qoi).unwrap();
+    assert_eq!(header.width as usize, image.width());
+    assert_eq!(header.height as usize, image.height());
+    let decoded = OwnedBitmap32::from_vec_rgba(pixels, image.size());
+    assert_eq!(decoded.slice(), image.slice());
+}
+
+#[test]
+fn image_format() {
+    assert_eq!(ImageFormat::from_path("a.png"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::from_path("B.QOI"), Some(ImageFormat::Qoi));
+    assert_eq!(ImageFormat::from_path("c.jpg"), None);
+    assert_eq!(ImageFormat::from_path("png"), None);
+    assert_eq!(ImageFormat::Qoi.extension(), "qoi");
+}
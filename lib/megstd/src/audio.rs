@@ -0,0 +1,111 @@
+//! Audio
+//!
+//! Notes are rendered by the oscillators of the system, and PCM samples are streamed
+//! to the audio mixer of the system. Sounds stop when their handles are dropped.
+
+use crate::{io::Result, sys::audio_imp::AudioHandle};
+use core::time::Duration;
+
+/// Oscillator Type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OscType {
+    Sine,
+    Square,
+    /// Pulse wave with the duty cycle between 0.0 and 1.0
+    Pulse(f64),
+    Sawtooth,
+    Triangle,
+}
+
+/// A note with the attack, decay and sustain envelope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub osc_type: OscType,
+    /// Frequency in Hz
+    pub freq: f64,
+    pub gain: f64,
+    pub attack: Duration,
+    pub decay: Duration,
+    /// Level after the decay between 0.0 and 1.0
+    pub sustain: f64,
+}
+
+impl Note {
+    /// Creates a note that sounds at full level until it is stopped
+    #[inline]
+    pub const fn new(osc_type: OscType, freq: f64) -> Self {
+        Self {
+            osc_type,
+            freq,
+            gain: 1.0,
+            attack: Duration::ZERO,
+            decay: Duration::ZERO,
+            sustain: 1.0,
+        }
+    }
+
+    #[inline]
+    pub fn gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    #[inline]
+    pub fn envelope(mut self, attack: Duration, decay: Duration, sustain: f64) -> Self {
+        self.attack = attack;
+        self.decay = decay;
+        self.sustain = sustain;
+        self
+    }
+}
+
+/// Audio context that owns the notes and the streams of the application
+pub struct AudioContext(AudioHandle);
+
+impl AudioContext {
+    #[inline]
+    pub fn new() -> Result<Self> {
+        AudioHandle::new_context().map(Self)
+    }
+
+    /// Starts the note, it sounds until the returned handle is dropped
+    #[inline]
+    pub fn note_on(&self, note: &Note) -> Result<PlayingNote> {
+        self.0
+            .note_on(note)
+            .map(|handle| PlayingNote { _handle: handle })
+    }
+
+    /// Opens a stream of 16bit mono PCM samples at the specified sample rate
+    #[inline]
+    pub fn open_stream(&self, sample_rate: u32) -> Result<PcmStream> {
+        self.0.open_stream(sample_rate).map(PcmStream)
+    }
+}
+
+/// A note that is sounding
+pub struct PlayingNote {
+    _handle: AudioHandle,
+}
+
+impl PlayingNote {
+    /// Stops the note
+    #[inline]
+    pub fn stop(self) {
+        drop(self)
+    }
+}
+
+/// A stream of PCM samples
+pub struct PcmStream(AudioHandle);
+
+impl PcmStream {
+    /// Queues the samples and returns the number of samples queued.
+    ///
+    /// Fewer samples are queued when the queue of the stream is full,
+    /// the rest should be written again later.
+    #[inline]
+    pub fn write(&self, samples: &[i16]) -> Result<usize> {
+        self.0.write(samples)
+    }
+}
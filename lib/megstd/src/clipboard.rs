@@ -0,0 +1,55 @@
+//! Clipboard
+//!
+//! The clipboard is shared by all applications and windows of the system.
+//! Windows receive `ClipboardChanged` messages when its data is replaced.
+
+use crate::{
+    drawing::*,
+    io::Result,
+    sys::{clipboard_imp, megos::clipboard::format},
+    *,
+};
+
+/// Format of the data in the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardFormat {
+    Empty,
+    /// UTF-8 text
+    Text,
+    /// 32bit ARGB bitmap
+    Bitmap,
+}
+
+/// Returns the format of the data in the clipboard
+#[inline]
+pub fn format() -> ClipboardFormat {
+    match clipboard_imp::format() {
+        format::TEXT => ClipboardFormat::Text,
+        format::BITMAP32 => ClipboardFormat::Bitmap,
+        _ => ClipboardFormat::Empty,
+    }
+}
+
+/// Returns the text if the clipboard contains text
+#[inline]
+pub fn get_text() -> Option<String> {
+    clipboard_imp::get_text()
+}
+
+/// Replaces the data of the clipboard with the text
+#[inline]
+pub fn set_text(text: &str) -> Result<()> {
+    clipboard_imp::set_text(text)
+}
+
+/// Returns the bitmap if the clipboard contains a bitmap
+#[inline]
+pub fn get_bitmap() -> Option<OwnedBitmap32> {
+    clipboard_imp::get_bitmap()
+}
+
+/// Replaces the data of the clipboard with the bitmap
+#[inline]
+pub fn set_bitmap(bitmap: &BitmapRef32) -> Result<()> {
+    clipboard_imp::set_bitmap(bitmap)
+}
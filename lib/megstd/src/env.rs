@@ -0,0 +1,126 @@
+// Process environment
+
+use crate::{
+    io::Result,
+    path::*,
+    sys::{env_imp, fs_imp},
+    *,
+};
+use alloc::vec;
+use core::fmt;
+
+/// Returns the arguments that this program was started with.
+pub fn args() -> Args {
+    Args {
+        iter: env_imp::args().into_iter(),
+    }
+}
+
+/// Returns the environment variables of the current process.
+pub fn vars() -> Vars {
+    Vars {
+        iter: env_imp::vars().into_iter(),
+    }
+}
+
+/// Fetches the environment variable `key` from the current process.
+pub fn var<K: AsRef<OsStr>>(key: K) -> core::result::Result<String, VarError> {
+    key.as_ref()
+        .to_str()
+        .and_then(env_imp::getenv)
+        .ok_or(VarError::NotPresent)
+}
+
+/// Sets the environment variable `key` to the value `value` for the current process.
+///
+/// The variable is inherited by the processes started after this call.
+pub fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(key: K, value: V) {
+    if let (Some(key), Some(value)) = (key.as_ref().to_str(), value.as_ref().to_str()) {
+        let _ = env_imp::setenv(key, value);
+    }
+}
+
+/// Removes the environment variable `key` from the current process.
+pub fn remove_var<K: AsRef<OsStr>>(key: K) {
+    if let Some(key) = key.as_ref().to_str() {
+        let _ = env_imp::unsetenv(key);
+    }
+}
+
+/// Returns the current working directory.
+#[inline]
+pub fn current_dir() -> Result<PathBuf> {
+    fs_imp::getcwd()
+}
+
+/// Changes the current working directory to the specified path.
+#[inline]
+pub fn set_current_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_imp::chdir(path.as_ref())
+}
+
+/// An iterator over the arguments of a process
+pub struct Args {
+    iter: vec::IntoIter<String>,
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    #[inline]
+    fn next(&mut self) -> Option<String> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+impl DoubleEndedIterator for Args {
+    #[inline]
+    fn next_back(&mut self) -> Option<String> {
+        self.iter.next_back()
+    }
+}
+
+/// An iterator over the environment variables of a process
+pub struct Vars {
+    iter: vec::IntoIter<(String, String)>,
+}
+
+impl Iterator for Vars {
+    type Item = (String, String);
+
+    #[inline]
+    fn next(&mut self) -> Option<(String, String)> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum VarError {
+    /// The specified environment variable was not present in the current process's environment.
+    NotPresent,
+    /// The specified environment variable was found, but it did not contain valid unicode data.
+    NotUnicode(OsString),
+}
+
+impl fmt::Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            VarError::NotPresent => write!(f, "environment variable not found"),
+            VarError::NotUnicode(ref s) => {
+                write!(f, "environment variable was not valid unicode: {:?}", s)
+            }
+        }
+    }
+}
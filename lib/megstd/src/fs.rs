@@ -2,15 +2,13 @@
 // Most of them are clones of Rust's original definition.
 
 use crate::{
-    io::{Read, Result, Write},
+    io::{ErrorKind, Read, Result, Write},
     path::*,
     sys::fs_imp,
     *,
 };
 
-pub struct File {
-    _phantom: (),
-}
+pub struct File(fs_imp::File);
 
 impl File {
     pub fn create<P: AsRef<Path>>(path: P) -> Result<File> {
@@ -25,6 +23,11 @@ impl File {
         OpenOptions::new().read(true).open(path.as_ref())
     }
 
+    #[inline]
+    pub fn metadata(&self) -> Result<Metadata> {
+        self.0.metadata().map(Metadata)
+    }
+
     pub fn sync_all(&self) -> Result<()> {
         todo!()
     }
@@ -33,8 +36,9 @@ impl File {
         todo!()
     }
 
-    pub fn set_len(&self, _size: u64) -> Result<()> {
-        todo!()
+    #[inline]
+    pub fn set_len(&self, size: u64) -> Result<()> {
+        self.0.set_len(size)
     }
 
     pub fn try_clone(&self) -> Result<File> {
@@ -47,22 +51,40 @@ impl File {
 }
 
 impl Read for File {
-    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-        todo!()
-    }
-
-    fn read_to_end(&mut self, _buf: &mut alloc::vec::Vec<u8>) -> Result<usize> {
-        todo!()
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.0.read(buf)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        const BUFFER_SIZE: usize = 0x1000;
+        let mut buffer = [0; BUFFER_SIZE];
+        let mut count_read = 0;
+        loop {
+            match self.read(&mut buffer) {
+                Ok(0) => return Ok(count_read),
+                Ok(len) => {
+                    buf.extend_from_slice(&buffer[..len]);
+                    count_read += len;
+                }
+                Err(err) => match err.kind() {
+                    ErrorKind::Interrupted => (),
+                    _ => return Err(err),
+                },
+            }
+        }
     }
 }
 
 impl Write for File {
-    fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-        todo!()
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write(buf)
     }
 
+    #[inline]
     fn flush(&mut self) -> Result<()> {
-        todo!()
+        self.0.flush()
     }
 }
 
@@ -112,17 +134,97 @@ impl OpenOptions {
     }
 
     #[inline]
-    pub fn open<P: AsRef<Path>>(&self, _path: P) -> Result<File> {
-        todo!()
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<File> {
+        fs_imp::File::open(path, self.0).map(File)
     }
 }
 
-pub fn read_dir<P: AsRef<Path>>(_path: P) -> Result<ReadDir> {
-    todo!()
+impl Default for OpenOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub fn canonicalize<P: AsRef<Path>>(_path: P) -> Result<PathBuf> {
-    todo!()
+#[inline]
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir> {
+    fs_imp::read_dir(path.as_ref()).map(ReadDir)
+}
+
+#[inline]
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    fs_imp::canonicalize(path.as_ref())
+}
+
+#[inline]
+pub fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    fs_imp::stat(path.as_ref()).map(Metadata)
+}
+
+#[inline]
+pub fn create_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_imp::mkdir(path.as_ref())
+}
+
+/// Recursively create a directory and all of its parent components if they are missing.
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref().to_str().ok_or(ErrorKind::InvalidInput)?;
+    let mut prefixes = path
+        .match_indices('/')
+        .map(|(index, _)| &path[..index])
+        .collect::<Vec<_>>();
+    prefixes.push(path);
+    for prefix in prefixes {
+        if prefix.is_empty() || prefix.ends_with('/') {
+            continue;
+        }
+        match metadata(prefix) {
+            Ok(metadata) => {
+                if !metadata.is_dir() {
+                    return Err(ErrorKind::NotADirectory.into());
+                }
+            }
+            Err(_) => create_dir(prefix)?,
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_imp::rmdir(path.as_ref())
+}
+
+#[inline]
+pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_imp::unlink(path.as_ref())
+}
+
+#[inline]
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    fs_imp::rename(from.as_ref(), to.as_ref())
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let mut vec = Vec::new();
+    File::open(path)?.read_to_end(&mut vec)?;
+    Ok(vec)
+}
+
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    String::from_utf8(read(path)?).map_err(|_| ErrorKind::InvalidData.into())
+}
+
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let mut file = File::create(path)?;
+    let mut contents = contents.as_ref();
+    while !contents.is_empty() {
+        match file.write(contents)? {
+            0 => return Err(ErrorKind::WriteZero.into()),
+            len => contents = &contents[len..],
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone)]
@@ -1 +1,139 @@
-// TODO:
+//! Game Input
+//!
+//! Game controllers connected to the system and the input simulated by the keyboard
+//! can be polled without waiting for key events.
+
+use crate::{
+    io::Result,
+    sys::{
+        game_imp,
+        megos::game::{event, OsGameState, CURRENT_INPUT, SIMULATED_INPUT},
+    },
+    *,
+};
+
+/// Identifier of a game input
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameInputId(u32);
+
+impl GameInputId {
+    /// The input simulated by the keyboard, which is always connected
+    pub const SIMULATED: Self = Self(SIMULATED_INPUT);
+
+    #[inline]
+    pub const fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Buttons pressed on a game input
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GameButtons(u16);
+
+impl GameButtons {
+    pub const DPAD_UP: Self = Self(1 << 0);
+    pub const DPAD_DOWN: Self = Self(1 << 1);
+    pub const DPAD_LEFT: Self = Self(1 << 2);
+    pub const DPAD_RIGHT: Self = Self(1 << 3);
+    pub const START: Self = Self(1 << 4);
+    pub const SELECT: Self = Self(1 << 5);
+    pub const THUMB_L: Self = Self(1 << 6);
+    pub const THUMB_R: Self = Self(1 << 7);
+    pub const L: Self = Self(1 << 8);
+    pub const R: Self = Self(1 << 9);
+    pub const MENU: Self = Self(1 << 10);
+    pub const A: Self = Self(1 << 12);
+    pub const B: Self = Self(1 << 13);
+    pub const X: Self = Self(1 << 14);
+    pub const Y: Self = Self(1 << 15);
+
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    #[inline]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    #[inline]
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub const fn contains(&self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+}
+
+/// Snapshot of the buttons and the axes of a game input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GameInputState {
+    pub buttons: GameButtons,
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    pub left_stick: (i16, i16),
+    pub right_stick: (i16, i16),
+}
+
+impl From<OsGameState> for GameInputState {
+    #[inline]
+    fn from(val: OsGameState) -> Self {
+        Self {
+            buttons: GameButtons(val.buttons),
+            left_trigger: val.lt,
+            right_trigger: val.rt,
+            left_stick: (val.x1, val.y1),
+            right_stick: (val.x2, val.y2),
+        }
+    }
+}
+
+/// Connection events of game inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameInputEvent {
+    Connected(GameInputId),
+    Disconnected(GameInputId),
+}
+
+/// Returns the ids of the connected game inputs, including the simulated input
+#[inline]
+pub fn inputs() -> Result<Vec<GameInputId>> {
+    game_imp::inputs().map(|v| v.into_iter().map(GameInputId).collect())
+}
+
+/// Returns the state of the specified game input
+#[inline]
+pub fn state(id: GameInputId) -> Result<GameInputState> {
+    game_imp::state(id.0).map(Into::into)
+}
+
+/// Returns the state of the game input currently in use
+///
+/// The most recently connected controller is used, or the simulated input if there are none.
+#[inline]
+pub fn current() -> Result<GameInputState> {
+    game_imp::state(CURRENT_INPUT).map(Into::into)
+}
+
+/// Returns the next connection event, or `None` if nothing has changed
+pub fn poll_event() -> Option<GameInputEvent> {
+    loop {
+        let event = game_imp::poll_event()?;
+        let id = GameInputId(event.id);
+        match event.kind {
+            event::CONNECTED => return Some(GameInputEvent::Connected(id)),
+            event::DISCONNECTED => return Some(GameInputEvent::Disconnected(id)),
+            _ => (),
+        }
+    }
+}
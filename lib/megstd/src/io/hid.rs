@@ -353,7 +353,15 @@ impl Usage {
     pub const KEY_F10: Self = Self(0x43);
     pub const KEY_F11: Self = Self(0x44);
     pub const KEY_F12: Self = Self(0x45);
+    pub const KEY_PRINT_SCREEN: Self = Self(0x46);
+    pub const KEY_SCROLL_LOCK: Self = Self(0x47);
+    pub const KEY_PAUSE: Self = Self(0x48);
+    pub const KEY_INSERT: Self = Self(0x49);
+    pub const KEY_HOME: Self = Self(0x4A);
+    pub const KEY_PAGE_UP: Self = Self(0x4B);
     pub const DELETE: Self = Self(0x4C);
+    pub const KEY_END: Self = Self(0x4D);
+    pub const KEY_PAGE_DOWN: Self = Self(0x4E);
     pub const KEY_RIGHT_ARROW: Self = Self(0x4F);
     pub const KEY_LEFT_ARROW: Self = Self(0x50);
     pub const KEY_DOWN_ARROW: Self = Self(0x51);
@@ -425,7 +433,7 @@ impl From<Usage> for HidUsage {
 }
 
 /// Modifier keys as defined by the HID specification.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Modifier(pub u8);
 
 impl Modifier {
@@ -536,7 +544,7 @@ impl<T: Into<isize> + Copy> MouseReport<T> {
 }
 
 /// Mouse buttons as defined by the HID specification.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MouseButton(pub u8);
 
 impl MouseButton {
@@ -1,8 +1,10 @@
 pub mod hid;
 
 mod error;
+mod stdio;
 use alloc::vec::Vec;
 pub use error::*;
+pub use stdio::*;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -21,5 +23,14 @@ pub trait Write {
 
     fn flush(&mut self) -> Result<()>;
 
-    //fn write_all(&mut self, buf: &[u8]) -> Result<()>
+    /// Writes the entire buffer, retrying the partial writes
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                len => buf = &buf[len..],
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,112 @@
+//! Standard streams of the process
+//!
+//! The shell connects the standard streams to the terminal, files or pipes.
+
+use super::{Read, Result, Write};
+use crate::{
+    sys::{
+        megos::fs::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO},
+        stdio_imp,
+    },
+    *,
+};
+
+/// Standard input of the process
+pub struct Stdin(());
+
+/// Standard output of the process
+pub struct Stdout(());
+
+/// Standard error of the process
+pub struct Stderr(());
+
+/// Returns the standard input of the process
+#[inline]
+pub const fn stdin() -> Stdin {
+    Stdin(())
+}
+
+/// Returns the standard output of the process
+#[inline]
+pub const fn stdout() -> Stdout {
+    Stdout(())
+}
+
+/// Returns the standard error of the process
+#[inline]
+pub const fn stderr() -> Stderr {
+    Stderr(())
+}
+
+impl Stdin {
+    /// Reads a line including the line feed, returns an empty string at the end of the stream.
+    pub fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0; 1];
+        while self.read(&mut byte)? > 0 {
+            line.push(byte[0]);
+            if byte[0] == b'\n' {
+                break;
+            }
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+impl Read for Stdin {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        stdio_imp::read(STDIN_FILENO, buf)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut chunk = [0; 256];
+        let mut total = 0;
+        loop {
+            let len = self.read(&mut chunk)?;
+            if len == 0 {
+                return Ok(total);
+            }
+            buf.extend_from_slice(&chunk[..len]);
+            total += len;
+        }
+    }
+}
+
+impl Write for Stdout {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        stdio_imp::write(STDOUT_FILENO, buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl core::fmt::Write for Stdout {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+impl Write for Stderr {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        stdio_imp::write(STDERR_FILENO, buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl core::fmt::Write for Stderr {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
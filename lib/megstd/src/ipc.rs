@@ -0,0 +1,46 @@
+//! Inter-process communication
+//!
+//! Processes exchange byte messages through named channels managed by the system.
+//! The process that creates a channel owns it, other processes connect to it by name.
+//! The channel is closed when its owner drops it or exits.
+
+use crate::{io::Result, sys::ipc_imp::ChannelHandle, *};
+
+pub use crate::sys::megos::channel::MAX_MESSAGE_SIZE;
+
+/// A named channel of byte messages
+pub struct Channel(ChannelHandle);
+
+impl Channel {
+    /// Creates a new channel that can hold the specified number of messages.
+    ///
+    /// Fails with `AlreadyExists` if another channel has the same name.
+    #[inline]
+    pub fn create(name: &str, capacity: usize) -> Result<Self> {
+        ChannelHandle::create(name, capacity).map(Self)
+    }
+
+    /// Connects to the channel with the specified name
+    #[inline]
+    pub fn connect(name: &str) -> Result<Self> {
+        ChannelHandle::connect(name).map(Self)
+    }
+
+    /// Sends the message, fails with `WouldBlock` if the channel is full
+    #[inline]
+    pub fn send(&self, message: &[u8]) -> Result<()> {
+        self.0.send(message)
+    }
+
+    /// Waits for the next message, fails with `BrokenPipe` if the channel is closed
+    #[inline]
+    pub fn recv(&self) -> Result<Vec<u8>> {
+        self.0.recv(true)
+    }
+
+    /// Receives the next message without waiting, fails with `WouldBlock` if there are none
+    #[inline]
+    pub fn try_recv(&self) -> Result<Vec<u8>> {
+        self.0.recv(false)
+    }
+}
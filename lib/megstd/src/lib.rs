@@ -7,14 +7,21 @@
 pub mod sys;
 
 pub use meggl as drawing;
+pub mod audio;
+pub mod clipboard;
+pub mod env;
 pub mod error;
 pub mod fs;
+#[cfg(feature = "game")]
 pub mod game;
 pub mod io;
+pub mod ipc;
 pub mod mem;
 pub mod osstr;
 pub mod path;
+pub mod process;
 pub mod rand;
+pub mod regex;
 pub mod string;
 pub mod time;
 pub mod uuid;
@@ -1,3 +1,66 @@
 // mem
 
 pub mod dispose;
+
+use crate::{
+    io::Result,
+    sys::{megos::heap::OsHeapStats, mem_imp},
+};
+
+/// Statistics of the heap of the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeapStats {
+    /// Size of the linear memory
+    pub memory_size: usize,
+    /// Maximum size of the linear memory
+    pub memory_limit: usize,
+    /// Size of the memory taken by the heap
+    pub heap_size: usize,
+    /// Size of the allocated blocks
+    pub used: usize,
+    /// Number of the allocated blocks
+    pub n_allocs: usize,
+    /// Number of the free blocks
+    pub n_free_blocks: usize,
+    /// Size of the largest free block
+    pub largest_free: usize,
+}
+
+impl HeapStats {
+    /// Size of the free blocks
+    #[inline]
+    pub const fn free(&self) -> usize {
+        self.heap_size - self.used
+    }
+
+    /// Fragmentation of the free memory in per mille,
+    /// 0 if all free memory is in a single block
+    #[inline]
+    pub const fn fragmentation(&self) -> usize {
+        match (self.largest_free * 1000).checked_div(self.free()) {
+            Some(v) => 1000 - v,
+            None => 0,
+        }
+    }
+}
+
+impl From<OsHeapStats> for HeapStats {
+    #[inline]
+    fn from(val: OsHeapStats) -> Self {
+        Self {
+            memory_size: val.memory_size as usize,
+            memory_limit: val.memory_limit as usize,
+            heap_size: val.heap_size as usize,
+            used: val.used as usize,
+            n_allocs: val.n_allocs as usize,
+            n_free_blocks: val.n_free_blocks as usize,
+            largest_free: val.largest_free as usize,
+        }
+    }
+}
+
+/// Returns the statistics of the heap of the application
+#[inline]
+pub fn heap_stats() -> Result<HeapStats> {
+    mem_imp::heap_stats().map(Into::into)
+}
@@ -150,6 +150,20 @@ impl AsRef<Path> for OsStr {
     }
 }
 
+impl AsRef<Path> for str {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
 #[repr(transparent)]
 #[derive(Clone)]
 pub struct PathBuf {
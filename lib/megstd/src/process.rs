@@ -0,0 +1,11 @@
+// Processes
+
+use crate::sys::env_imp;
+
+/// Terminates the current process with the specified exit code.
+///
+/// The exit code is passed to the parent process, such as the shell.
+#[inline]
+pub fn exit(code: i32) -> ! {
+    env_imp::exit(code)
+}
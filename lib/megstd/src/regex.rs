@@ -0,0 +1,595 @@
+//! Simple regular expressions
+//!
+//! Supports the POSIX extended syntax: `.`, `[...]`, `[^...]`, `^`, `$`,
+//! `*`, `+`, `?`, `{m,n}`, `(...)` and `|`, and the `\d`, `\w` and `\s` classes.
+//!
+//! The pattern is compiled into a Thompson NFA and simulated without backtracking,
+//! so the time to match is proportional to the length of the text and the pattern.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{iter::Peekable, str::Chars};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegexError {
+    /// `(` or `)` is not closed
+    UnmatchedParen,
+    /// `[` is not closed
+    UnmatchedBracket,
+    /// `*`, `+`, `?` or `{` has nothing to repeat
+    NothingToRepeat,
+    /// The range of the class or the repetition is reversed
+    InvalidRange,
+    /// The pattern ends with `\`
+    TrailingBackslash,
+    /// The compiled pattern exceeds the limit, usually because of a large repetition count
+    TooLarge,
+}
+
+pub type Result<T> = core::result::Result<T, RegexError>;
+
+/// Compiled regular expression
+pub struct Regex {
+    prog: Vec<Inst>,
+    ignore_case: bool,
+}
+
+enum Node {
+    Char(char),
+    Any,
+    Class(CharClass),
+    Start,
+    End,
+    /// Alternatives of the sequences
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// Instruction of the compiled program
+enum Inst {
+    Char(char),
+    Any,
+    Class(CharClass),
+    Start,
+    End,
+    /// Continues at both, the first one has priority
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Clone)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negate: bool,
+}
+
+impl Regex {
+    /// Maximum number of the instructions of the compiled program
+    const MAX_PROG_LEN: usize = 0x10000;
+
+    pub fn new(pattern: &str) -> Result<Self> {
+        let mut iter = pattern.chars().peekable();
+        let nodes = Self::parse_alternatives(&mut iter, 0)?;
+        if iter.next().is_some() {
+            return Err(RegexError::UnmatchedParen);
+        }
+        Self::compile(&nodes)
+    }
+
+    /// Creates a regular expression that matches the whole string with the wildcard pattern,
+    /// `*`, `?` and `[...]` (or `[!...]`)
+    pub fn from_glob(pattern: &str) -> Result<Self> {
+        let mut iter = pattern.chars().peekable();
+        let mut nodes = Vec::new();
+        nodes.push(Node::Start);
+        while let Some(ch) = iter.next() {
+            nodes.push(match ch {
+                '*' => Node::Repeat {
+                    node: Box::new(Node::Any),
+                    min: 0,
+                    max: None,
+                },
+                '?' => Node::Any,
+                '[' => Node::Class(CharClass::parse(&mut iter, '!')?),
+                '\\' => Node::Char(iter.next().ok_or(RegexError::TrailingBackslash)?),
+                _ => Node::Char(ch),
+            });
+        }
+        nodes.push(Node::End);
+        Self::compile(&nodes)
+    }
+
+    /// Ignores the case of the letters when matching
+    #[inline]
+    pub fn ignore_case(mut self, value: bool) -> Self {
+        self.ignore_case = value;
+        self
+    }
+
+    #[inline]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// Returns the byte range of the leftmost match
+    ///
+    /// Of the matches at the same position, the one that a backtracking matcher would find first
+    /// is chosen, that is, repetitions are greedy and alternatives are tried from the left.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let is_anchored = matches!(self.prog.first(), Some(Inst::Start));
+
+        // Threads are the pairs of the program counter and the start position, in priority order
+        let mut threads = Vec::new();
+        let mut next_threads = Vec::new();
+        let mut stack = Vec::new();
+        let mut visited = Vec::new();
+        visited.resize(self.prog.len(), usize::MAX);
+        let mut result = None;
+
+        for pos in 0..=chars.len() {
+            // A new thread starting here has the lowest priority
+            if result.is_none() && (pos == 0 || !is_anchored) {
+                self.add_thread(&mut threads, &mut stack, &mut visited, 0, pos, pos, &chars);
+            }
+            if threads.is_empty() && (result.is_some() || is_anchored) {
+                break;
+            }
+            for &(pc, start) in threads.iter() {
+                match &self.prog[pc] {
+                    Inst::Match => {
+                        // Threads with lower priority are no longer needed
+                        result = Some((start, pos));
+                        break;
+                    }
+                    inst => {
+                        if chars.get(pos).is_some_and(|&ch| self.match_char(inst, ch)) {
+                            self.add_thread(
+                                &mut next_threads,
+                                &mut stack,
+                                &mut visited,
+                                pc + 1,
+                                start,
+                                pos + 1,
+                                &chars,
+                            );
+                        }
+                    }
+                }
+            }
+            threads.clear();
+            core::mem::swap(&mut threads, &mut next_threads);
+        }
+
+        result.map(|(start, end)| {
+            let offset = |pos: usize| chars[..pos].iter().map(|v| v.len_utf8()).sum();
+            (offset(start), offset(end))
+        })
+    }
+
+    /// Follows the instructions that do not consume a character, and adds the threads that wait
+    /// for the next character or the match to the list in priority order
+    #[allow(clippy::too_many_arguments)]
+    fn add_thread(
+        &self,
+        threads: &mut Vec<(usize, usize)>,
+        stack: &mut Vec<usize>,
+        visited: &mut [usize],
+        pc: usize,
+        start: usize,
+        pos: usize,
+        chars: &[char],
+    ) {
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            // Each instruction is visited once per position, which also breaks empty loops
+            if visited[pc] == pos {
+                continue;
+            }
+            visited[pc] = pos;
+            match self.prog[pc] {
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Jump(next) => stack.push(next),
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::End => {
+                    if pos == chars.len() {
+                        stack.push(pc + 1);
+                    }
+                }
+                _ => threads.push((pc, start)),
+            }
+        }
+    }
+
+    fn match_char(&self, inst: &Inst, ch: char) -> bool {
+        let test = |ch: char| match inst {
+            Inst::Char(expected) => *expected == ch,
+            Inst::Any => true,
+            Inst::Class(class) => class.contains(ch),
+            _ => false,
+        };
+        if self.ignore_case {
+            test(ch) || ch.to_lowercase().any(&test) || ch.to_uppercase().any(&test)
+        } else {
+            test(ch)
+        }
+    }
+
+    fn compile(nodes: &[Node]) -> Result<Self> {
+        let mut prog = Vec::new();
+        Self::compile_sequence(nodes, &mut prog)?;
+        prog.push(Inst::Match);
+        Ok(Self {
+            prog,
+            ignore_case: false,
+        })
+    }
+
+    fn compile_sequence(nodes: &[Node], prog: &mut Vec<Inst>) -> Result<()> {
+        for node in nodes {
+            Self::compile_node(node, prog)?;
+        }
+        Ok(())
+    }
+
+    fn compile_node(node: &Node, prog: &mut Vec<Inst>) -> Result<()> {
+        if prog.len() >= Self::MAX_PROG_LEN {
+            return Err(RegexError::TooLarge);
+        }
+        match node {
+            Node::Char(ch) => prog.push(Inst::Char(*ch)),
+            Node::Any => prog.push(Inst::Any),
+            Node::Class(class) => prog.push(Inst::Class(class.clone())),
+            Node::Start => prog.push(Inst::Start),
+            Node::End => prog.push(Inst::End),
+            Node::Group(alternatives) => {
+                let Some((last, alternatives)) = alternatives.split_last() else {
+                    return Ok(());
+                };
+                let mut jumps = Vec::new();
+                for nodes in alternatives {
+                    let split = prog.len();
+                    prog.push(Inst::Split(split + 1, 0));
+                    Self::compile_sequence(nodes, prog)?;
+                    jumps.push(prog.len());
+                    prog.push(Inst::Jump(0));
+                    prog[split] = Inst::Split(split + 1, prog.len());
+                }
+                Self::compile_sequence(last, prog)?;
+                let end = prog.len();
+                for jump in jumps {
+                    prog[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    Self::compile_node(node, prog)?;
+                }
+                match max {
+                    None => {
+                        let split = prog.len();
+                        prog.push(Inst::Split(split + 1, 0));
+                        Self::compile_node(node, prog)?;
+                        prog.push(Inst::Jump(split));
+                        prog[split] = Inst::Split(split + 1, prog.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(prog.len());
+                            prog.push(Inst::Split(prog.len() + 1, 0));
+                            Self::compile_node(node, prog)?;
+                        }
+                        let end = prog.len();
+                        for split in splits {
+                            prog[split] = Inst::Split(split + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_alternatives(iter: &mut Peekable<Chars>, depth: usize) -> Result<Vec<Node>> {
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(Self::parse_sequence(iter, depth)?);
+            if iter.next_if_eq(&'|').is_none() {
+                break;
+            }
+        }
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Vec::from([Node::Group(alternatives)]))
+        }
+    }
+
+    fn parse_sequence(iter: &mut Peekable<Chars>, depth: usize) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        while let Some(&ch) = iter.peek() {
+            if ch == '|' || (ch == ')' && depth > 0) {
+                break;
+            }
+            iter.next();
+            let node = match ch {
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '[' => Node::Class(CharClass::parse(iter, '^')?),
+                '(' => {
+                    let nodes = Self::parse_alternatives(iter, depth + 1)?;
+                    if iter.next() != Some(')') {
+                        return Err(RegexError::UnmatchedParen);
+                    }
+                    Node::Group(Vec::from([nodes]))
+                }
+                ')' => return Err(RegexError::UnmatchedParen),
+                '*' | '+' | '?' => return Err(RegexError::NothingToRepeat),
+                '\\' => Self::parse_escape(iter)?,
+                _ => Node::Char(ch),
+            };
+            nodes.push(node);
+
+            while let Some((min, max)) = Self::parse_repeat(iter)? {
+                let node = nodes.pop().unwrap();
+                if matches!(node, Node::Start | Node::End) {
+                    return Err(RegexError::NothingToRepeat);
+                }
+                nodes.push(Node::Repeat {
+                    node: Box::new(node),
+                    min,
+                    max,
+                });
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn parse_escape(iter: &mut Peekable<Chars>) -> Result<Node> {
+        let ch = iter.next().ok_or(RegexError::TrailingBackslash)?;
+        Ok(match CharClass::from_escape(ch) {
+            Some(class) => Node::Class(class),
+            None => Node::Char(match ch {
+                'n' => '\n',
+                't' => '\t',
+                _ => ch,
+            }),
+        })
+    }
+
+    /// Parses the quantifier, `{` is a literal if it is not a valid repetition
+    fn parse_repeat(iter: &mut Peekable<Chars>) -> Result<Option<(usize, Option<usize>)>> {
+        match iter.peek() {
+            Some('*') => {
+                iter.next();
+                Ok(Some((0, None)))
+            }
+            Some('+') => {
+                iter.next();
+                Ok(Some((1, None)))
+            }
+            Some('?') => {
+                iter.next();
+                Ok(Some((0, Some(1))))
+            }
+            Some('{') => {
+                let mut lookahead = iter.clone();
+                lookahead.next();
+                let Some(min) = Self::parse_number(&mut lookahead) else {
+                    return Ok(None);
+                };
+                let max = if lookahead.next_if_eq(&',').is_some() {
+                    Self::parse_number(&mut lookahead)
+                } else {
+                    Some(min)
+                };
+                if lookahead.next() != Some('}') {
+                    return Ok(None);
+                }
+                if max.is_some_and(|max| max < min) {
+                    return Err(RegexError::InvalidRange);
+                }
+                *iter = lookahead;
+                Ok(Some((min, max)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_number(iter: &mut Peekable<Chars>) -> Option<usize> {
+        let mut value = None;
+        while let Some(digit) = iter.peek().and_then(|v| v.to_digit(10)) {
+            iter.next();
+            value = Some(value.unwrap_or(0usize).saturating_mul(10) + digit as usize);
+        }
+        value
+    }
+}
+
+impl CharClass {
+    /// Parses the bracket expression after `[`
+    fn parse(iter: &mut Peekable<Chars>, negate_char: char) -> Result<Self> {
+        let negate = iter.next_if_eq(&negate_char).is_some();
+        let mut ranges = Vec::new();
+        let mut is_first = true;
+        loop {
+            let ch = iter.next().ok_or(RegexError::UnmatchedBracket)?;
+            let start = match ch {
+                ']' if !is_first => break,
+                '\\' => {
+                    let ch = iter.next().ok_or(RegexError::TrailingBackslash)?;
+                    if let Some(class) = Self::from_escape(ch).filter(|v| !v.negate) {
+                        ranges.extend(class.ranges);
+                        is_first = false;
+                        continue;
+                    }
+                    ch
+                }
+                _ => ch,
+            };
+            is_first = false;
+
+            let mut lookahead = iter.clone();
+            if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|&v| v != ']') {
+                iter.next();
+                let end = match iter.next().ok_or(RegexError::UnmatchedBracket)? {
+                    '\\' => iter.next().ok_or(RegexError::TrailingBackslash)?,
+                    ch => ch,
+                };
+                if end < start {
+                    return Err(RegexError::InvalidRange);
+                }
+                ranges.push((start, end));
+            } else {
+                ranges.push((start, start));
+            }
+        }
+        Ok(Self { ranges, negate })
+    }
+
+    fn from_escape(ch: char) -> Option<Self> {
+        let (ranges, negate): (&[(char, char)], bool) = match ch {
+            'd' => (&[('0', '9')], false),
+            'D' => (&[('0', '9')], true),
+            'w' => (&[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')], false),
+            'W' => (&[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')], true),
+            's' => (&[('\t', '\r'), (' ', ' ')], false),
+            'S' => (&[('\t', '\r'), (' ', ' ')], true),
+            _ => return None,
+        };
+        Some(Self {
+            ranges: ranges.to_vec(),
+            negate,
+        })
+    }
+
+    #[inline]
+    fn contains(&self, ch: char) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&ch))
+            != self.negate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find(text)
+    }
+
+    #[test]
+    fn literal() {
+        assert_eq!(find("abc", "xxabcxx"), Some((2, 5)));
+        assert_eq!(find("abc", "xxabxx"), None);
+        assert_eq!(find("", "abc"), Some((0, 0)));
+        assert_eq!(find("a.c", "abc"), Some((0, 3)));
+        assert_eq!(find("\\.", "abc.d"), Some((3, 4)));
+        assert_eq!(find("é", "caféx"), Some((3, 5)));
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(find("^abc", "abcabc"), Some((0, 3)));
+        assert_eq!(find("^abc", "xabc"), None);
+        assert_eq!(find("abc$", "abcabc"), Some((3, 6)));
+        assert_eq!(find("abc$", "abcx"), None);
+        assert_eq!(find("^$", ""), Some((0, 0)));
+        assert_eq!(find("^$", "a"), None);
+        assert_eq!(find("$", "abc"), Some((3, 3)));
+        assert_eq!(find("a|^b", "cb"), None);
+        assert_eq!(find("a|^b", "bc"), Some((0, 1)));
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(find("[a-c]+", "xxbcay"), Some((2, 5)));
+        assert_eq!(find("[^a-c]+", "abxyc"), Some((2, 4)));
+        assert_eq!(find("[]a]+", "x]a]"), Some((1, 4)));
+        assert_eq!(find("[a-]+", "x-a-"), Some((1, 4)));
+        assert_eq!(find("\\d+", "abc123def"), Some((3, 6)));
+        assert_eq!(find("\\w+", "  foo_1 "), Some((2, 7)));
+        assert_eq!(find("\\s", "ab c"), Some((2, 3)));
+        assert_eq!(find("[\\d.]+", "v1.25;"), Some((1, 5)));
+        assert_eq!(Regex::new("[abc").err(), Some(RegexError::UnmatchedBracket));
+        assert_eq!(Regex::new("[z-a]").err(), Some(RegexError::InvalidRange));
+    }
+
+    #[test]
+    fn alternation() {
+        assert_eq!(find("cat|dog", "hotdog"), Some((3, 6)));
+        assert_eq!(find("a|ab", "ab"), Some((0, 1)));
+        assert_eq!(find("ab|a", "ab"), Some((0, 2)));
+        assert_eq!(find("x(a|b)y", "xby"), Some((0, 3)));
+        assert_eq!(find("(a|b|)c", "c"), Some((0, 1)));
+        assert_eq!(Regex::new("(ab").err(), Some(RegexError::UnmatchedParen));
+        assert_eq!(Regex::new("ab)").err(), Some(RegexError::UnmatchedParen));
+    }
+
+    #[test]
+    fn repetition() {
+        assert_eq!(find("ab*", "abbbc"), Some((0, 4)));
+        assert_eq!(find("ab+", "ac"), None);
+        assert_eq!(find("ab?c", "ac abc"), Some((0, 2)));
+        assert_eq!(find("a{2,3}", "aaaa"), Some((0, 3)));
+        assert_eq!(find("a{2}", "a aa"), Some((2, 4)));
+        assert_eq!(find("a{2,}", "aaaaa"), Some((0, 5)));
+        assert_eq!(find("a{,2}", "a{,2}"), Some((0, 5)));
+        assert_eq!(find("(ab)+c", "ababc"), Some((0, 5)));
+        assert_eq!(find("a.*b", "axbxb"), Some((0, 5)));
+        assert_eq!(find("(a*)*b", "aab"), Some((0, 3)));
+        assert_eq!(find("(a|b)*c", "abbac"), Some((0, 5)));
+        assert_eq!(find("(b|){2}a", "xa"), Some((1, 2)));
+        assert_eq!(Regex::new("*a").err(), Some(RegexError::NothingToRepeat));
+        assert_eq!(Regex::new("a{3,1}").err(), Some(RegexError::InvalidRange));
+        assert_eq!(
+            Regex::new("(a{1000}){1000}").err(),
+            Some(RegexError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn pathological() {
+        let text = "ab".repeat(100_000);
+        assert_eq!(find("^(ab)*$", &text), Some((0, text.len())));
+
+        let text = "a".repeat(28);
+        assert_eq!(find("(a|a)*b", &text), None);
+        assert_eq!(find("(a*)*b", &text), None);
+        assert_eq!(find("(a|a)*", &text), Some((0, 28)));
+    }
+
+    #[test]
+    fn ignore_case() {
+        let regex = Regex::new("hello").unwrap().ignore_case(true);
+        assert!(regex.is_match("Say HeLLo"));
+        assert!(!Regex::new("hello").unwrap().is_match("HELLO"));
+    }
+
+    #[test]
+    fn glob() {
+        let glob = |pattern: &str, text: &str| Regex::from_glob(pattern).unwrap().is_match(text);
+        assert!(glob("*.rs", "main.rs"));
+        assert!(!glob("*.rs", "main.rs.bak"));
+        assert!(glob("file?.txt", "file1.txt"));
+        assert!(!glob("file?.txt", "file10.txt"));
+        assert!(glob("[!a]*", "bcd"));
+        assert!(!glob("[!a]*", "abc"));
+        assert!(glob("a\\*", "a*"));
+    }
+}
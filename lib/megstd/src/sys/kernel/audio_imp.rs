@@ -0,0 +1,23 @@
+// Audio Implementation
+
+use crate::{audio::Note, io::Result};
+
+pub struct AudioHandle(());
+
+impl AudioHandle {
+    pub fn new_context() -> Result<Self> {
+        todo!()
+    }
+
+    pub fn note_on(&self, _note: &Note) -> Result<Self> {
+        todo!()
+    }
+
+    pub fn open_stream(&self, _sample_rate: u32) -> Result<Self> {
+        todo!()
+    }
+
+    pub fn write(&self, _samples: &[i16]) -> Result<usize> {
+        todo!()
+    }
+}
@@ -0,0 +1,23 @@
+// Clipboard Implementation
+
+use crate::{drawing::*, io::Result, *};
+
+pub fn format() -> u32 {
+    todo!()
+}
+
+pub fn get_text() -> Option<String> {
+    todo!()
+}
+
+pub fn set_text(_text: &str) -> Result<()> {
+    todo!()
+}
+
+pub fn get_bitmap() -> Option<OwnedBitmap32> {
+    todo!()
+}
+
+pub fn set_bitmap(_bitmap: &BitmapRef32) -> Result<()> {
+    todo!()
+}
@@ -22,6 +22,14 @@ impl File {
     pub fn flush(&mut self) -> Result<()> {
         todo!()
     }
+
+    pub fn metadata(&self) -> Result<Metadata> {
+        todo!()
+    }
+
+    pub fn set_len(&self, _size: u64) -> Result<()> {
+        todo!()
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -49,6 +57,19 @@ impl OpenOptions {
         }
     }
 
+    /// Creates options from the bits passed by the application, ignoring unknown bits.
+    #[inline]
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(
+            bits & (Self::READ.0
+                | Self::WRITE.0
+                | Self::APPEND.0
+                | Self::TRUNC.0
+                | Self::CREAT.0
+                | Self::EXCL.0),
+        )
+    }
+
     #[inline]
     pub const fn bits(&self) -> u32 {
         self.0
@@ -192,3 +213,39 @@ impl DirEntry {
         todo!()
     }
 }
+
+pub fn read_dir(_path: &Path) -> Result<ReadDir> {
+    todo!()
+}
+
+pub fn stat(_path: &Path) -> Result<Metadata> {
+    todo!()
+}
+
+pub fn mkdir(_path: &Path) -> Result<()> {
+    todo!()
+}
+
+pub fn rmdir(_path: &Path) -> Result<()> {
+    todo!()
+}
+
+pub fn unlink(_path: &Path) -> Result<()> {
+    todo!()
+}
+
+pub fn rename(_old_path: &Path, _new_path: &Path) -> Result<()> {
+    todo!()
+}
+
+pub fn getcwd() -> Result<PathBuf> {
+    todo!()
+}
+
+pub fn chdir(_path: &Path) -> Result<()> {
+    todo!()
+}
+
+pub fn canonicalize(_path: &Path) -> Result<PathBuf> {
+    todo!()
+}
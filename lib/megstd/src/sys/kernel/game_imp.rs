@@ -0,0 +1,19 @@
+// Game Input Implementation
+
+use crate::{
+    io::Result,
+    sys::megos::game::{OsGameEvent, OsGameState},
+    *,
+};
+
+pub fn inputs() -> Result<Vec<u32>> {
+    todo!()
+}
+
+pub fn state(_id: u32) -> Result<OsGameState> {
+    todo!()
+}
+
+pub fn poll_event() -> Option<OsGameEvent> {
+    todo!()
+}
@@ -0,0 +1,23 @@
+// Inter-process Communication Implementation
+
+use crate::{io::Result, *};
+
+pub struct ChannelHandle(());
+
+impl ChannelHandle {
+    pub fn create(_name: &str, _capacity: usize) -> Result<Self> {
+        todo!()
+    }
+
+    pub fn connect(_name: &str) -> Result<Self> {
+        todo!()
+    }
+
+    pub fn send(&self, _message: &[u8]) -> Result<()> {
+        todo!()
+    }
+
+    pub fn recv(&self, _wait: bool) -> Result<Vec<u8>> {
+        todo!()
+    }
+}
@@ -0,0 +1,7 @@
+// Memory Implementation
+
+use crate::{io::Result, sys::megos::heap::OsHeapStats};
+
+pub fn heap_stats() -> Result<OsHeapStats> {
+    todo!()
+}
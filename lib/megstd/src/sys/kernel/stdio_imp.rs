@@ -0,0 +1,11 @@
+// Standard Streams Implementation
+
+use crate::io::Result;
+
+pub fn read(_handle: usize, _buf: &mut [u8]) -> Result<usize> {
+    todo!()
+}
+
+pub fn write(_handle: usize, _buf: &[u8]) -> Result<usize> {
+    todo!()
+}
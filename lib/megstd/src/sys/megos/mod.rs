@@ -12,4 +12,552 @@ pub mod window {
     pub const THIN_FRAME: u32 = 1 << 3;
     /// Full Screen
     pub const FULLSCREEN: u32 = 1 << 4;
+
+    /// Pixel formats of `SetBackBuffer`
+    pub mod back_buffer {
+        /// 8bit indexed color with the system palette
+        pub const INDEXED8: u32 = 8;
+        /// 32bit ARGB color
+        pub const ARGB32: u32 = 32;
+    }
+
+    /// Kinds of messages passed by `GetMessage` and `PeekMessage`
+    pub mod message {
+        pub const CLOSE: u32 = 1;
+        pub const BACK: u32 = 2;
+        pub const DRAW: u32 = 3;
+        pub const ACTIVATED: u32 = 4;
+        pub const DEACTIVATED: u32 = 5;
+        /// usage, modifier << 16, flags << 24
+        pub const KEY: u32 = 6;
+        /// code point
+        pub const CHAR: u32 = 7;
+        /// x, y, buttons | event_buttons << 8
+        pub const MOUSE_MOVE: u32 = 8;
+        pub const MOUSE_DOWN: u32 = 9;
+        pub const MOUSE_UP: u32 = 10;
+        pub const MOUSE_ENTER: u32 = 11;
+        pub const MOUSE_LEAVE: u32 = 12;
+        /// timer id
+        pub const TIMER: u32 = 13;
+        /// user defined value
+        pub const USER: u32 = 14;
+        /// width, height of the content area
+        pub const RESIZED: u32 = 15;
+        pub const CLIPBOARD_CHANGED: u32 = 16;
+    }
+
+    /// Window message passed by `GetMessage` and `PeekMessage`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct OsWindowMessage {
+        pub kind: u32,
+        pub params: [u32; 3],
+    }
+
+    impl OsWindowMessage {
+        /// Size of the encoded message
+        pub const SIZE: usize = 16;
+
+        #[inline]
+        pub const fn new(kind: u32, params: [u32; 3]) -> Self {
+            Self { kind, params }
+        }
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            let values = [self.kind, self.params[0], self.params[1], self.params[2]];
+            for (chunk, value) in buf.chunks_exact_mut(4).zip(values) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            let mut values = buf
+                .chunks_exact(4)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]));
+            Some(Self {
+                kind: values.next()?,
+                params: [values.next()?, values.next()?, values.next()?],
+            })
+        }
+    }
+}
+
+pub mod fs {
+    use crate::fs::FileType;
+
+    /// Handle of the standard input, opened when the application starts
+    pub const STDIN_FILENO: usize = 0;
+    /// Handle of the standard output, opened when the application starts
+    pub const STDOUT_FILENO: usize = 1;
+    /// Handle of the standard error, opened when the application starts
+    pub const STDERR_FILENO: usize = 2;
+
+    /// Requests of `IoCtl`
+    pub mod ioctl {
+        /// Get the metadata of the file
+        pub const FSTAT: usize = 1;
+        /// Truncate or extend the file to the specified length
+        pub const SET_LEN: usize = 2;
+        /// Flush the file
+        pub const FLUSH: usize = 3;
+    }
+
+    /// Metadata of a file passed by `Stat`, `ReadDir` and `IoCtl`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OsStat {
+        pub file_type: FileType,
+        pub len: u64,
+    }
+
+    impl OsStat {
+        /// Size of the encoded metadata
+        pub const SIZE: usize = 16;
+
+        #[inline]
+        pub const fn new(file_type: FileType, len: u64) -> Self {
+            Self { file_type, len }
+        }
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            let file_type: u32 = match self.file_type {
+                FileType::File => 1,
+                FileType::Dir => 2,
+                FileType::Symlink => 3,
+                FileType::BlockDev => 4,
+                FileType::CharDev => 5,
+                FileType::Fifo => 6,
+                FileType::Socket => 7,
+            };
+            buf[..4].copy_from_slice(&file_type.to_le_bytes());
+            buf[4..8].fill(0);
+            buf[8..].copy_from_slice(&self.len.to_le_bytes());
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            let file_type = match u32::from_le_bytes(buf[..4].try_into().ok()?) {
+                1 => FileType::File,
+                2 => FileType::Dir,
+                3 => FileType::Symlink,
+                4 => FileType::BlockDev,
+                5 => FileType::CharDev,
+                6 => FileType::Fifo,
+                7 => FileType::Socket,
+                _ => return None,
+            };
+            let len = u64::from_le_bytes(buf[8..].try_into().ok()?);
+            Some(Self { file_type, len })
+        }
+    }
+}
+
+pub mod error {
+    use crate::io::ErrorKind;
+
+    /// Error kinds that can be reported by system calls, `-1 - index` is returned on error
+    const ERROR_KINDS: [ErrorKind; 14] = [
+        ErrorKind::Other,
+        ErrorKind::NotFound,
+        ErrorKind::PermissionDenied,
+        ErrorKind::AlreadyExists,
+        ErrorKind::NotADirectory,
+        ErrorKind::IsADirectory,
+        ErrorKind::DirectoryNotEmpty,
+        ErrorKind::InvalidInput,
+        ErrorKind::InvalidData,
+        ErrorKind::UnexpectedEof,
+        ErrorKind::OutOfMemory,
+        ErrorKind::Unsupported,
+        ErrorKind::WouldBlock,
+        ErrorKind::BrokenPipe,
+    ];
+
+    /// Encodes the error kind as a negative result of the system call
+    pub fn encode(kind: ErrorKind) -> isize {
+        let index = ERROR_KINDS.iter().position(|v| *v == kind).unwrap_or(0);
+        -1 - index as isize
+    }
+
+    /// Decodes a negative result of the system call
+    pub fn decode(val: isize) -> ErrorKind {
+        (-1 - val)
+            .try_into()
+            .ok()
+            .and_then(|index: usize| ERROR_KINDS.get(index))
+            .copied()
+            .unwrap_or(ErrorKind::Other)
+    }
+}
+
+pub mod caps {
+    use crate::{io::ErrorKind, *};
+
+    /// Name of the custom section in which the application declares its capabilities
+    ///
+    /// The section contains one capability per line in the form of `r /path` or `rw /path`,
+    /// empty lines and lines beginning with `#` are ignored.
+    /// An application without the section is not allowed to access any files,
+    /// `rw /` must be declared explicitly for full access.
+    ///
+    /// The application declares the section with a static in its crate, for example:
+    ///
+    /// ```ignore
+    /// #[used]
+    /// #[link_section = "megos.caps"]
+    /// static CAPS: [u8; 9] = *b"rw /home\n";
+    /// ```
+    ///
+    /// The build of the applications keeps the section when it strips the others.
+    pub const SECTION_NAME: &str = "megos.caps";
+
+    /// Access rights granted by a capability
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Access {
+        Read,
+        ReadWrite,
+    }
+
+    /// A directory or device that the application is allowed to access
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Capability {
+        path: String,
+        access: Access,
+    }
+
+    impl Capability {
+        #[inline]
+        pub fn new(path: &str, access: Access) -> Self {
+            let path = path.trim_end_matches('/');
+            Self {
+                path: if path.is_empty() { "/" } else { path }.to_owned(),
+                access,
+            }
+        }
+
+        /// Parses a line of the manifest
+        pub fn parse(line: &str) -> Option<Self> {
+            let (access, path) = line.split_once(char::is_whitespace)?;
+            let access = match access {
+                "r" => Access::Read,
+                "rw" => Access::ReadWrite,
+                _ => return None,
+            };
+            let path = path.trim();
+            path.starts_with('/').then(|| Self::new(path, access))
+        }
+
+        #[inline]
+        pub fn path(&self) -> &str {
+            &self.path
+        }
+
+        #[inline]
+        pub const fn access(&self) -> Access {
+            self.access
+        }
+
+        /// Returns whether the absolute path is under this capability
+        pub fn contains(&self, path: &str) -> bool {
+            self.path == "/"
+                || path
+                    .strip_prefix(self.path.as_str())
+                    .map(|v| v.is_empty() || v.starts_with('/'))
+                    .unwrap_or(false)
+        }
+    }
+
+    /// Per-process table of capabilities
+    #[derive(Debug, Clone, Default)]
+    pub struct CapabilityTable {
+        caps: Vec<Capability>,
+    }
+
+    impl CapabilityTable {
+        #[inline]
+        pub const fn new() -> Self {
+            Self { caps: Vec::new() }
+        }
+
+        /// Parses the contents of the manifest section
+        pub fn from_manifest(manifest: &[u8]) -> Option<Self> {
+            let mut table = Self::new();
+            for line in core::str::from_utf8(manifest).ok()?.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                table.add(Capability::parse(line)?);
+            }
+            Some(table)
+        }
+
+        #[inline]
+        pub fn add(&mut self, cap: Capability) {
+            self.caps.push(cap);
+        }
+
+        #[inline]
+        pub fn is_empty(&self) -> bool {
+            self.caps.is_empty()
+        }
+
+        #[inline]
+        pub fn iter(&self) -> impl Iterator<Item = &Capability> {
+            self.caps.iter()
+        }
+
+        /// Returns the access rights of the absolute path, the most specific capability wins
+        pub fn access(&self, path: &str) -> Option<Access> {
+            self.caps
+                .iter()
+                .filter(|v| v.contains(path))
+                .max_by_key(|v| v.path.len())
+                .map(|v| v.access)
+        }
+
+        /// Checks whether the absolute path can be accessed
+        pub fn check(&self, path: &str, access: Access) -> Result<(), ErrorKind> {
+            match self.access(path) {
+                Some(v) if v >= access => Ok(()),
+                _ => Err(ErrorKind::PermissionDenied),
+            }
+        }
+    }
+}
+
+pub mod audio {
+    /// Oscillator types of `OsNoteParams`
+    pub mod osc {
+        pub const SINE: u32 = 0;
+        pub const SQUARE: u32 = 1;
+        pub const PULSE: u32 = 2;
+        pub const SAWTOOTH: u32 = 3;
+        pub const TRIANGLE: u32 = 4;
+    }
+
+    /// Parameters of a note passed by `AudioNoteOn`
+    ///
+    /// Frequencies are in millihertz, times are in milliseconds and ratios are in permille.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OsNoteParams {
+        pub osc_type: u32,
+        pub duty_cycle: u32,
+        pub freq: u32,
+        pub gain: u32,
+        pub attack: u32,
+        pub decay: u32,
+        pub sustain: u32,
+    }
+
+    impl OsNoteParams {
+        /// Size of the encoded parameters
+        pub const SIZE: usize = 28;
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            let values = [
+                self.osc_type,
+                self.duty_cycle,
+                self.freq,
+                self.gain,
+                self.attack,
+                self.decay,
+                self.sustain,
+            ];
+            for (chunk, value) in buf.chunks_exact_mut(4).zip(values) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            let mut values = buf
+                .chunks_exact(4)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]));
+            Some(Self {
+                osc_type: values.next()?,
+                duty_cycle: values.next()?,
+                freq: values.next()?,
+                gain: values.next()?,
+                attack: values.next()?,
+                decay: values.next()?,
+                sustain: values.next()?,
+            })
+        }
+    }
+}
+
+pub mod clipboard {
+    /// Formats returned by `ClipboardFormat`
+    pub mod format {
+        pub const EMPTY: u32 = 0;
+        pub const TEXT: u32 = 1;
+        pub const BITMAP32: u32 = 2;
+    }
+
+    /// Size of the header of the bitmap passed by `ClipboardGetBitmap`,
+    /// the width and the height followed by the pixels
+    pub const BITMAP_HEADER_SIZE: usize = 8;
+}
+
+pub mod channel {
+    /// Maximum size of a message
+    pub const MAX_MESSAGE_SIZE: usize = 4096;
+
+    /// Flags of `ChannelReceive`
+    pub mod flags {
+        /// Fails with `WouldBlock` instead of waiting for a message
+        pub const NONBLOCK: u32 = 1;
+    }
+}
+
+pub mod heap {
+    /// Statistics of the heap passed by `HeapStats`, all sizes are in bytes
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct OsHeapStats {
+        /// Size of the linear memory
+        pub memory_size: u32,
+        /// Maximum size of the linear memory
+        pub memory_limit: u32,
+        /// Size of the memory taken by the heap
+        pub heap_size: u32,
+        /// Size of the allocated blocks
+        pub used: u32,
+        /// Number of the allocated blocks
+        pub n_allocs: u32,
+        /// Number of the free blocks
+        pub n_free_blocks: u32,
+        /// Size of the largest free block
+        pub largest_free: u32,
+    }
+
+    impl OsHeapStats {
+        /// Size of the encoded statistics
+        pub const SIZE: usize = 28;
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            for (chunk, value) in buf.chunks_exact_mut(4).zip([
+                self.memory_size,
+                self.memory_limit,
+                self.heap_size,
+                self.used,
+                self.n_allocs,
+                self.n_free_blocks,
+                self.largest_free,
+            ]) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            let mut values = buf
+                .chunks_exact(4)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]));
+            Some(Self {
+                memory_size: values.next()?,
+                memory_limit: values.next()?,
+                heap_size: values.next()?,
+                used: values.next()?,
+                n_allocs: values.next()?,
+                n_free_blocks: values.next()?,
+                largest_free: values.next()?,
+            })
+        }
+    }
+}
+
+pub mod game {
+    /// Id of the input simulated by the keyboard
+    pub const SIMULATED_INPUT: u32 = 0;
+    /// Id that refers to the input currently in use
+    pub const CURRENT_INPUT: u32 = u32::MAX;
+
+    /// Kinds of events passed by `GamePollEvent`
+    pub mod event {
+        pub const CONNECTED: u32 = 1;
+        pub const DISCONNECTED: u32 = 2;
+    }
+
+    /// State of a game input passed by `GameGetState`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct OsGameState {
+        pub buttons: u16,
+        pub lt: u8,
+        pub rt: u8,
+        pub x1: i16,
+        pub y1: i16,
+        pub x2: i16,
+        pub y2: i16,
+    }
+
+    impl OsGameState {
+        /// Size of the encoded state
+        pub const SIZE: usize = 12;
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            buf[..2].copy_from_slice(&self.buttons.to_le_bytes());
+            buf[2] = self.lt;
+            buf[3] = self.rt;
+            for (chunk, value) in buf[4..]
+                .chunks_exact_mut(2)
+                .zip([self.x1, self.y1, self.x2, self.y2])
+            {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            let mut values = buf[4..]
+                .chunks_exact(2)
+                .map(|v| i16::from_le_bytes([v[0], v[1]]));
+            Some(Self {
+                buttons: u16::from_le_bytes([buf[0], buf[1]]),
+                lt: buf[2],
+                rt: buf[3],
+                x1: values.next()?,
+                y1: values.next()?,
+                x2: values.next()?,
+                y2: values.next()?,
+            })
+        }
+    }
+
+    /// Connection event of a game input passed by `GamePollEvent`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OsGameEvent {
+        pub kind: u32,
+        pub id: u32,
+    }
+
+    impl OsGameEvent {
+        /// Size of the encoded event
+        pub const SIZE: usize = 8;
+
+        pub fn encode(&self, buf: &mut [u8]) -> Option<()> {
+            let buf = buf.get_mut(..Self::SIZE)?;
+            buf[..4].copy_from_slice(&self.kind.to_le_bytes());
+            buf[4..].copy_from_slice(&self.id.to_le_bytes());
+            Some(())
+        }
+
+        pub fn decode(buf: &[u8]) -> Option<Self> {
+            let buf = buf.get(..Self::SIZE)?;
+            Some(Self {
+                kind: u32::from_le_bytes(buf[..4].try_into().ok()?),
+                id: u32::from_le_bytes(buf[4..].try_into().ok()?),
+            })
+        }
+    }
 }
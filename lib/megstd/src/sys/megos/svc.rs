@@ -46,38 +46,112 @@ pub enum Function {
     ReadChar,
     /// Draw a shape in a window
     DrawShape,
+    /// Wait for a window message
+    GetMessage,
+    /// Read a window message
+    PeekMessage,
+    /// Post a timer message to a window after the specified milliseconds
+    SetTimer,
+    /// Register a bitmap in the linear memory as the back buffer of a window
+    SetBackBuffer,
+    /// Copy a rectangle of the back buffer to a window and show it
+    Present,
 
     /// Returns a simple pseudo-random number
     Rand = 100,
     /// Set the seed of the random number
     Srand,
-    /// Allocates memory blocks with a simple allocator
+    /// Allocates a memory block on the heap, which grows the linear memory on demand
     Alloc,
     /// Frees an allocated memory block
     Dealloc,
 
     // File and directory functions
+    /// Create a directory
     MkDir,
-
+    /// Remove an empty directory
     RmDir,
-
+    /// Change the current directory
     ChDir,
-
+    /// Open a file
     Open,
-
+    /// Close a file or directory handle
     Close,
-
+    /// Read from a file, handles 0 to 2 are the standard streams
     Read,
-
+    /// Write to a file, handles 0 to 2 are the standard streams
     Write,
-
+    /// Reposition the offset of a file
     LSeek,
-
+    /// Control a file
     IoCtl,
-
+    /// Remove a file
     Unlink,
-
+    /// Open a directory
     OpenDir,
-
+    /// Read a directory entry
     ReadDir,
+    /// Get the metadata of a file
+    Stat,
+    /// Rename a file or directory
+    Rename,
+    /// Get the current directory
+    GetCwd,
+
+    // Process environment functions
+    /// Get the arguments of the process
+    Args,
+    /// Get the environment variables of the process
+    Environ,
+    /// Set an environment variable
+    SetEnv,
+    /// Remove an environment variable
+    UnsetEnv,
+
+    // Audio functions
+    /// Create an audio context
+    AudioNewContext,
+    /// Start a note described by `OsNoteParams` in the audio context
+    AudioNoteOn,
+    /// Open a stream of 16bit mono PCM samples in the audio context
+    AudioOpenStream,
+    /// Queue the PCM samples to the stream, returns the number of samples queued
+    AudioWrite,
+    /// Close the audio context, the note or the stream
+    AudioClose,
+
+    // Game input functions
+    /// Get the ids of the connected game inputs
+    GameEnumInputs,
+    /// Get the state of the game input as `OsGameState`
+    GameGetState,
+    /// Get a connection event of the game inputs as `OsGameEvent`
+    GamePollEvent,
+
+    // Clipboard functions
+    /// Get the format of the data in the clipboard
+    ClipboardFormat,
+    /// Get the text in the clipboard, returns the length of the text
+    ClipboardGetText,
+    /// Put the text in the clipboard
+    ClipboardSetText,
+    /// Get the bitmap in the clipboard, returns the size of the encoded bitmap
+    ClipboardGetBitmap,
+    /// Put the bitmap of 32bit ARGB pixels in the clipboard
+    ClipboardSetBitmap,
+
+    // Channel functions
+    /// Create a named channel owned by the process, returns the handle
+    ChannelCreate,
+    /// Connect to the named channel, returns the handle
+    ChannelConnect,
+    /// Send a message to the channel
+    ChannelSend,
+    /// Receive a message from the channel, returns the size of the message
+    ChannelReceive,
+    /// Close the handle of the channel
+    ChannelClose,
+
+    /// Get the statistics of the heap as `OsHeapStats`
+    HeapStats,
 }
@@ -0,0 +1,27 @@
+// Process Environment Implementation
+
+use crate::{io::Result, *};
+
+pub fn args() -> Vec<String> {
+    todo!()
+}
+
+pub fn vars() -> Vec<(String, String)> {
+    todo!()
+}
+
+pub fn getenv(_key: &str) -> Option<String> {
+    todo!()
+}
+
+pub fn setenv(_key: &str, _value: &str) -> Result<()> {
+    todo!()
+}
+
+pub fn unsetenv(_key: &str) -> Result<()> {
+    todo!()
+}
+
+pub fn exit(_code: i32) -> ! {
+    todo!()
+}
@@ -3,7 +3,15 @@ pub mod prelude {}
 
 pub mod window {}
 
+pub mod audio_imp;
+pub mod clipboard_imp;
+pub mod env_imp;
 pub mod fs_imp;
+#[cfg(feature = "game")]
+pub mod game_imp;
+pub mod ipc_imp;
+pub mod mem_imp;
+pub mod stdio_imp;
 
 pub mod path {
     pub const MAIN_SEP_STR: &'static str = "/";
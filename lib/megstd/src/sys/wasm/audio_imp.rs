@@ -0,0 +1,61 @@
+// Audio Implementation
+
+use super::{cvt, syscall::*};
+use crate::{
+    audio::{Note, OscType},
+    io::Result,
+    sys::megos::audio::{osc, OsNoteParams},
+};
+use core::slice;
+
+/// Handle of the audio context, the note or the stream
+pub struct AudioHandle(usize);
+
+impl AudioHandle {
+    #[inline]
+    pub fn new_context() -> Result<Self> {
+        cvt(os_audio_new_context()).map(Self)
+    }
+
+    pub fn note_on(&self, note: &Note) -> Result<Self> {
+        let (osc_type, duty_cycle) = match note.osc_type {
+            OscType::Sine => (osc::SINE, 0.0),
+            OscType::Square => (osc::SQUARE, 0.5),
+            OscType::Pulse(duty_cycle) => (osc::PULSE, duty_cycle),
+            OscType::Sawtooth => (osc::SAWTOOTH, 0.0),
+            OscType::Triangle => (osc::TRIANGLE, 0.0),
+        };
+        let params = OsNoteParams {
+            osc_type,
+            duty_cycle: (duty_cycle * 1000.0) as u32,
+            freq: (note.freq * 1000.0) as u32,
+            gain: (note.gain * 1000.0) as u32,
+            attack: note.attack.as_millis() as u32,
+            decay: note.decay.as_millis() as u32,
+            sustain: (note.sustain * 1000.0) as u32,
+        };
+        let mut buf = [0; OsNoteParams::SIZE];
+        params.encode(&mut buf);
+        cvt(os_audio_note_on(self.0, &buf)).map(Self)
+    }
+
+    #[inline]
+    pub fn open_stream(&self, sample_rate: u32) -> Result<Self> {
+        cvt(os_audio_open_stream(self.0, sample_rate)).map(Self)
+    }
+
+    #[inline]
+    pub fn write(&self, samples: &[i16]) -> Result<usize> {
+        // wasm is always little endian
+        let samples =
+            unsafe { slice::from_raw_parts(samples.as_ptr() as *const u8, samples.len() * 2) };
+        cvt(os_audio_write(self.0, samples))
+    }
+}
+
+impl Drop for AudioHandle {
+    #[inline]
+    fn drop(&mut self) {
+        os_audio_close(self.0);
+    }
+}
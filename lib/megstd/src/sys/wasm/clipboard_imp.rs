@@ -0,0 +1,70 @@
+// Clipboard Implementation
+
+use super::{cvt, syscall::*};
+use crate::{
+    drawing::*,
+    io::Result,
+    sys::megos::clipboard::{format, BITMAP_HEADER_SIZE},
+    *,
+};
+
+#[inline]
+pub fn format() -> u32 {
+    os_clipboard_format()
+}
+
+pub fn get_text() -> Option<String> {
+    let buf = read_block(os_clipboard_get_text, 256)?;
+    String::from_utf8(buf).ok()
+}
+
+#[inline]
+pub fn set_text(text: &str) -> Result<()> {
+    cvt(os_clipboard_set_text(text)).map(|_| ())
+}
+
+pub fn get_bitmap() -> Option<OwnedBitmap32> {
+    if format() != format::BITMAP32 {
+        return None;
+    }
+    let buf = read_block(os_clipboard_get_bitmap, BITMAP_HEADER_SIZE)?;
+    let (header, pixels) = buf.split_at_checked(BITMAP_HEADER_SIZE)?;
+    let width = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let height = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+    let pixels = pixels
+        .chunks_exact(4)
+        .map(|v| TrueColor::from_argb(u32::from_le_bytes([v[0], v[1], v[2], v[3]])))
+        .collect::<Vec<_>>();
+    (pixels.len() == width * height)
+        .then(|| OwnedBitmap32::from_vec(pixels, Size::new(width as isize, height as isize)))
+}
+
+pub fn set_bitmap(bitmap: &BitmapRef32) -> Result<()> {
+    let size = bitmap.size();
+    let mut pixels = Vec::with_capacity(size.width as usize * size.height as usize * 4);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let color = bitmap.get_pixel(Point::new(x, y)).unwrap_or_default();
+            pixels.extend_from_slice(&color.argb().to_le_bytes());
+        }
+    }
+    cvt(os_clipboard_set_bitmap(
+        size.width as usize,
+        size.height as usize,
+        &pixels,
+    ))
+    .map(|_| ())
+}
+
+/// Reads the data whose size is not known in advance
+fn read_block(f: fn(&mut [u8]) -> isize, initial_size: usize) -> Option<Vec<u8>> {
+    let mut buf = alloc::vec![0; initial_size];
+    loop {
+        let len = cvt(f(&mut buf)).ok()?;
+        if len <= buf.len() {
+            buf.truncate(len);
+            return Some(buf);
+        }
+        buf.resize(len, 0);
+    }
+}
@@ -0,0 +1,64 @@
+// Process Environment Implementation
+
+use super::{cvt, syscall::*};
+use crate::{io::Result, *};
+
+pub fn args() -> Vec<String> {
+    read_block(os_args)
+}
+
+pub fn vars() -> Vec<(String, String)> {
+    read_block(os_environ)
+        .into_iter()
+        .filter_map(|v| {
+            v.split_once('=')
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+pub fn getenv(key: &str) -> Option<String> {
+    vars()
+        .into_iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value)
+}
+
+pub fn setenv(key: &str, value: &str) -> Result<()> {
+    cvt(os_setenv(key, value)).map(|_| ())
+}
+
+pub fn unsetenv(key: &str) -> Result<()> {
+    cvt(os_unsetenv(key)).map(|_| ())
+}
+
+#[inline]
+pub fn exit(code: i32) -> ! {
+    os_exit(code as usize)
+}
+
+/// Reads the strings terminated by NUL
+fn read_block(f: fn(&mut [u8]) -> isize) -> Vec<String> {
+    let mut buf = alloc::vec![0; 256];
+    loop {
+        let len = f(&mut buf);
+        if len < 0 {
+            return Vec::new();
+        }
+        let len = len as usize;
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    if buf.is_empty() {
+        return Vec::new();
+    }
+    if buf.last() == Some(&0) {
+        buf.pop();
+    }
+    buf.split(|v| *v == 0)
+        .map(|v| String::from_utf8_lossy(v).into_owned())
+        .collect()
+}
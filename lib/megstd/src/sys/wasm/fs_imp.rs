@@ -1,29 +1,56 @@
 // FileSystem Implementation
 
-use super::syscall::*;
-use crate::{fs::*, io::Result, path::*, sys::fcntl::*, *};
+use super::{cvt, syscall::*};
+use crate::{
+    fs::*,
+    io::{ErrorKind, Result},
+    path::*,
+    sys::{
+        fcntl::*,
+        megos::fs::{ioctl, OsStat},
+        path::MAIN_SEP_STR,
+    },
+    *,
+};
+use core::str;
 
 pub struct File {
-    _phantom: (),
+    handle: usize,
 }
 
 impl File {
-    pub fn open<P: AsRef<Path>>(_path: P, options: OpenOptions) -> Result<File> {
-        let path = _path.as_ref();
-        let _ = os_open(path.as_os_str().to_str().unwrap(), options.bits() as usize);
-        todo!()
+    pub fn open<P: AsRef<Path>>(path: P, options: OpenOptions) -> Result<File> {
+        let path = path_str(path.as_ref())?;
+        cvt(os_open(path, options.bits() as usize)).map(|handle| File { handle })
     }
 
-    pub fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-        todo!()
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        cvt(os_read(self.handle, buf))
     }
 
-    pub fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-        todo!()
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        cvt(os_write(self.handle, buf))
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        todo!()
+        cvt(os_ioctl(self.handle, ioctl::FLUSH, &mut [])).map(|_| ())
+    }
+
+    pub fn metadata(&self) -> Result<Metadata> {
+        let mut stat = [0; OsStat::SIZE];
+        cvt(os_ioctl(self.handle, ioctl::FSTAT, &mut stat))?;
+        Metadata::decode(&stat)
+    }
+
+    pub fn set_len(&self, size: u64) -> Result<()> {
+        let mut arg = size.to_le_bytes();
+        cvt(os_ioctl(self.handle, ioctl::SET_LEN, &mut arg)).map(|_| ())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        os_close(self.handle);
     }
 }
 
@@ -59,7 +86,7 @@ impl OpenOptions {
 
     #[inline]
     pub const fn contains(&self, bit: Self) -> bool {
-        (self.0 & bit.0) == self.0
+        (self.0 & bit.0) == bit.0
     }
 
     #[inline]
@@ -126,22 +153,28 @@ impl Default for OpenOptions {
 
 #[derive(Debug, Clone)]
 pub struct Metadata {
-    _phantom: (),
+    stat: OsStat,
 }
 
 impl Metadata {
+    fn decode(stat: &[u8]) -> Result<Self> {
+        OsStat::decode(stat)
+            .map(|stat| Self { stat })
+            .ok_or(ErrorKind::InvalidData.into())
+    }
+
     pub fn file_type(&self) -> FileType {
-        todo!()
+        self.stat.file_type
     }
 
     #[inline]
     pub fn len(&self) -> u64 {
-        todo!()
+        self.stat.len
     }
 
     #[inline]
     pub fn permissions(&self) -> Permissions {
-        todo!()
+        Permissions(0)
     }
 
     // pub fn modified(&self) -> Result<SystemTime>
@@ -153,45 +186,182 @@ impl Metadata {
 pub struct Permissions(usize);
 
 impl Permissions {
+    const READONLY: usize = 0b0000_0001;
+
     pub fn readonly(&self) -> bool {
-        todo!()
+        (self.0 & Self::READONLY) != 0
     }
 
-    pub fn set_readonly(&mut self, _readonly: bool) {
-        todo!()
+    pub fn set_readonly(&mut self, readonly: bool) {
+        if readonly {
+            self.0 |= Self::READONLY;
+        } else {
+            self.0 &= !Self::READONLY;
+        }
     }
 }
 
 pub struct ReadDir {
-    _phantom: (),
+    handle: usize,
+    path: String,
 }
 
 impl Iterator for ReadDir {
     type Item = Result<DirEntry>;
 
     fn next(&mut self) -> Option<Result<DirEntry>> {
-        todo!()
+        let mut name = alloc::vec![0; DirEntry::MAX_NAME_LEN];
+        let mut stat = [0; OsStat::SIZE];
+        let len = loop {
+            match cvt(os_readdir(self.handle, &mut name, &mut stat)) {
+                Ok(0) => return None,
+                // The name is longer than the buffer, the same entry is read again
+                Ok(v) if v > name.len() => name.resize(v, 0),
+                Ok(v) => break v,
+                Err(err) => return Some(Err(err)),
+            }
+        };
+        let Some(name) = name.get(..len).and_then(|v| str::from_utf8(v).ok()) else {
+            return Some(Err(ErrorKind::InvalidData.into()));
+        };
+        Some(Metadata::decode(&stat).map(|metadata| DirEntry {
+            path: join_path(&self.path, name),
+            name: name.to_owned(),
+            metadata,
+        }))
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        os_close(self.handle);
     }
 }
 
 pub struct DirEntry {
-    _phantom: (),
+    path: String,
+    name: String,
+    metadata: Metadata,
 }
 
 impl DirEntry {
+    const MAX_NAME_LEN: usize = 256;
+
     pub fn path(&self) -> PathBuf {
-        todo!()
+        path_buf(&self.path)
     }
 
     pub fn metadata(&self) -> Result<Metadata> {
-        todo!()
+        Ok(self.metadata.clone())
     }
 
     pub fn file_type(&self) -> Result<FileType> {
-        todo!()
+        Ok(self.metadata.file_type())
     }
 
     pub fn file_name(&self) -> OsString {
-        todo!()
+        let mut result = OsString::new();
+        result.push(&self.name);
+        result
+    }
+}
+
+pub fn read_dir(path: &Path) -> Result<ReadDir> {
+    let path = path_str(path)?;
+    cvt(os_opendir(path)).map(|handle| ReadDir {
+        handle,
+        path: path.to_owned(),
+    })
+}
+
+pub fn stat(path: &Path) -> Result<Metadata> {
+    let mut stat = [0; OsStat::SIZE];
+    cvt(os_stat(path_str(path)?, &mut stat))?;
+    Metadata::decode(&stat)
+}
+
+pub fn mkdir(path: &Path) -> Result<()> {
+    cvt(os_mkdir(path_str(path)?)).map(|_| ())
+}
+
+pub fn rmdir(path: &Path) -> Result<()> {
+    cvt(os_rmdir(path_str(path)?)).map(|_| ())
+}
+
+pub fn unlink(path: &Path) -> Result<()> {
+    cvt(os_unlink(path_str(path)?)).map(|_| ())
+}
+
+pub fn rename(old_path: &Path, new_path: &Path) -> Result<()> {
+    cvt(os_rename(path_str(old_path)?, path_str(new_path)?)).map(|_| ())
+}
+
+pub fn getcwd() -> Result<PathBuf> {
+    let mut buf = alloc::vec![0; DirEntry::MAX_NAME_LEN];
+    loop {
+        let len = cvt(os_getcwd(&mut buf))?;
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    String::from_utf8(buf)
+        .map(|v| path_buf(&v))
+        .map_err(|_| ErrorKind::InvalidData.into())
+}
+
+pub fn chdir(path: &Path) -> Result<()> {
+    cvt(os_chdir(path_str(path)?)).map(|_| ())
+}
+
+pub fn canonicalize(path: &Path) -> Result<PathBuf> {
+    let path = path_str(path)?;
+    let cwd = getcwd()?;
+    let base = if path.starts_with(MAIN_SEP_STR) {
+        ""
+    } else {
+        path_str(&cwd)?
+    };
+
+    let mut components = Vec::new();
+    for component in base.split(MAIN_SEP_STR).chain(path.split(MAIN_SEP_STR)) {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let mut result = String::new();
+    for component in &components {
+        result.push_str(MAIN_SEP_STR);
+        result.push_str(component);
+    }
+    if result.is_empty() {
+        result.push_str(MAIN_SEP_STR);
+    }
+
+    stat(Path::new(&result))?;
+    Ok(path_buf(&result))
+}
+
+#[inline]
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or(ErrorKind::InvalidInput.into())
+}
+
+fn path_buf(path: &str) -> PathBuf {
+    let mut result = OsString::new();
+    result.push(path);
+    PathBuf::from(result)
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.ends_with(MAIN_SEP_STR) {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}{}{}", dir, MAIN_SEP_STR, name)
     }
 }
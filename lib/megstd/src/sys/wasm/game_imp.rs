@@ -0,0 +1,36 @@
+// Game Input Implementation
+
+use super::{cvt, syscall::*};
+use crate::{
+    io::Result,
+    sys::megos::game::{OsGameEvent, OsGameState},
+    *,
+};
+
+pub fn inputs() -> Result<Vec<u32>> {
+    let mut buf = alloc::vec![0; 64];
+    loop {
+        let count = cvt(os_game_enum_inputs(&mut buf))?;
+        if count * 4 <= buf.len() {
+            return Ok(buf[..count * 4]
+                .chunks_exact(4)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .collect());
+        }
+        buf.resize(count * 4, 0);
+    }
+}
+
+pub fn state(id: u32) -> Result<OsGameState> {
+    let mut buf = [0; OsGameState::SIZE];
+    cvt(os_game_get_state(id, &mut buf))?;
+    Ok(OsGameState::decode(&buf).unwrap_or_default())
+}
+
+pub fn poll_event() -> Option<OsGameEvent> {
+    let mut buf = [0; OsGameEvent::SIZE];
+    match os_game_poll_event(&mut buf) {
+        1 => OsGameEvent::decode(&buf),
+        _ => None,
+    }
+}
@@ -0,0 +1,43 @@
+// Inter-process Communication Implementation
+
+use super::{cvt, syscall::*};
+use crate::{
+    io::Result,
+    sys::megos::channel::{flags, MAX_MESSAGE_SIZE},
+    *,
+};
+
+/// Handle of the channel
+pub struct ChannelHandle(usize);
+
+impl ChannelHandle {
+    #[inline]
+    pub fn create(name: &str, capacity: usize) -> Result<Self> {
+        cvt(os_channel_create(name, capacity)).map(Self)
+    }
+
+    #[inline]
+    pub fn connect(name: &str) -> Result<Self> {
+        cvt(os_channel_connect(name)).map(Self)
+    }
+
+    #[inline]
+    pub fn send(&self, message: &[u8]) -> Result<()> {
+        cvt(os_channel_send(self.0, message)).map(|_| ())
+    }
+
+    pub fn recv(&self, wait: bool) -> Result<Vec<u8>> {
+        let flags = if wait { 0 } else { flags::NONBLOCK };
+        let mut buf = alloc::vec![0; MAX_MESSAGE_SIZE];
+        let len = cvt(os_channel_receive(self.0, &mut buf, flags))?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+}
+
+impl Drop for ChannelHandle {
+    #[inline]
+    fn drop(&mut self) {
+        os_channel_close(self.0);
+    }
+}
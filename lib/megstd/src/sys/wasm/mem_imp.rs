@@ -0,0 +1,10 @@
+// Memory Implementation
+
+use super::{cvt, syscall::*};
+use crate::{io::Result, sys::megos::heap::OsHeapStats};
+
+pub fn heap_stats() -> Result<OsHeapStats> {
+    let mut buf = [0; OsHeapStats::SIZE];
+    cvt(os_heap_stats(&mut buf))?;
+    Ok(OsHeapStats::decode(&buf).unwrap_or_default())
+}
@@ -1,7 +1,15 @@
 #[macro_use]
 pub mod prelude;
 
+pub mod audio_imp;
+pub mod clipboard_imp;
+pub mod env_imp;
 pub mod fs_imp;
+#[cfg(feature = "game")]
+pub mod game_imp;
+pub mod ipc_imp;
+pub mod mem_imp;
+pub mod stdio_imp;
 mod os_alloc;
 
 #[macro_use]
@@ -26,3 +34,13 @@ pub mod fcntl {
     pub const O_APPEND: usize = 0o00002000;
     pub const O_NONBLOCK: usize = 0o00004000;
 }
+
+/// Converts the result of the system call
+#[inline]
+fn cvt(result: isize) -> crate::io::Result<usize> {
+    if result < 0 {
+        Err(super::megos::error::decode(result).into())
+    } else {
+        Ok(result as usize)
+    }
+}
@@ -0,0 +1,14 @@
+// Standard Streams Implementation
+
+use super::{cvt, syscall::*};
+use crate::io::Result;
+
+#[inline]
+pub fn read(handle: usize, buf: &mut [u8]) -> Result<usize> {
+    cvt(os_read(handle, buf))
+}
+
+#[inline]
+pub fn write(handle: usize, buf: &[u8]) -> Result<usize> {
+    cvt(os_write(handle, buf))
+}
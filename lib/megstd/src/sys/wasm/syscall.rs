@@ -59,10 +59,11 @@ macro_rules! syscall {
     };
 }
 
+/// Terminate the process with the exit code.
 #[inline]
-pub fn os_exit() -> ! {
+pub fn os_exit(code: usize) -> ! {
     unsafe {
-        syscall!(Exit);
+        syscall!(Exit, code);
         asm!("", options(noreturn, nostack));
     }
 }
@@ -193,6 +194,40 @@ pub fn os_read_char(window: usize) -> u32 {
     unsafe { syscall!(ReadChar, window) as u32 }
 }
 
+/// Wait for a window message, returns 0 if the window has been closed.
+#[inline]
+pub fn os_get_message(window: usize, buf: &mut [u8]) -> isize {
+    unsafe { syscall!(GetMessage, window, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+/// Read a window message, returns 0 if there are no messages.
+#[inline]
+pub fn os_peek_message(window: usize, buf: &mut [u8]) -> isize {
+    unsafe { syscall!(PeekMessage, window, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_set_timer(window: usize, timer_id: usize, ms: u32) {
+    unsafe { syscall!(SetTimer, window, timer_id, ms) };
+}
+
+/// Register the back buffer of the window, the null pointer unregisters it.
+#[inline]
+pub fn os_set_back_buffer(
+    window: usize,
+    base: *const u8,
+    width: usize,
+    height: usize,
+    format: u32,
+) -> isize {
+    unsafe { syscall!(SetBackBuffer, window, base, width, height, format) as isize }
+}
+
+#[inline]
+pub fn os_present(window: usize, x: usize, y: usize, width: usize, height: usize) {
+    unsafe { syscall!(Present, window, x, y, width, height) };
+}
+
 /// Draw a bitmap in a window
 #[inline]
 pub fn os_blt8(ctx: usize, x: usize, y: usize, bitmap: usize) {
@@ -233,7 +268,7 @@ pub fn os_srand(srand: u32) -> u32 {
     unsafe { syscall!(Srand, srand) as u32 }
 }
 
-/// Allocates memory blocks with a simple allocator
+/// Allocates a memory block on the heap, returns null if the heap reaches the limit
 #[inline]
 #[must_use]
 pub unsafe fn os_alloc(size: usize, align: usize) -> *mut u8 {
@@ -271,3 +306,211 @@ pub fn os_write(handle: usize, buf: &[u8]) -> isize {
 pub fn os_lseek(handle: usize, offset: i32, whence: usize) -> isize {
     unsafe { syscall!(LSeek, handle, offset, whence) as isize }
 }
+
+#[inline]
+pub fn os_ioctl(handle: usize, request: usize, arg: &mut [u8]) -> isize {
+    unsafe { syscall!(IoCtl, handle, request, arg.as_mut_ptr(), arg.len()) as isize }
+}
+
+#[inline]
+pub fn os_mkdir(path: &str) -> isize {
+    unsafe { syscall!(MkDir, path.as_ptr(), path.len()) as isize }
+}
+
+#[inline]
+pub fn os_rmdir(path: &str) -> isize {
+    unsafe { syscall!(RmDir, path.as_ptr(), path.len()) as isize }
+}
+
+#[inline]
+pub fn os_chdir(path: &str) -> isize {
+    unsafe { syscall!(ChDir, path.as_ptr(), path.len()) as isize }
+}
+
+/// Get the current directory, returns the length of the path.
+#[inline]
+pub fn os_getcwd(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(GetCwd, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_unlink(path: &str) -> isize {
+    unsafe { syscall!(Unlink, path.as_ptr(), path.len()) as isize }
+}
+
+#[inline]
+pub fn os_rename(old_path: &str, new_path: &str) -> isize {
+    unsafe {
+        syscall!(
+            Rename,
+            old_path.as_ptr(),
+            old_path.len(),
+            new_path.as_ptr(),
+            new_path.len()
+        ) as isize
+    }
+}
+
+#[inline]
+pub fn os_stat(path: &str, stat: &mut [u8]) -> isize {
+    unsafe {
+        syscall!(
+            Stat,
+            path.as_ptr(),
+            path.len(),
+            stat.as_mut_ptr(),
+            stat.len()
+        ) as isize
+    }
+}
+
+#[inline]
+#[must_use]
+pub fn os_opendir(path: &str) -> isize {
+    unsafe { syscall!(OpenDir, path.as_ptr(), path.len()) as isize }
+}
+
+/// Read a directory entry, returns the length of the name or 0 at the end of the directory.
+///
+/// If the name is longer than `name`, the entry is not consumed and its length is returned.
+#[inline]
+pub fn os_readdir(handle: usize, name: &mut [u8], stat: &mut [u8]) -> isize {
+    unsafe {
+        syscall!(
+            ReadDir,
+            handle,
+            name.as_mut_ptr(),
+            name.len(),
+            stat.as_mut_ptr(),
+            stat.len()
+        ) as isize
+    }
+}
+
+/// Get the arguments terminated by NUL, returns the length of the arguments.
+#[inline]
+pub fn os_args(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(Args, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+/// Get the environment variables as `KEY=VALUE` terminated by NUL, returns the length of them.
+#[inline]
+pub fn os_environ(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(Environ, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_setenv(key: &str, value: &str) -> isize {
+    unsafe { syscall!(SetEnv, key.as_ptr(), key.len(), value.as_ptr(), value.len()) as isize }
+}
+
+#[inline]
+pub fn os_unsetenv(key: &str) -> isize {
+    unsafe { syscall!(UnsetEnv, key.as_ptr(), key.len()) as isize }
+}
+
+#[inline]
+pub fn os_audio_new_context() -> isize {
+    unsafe { syscall!(AudioNewContext) as isize }
+}
+
+/// Start a note described by `OsNoteParams`, returns the handle of the note.
+#[inline]
+pub fn os_audio_note_on(ctx: usize, params: &[u8]) -> isize {
+    unsafe { syscall!(AudioNoteOn, ctx, params.as_ptr(), params.len()) as isize }
+}
+
+#[inline]
+pub fn os_audio_open_stream(ctx: usize, sample_rate: u32) -> isize {
+    unsafe { syscall!(AudioOpenStream, ctx, sample_rate) as isize }
+}
+
+/// Queue the 16bit little endian samples, returns the number of samples queued.
+#[inline]
+pub fn os_audio_write(stream: usize, samples: &[u8]) -> isize {
+    unsafe { syscall!(AudioWrite, stream, samples.as_ptr(), samples.len()) as isize }
+}
+
+#[inline]
+pub fn os_audio_close(handle: usize) {
+    unsafe { syscall!(AudioClose, handle) };
+}
+
+/// Get the ids of the connected game inputs as 32bit little endian values, returns the number of inputs.
+#[inline]
+pub fn os_game_enum_inputs(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(GameEnumInputs, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_game_get_state(id: u32, buf: &mut [u8]) -> isize {
+    unsafe { syscall!(GameGetState, id, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+/// Get a connection event, returns 0 if there are no events.
+#[inline]
+pub fn os_game_poll_event(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(GamePollEvent, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_clipboard_format() -> u32 {
+    unsafe { syscall!(ClipboardFormat) as u32 }
+}
+
+/// Get the text in the clipboard, returns the length of the whole text.
+#[inline]
+pub fn os_clipboard_get_text(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(ClipboardGetText, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+#[inline]
+pub fn os_clipboard_set_text(text: &str) -> isize {
+    unsafe { syscall!(ClipboardSetText, text.as_ptr(), text.len()) as isize }
+}
+
+/// Get the bitmap in the clipboard, returns the size of the whole encoded bitmap.
+#[inline]
+pub fn os_clipboard_get_bitmap(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(ClipboardGetBitmap, buf.as_mut_ptr(), buf.len()) as isize }
+}
+
+/// Put the bitmap of 32bit little endian pixels in the clipboard.
+#[inline]
+pub fn os_clipboard_set_bitmap(width: usize, height: usize, pixels: &[u8]) -> isize {
+    unsafe { syscall!(ClipboardSetBitmap, width, height, pixels.as_ptr(), pixels.len()) as isize }
+}
+
+/// Create a named channel that can hold the specified number of messages.
+#[inline]
+pub fn os_channel_create(name: &str, capacity: usize) -> isize {
+    unsafe { syscall!(ChannelCreate, name.as_ptr(), name.len(), capacity) as isize }
+}
+
+#[inline]
+pub fn os_channel_connect(name: &str) -> isize {
+    unsafe { syscall!(ChannelConnect, name.as_ptr(), name.len()) as isize }
+}
+
+#[inline]
+pub fn os_channel_send(handle: usize, message: &[u8]) -> isize {
+    unsafe { syscall!(ChannelSend, handle, message.as_ptr(), message.len()) as isize }
+}
+
+/// Receive a message from the channel, returns the size of the whole message.
+///
+/// The rest of the message is discarded if the buffer is smaller than the message.
+#[inline]
+pub fn os_channel_receive(handle: usize, buf: &mut [u8], flags: u32) -> isize {
+    unsafe { syscall!(ChannelReceive, handle, buf.as_mut_ptr(), buf.len(), flags) as isize }
+}
+
+#[inline]
+pub fn os_channel_close(handle: usize) -> isize {
+    unsafe { syscall!(ChannelClose, handle) as isize }
+}
+
+#[inline]
+pub fn os_heap_stats(buf: &mut [u8]) -> isize {
+    unsafe { syscall!(HeapStats, buf.as_mut_ptr(), buf.len()) as isize }
+}
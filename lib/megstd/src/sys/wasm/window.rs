@@ -1,8 +1,13 @@
 // MEG-OS Window API
 
 pub use crate::drawing::*;
-use crate::sys::megos;
+pub use crate::io::hid::{Modifier, MouseButton, Usage};
+use crate::sys::megos::{
+    self,
+    window::{back_buffer, message, OsWindowMessage},
+};
 use crate::sys::syscall::{self, OsDrawShape};
+use core::time::Duration;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct WindowHandle(pub usize);
@@ -60,6 +65,208 @@ impl Window {
             c => Some(unsafe { core::char::from_u32_unchecked(c as u32) }),
         }
     }
+
+    /// Waits for the next message, returns `None` if the window no longer exists.
+    #[inline]
+    pub fn get_message(&self) -> Option<WindowMessage> {
+        let mut buf = [0; OsWindowMessage::SIZE];
+        match syscall::os_get_message(self.handle.0, &mut buf) {
+            1 => OsWindowMessage::decode(&buf).and_then(WindowMessage::from_os),
+            _ => None,
+        }
+    }
+
+    /// Reads the next message without waiting.
+    #[inline]
+    pub fn peek_message(&self) -> Option<WindowMessage> {
+        let mut buf = [0; OsWindowMessage::SIZE];
+        match syscall::os_peek_message(self.handle.0, &mut buf) {
+            1 => OsWindowMessage::decode(&buf).and_then(WindowMessage::from_os),
+            _ => None,
+        }
+    }
+
+    /// Posts `WindowMessage::Timer(timer_id)` to the window once after the duration.
+    #[inline]
+    pub fn set_timer(&self, timer_id: usize, duration: Duration) {
+        syscall::os_set_timer(
+            self.handle.0,
+            timer_id,
+            duration.as_millis().min(u32::MAX as u128) as u32,
+        );
+    }
+
+    /// Allocates a 32bit bitmap shared with the window as its contents,
+    /// returns `None` if the window does not accept the size.
+    #[inline]
+    pub fn back_buffer32(&self, size: Size) -> Option<BackBuffer<OwnedBitmap32>> {
+        BackBuffer::new(
+            self.handle,
+            OwnedBitmap32::new(size, TrueColor::from_rgb(0)),
+            back_buffer::ARGB32,
+        )
+    }
+
+    /// Allocates an 8bit indexed bitmap shared with the window as its contents,
+    /// returns `None` if the window does not accept the size.
+    #[inline]
+    pub fn back_buffer8(&self, size: Size) -> Option<BackBuffer<OwnedBitmap8>> {
+        BackBuffer::new(
+            self.handle,
+            OwnedBitmap8::new(size, IndexedColor::BLACK),
+            back_buffer::INDEXED8,
+        )
+    }
+
+    /// Dispatches messages to the handler until it returns `false` or the window is closed.
+    ///
+    /// `WindowMessage::Close` is passed to the handler before the loop ends.
+    pub fn run<F>(&self, mut f: F)
+    where
+        F: FnMut(&Self, WindowMessage) -> bool,
+    {
+        while let Some(message) = self.get_message() {
+            let is_close = message == WindowMessage::Close;
+            if !f(self, message) || is_close {
+                break;
+            }
+        }
+    }
+}
+
+/// Bitmap in the application memory that the system reads directly when presenting
+///
+/// Drawing to the bitmap costs no system calls, and `present` shows the changes at once.
+/// The window stops using the bitmap when the back buffer is dropped.
+pub struct BackBuffer<T: RasterImage> {
+    window: WindowHandle,
+    bitmap: T,
+}
+
+impl<T: RasterImage> BackBuffer<T> {
+    fn new(window: WindowHandle, bitmap: T, format: u32) -> Option<Self> {
+        let size = bitmap.size();
+        let result = syscall::os_set_back_buffer(
+            window.0,
+            bitmap.slice().as_ptr() as *const u8,
+            size.width as usize,
+            size.height as usize,
+            format,
+        );
+        (result == 0).then(|| Self { window, bitmap })
+    }
+
+    #[inline]
+    pub const fn bitmap(&self) -> &T {
+        &self.bitmap
+    }
+
+    #[inline]
+    pub fn bitmap_mut(&mut self) -> &mut T {
+        &mut self.bitmap
+    }
+
+    /// Shows the specified rectangle of the bitmap in the window.
+    #[inline]
+    pub fn present(&self, rect: Rect) {
+        syscall::os_present(
+            self.window.0,
+            rect.min_x() as usize,
+            rect.min_y() as usize,
+            rect.width() as usize,
+            rect.height() as usize,
+        );
+    }
+
+    /// Shows the whole bitmap in the window.
+    #[inline]
+    pub fn present_all(&self) {
+        self.present(self.bitmap.bounds());
+    }
+}
+
+impl<T: RasterImage> Drop for BackBuffer<T> {
+    #[inline]
+    fn drop(&mut self) {
+        syscall::os_set_back_buffer(self.window.0, core::ptr::null(), 0, 0, 0);
+    }
+}
+
+/// Messages received by the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMessage {
+    /// Requested to close the window
+    Close,
+    Back,
+    /// Needs to be redrawn
+    Draw,
+    Activated,
+    Deactivated,
+    /// Raw keyboard event
+    Key(KeyEvent),
+    /// Unicode converted keyboard event
+    Char(char),
+    MouseMove(MouseEvent),
+    MouseDown(MouseEvent),
+    MouseUp(MouseEvent),
+    MouseEnter(MouseEvent),
+    MouseLeave(MouseEvent),
+    Timer(usize),
+    User(usize),
+    /// The size of the content area has been changed
+    Resized(Size),
+    /// The data of the clipboard has been changed
+    ClipboardChanged,
+}
+
+impl WindowMessage {
+    fn from_os(val: OsWindowMessage) -> Option<Self> {
+        let [p1, p2, p3] = val.params;
+        let mouse = || MouseEvent {
+            point: Point::new(p1 as i32 as isize, p2 as i32 as isize),
+            buttons: MouseButton(p3 as u8),
+            event_buttons: MouseButton((p3 >> 8) as u8),
+        };
+        Some(match val.kind {
+            message::CLOSE => Self::Close,
+            message::BACK => Self::Back,
+            message::DRAW => Self::Draw,
+            message::ACTIVATED => Self::Activated,
+            message::DEACTIVATED => Self::Deactivated,
+            message::KEY => Self::Key(KeyEvent {
+                usage: Usage(p1 as u8),
+                modifier: Modifier((p1 >> 16) as u8),
+                is_break: (p1 & 0x8000_0000) != 0,
+            }),
+            message::CHAR => Self::Char(char::from_u32(p1)?),
+            message::MOUSE_MOVE => Self::MouseMove(mouse()),
+            message::MOUSE_DOWN => Self::MouseDown(mouse()),
+            message::MOUSE_UP => Self::MouseUp(mouse()),
+            message::MOUSE_ENTER => Self::MouseEnter(mouse()),
+            message::MOUSE_LEAVE => Self::MouseLeave(mouse()),
+            message::TIMER => Self::Timer(p1 as usize),
+            message::USER => Self::User(p1 as usize),
+            message::RESIZED => Self::Resized(Size::new(p1 as isize, p2 as isize)),
+            message::CLIPBOARD_CHANGED => Self::ClipboardChanged,
+            _ => return None,
+        })
+    }
+}
+
+/// Keyboard event with the usage defined by the USB-HID specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub usage: Usage,
+    pub modifier: Modifier,
+    pub is_break: bool,
+}
+
+/// Mouse event in the coordinates of the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub point: Point,
+    pub buttons: MouseButton,
+    pub event_buttons: MouseButton,
 }
 
 pub struct DrawingContext {
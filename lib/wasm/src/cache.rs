@@ -0,0 +1,381 @@
+//! Precompiled Module Cache
+//!
+//! A cache file holds a module after its code blocks have been lowered to intermediate code,
+//! so that the next launch can skip decoding and code generation.
+//! Imported functions are not stored and are resolved again when loading.
+
+use crate::{intcode::*, opcode::*, *};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+
+/// Precompiled module cache
+pub struct WasmCache;
+
+impl WasmCache {
+    /// Extension of cache files
+    pub const FILE_EXT: &'static str = "wac";
+
+    /// Magic number of cache files
+    const MAGIC: [u8; 4] = *b"\0wac";
+
+    /// Format version, this must be increased whenever the layout of the cache or
+    /// the intermediate code changes.
+    const FORMAT_VERSION: u64 = 1;
+
+    /// Returns the 64-bit FNV-1a hash of the module, which is used as the cache key.
+    pub fn hash(blob: &[u8]) -> u64 {
+        let mut hash = 0xCBF2_9CE4_8422_2325u64;
+        for byte in blob {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        hash
+    }
+
+    /// Returns the file name of the cache for the module
+    pub fn file_name(blob: &[u8]) -> String {
+        format!("{:016x}.{}", Self::hash(blob), Self::FILE_EXT)
+    }
+
+    /// Features of the interpreter that affect the intermediate code
+    const fn features() -> u64 {
+        let mut features = 0;
+        if cfg!(feature = "float") {
+            features |= 1;
+        }
+        if cfg!(feature = "float64") {
+            features |= 2;
+        }
+        features
+    }
+
+    /// Encodes a module loaded from `blob` into the cache format.
+    pub fn encode(module: &WasmModule, blob: &[u8]) -> Vec<u8> {
+        let mut vec = Vec::new();
+        vec.extend_from_slice(&Self::MAGIC);
+        Leb128Stream::write_unsigned(&mut vec, Self::FORMAT_VERSION);
+        Self::write_str(&mut vec, env!("CARGO_PKG_VERSION"));
+        Leb128Stream::write_unsigned(&mut vec, Self::features());
+        vec.extend_from_slice(&Self::hash(blob).to_le_bytes());
+        Leb128Stream::write_unsigned(&mut vec, blob.len() as u64);
+        module.write_cache(&mut vec);
+        vec
+    }
+
+    /// Instantiates a module from the cache of `blob`.
+    ///
+    /// Returns [`WasmDecodeErrorKind::StaleCache`] if the cache was not made from the same module
+    /// by the same version of the runtime.
+    pub fn instantiate<F>(
+        cache: &[u8],
+        blob: &[u8],
+        resolver: F,
+    ) -> Result<WasmModule, WasmDecodeErrorKind>
+    where
+        F: FnMut(&str, &str, &WasmType) -> ImportResult<WasmDynFunc> + Copy,
+    {
+        let mut loader = WasmLoader::new();
+        loader.load_cache(cache, blob, resolver)?;
+        Ok(loader.into_module())
+    }
+
+    #[inline]
+    fn write_str(vec: &mut Vec<u8>, s: &str) {
+        Leb128Stream::write_unsigned(vec, s.len() as u64);
+        vec.extend_from_slice(s.as_bytes());
+    }
+
+    /// Checks the header of the cache and returns the stream of its body.
+    pub(crate) fn validate<'a>(
+        cache: &'a [u8],
+        blob: &[u8],
+    ) -> Result<Leb128Stream<'a>, WasmDecodeErrorKind> {
+        let mut stream = Leb128Stream::from_slice(cache);
+        let is_valid = stream
+            .get_bytes(Self::MAGIC.len())
+            .map(|v| v == Self::MAGIC)
+            .unwrap_or(false)
+            && stream.read_unsigned() == Ok(Self::FORMAT_VERSION)
+            && stream.get_string() == Ok(env!("CARGO_PKG_VERSION"))
+            && stream.read_unsigned() == Ok(Self::features())
+            && stream
+                .get_bytes(8)
+                .map(|v| v == Self::hash(blob).to_le_bytes())
+                == Ok(true)
+            && stream.read_unsigned() == Ok(blob.len() as u64);
+        if is_valid {
+            Ok(stream)
+        } else {
+            Err(WasmDecodeErrorKind::StaleCache)
+        }
+    }
+}
+
+/// Items that can be stored in the cache
+pub(crate) trait WasmCacheItem: Sized {
+    fn write_cache(&self, vec: &mut Vec<u8>);
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind>;
+}
+
+impl WasmCacheItem for bool {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.push(*self as u8);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_byte().map(|v| v != 0)
+    }
+}
+
+impl WasmCacheItem for u8 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.push(*self);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_byte()
+    }
+}
+
+impl WasmCacheItem for u32 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_unsigned(vec, *self as u64);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_unsigned().and_then(|v| {
+            v.try_into()
+                .map_err(|_| WasmDecodeErrorKind::UnexpectedToken)
+        })
+    }
+}
+
+impl WasmCacheItem for usize {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_unsigned(vec, *self as u64);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_unsigned().and_then(|v| {
+            v.try_into()
+                .map_err(|_| WasmDecodeErrorKind::UnexpectedToken)
+        })
+    }
+}
+
+impl WasmCacheItem for i32 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_signed(vec, *self as i64);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_signed().and_then(|v| {
+            v.try_into()
+                .map_err(|_| WasmDecodeErrorKind::UnexpectedToken)
+        })
+    }
+}
+
+impl WasmCacheItem for i64 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_signed(vec, *self);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_signed()
+    }
+}
+
+impl WasmCacheItem for f32 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.extend_from_slice(&self.to_le_bytes());
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_f32()
+    }
+}
+
+impl WasmCacheItem for f64 {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.extend_from_slice(&self.to_le_bytes());
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_f64()
+    }
+}
+
+impl WasmCacheItem for String {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        WasmCache::write_str(vec, self);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.get_string().map(|v| v.into())
+    }
+}
+
+impl<T: WasmCacheItem> WasmCacheItem for Option<T> {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        match self {
+            Some(v) => {
+                vec.push(1);
+                v.write_cache(vec);
+            }
+            None => vec.push(0),
+        }
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        match stream.read_byte()? {
+            0 => Ok(None),
+            1 => T::read_cache(stream).map(|v| Some(v)),
+            _ => Err(WasmDecodeErrorKind::UnexpectedToken),
+        }
+    }
+}
+
+impl<T: WasmCacheItem> WasmCacheItem for Vec<T> {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_unsigned(vec, self.len() as u64);
+        for item in self {
+            item.write_cache(vec);
+        }
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let len = usize::read_cache(stream)?;
+        // Each item occupies at least one byte
+        if len > stream.len() - stream.position() {
+            return Err(WasmDecodeErrorKind::UnexpectedEof);
+        }
+        let mut result = Vec::with_capacity(len);
+        for _ in 0..len {
+            result.push(T::read_cache(stream)?);
+        }
+        Ok(result)
+    }
+}
+
+impl<T: WasmCacheItem> WasmCacheItem for Box<[T]> {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_unsigned(vec, self.len() as u64);
+        for item in self.iter() {
+            item.write_cache(vec);
+        }
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        Vec::read_cache(stream).map(|v| v.into_boxed_slice())
+    }
+}
+
+impl<T: WasmCacheItem, U: WasmCacheItem> WasmCacheItem for (T, U) {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.0.write_cache(vec);
+        self.1.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let a = T::read_cache(stream)?;
+        let b = U::read_cache(stream)?;
+        Ok((a, b))
+    }
+}
+
+impl WasmCacheItem for LocalVarIndex {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.as_usize().write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        usize::read_cache(stream).map(LocalVarIndex::new)
+    }
+}
+
+impl WasmCacheItem for StackLevel {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.as_usize().write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        usize::read_cache(stream).map(StackLevel::from)
+    }
+}
+
+impl WasmCacheItem for WasmOpcode {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.push(self.leading_byte());
+        if let Some(trail_code) = self.trail_code() {
+            trail_code.write_cache(vec);
+        }
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream.read_opcode()
+    }
+}
+
+impl WasmCacheItem for WasmValType {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        vec.push(*self as u8);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        stream
+            .read_byte()
+            .and_then(|v| WasmValType::from_u64(v as u64))
+    }
+}
+
+impl WasmCacheItem for WasmImc {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.position.write_cache(vec);
+        self.opcode.write_cache(vec);
+        self.stack_level.write_cache(vec);
+        self.mnemonic.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let position = u32::read_cache(stream)?;
+        let opcode = WasmOpcode::read_cache(stream)?;
+        let stack_level = StackLevel::read_cache(stream)?;
+        let mnemonic = WasmIntMnemonic::read_cache(stream)?;
+        Ok(Self {
+            position,
+            opcode,
+            mnemonic,
+            stack_level,
+        })
+    }
+}
@@ -0,0 +1,431 @@
+//! WebAssembly Debugger
+
+use crate::{opcode::WasmOpcode, *};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+/// Hooks called by the interpreter while a debugger is attached.
+pub trait WasmDebugHook {
+    /// Returns whether the execution should stop before the code at `position` of the function.
+    fn should_break(&mut self, func_index: usize, position: usize) -> bool;
+
+    /// Called when the execution stops, returns what to do next.
+    fn on_break(&mut self, frame: &WasmDebugFrame) -> WasmDebugAction;
+
+    /// Called when a function is entered.
+    fn on_enter(&mut self, _func_index: usize) {}
+
+    /// Called when a function is returned.
+    fn on_leave(&mut self, _func_index: usize) {}
+}
+
+/// What to do after a break
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmDebugAction {
+    /// Resume the execution
+    Continue,
+    /// Terminate the execution
+    Abort,
+}
+
+/// A snapshot of the interpreter state at a break
+pub struct WasmDebugFrame<'a> {
+    pub module: &'a WasmModule,
+    pub func_index: usize,
+    pub position: usize,
+    pub file_position: usize,
+    pub opcode: WasmOpcode,
+    pub locals: &'a [WasmUnsafeValue],
+    pub value_stack: &'a [WasmUnsafeValue],
+    pub stack_level: usize,
+}
+
+impl WasmDebugFrame<'_> {
+    /// Returns the types of parameters and local variables of the current function
+    pub fn local_types(&self) -> &[WasmValType] {
+        self.module
+            .functions()
+            .get(self.func_index)
+            .and_then(|v| v.code_block())
+            .map(|v| v.local_types())
+            .unwrap_or(&[])
+    }
+}
+
+/// Console for the debugger front end
+pub trait WasmDebugConsole: Write {
+    /// Reads a command line, returns `None` at the end of input.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// Breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmBreakpoint {
+    pub func_index: usize,
+    /// Position in the function body, `None` means the first code of the function.
+    pub position: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    /// Run until a breakpoint
+    Run,
+    /// Stop at the next code
+    Step,
+    /// Stop at the next code in the same or outer function
+    Next(usize),
+    /// Stop when the current function returns
+    Finish(usize),
+}
+
+/// Interactive debugger with a line-oriented command protocol
+///
+/// The same commands are used by the `wdb` command in the OS and by the host runner.
+pub struct WasmDebugger<T: WasmDebugConsole> {
+    console: T,
+    breakpoints: Vec<WasmBreakpoint>,
+    call_stack: Vec<usize>,
+    mode: StepMode,
+    last_command: String,
+    is_entry: bool,
+}
+
+impl<T: WasmDebugConsole> WasmDebugger<T> {
+    const PROMPT: &'static str = "(wdb) ";
+    const DEFAULT_DUMP_SIZE: usize = 64;
+
+    /// Creates a new debugger, which stops before the first code.
+    #[inline]
+    pub const fn new(console: T) -> Self {
+        Self {
+            console,
+            breakpoints: Vec::new(),
+            call_stack: Vec::new(),
+            mode: StepMode::Step,
+            last_command: String::new(),
+            is_entry: false,
+        }
+    }
+
+    #[inline]
+    pub fn console(&mut self) -> &mut T {
+        &mut self.console
+    }
+
+    #[inline]
+    pub fn breakpoints(&self) -> &[WasmBreakpoint] {
+        self.breakpoints.as_slice()
+    }
+
+    /// Adds the breakpoint if it does not exist, returns the index of the breakpoint
+    #[inline]
+    pub fn add_breakpoint(&mut self, breakpoint: WasmBreakpoint) -> usize {
+        match self.breakpoints.iter().position(|v| *v == breakpoint) {
+            Some(index) => index,
+            None => {
+                self.breakpoints.push(breakpoint);
+                self.breakpoints.len() - 1
+            }
+        }
+    }
+
+    /// Executes a command, returns an action if the execution should be resumed.
+    pub fn execute(&mut self, line: &str, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        let mut args = line.split_whitespace();
+        let command = args.next()?;
+        let args = args.collect::<Vec<_>>();
+        let depth = self.call_stack.len();
+        let result = match command {
+            "c" | "continue" => {
+                self.mode = StepMode::Run;
+                Some(WasmDebugAction::Continue)
+            }
+            "s" | "step" => {
+                self.mode = StepMode::Step;
+                Some(WasmDebugAction::Continue)
+            }
+            "n" | "next" => {
+                self.mode = StepMode::Next(depth);
+                Some(WasmDebugAction::Continue)
+            }
+            "fin" | "finish" => {
+                self.mode = StepMode::Finish(depth);
+                Some(WasmDebugAction::Continue)
+            }
+            "q" | "quit" => Some(WasmDebugAction::Abort),
+            "b" | "break" => self.cmd_break(frame, &args),
+            "d" | "delete" => self.cmd_delete(&args),
+            "bl" | "breakpoints" => self.cmd_breakpoints(frame),
+            "bt" | "backtrace" => self.cmd_backtrace(frame),
+            "w" | "where" => {
+                self.print_location(frame);
+                None
+            }
+            "l" | "locals" => self.cmd_locals(frame),
+            "st" | "stack" => self.cmd_stack(frame),
+            "g" | "globals" => self.cmd_globals(frame),
+            "x" | "memory" => self.cmd_memory(frame, &args),
+            "h" | "help" | "?" => self.cmd_help(),
+            _ => {
+                let _ = writeln!(self.console, "unknown command: {}", command);
+                None
+            }
+        };
+        self.last_command = line.into();
+        result
+    }
+
+    fn function_name(module: &WasmModule, func_index: usize) -> Option<&str> {
+        module.names().and_then(|v| v.func_by_index(func_index))
+    }
+
+    /// Parses a function by its name, `$index` or index
+    fn parse_function(module: &WasmModule, s: &str) -> Option<usize> {
+        if let Some(index) = s.strip_prefix('$').and_then(|v| v.parse().ok()) {
+            return Some(index);
+        }
+        if let Some(names) = module.names() {
+            if let Some((index, _)) = names.functions().iter().find(|(_, name)| name == s) {
+                return Some(*index);
+            }
+        }
+        if let Some(index) = module.exports().iter().find_map(|v| match v.index() {
+            WasmExportIndex::Function(index) if v.name() == s => Some(index),
+            _ => None,
+        }) {
+            return Some(index);
+        }
+        s.parse().ok()
+    }
+
+    fn parse_number(s: &str) -> Option<usize> {
+        match s.strip_prefix("0x") {
+            Some(v) => usize::from_str_radix(v, 16).ok(),
+            None => s.parse().ok(),
+        }
+    }
+
+    fn print_location(&mut self, frame: &WasmDebugFrame) {
+        let _ = write!(self.console, "${}", frame.func_index);
+        if let Some(name) = Self::function_name(frame.module, frame.func_index) {
+            let _ = write!(self.console, " {}", name);
+        }
+        let _ = writeln!(
+            self.console,
+            " +{} (0x{:x}): {}",
+            frame.position, frame.file_position, frame.opcode
+        );
+    }
+
+    fn cmd_break(&mut self, frame: &WasmDebugFrame, args: &[&str]) -> Option<WasmDebugAction> {
+        let Some(arg) = args.first() else {
+            let _ = writeln!(self.console, "usage: break FUNCTION[+OFFSET]");
+            return None;
+        };
+        let (func, position) = match arg.split_once('+') {
+            Some((func, offset)) => (func, Self::parse_number(offset)),
+            None => (*arg, None),
+        };
+        match Self::parse_function(frame.module, func) {
+            Some(func_index) if func_index < frame.module.functions().len() => {
+                let index = self.add_breakpoint(WasmBreakpoint {
+                    func_index,
+                    position,
+                });
+                let _ = writeln!(self.console, "breakpoint #{} at ${}", index, func_index);
+            }
+            _ => {
+                let _ = writeln!(self.console, "no such function: {}", func);
+            }
+        }
+        None
+    }
+
+    fn cmd_delete(&mut self, args: &[&str]) -> Option<WasmDebugAction> {
+        match args.first().and_then(|v| v.parse::<usize>().ok()) {
+            Some(index) if index < self.breakpoints.len() => {
+                self.breakpoints.remove(index);
+            }
+            Some(_) => {
+                let _ = writeln!(self.console, "no such breakpoint");
+            }
+            None => self.breakpoints.clear(),
+        }
+        None
+    }
+
+    fn cmd_breakpoints(&mut self, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        for (index, bp) in self.breakpoints.iter().enumerate() {
+            let _ = write!(self.console, "#{} ${}", index, bp.func_index);
+            if let Some(name) = Self::function_name(frame.module, bp.func_index) {
+                let _ = write!(self.console, " {}", name);
+            }
+            match bp.position {
+                Some(position) => {
+                    let _ = writeln!(self.console, " +{}", position);
+                }
+                None => {
+                    let _ = writeln!(self.console);
+                }
+            }
+        }
+        None
+    }
+
+    fn cmd_backtrace(&mut self, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        for (depth, func_index) in self.call_stack.iter().rev().enumerate() {
+            let _ = write!(self.console, "#{} ${}", depth, func_index);
+            if let Some(name) = Self::function_name(frame.module, *func_index) {
+                let _ = write!(self.console, " {}", name);
+            }
+            let _ = writeln!(self.console);
+        }
+        None
+    }
+
+    fn write_value(&mut self, val_type: Option<WasmValType>, value: &WasmUnsafeValue) {
+        let _ = match val_type {
+            Some(WasmValType::I32) => {
+                let v = unsafe { value.get_i32() };
+                write!(self.console, "i32 {} (0x{:08x})", v, v)
+            }
+            Some(WasmValType::I64) => {
+                let v = unsafe { value.get_i64() };
+                write!(self.console, "i64 {} (0x{:016x})", v, v)
+            }
+            Some(WasmValType::F32) => write!(self.console, "f32 {}", unsafe { value.get_f32() }),
+            Some(WasmValType::F64) => write!(self.console, "f64 {}", unsafe { value.get_f64() }),
+            None => write!(self.console, "0x{:016x}", unsafe { value.get_u64() }),
+        };
+    }
+
+    fn cmd_locals(&mut self, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        let local_types = frame.local_types();
+        for (index, val_type) in local_types.iter().enumerate() {
+            let Some(value) = frame.locals.get(index) else {
+                break;
+            };
+            let _ = write!(self.console, "local[{}] = ", index);
+            self.write_value(Some(*val_type), value);
+            let _ = writeln!(self.console);
+        }
+        None
+    }
+
+    fn cmd_stack(&mut self, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        // The slot at the stack level holds the first operand of the current code
+        let len = usize::min(frame.stack_level + 1, frame.value_stack.len());
+        for (index, value) in frame.value_stack[..len].iter().enumerate() {
+            let _ = write!(self.console, "[{}] ", index);
+            self.write_value(None, value);
+            if index == frame.stack_level {
+                let _ = write!(self.console, " <-");
+            }
+            let _ = writeln!(self.console);
+        }
+        None
+    }
+
+    fn cmd_globals(&mut self, frame: &WasmDebugFrame) -> Option<WasmDebugAction> {
+        for (index, global) in frame.module.globals().iter().enumerate() {
+            let _ = write!(self.console, "global[{}]", index);
+            if let Some(name) = frame.module.names().and_then(|v| v.global_by_index(index)) {
+                let _ = write!(self.console, " {}", name);
+            }
+            let value = WasmUnsafeValue::from(global.value());
+            let _ = write!(self.console, " = ");
+            self.write_value(Some(global.val_type()), &value);
+            let _ = writeln!(self.console);
+        }
+        None
+    }
+
+    fn cmd_memory(&mut self, frame: &WasmDebugFrame, args: &[&str]) -> Option<WasmDebugAction> {
+        let Some(base) = args.first().and_then(|v| Self::parse_number(v)) else {
+            let _ = writeln!(self.console, "usage: x ADDRESS [LENGTH]");
+            return None;
+        };
+        let len = args
+            .get(1)
+            .and_then(|v| Self::parse_number(v))
+            .unwrap_or(Self::DEFAULT_DUMP_SIZE);
+        let Some(slice) = frame.module.memory(0).and_then(|v| v.slice(base, len).ok()) else {
+            let _ = writeln!(self.console, "out of bounds");
+            return None;
+        };
+        for (index, line) in slice.chunks(16).enumerate() {
+            let _ = write!(self.console, "{:08x}:", base + index * 16);
+            for byte in line {
+                let _ = write!(self.console, " {:02x}", byte);
+            }
+            for _ in line.len()..16 {
+                let _ = write!(self.console, "   ");
+            }
+            let _ = write!(self.console, "  ");
+            for byte in line {
+                let c = match *byte {
+                    0x20..=0x7E => *byte as char,
+                    _ => '.',
+                };
+                let _ = write!(self.console, "{}", c);
+            }
+            let _ = writeln!(self.console);
+        }
+        None
+    }
+
+    fn cmd_help(&mut self) -> Option<WasmDebugAction> {
+        let _ = self.console.write_str(
+            "c(ontinue) s(tep) n(ext) fin(ish) q(uit)\n\
+             b(reak) FUNCTION[+OFFSET]  d(elete) [N]  bl (breakpoints)\n\
+             bt (backtrace)  w(here)  l(ocals)  st(ack)  g(lobals)  x ADDRESS [LENGTH]\n",
+        );
+        None
+    }
+}
+
+impl<T: WasmDebugConsole> WasmDebugHook for WasmDebugger<T> {
+    fn should_break(&mut self, func_index: usize, position: usize) -> bool {
+        let depth = self.call_stack.len();
+        let is_entry = core::mem::replace(&mut self.is_entry, false);
+        match self.mode {
+            StepMode::Step => return true,
+            StepMode::Next(v) if depth <= v => return true,
+            StepMode::Finish(v) if depth < v => return true,
+            _ => (),
+        }
+        self.breakpoints.iter().any(|bp| {
+            bp.func_index == func_index
+                && match bp.position {
+                    Some(v) => v == position,
+                    None => is_entry,
+                }
+        })
+    }
+
+    fn on_break(&mut self, frame: &WasmDebugFrame) -> WasmDebugAction {
+        self.print_location(frame);
+        loop {
+            let _ = self.console.write_str(Self::PROMPT);
+            let Some(line) = self.console.read_line() else {
+                return WasmDebugAction::Abort;
+            };
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => line.into(),
+            };
+            if let Some(action) = self.execute(&line, frame) {
+                return action;
+            }
+        }
+    }
+
+    fn on_enter(&mut self, func_index: usize) {
+        self.call_stack.push(func_index);
+        self.is_entry = true;
+    }
+
+    fn on_leave(&mut self, _func_index: usize) {
+        self.call_stack.pop();
+    }
+}
@@ -0,0 +1,183 @@
+//! Heap on the linear memory
+//!
+//! The runtime manages the heap for the allocation requests of the application.
+//! The heap takes the pages above the current size of the linear memory,
+//! and grows the memory on demand until it reaches the limit.
+
+use crate::*;
+use alloc::collections::BTreeMap;
+use core::{alloc::Layout, num::NonZeroU32};
+
+/// First fit allocator on the linear memory
+pub struct WasmHeap {
+    /// Maximum number of pages of the linear memory
+    limit: usize,
+    /// Size of the memory taken by the heap
+    heap_size: usize,
+    /// Free blocks by the base address
+    free: BTreeMap<u32, u32>,
+    /// Allocated blocks by the base address
+    allocated: BTreeMap<u32, u32>,
+    used: usize,
+}
+
+impl WasmHeap {
+    /// Minimum alignment and granularity of the blocks
+    pub const MIN_ALIGN: usize = 16;
+
+    /// Creates a heap that can grow the linear memory up to the specified number of pages
+    #[inline]
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            heap_size: 0,
+            free: BTreeMap::new(),
+            allocated: BTreeMap::new(),
+            used: 0,
+        }
+    }
+
+    /// Returns the maximum number of pages of the linear memory
+    #[inline]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Allocates the block, returns `None` if the memory cannot grow any more.
+    pub fn alloc(&mut self, memory: &WasmMemory, layout: Layout) -> Option<NonZeroU32> {
+        let align = layout.align().max(Self::MIN_ALIGN);
+        let size = layout
+            .size()
+            .max(1)
+            .checked_next_multiple_of(Self::MIN_ALIGN)?;
+
+        if let Some(result) = self.alloc_free(size, align) {
+            return Some(result);
+        }
+
+        // The free block at the end of the memory can be extended
+        let memory_end = memory.size() as usize * WasmMemory::PAGE_SIZE;
+        let last_free = self
+            .free
+            .last_key_value()
+            .filter(|(base, size)| **base as usize + **size as usize == memory_end)
+            .map(|(_, size)| *size as usize)
+            .unwrap_or(0);
+        let required = size.checked_add(align)?.saturating_sub(last_free);
+        let delta = required.div_ceil(WasmMemory::PAGE_SIZE);
+        if memory.size() as usize + delta > self.limit {
+            return None;
+        }
+        let old_size = memory.grow(delta as i32);
+        if old_size < 0 {
+            return None;
+        }
+        let base = old_size as usize * WasmMemory::PAGE_SIZE;
+        let len = delta * WasmMemory::PAGE_SIZE;
+        self.heap_size += len;
+        self.insert_free(base as u32, len as u32);
+
+        self.alloc_free(size, align)
+    }
+
+    fn alloc_free(&mut self, size: usize, align: usize) -> Option<NonZeroU32> {
+        let (block_base, block_size, base) =
+            self.free.iter().find_map(|(&block_base, &block_size)| {
+                // Address 0 is reserved for null
+                let base = (block_base as usize).max(align).next_multiple_of(align);
+                (base + size <= block_base as usize + block_size as usize)
+                    .then_some((block_base, block_size, base))
+            })?;
+        self.free.remove(&block_base);
+        if base > block_base as usize {
+            self.free
+                .insert(block_base, (base - block_base as usize) as u32);
+        }
+        let end = base + size;
+        let block_end = block_base as usize + block_size as usize;
+        if end < block_end {
+            self.free.insert(end as u32, (block_end - end) as u32);
+        }
+        self.allocated.insert(base as u32, size as u32);
+        self.used += size;
+        NonZeroU32::new(base as u32)
+    }
+
+    /// Deallocates the block, returns `false` if the block was not allocated by the heap.
+    pub fn dealloc(&mut self, base: u32) -> bool {
+        let Some(size) = self.allocated.remove(&base) else {
+            return false;
+        };
+        self.used -= size as usize;
+        self.insert_free(base, size);
+        true
+    }
+
+    /// Inserts the free block and merges it with the adjacent blocks
+    fn insert_free(&mut self, mut base: u32, mut size: u32) {
+        if let Some((&prev_base, &prev_size)) = self.free.range(..base).next_back() {
+            if prev_base as usize + prev_size as usize == base as usize {
+                self.free.remove(&prev_base);
+                base = prev_base;
+                size += prev_size;
+            }
+        }
+        let end = base as usize + size as usize;
+        if let Ok(next_base) = u32::try_from(end) {
+            if let Some(next_size) = self.free.remove(&next_base) {
+                size += next_size;
+            }
+        }
+        self.free.insert(base, size);
+    }
+
+    /// Returns the statistics of the heap
+    pub fn stats(&self, memory: &WasmMemory) -> WasmHeapStats {
+        WasmHeapStats {
+            memory_size: memory.size() as usize * WasmMemory::PAGE_SIZE,
+            memory_limit: self.limit * WasmMemory::PAGE_SIZE,
+            heap_size: self.heap_size,
+            used: self.used,
+            n_allocs: self.allocated.len(),
+            n_free_blocks: self.free.len(),
+            largest_free: self.free.values().max().copied().unwrap_or(0) as usize,
+        }
+    }
+}
+
+/// Statistics of [`WasmHeap`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WasmHeapStats {
+    /// Size of the linear memory
+    pub memory_size: usize,
+    /// Maximum size of the linear memory
+    pub memory_limit: usize,
+    /// Size of the memory taken by the heap
+    pub heap_size: usize,
+    /// Size of the allocated blocks
+    pub used: usize,
+    /// Number of the allocated blocks
+    pub n_allocs: usize,
+    /// Number of the free blocks
+    pub n_free_blocks: usize,
+    /// Size of the largest free block
+    pub largest_free: usize,
+}
+
+impl WasmHeapStats {
+    /// Size of the free blocks
+    #[inline]
+    pub const fn free(&self) -> usize {
+        self.heap_size - self.used
+    }
+
+    /// Fragmentation of the free memory in per mille,
+    /// 0 if all free memory is in a single block
+    #[inline]
+    pub const fn fragmentation(&self) -> usize {
+        match (self.largest_free * 1000).checked_div(self.free()) {
+            Some(v) => 1000 - v,
+            None => 0,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
+    cache::*,
     opcode::{WasmOpcode, WasmSingleOpcode},
-    LocalVarIndex, StackLevel,
+    Leb128Stream, LocalVarIndex, StackLevel, WasmDecodeErrorKind,
 };
 use alloc::{boxed::Box, vec::Vec};
 
@@ -199,6 +200,199 @@ pub enum WasmIntMnemonic {
     FusedI64BrNe(usize),
 }
 
+/// Implements the cache encoding of the intermediate code.
+///
+/// Each mnemonic is tagged with a fixed number, which must not be reused.
+/// When the intermediate code changes, increase the format version of [`WasmCache`].
+macro_rules! int_mnemonic_cache_item {
+    (
+        $(
+            $(#[$meta:meta])*
+            $tag:literal => $variant:ident $( ( $( $arg:ident : $ty:ty ),* ) )?,
+        )*
+    ) => {
+        impl WasmCacheItem for WasmIntMnemonic {
+            fn write_cache(&self, vec: &mut Vec<u8>) {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        Self::$variant $( ( $( $arg ),* ) )? => {
+                            Leb128Stream::write_unsigned(vec, $tag);
+                            $( $( $arg.write_cache(vec); )* )?
+                        }
+                    )*
+                }
+            }
+
+            fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+                match stream.read_unsigned()? {
+                    $(
+                        $(#[$meta])*
+                        $tag => Ok(Self::$variant $( ( $( <$ty>::read_cache(stream)? ),* ) )?),
+                    )*
+                    _ => Err(WasmDecodeErrorKind::UnexpectedToken),
+                }
+            }
+        }
+    };
+}
+
+int_mnemonic_cache_item! {
+    0 => Undefined,
+    1 => Unreachable,
+    2 => Nop,
+    3 => Block(a: usize),
+    4 => End(a: usize),
+    5 => Br(a: usize),
+    6 => BrIf(a: usize),
+    7 => BrTable(a: Box<[usize]>),
+    8 => Return,
+    9 => Call(a: usize),
+    10 => CallIndirect(a: usize),
+    11 => Select,
+    12 => LocalGet(a: LocalVarIndex),
+    13 => LocalSet(a: LocalVarIndex),
+    14 => LocalTee(a: LocalVarIndex),
+    15 => LocalGet32(a: LocalVarIndex),
+    16 => LocalSet32(a: LocalVarIndex),
+    17 => LocalTee32(a: LocalVarIndex),
+    18 => GlobalGet(a: usize),
+    19 => GlobalSet(a: usize),
+    20 => I32Load(a: u32),
+    21 => I32Load8S(a: u32),
+    22 => I32Load8U(a: u32),
+    23 => I32Load16S(a: u32),
+    24 => I32Load16U(a: u32),
+    25 => I32Store(a: u32),
+    26 => I32Store8(a: u32),
+    27 => I32Store16(a: u32),
+    28 => I64Load(a: u32),
+    29 => I64Load8S(a: u32),
+    30 => I64Load8U(a: u32),
+    31 => I64Load16S(a: u32),
+    32 => I64Load16U(a: u32),
+    33 => I64Load32S(a: u32),
+    34 => I64Load32U(a: u32),
+    35 => I64Store(a: u32),
+    36 => I64Store8(a: u32),
+    37 => I64Store16(a: u32),
+    38 => I64Store32(a: u32),
+    #[cfg(feature = "float")]
+    39 => F32Load(a: u32),
+    #[cfg(feature = "float")]
+    40 => F32Store(a: u32),
+    #[cfg(feature = "float64")]
+    41 => F64Load(a: u32),
+    #[cfg(feature = "float64")]
+    42 => F64Store(a: u32),
+    43 => MemorySize,
+    44 => MemoryGrow,
+    45 => MemoryCopy,
+    46 => MemoryFill,
+    47 => I32Const(a: i32),
+    48 => I64Const(a: i64),
+    #[cfg(feature = "float")]
+    49 => F32Const(a: f32),
+    #[cfg(feature = "float64")]
+    50 => F64Const(a: f64),
+    51 => I32Eqz,
+    52 => I32Eq,
+    53 => I32Ne,
+    54 => I32LtS,
+    55 => I32LtU,
+    56 => I32GtS,
+    57 => I32GtU,
+    58 => I32LeS,
+    59 => I32LeU,
+    60 => I32GeS,
+    61 => I32GeU,
+    62 => I32Clz,
+    63 => I32Ctz,
+    64 => I32Popcnt,
+    65 => I32Add,
+    66 => I32Sub,
+    67 => I32Mul,
+    68 => I32DivS,
+    69 => I32DivU,
+    70 => I32RemS,
+    71 => I32RemU,
+    72 => I32And,
+    73 => I32Or,
+    74 => I32Xor,
+    75 => I32Shl,
+    76 => I32ShrS,
+    77 => I32ShrU,
+    78 => I32Rotl,
+    79 => I32Rotr,
+    80 => I64Eqz,
+    81 => I64Eq,
+    82 => I64Ne,
+    83 => I64LtS,
+    84 => I64LtU,
+    85 => I64GtS,
+    86 => I64GtU,
+    87 => I64LeS,
+    88 => I64LeU,
+    89 => I64GeS,
+    90 => I64GeU,
+    91 => I64Clz,
+    92 => I64Ctz,
+    93 => I64Popcnt,
+    94 => I64Add,
+    95 => I64Sub,
+    96 => I64Mul,
+    97 => I64DivS,
+    98 => I64DivU,
+    99 => I64RemS,
+    100 => I64RemU,
+    101 => I64And,
+    102 => I64Or,
+    103 => I64Xor,
+    104 => I64Shl,
+    105 => I64ShrS,
+    106 => I64ShrU,
+    107 => I64Rotl,
+    108 => I64Rotr,
+    109 => I64Extend8S,
+    110 => I64Extend16S,
+    111 => I64Extend32S,
+    112 => I64ExtendI32S,
+    113 => I64ExtendI32U,
+    114 => I32WrapI64,
+    115 => I32Extend8S,
+    116 => I32Extend16S,
+    117 => I32ReinterpretF32,
+    118 => I64ReinterpretF64,
+    119 => F32ReinterpretI32,
+    120 => F64ReinterpretI64,
+    121 => FusedI32SetConst(a: LocalVarIndex, b: i32),
+    122 => FusedI32AddI(a: i32),
+    123 => FusedI32SubI(a: i32),
+    124 => FusedI32AndI(a: i32),
+    125 => FusedI32OrI(a: i32),
+    126 => FusedI32XorI(a: i32),
+    127 => FusedI32ShlI(a: i32),
+    128 => FusedI32ShrSI(a: i32),
+    129 => FusedI32ShrUI(a: i32),
+    130 => FusedI64SetConst(a: LocalVarIndex, b: i64),
+    131 => FusedI64AddI(a: i64),
+    132 => FusedI64SubI(a: i64),
+    133 => FusedI32BrZ(a: usize),
+    134 => FusedI32BrEq(a: usize),
+    135 => FusedI32BrNe(a: usize),
+    136 => FusedI32BrLtS(a: usize),
+    137 => FusedI32BrLtU(a: usize),
+    138 => FusedI32BrGtS(a: usize),
+    139 => FusedI32BrGtU(a: usize),
+    140 => FusedI32BrLeS(a: usize),
+    141 => FusedI32BrLeU(a: usize),
+    142 => FusedI32BrGeS(a: usize),
+    143 => FusedI32BrGeU(a: usize),
+    144 => FusedI64BrZ(a: usize),
+    145 => FusedI64BrEq(a: usize),
+    146 => FusedI64BrNe(a: usize),
+}
+
 /// Wasm Intermediate Code
 #[derive(Debug)]
 pub struct WasmImc {
@@ -1,9 +1,12 @@
 //! WebAssembly Intermediate Code Interpreter
 
 use super::{intcode::*, opcode::WasmSingleOpcode, stack::*, wasm::*};
-use crate::opcode::WasmOpcode;
+use crate::{debug::*, opcode::WasmOpcode};
 use alloc::{borrow::ToOwned, string::String, vec::Vec};
-use core::fmt;
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 const INITIAL_VALUE_STACK_SIZE: usize = 512;
 
@@ -11,6 +14,8 @@ const INITIAL_VALUE_STACK_SIZE: usize = 512;
 pub struct WasmInterpreter<'a> {
     module: &'a WasmModule,
     func_index: usize,
+    debugger: Option<&'a mut dyn WasmDebugHook>,
+    abort_flag: Option<&'a AtomicBool>,
 }
 
 impl<'a> WasmInterpreter<'a> {
@@ -19,8 +24,29 @@ impl<'a> WasmInterpreter<'a> {
         Self {
             module,
             func_index: 0,
+            debugger: None,
+            abort_flag: None,
         }
     }
+
+    #[inline]
+    pub fn with_debugger(module: &'a WasmModule, debugger: &'a mut dyn WasmDebugHook) -> Self {
+        Self {
+            module,
+            func_index: 0,
+            debugger: Some(debugger),
+            abort_flag: None,
+        }
+    }
+
+    /// Stops the execution with [`WasmRuntimeErrorKind::Exit`] once the flag is set.
+    ///
+    /// The flag is polled only at calls and backward branches.
+    #[inline]
+    pub fn with_abort_flag(mut self, abort_flag: &'a AtomicBool) -> Self {
+        self.abort_flag = Some(abort_flag);
+        self
+    }
 }
 
 impl WasmInterpreter<'_> {
@@ -47,6 +73,28 @@ impl WasmInterpreter<'_> {
         }
     }
 
+    #[inline]
+    fn is_aborted(&self) -> bool {
+        self.abort_flag
+            .map(|v| v.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+
+    /// Moves to the branch target, polling the abort flag on backward branches
+    #[inline]
+    fn branch(
+        &self,
+        codes: &mut WasmIntermediateCodeStream,
+        code: &WasmImc,
+        target: usize,
+    ) -> Result<(), WasmRuntimeError> {
+        if target < codes.position() && self.is_aborted() {
+            return Err(self.error(WasmRuntimeErrorKind::Exit, code));
+        }
+        codes.set_position(target);
+        Ok(())
+    }
+
     #[inline]
     pub fn invoke(
         &mut self,
@@ -56,13 +104,20 @@ impl WasmInterpreter<'_> {
         result_types: &[WasmValType],
     ) -> Result<Option<WasmValue>, WasmRuntimeError> {
         let mut heap = StackHeap::with_capacity(0x10000);
-        self._interpret(
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.on_enter(func_index);
+        }
+        let result = self._interpret(
             func_index,
             code_block,
             LocalVariables::new(locals),
             result_types,
             &mut heap,
-        )
+        );
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.on_leave(func_index);
+        }
+        result
     }
 
     fn _interpret(
@@ -81,9 +136,28 @@ impl WasmInterpreter<'_> {
 
         let mut result_stack_level = StackLevel::zero();
 
-        let memory = unsafe { self.module.memory_unchecked(0) };
+        let empty_memory = WasmMemory::empty();
+        let memory = self.module.memory(0).unwrap_or(&empty_memory);
 
         while let Some(code) = codes.fetch() {
+            if let Some(debugger) = self.debugger.as_mut() {
+                if debugger.should_break(func_index, code.source_position()) {
+                    let frame = WasmDebugFrame {
+                        module: self.module,
+                        func_index,
+                        position: code.source_position(),
+                        file_position: code_block.file_position() + code.source_position(),
+                        opcode: code.opcode(),
+                        locals: locals.0,
+                        value_stack: value_stack.0,
+                        stack_level: code.base_stack_level().as_usize(),
+                    };
+                    if debugger.on_break(&frame) == WasmDebugAction::Abort {
+                        return Err(self.error(WasmRuntimeErrorKind::Exit, code));
+                    }
+                }
+            }
+
             match *code.mnemonic() {
                 WasmIntMnemonic::Unreachable
                 | WasmIntMnemonic::Nop
@@ -102,7 +176,7 @@ impl WasmInterpreter<'_> {
                 }
 
                 WasmIntMnemonic::Br(target) => {
-                    codes.set_position(target);
+                    self.branch(&mut codes, code, target)?;
                 }
                 WasmIntMnemonic::BrIf(target) => {
                     let cc = unsafe {
@@ -111,7 +185,7 @@ impl WasmInterpreter<'_> {
                             .get_bool()
                     };
                     if cc {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::BrTable(ref table) => {
@@ -120,7 +194,7 @@ impl WasmInterpreter<'_> {
                         value_stack.get_unchecked(code.base_stack_level()).get_u32() as usize
                     });
                     let target = unsafe { *table.get_unchecked(index) };
-                    codes.set_position(target);
+                    self.branch(&mut codes, code, target)?;
                 }
 
                 WasmIntMnemonic::Return => {
@@ -950,7 +1024,7 @@ impl WasmInterpreter<'_> {
                             == 0
                     };
                     if cc {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrEq(target) => {
@@ -958,7 +1032,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() == rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrNe(target) => {
@@ -966,7 +1040,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() != rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrLtS(target) => {
@@ -974,7 +1048,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_i32() < rhs.get_i32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrLtU(target) => {
@@ -982,7 +1056,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() < rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrGtS(target) => {
@@ -990,7 +1064,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_i32() > rhs.get_i32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrGtU(target) => {
@@ -998,7 +1072,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() > rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrLeS(target) => {
@@ -1006,7 +1080,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_i32() <= rhs.get_i32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrLeU(target) => {
@@ -1014,7 +1088,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() <= rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrGeS(target) => {
@@ -1022,7 +1096,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_i32() >= rhs.get_i32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI32BrGeU(target) => {
@@ -1030,7 +1104,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u32() >= rhs.get_u32() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
 
@@ -1042,7 +1116,7 @@ impl WasmInterpreter<'_> {
                             == 0
                     };
                     if cc {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI64BrEq(target) => {
@@ -1050,7 +1124,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u64() == rhs.get_u64() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
                 WasmIntMnemonic::FusedI64BrNe(target) => {
@@ -1058,7 +1132,7 @@ impl WasmInterpreter<'_> {
                     let rhs = unsafe { *value_stack.get_unchecked(stack_level + 1) };
                     let lhs = unsafe { *value_stack.get_unchecked(stack_level) };
                     if unsafe { lhs.get_u64() != rhs.get_u64() } {
-                        codes.set_position(target);
+                        self.branch(&mut codes, code, target)?;
                     }
                 }
             }
@@ -1079,6 +1153,9 @@ impl WasmInterpreter<'_> {
         value_stack: &mut ValueStack,
         heap: &mut StackHeap,
     ) -> Result<(), WasmRuntimeError> {
+        if self.is_aborted() {
+            return Err(self.error(WasmRuntimeErrorKind::Exit, code));
+        }
         let stack_pointer = code.base_stack_level();
         let current_function = self.func_index;
         let module = self.module;
@@ -1119,14 +1196,20 @@ impl WasmInterpreter<'_> {
                     *local = WasmUnsafeValue::zero();
                 }
 
-                self._interpret(
+                if let Some(debugger) = self.debugger.as_mut() {
+                    debugger.on_enter(target.index());
+                }
+                let result = self._interpret(
                     target.index(),
                     code_block,
                     locals.as_locals(),
                     result_types,
                     heap,
-                )
-                .and_then(|v| {
+                );
+                if let Some(debugger) = self.debugger.as_mut() {
+                    debugger.on_leave(target.index());
+                }
+                result.and_then(|v| {
                     if let Some(result) = v {
                         let var = unsafe { value_stack.get_unchecked_mut(stack_under) };
                         *var = WasmUnsafeValue::from(result);
@@ -1167,18 +1250,15 @@ impl<'a> WasmIntermediateCodeStream<'a> {
     fn from_codes(codes: &'a [WasmImc]) -> Self {
         Self { codes, position: 0 }
     }
-}
 
-impl WasmIntermediateCodeStream<'_> {
     #[inline]
-    fn fetch(&mut self) -> Option<&WasmImc> {
+    fn fetch(&mut self) -> Option<&'a WasmImc> {
         self.codes.get(self.position).map(|v| {
             self.position += 1;
             v
         })
     }
 
-    #[allow(dead_code)]
     #[inline]
     const fn position(&self) -> usize {
         self.position
@@ -1192,10 +1272,59 @@ impl WasmIntermediateCodeStream<'_> {
 
 pub trait WasmInvocation {
     fn invoke(&self, params: &[WasmValue]) -> Result<Option<WasmValue>, WasmRuntimeError>;
+
+    /// Invokes the function with a debugger attached.
+    fn invoke_with_debugger(
+        &self,
+        params: &[WasmValue],
+        debugger: &mut dyn WasmDebugHook,
+    ) -> Result<Option<WasmValue>, WasmRuntimeError>;
+
+    /// Invokes the function, which exits with [`WasmRuntimeErrorKind::Exit`] once the flag is set.
+    fn invoke_with_abort_flag(
+        &self,
+        params: &[WasmValue],
+        abort_flag: &AtomicBool,
+    ) -> Result<Option<WasmValue>, WasmRuntimeError>;
 }
 
 impl WasmInvocation for WasmRunnable<'_> {
+    #[inline]
     fn invoke(&self, params: &[WasmValue]) -> Result<Option<WasmValue>, WasmRuntimeError> {
+        self._invoke(params, WasmInterpreter::new(self.module()))
+    }
+
+    #[inline]
+    fn invoke_with_debugger(
+        &self,
+        params: &[WasmValue],
+        debugger: &mut dyn WasmDebugHook,
+    ) -> Result<Option<WasmValue>, WasmRuntimeError> {
+        self._invoke(
+            params,
+            WasmInterpreter::with_debugger(self.module(), debugger),
+        )
+    }
+
+    #[inline]
+    fn invoke_with_abort_flag(
+        &self,
+        params: &[WasmValue],
+        abort_flag: &AtomicBool,
+    ) -> Result<Option<WasmValue>, WasmRuntimeError> {
+        self._invoke(
+            params,
+            WasmInterpreter::new(self.module()).with_abort_flag(abort_flag),
+        )
+    }
+}
+
+impl WasmRunnable<'_> {
+    fn _invoke(
+        &self,
+        params: &[WasmValue],
+        mut interp: WasmInterpreter,
+    ) -> Result<Option<WasmValue>, WasmRuntimeError> {
         let function = self.function();
         let code_block = function
             .code_block()
@@ -1222,7 +1351,6 @@ impl WasmInvocation for WasmRunnable<'_> {
 
         let result_types = function.result_types();
 
-        let mut interp = WasmInterpreter::new(self.module());
         interp.invoke(
             function.index(),
             code_block,
@@ -11,9 +11,13 @@
 mod wasm;
 pub use crate::wasm::*;
 
+pub mod cache;
+pub mod debug;
+pub mod heap;
 pub mod intcode;
 pub mod intr;
 pub mod opcode;
+pub mod profile;
 pub mod stack;
 
 #[cfg(test)]
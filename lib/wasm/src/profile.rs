@@ -0,0 +1,128 @@
+//! WebAssembly Sampling Profiler
+
+use crate::{debug::*, *};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::{self, Write};
+
+/// Sampling profiler that aggregates the call stacks of the interpreter
+///
+/// The profiler tracks the call stack through [`WasmDebugHook`], and [`WasmProfiler::sample`]
+/// records the current stack. Who decides when to take a sample is up to the runtime.
+pub struct WasmProfiler {
+    call_stack: Vec<usize>,
+    samples: BTreeMap<Vec<usize>, usize>,
+    n_samples: usize,
+}
+
+impl WasmProfiler {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            call_stack: Vec::new(),
+            samples: BTreeMap::new(),
+            n_samples: 0,
+        }
+    }
+
+    /// Returns the current call stack, the innermost function is the last.
+    #[inline]
+    pub fn call_stack(&self) -> &[usize] {
+        self.call_stack.as_slice()
+    }
+
+    /// Returns the total number of samples
+    #[inline]
+    pub const fn n_samples(&self) -> usize {
+        self.n_samples
+    }
+
+    /// Records the current call stack
+    pub fn sample(&mut self) {
+        if self.call_stack.is_empty() {
+            return;
+        }
+        self.n_samples += 1;
+        match self.samples.get_mut(&self.call_stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.samples.insert(self.call_stack.clone(), 1);
+            }
+        }
+    }
+
+    /// Returns the number of samples in which each function was running, in descending order.
+    pub fn self_hits(&self) -> Vec<(usize, usize)> {
+        let mut hits = BTreeMap::new();
+        for (stack, count) in &self.samples {
+            if let Some(func_index) = stack.last() {
+                *hits.entry(*func_index).or_insert(0) += *count;
+            }
+        }
+        let mut hits = hits.into_iter().collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+
+    /// Writes the name of the function, which can be used as a frame of folded stacks.
+    pub fn write_function_name<W: Write>(
+        module: &WasmModule,
+        func_index: usize,
+        f: &mut W,
+    ) -> fmt::Result {
+        match module.names().and_then(|v| v.func_by_index(func_index)) {
+            Some(name) => {
+                for c in name.chars() {
+                    match c {
+                        ';' | ' ' | '\t' | '\r' | '\n' => f.write_char('_')?,
+                        _ => f.write_char(c)?,
+                    }
+                }
+                Ok(())
+            }
+            None => write!(f, "${}", func_index),
+        }
+    }
+
+    /// Writes samples in the folded stack format, one stack per line such as `main;foo;bar 42`.
+    pub fn write_folded<W: Write>(&self, module: &WasmModule, f: &mut W) -> fmt::Result {
+        for (stack, count) in &self.samples {
+            for (index, func_index) in stack.iter().enumerate() {
+                if index > 0 {
+                    f.write_char(';')?;
+                }
+                Self::write_function_name(module, *func_index, f)?;
+            }
+            writeln!(f, " {}", count)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for WasmProfiler {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmDebugHook for WasmProfiler {
+    #[inline]
+    fn should_break(&mut self, _func_index: usize, _position: usize) -> bool {
+        false
+    }
+
+    #[inline]
+    fn on_break(&mut self, _frame: &WasmDebugFrame) -> WasmDebugAction {
+        WasmDebugAction::Continue
+    }
+
+    #[inline]
+    fn on_enter(&mut self, func_index: usize) {
+        self.call_stack.push(func_index);
+    }
+
+    #[inline]
+    fn on_leave(&mut self, _func_index: usize) {
+        self.call_stack.pop();
+    }
+}
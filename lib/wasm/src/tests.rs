@@ -1,10 +1,15 @@
 // test
 
 use crate::{
+    cache::*,
+    debug::*,
+    heap::*,
     intr::{WasmInterpreter, WasmInvocation},
+    profile::*,
     WasmValType, *,
 };
-use alloc::borrow::ToOwned;
+use alloc::{borrow::ToOwned, collections::VecDeque, string::String, vec::Vec};
+use core::{alloc::Layout, fmt};
 
 #[cfg(feature = "float")]
 use core::f64::consts::PI;
@@ -36,6 +41,19 @@ fn instantiate_unexpected_eof() {
     WasmLoader::instantiate(&data, |_, _, _| unreachable!()).unwrap();
 }
 
+#[test]
+fn custom_section() {
+    let data = [
+        0, 97, 115, 109, 1, 0, 0, 0, 0, 5, 4, b'n', b'a', b'm', b'e', 0, 6, 3, b'f', b'o', b'o',
+        b'b', b'a', 0, 4, 3, b'b', b'a', b'r',
+    ];
+    WasmLoader::instantiate(&data, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(WasmLoader::custom_section(&data, "foo"), Some(&b"ba"[..]));
+    assert_eq!(WasmLoader::custom_section(&data, "bar"), Some(&b""[..]));
+    assert_eq!(WasmLoader::custom_section(&data, "baz"), None);
+    assert_eq!(WasmLoader::custom_section(&data[..18], "foo"), None);
+}
+
 #[test]
 fn instantiate_fibonacci() {
     let slice = [
@@ -49,6 +67,222 @@ fn instantiate_fibonacci() {
     let _ = module.func_by_index(0).unwrap();
 }
 
+fn fibonacci_module() -> WasmModule {
+    WasmLoader::instantiate(&FIBONACCI_WASM, |_, _, _| unreachable!()).unwrap()
+}
+
+const FIBONACCI_WASM: [u8; 71] = [
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x02, 0x01, 0x00, 0x0A, 0x31, 0x01, 0x2F, 0x01, 0x01, 0x7F, 0x41, 0x00, 0x21, 0x01, 0x02,
+    0x40, 0x03, 0x40, 0x20, 0x00, 0x41, 0x02, 0x49, 0x0D, 0x01, 0x20, 0x00, 0x41, 0x7F, 0x6A, 0x10,
+    0x00, 0x20, 0x01, 0x6A, 0x21, 0x01, 0x20, 0x00, 0x41, 0x7E, 0x6A, 0x21, 0x00, 0x0C, 0x00, 0x0B,
+    0x0B, 0x20, 0x00, 0x20, 0x01, 0x6A, 0x0B,
+];
+
+#[derive(Default)]
+struct CountingHook {
+    breaks: usize,
+    enters: usize,
+    leaves: usize,
+    abort: bool,
+}
+
+impl WasmDebugHook for CountingHook {
+    fn should_break(&mut self, _func_index: usize, _position: usize) -> bool {
+        true
+    }
+
+    fn on_break(&mut self, frame: &WasmDebugFrame) -> WasmDebugAction {
+        assert_eq!(frame.func_index, 0);
+        assert_eq!(frame.local_types(), [WasmValType::I32, WasmValType::I32]);
+        self.breaks += 1;
+        if self.abort {
+            WasmDebugAction::Abort
+        } else {
+            WasmDebugAction::Continue
+        }
+    }
+
+    fn on_enter(&mut self, _func_index: usize) {
+        self.enters += 1;
+    }
+
+    fn on_leave(&mut self, _func_index: usize) {
+        self.leaves += 1;
+    }
+}
+
+#[test]
+fn debug_hook() {
+    let module = fibonacci_module();
+    let runnable = module.func_by_index(0).unwrap();
+    let expected = runnable
+        .invoke(&[WasmValue::I32(10)])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+
+    let mut hook = CountingHook::default();
+    let result = runnable
+        .invoke_with_debugger(&[WasmValue::I32(10)], &mut hook)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, expected);
+    assert!(hook.breaks > hook.enters);
+    assert!(hook.enters > 1);
+    assert_eq!(hook.enters, hook.leaves);
+
+    let mut hook = CountingHook {
+        abort: true,
+        ..Default::default()
+    };
+    let result = runnable.invoke_with_debugger(&[WasmValue::I32(10)], &mut hook);
+    assert_eq!(result.unwrap_err().kind(), WasmRuntimeErrorKind::Exit);
+    assert_eq!(hook.breaks, 1);
+}
+
+#[test]
+fn abort_flag() {
+    use core::sync::atomic::AtomicBool;
+
+    let module = fibonacci_module();
+    let runnable = module.func_by_index(0).unwrap();
+    let expected = runnable
+        .invoke(&[WasmValue::I32(10)])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+
+    let flag = AtomicBool::new(false);
+    let result = runnable
+        .invoke_with_abort_flag(&[WasmValue::I32(10)], &flag)
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, expected);
+
+    let flag = AtomicBool::new(true);
+    let result = runnable.invoke_with_abort_flag(&[WasmValue::I32(10)], &flag);
+    assert_eq!(result.unwrap_err().kind(), WasmRuntimeErrorKind::Exit);
+
+    // (loop (br 0))
+    let slice = [0, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info = WasmCodeBlock::generate(0, 0, &mut stream, &[], &[], &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module).with_abort_flag(&flag);
+    let result = interp.invoke(0, &info, &mut [], &[]);
+    assert_eq!(result.unwrap_err().kind(), WasmRuntimeErrorKind::Exit);
+}
+
+struct ScriptConsole {
+    input: VecDeque<&'static str>,
+    output: String,
+}
+
+impl fmt::Write for ScriptConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.output.write_str(s)
+    }
+}
+
+impl WasmDebugConsole for ScriptConsole {
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front().map(|v| v.to_owned())
+    }
+}
+
+#[test]
+fn debugger_commands() {
+    let module = fibonacci_module();
+    let runnable = module.func_by_index(0).unwrap();
+
+    let console = ScriptConsole {
+        input: ["b $0", "c", "bt", "l", "d", "n", "", "q"].into(),
+        output: String::new(),
+    };
+    let mut debugger = WasmDebugger::new(console);
+    let result = runnable.invoke_with_debugger(&[WasmValue::I32(10)], &mut debugger);
+    assert_eq!(result.unwrap_err().kind(), WasmRuntimeErrorKind::Exit);
+    assert!(debugger.breakpoints().is_empty());
+
+    let output = &debugger.console().output;
+    assert!(output.contains("breakpoint #0 at $0"));
+    assert!(output.contains("#0 $0\n#1 $0\n"));
+    assert!(output.contains("local[0] = i32 9 "));
+    assert_eq!(output.matches("(wdb) ").count(), 8);
+}
+
+#[test]
+fn debugger_duplicate_breakpoint() {
+    let module = fibonacci_module();
+    let runnable = module.func_by_index(0).unwrap();
+
+    let console = ScriptConsole {
+        input: ["b $0", "b $0+2", "b $0", "q"].into(),
+        output: String::new(),
+    };
+    let mut debugger = WasmDebugger::new(console);
+    let _ = runnable.invoke_with_debugger(&[WasmValue::I32(10)], &mut debugger);
+    assert_eq!(debugger.breakpoints().len(), 2);
+
+    let output = &debugger.console().output;
+    assert_eq!(output.matches("breakpoint #0 at $0").count(), 2);
+    assert_eq!(output.matches("breakpoint #1 at $0").count(), 1);
+}
+
+struct SampleEveryCode(WasmProfiler);
+
+impl WasmDebugHook for SampleEveryCode {
+    fn should_break(&mut self, _func_index: usize, _position: usize) -> bool {
+        self.0.sample();
+        false
+    }
+
+    fn on_break(&mut self, _frame: &WasmDebugFrame) -> WasmDebugAction {
+        unreachable!()
+    }
+
+    fn on_enter(&mut self, func_index: usize) {
+        self.0.on_enter(func_index);
+    }
+
+    fn on_leave(&mut self, func_index: usize) {
+        self.0.on_leave(func_index);
+    }
+}
+
+#[test]
+fn profiler() {
+    let module = fibonacci_module();
+    let runnable = module.func_by_index(0).unwrap();
+
+    let mut hook = SampleEveryCode(WasmProfiler::new());
+    runnable
+        .invoke_with_debugger(&[WasmValue::I32(5)], &mut hook)
+        .unwrap();
+    let profiler = hook.0;
+    assert!(profiler.call_stack().is_empty());
+    assert!(profiler.n_samples() > 0);
+    assert_eq!(profiler.self_hits(), [(0, profiler.n_samples())]);
+
+    let mut folded = String::new();
+    profiler.write_folded(&module, &mut folded).unwrap();
+    let mut total = 0;
+    for line in folded.lines() {
+        let (stack, count) = line.rsplit_once(' ').unwrap();
+        assert!(stack.split(';').all(|v| v == "$0"));
+        total += count.parse::<usize>().unwrap();
+    }
+    assert_eq!(total, profiler.n_samples());
+    assert!(folded.contains("$0;$0;$0 "));
+}
+
 #[test]
 fn leb128() {
     let data = [
@@ -79,6 +313,118 @@ fn leb128() {
     assert_eq!(test, -559038737);
 }
 
+#[test]
+fn empty_memory() {
+    let memory = WasmMemory::empty();
+    assert_eq!(memory.size(), 0);
+    assert_eq!(memory.read_u8(0, 0), Err(WasmRuntimeErrorKind::OutOfBounds));
+    assert_eq!(
+        memory.read_u32(0, 0),
+        Err(WasmRuntimeErrorKind::OutOfBounds)
+    );
+    assert_eq!(
+        memory.read_u64(0, 0),
+        Err(WasmRuntimeErrorKind::OutOfBounds)
+    );
+}
+
+#[test]
+fn heap_alloc() {
+    let memory = WasmMemory::empty();
+    memory.grow(1);
+    let mut heap = WasmHeap::new(3);
+
+    // The heap takes the pages above the current size
+    let p1 = heap
+        .alloc(&memory, Layout::from_size_align(10, 1).unwrap())
+        .unwrap();
+    assert_eq!(p1.get() as usize, WasmMemory::PAGE_SIZE);
+    assert_eq!(memory.size(), 2);
+    let p2 = heap
+        .alloc(&memory, Layout::from_size_align(100, 64).unwrap())
+        .unwrap();
+    assert_eq!(p2.get() % 64, 0);
+    assert!(p2.get() >= p1.get() + 16);
+
+    let stats = heap.stats(&memory);
+    assert_eq!(stats.heap_size, WasmMemory::PAGE_SIZE);
+    assert_eq!(stats.used, 16 + 112);
+    assert_eq!(stats.n_allocs, 2);
+    assert_eq!(stats.n_free_blocks, 2);
+
+    // Freed blocks are merged with the adjacent blocks
+    assert!(heap.dealloc(p1.get()));
+    assert!(!heap.dealloc(p1.get()));
+    assert!(heap.dealloc(p2.get()));
+    let stats = heap.stats(&memory);
+    assert_eq!(stats.used, 0);
+    assert_eq!(stats.n_free_blocks, 1);
+    assert_eq!(stats.largest_free, WasmMemory::PAGE_SIZE);
+    assert_eq!(stats.fragmentation(), 0);
+
+    // The free block at the end is extended, up to the limit
+    let p3 = heap
+        .alloc(
+            &memory,
+            Layout::from_size_align(WasmMemory::PAGE_SIZE + 1, 16).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(p3, p1);
+    assert_eq!(memory.size(), 3);
+    assert_eq!(
+        heap.alloc(
+            &memory,
+            Layout::from_size_align(WasmMemory::PAGE_SIZE, 16).unwrap()
+        ),
+        None
+    );
+    assert_eq!(memory.size(), 3);
+}
+
+#[test]
+fn heap_fragmentation() {
+    let memory = WasmMemory::empty();
+    memory.grow(1);
+    let mut heap = WasmHeap::new(2);
+    let layout = Layout::from_size_align(1024, 16).unwrap();
+    let blocks = (0..64)
+        .map(|_| heap.alloc(&memory, layout).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(heap.alloc(&memory, layout), None);
+
+    for block in blocks.iter().step_by(2) {
+        assert!(heap.dealloc(block.get()));
+    }
+    let stats = heap.stats(&memory);
+    assert_eq!(stats.free(), 32 * 1024);
+    assert_eq!(stats.n_free_blocks, 32);
+    assert_eq!(stats.largest_free, 1024);
+    assert_eq!(stats.fragmentation(), 969);
+
+    // Reuses the free block
+    assert_eq!(heap.alloc(&memory, layout), Some(blocks[0]));
+    assert_eq!(
+        heap.alloc(&memory, Layout::from_size_align(2048, 16).unwrap()),
+        None
+    );
+}
+
+#[test]
+fn void_function() {
+    let slice = [0, 0x0B];
+    let param_types = [];
+    let result_types = [];
+    let mut stream = Leb128Stream::from_slice(&slice);
+    let module = WasmModule::new();
+    let info =
+        WasmCodeBlock::generate(0, 0, &mut stream, &param_types, &result_types, &module).unwrap();
+    let mut interp = WasmInterpreter::new(&module);
+
+    let mut locals = [];
+    let result = interp.invoke(0, &info, &mut locals, &result_types).unwrap();
+    assert!(result.is_none());
+}
+
 #[test]
 fn add() {
     let slice = [0, 0x20, 0, 0x20, 1, 0x6A, 0x0B];
@@ -152,7 +498,7 @@ fn const_local() {
         WasmCodeBlock::generate(0, 0, &mut stream, &param_types, &result_types, &module).unwrap();
     let mut interp = WasmInterpreter::new(&module);
 
-    let mut locals = [];
+    let mut locals = [WasmUnsafeValue::zero()];
     let result = interp
         .invoke(0, &info, &mut locals, &result_types)
         .unwrap()
@@ -993,3 +1339,54 @@ fn float64_reinterpret() {
         .unwrap();
     assert_eq!(result, 0x400921fb54442d18u64);
 }
+
+#[test]
+fn precompiled_cache() {
+    let module = fibonacci_module();
+    let cache = WasmCache::encode(&module, &FIBONACCI_WASM);
+    let module = WasmCache::instantiate(&cache, &FIBONACCI_WASM, |_, _, _| unreachable!()).unwrap();
+    let result = module
+        .func_by_index(0)
+        .unwrap()
+        .invoke(&[20.into()])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 6765);
+
+    let mut blob = FIBONACCI_WASM;
+    blob[0x1C] = 0x01;
+    assert_eq!(
+        WasmCache::instantiate(&cache, &blob, |_, _, _| unreachable!()).err(),
+        Some(WasmDecodeErrorKind::StaleCache)
+    );
+    assert!(
+        WasmCache::instantiate(&cache[..cache.len() - 1], &FIBONACCI_WASM, |_, _, _| {
+            unreachable!()
+        })
+        .is_err()
+    );
+
+    // (memory 1) (data (i32.const 0x1000) "\2a")
+    // (func (export "load") (result i32) i32.const 0x1000 i32.load8_u)
+    let slice = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F,
+        0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x6C, 0x6F,
+        0x61, 0x64, 0x00, 0x00, 0x0A, 0x0A, 0x01, 0x08, 0x00, 0x41, 0x80, 0x20, 0x2D, 0x00, 0x00,
+        0x0B, 0x0B, 0x08, 0x01, 0x00, 0x41, 0x80, 0x20, 0x0B, 0x01, 0x2A,
+    ];
+    let module = WasmLoader::instantiate(&slice, |_, _, _| unreachable!()).unwrap();
+    let cache = WasmCache::encode(&module, &slice);
+    let module = WasmCache::instantiate(&cache, &slice, |_, _, _| unreachable!()).unwrap();
+    assert_eq!(module.memory(0).unwrap().size(), 1);
+    let result = module
+        .func("load")
+        .unwrap()
+        .invoke(&[])
+        .unwrap()
+        .unwrap()
+        .get_i32()
+        .unwrap();
+    assert_eq!(result, 0x2A);
+}
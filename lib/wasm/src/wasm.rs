@@ -1,4 +1,4 @@
-use crate::{intcode::*, opcode::*, *};
+use crate::{cache::*, intcode::*, opcode::*, *};
 use alloc::{borrow::ToOwned, boxed::Box, format, string::*, vec::Vec};
 use bitflags::*;
 use core::{
@@ -50,6 +50,25 @@ impl WasmLoader {
             && unsafe { WasmEndian::read_u32(blob, 4) } == Self::VER_CURRENT
     }
 
+    /// Returns the contents of the first custom section with the specified name
+    pub fn custom_section<'a>(blob: &'a [u8], name: &str) -> Option<&'a [u8]> {
+        if !Self::identity(blob) {
+            return None;
+        }
+        let mut stream = Leb128Stream::from_slice(&blob[8..]);
+        while let Some((section_type, file_position, length)) = stream.next_section_triple().ok()? {
+            if section_type != WasmSectionType::Custom {
+                continue;
+            }
+            let content = blob.get(file_position..file_position + length)?;
+            let mut section = Leb128Stream::from_slice(content);
+            if section.get_string().ok()? == name {
+                return content.get(section.position()..);
+            }
+        }
+        None
+    }
+
     /// Instantiate wasm modules from slice
     pub fn instantiate<F>(blob: &[u8], resolver: F) -> Result<WasmModule, WasmDecodeErrorKind>
     where
@@ -117,6 +136,62 @@ impl WasmLoader {
         self.module
     }
 
+    /// Load wasm from the precompiled cache of `blob`
+    ///
+    /// Imported functions are resolved again with `import_resolver`.
+    pub fn load_cache<F>(
+        &mut self,
+        cache: &[u8],
+        blob: &[u8],
+        mut import_resolver: F,
+    ) -> Result<(), WasmDecodeErrorKind>
+    where
+        F: FnMut(&str, &str, &WasmType) -> ImportResult<WasmDynFunc> + Copy,
+    {
+        let mut stream = WasmCache::validate(cache, blob)?;
+        let mut module = WasmModule::read_cache(&mut stream)?;
+        if !stream.is_eof() {
+            return Err(WasmDecodeErrorKind::StaleCache);
+        }
+
+        for import in &module.imports {
+            let WasmImportIndex::Type(index) = import.index else {
+                continue;
+            };
+            let func_type = module
+                .types
+                .get(index)
+                .ok_or(WasmDecodeErrorKind::InvalidType)?;
+            let dlink = match import_resolver(import.mod_name(), import.name(), func_type) {
+                ImportResult::Ok(v) => v,
+                ImportResult::NoMethod => {
+                    return Err(WasmDecodeErrorKind::NoMethod(import.name().to_owned()))
+                }
+                ImportResult::NoModule => {
+                    return Err(WasmDecodeErrorKind::NoModule(import.mod_name().to_owned()))
+                }
+                // Deferred linking is not supported for cached modules
+                ImportResult::Later => return Err(WasmDecodeErrorKind::StaleCache),
+            };
+            module
+                .functions
+                .get_mut(import.func_ref)
+                .ok_or(WasmDecodeErrorKind::StaleCache)?
+                .dlink = Some(dlink);
+        }
+        if module
+            .functions
+            .iter()
+            .take(module.n_ext_func)
+            .any(|v| v.dlink.is_none())
+        {
+            return Err(WasmDecodeErrorKind::StaleCache);
+        }
+
+        self.module = module;
+        Ok(())
+    }
+
     /// Parse "type" section
     fn parse_sec_type(&mut self, mut section: WasmSection) -> Result<(), WasmDecodeErrorKind> {
         let n_items = section.stream.read_unsigned()? as usize;
@@ -522,6 +597,348 @@ impl WasmModule {
     }
 }
 
+impl WasmCacheItem for WasmModule {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.types.write_cache(vec);
+        self.imports.write_cache(vec);
+        // Imported functions are linked again when loading
+        self.functions.len().write_cache(vec);
+        for function in &self.functions {
+            function.index.write_cache(vec);
+            function.type_index.write_cache(vec);
+            function.origin.write_cache(vec);
+            function.code_block.write_cache(vec);
+        }
+        self.tables.write_cache(vec);
+        self.memories.write_cache(vec);
+        self.globals.write_cache(vec);
+        self.exports.write_cache(vec);
+        self.start.write_cache(vec);
+        self.data_count.write_cache(vec);
+        self.names.write_cache(vec);
+        self.n_ext_func.write_cache(vec);
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let types = Vec::<WasmType>::read_cache(stream)?;
+        let imports = Vec::read_cache(stream)?;
+
+        let n_items = usize::read_cache(stream)?;
+        let mut functions = Vec::with_capacity(n_items.min(stream.len() - stream.position()));
+        for _ in 0..n_items {
+            let index = usize::read_cache(stream)?;
+            let type_index = usize::read_cache(stream)?;
+            let func_type = types
+                .get(type_index)
+                .ok_or(WasmDecodeErrorKind::InvalidType)?
+                .clone();
+            let origin = WasmFunctionOrigin::read_cache(stream)?;
+            let code_block = Option::read_cache(stream)?;
+            functions.push(WasmFunction {
+                index,
+                type_index,
+                func_type,
+                origin,
+                code_block,
+                dlink: None,
+            });
+        }
+
+        let tables = Vec::read_cache(stream)?;
+        let memories = Vec::read_cache(stream)?;
+        let globals = Vec::read_cache(stream)?;
+        let exports = Vec::read_cache(stream)?;
+        let start = Option::read_cache(stream)?;
+        let data_count = Option::read_cache(stream)?;
+        let names = Option::read_cache(stream)?;
+        let n_ext_func = usize::read_cache(stream)?;
+        if n_ext_func > functions.len() {
+            return Err(WasmDecodeErrorKind::StaleCache);
+        }
+
+        Ok(Self {
+            types,
+            imports,
+            exports,
+            memories,
+            tables,
+            functions,
+            start,
+            globals,
+            data_count,
+            names,
+            n_ext_func,
+        })
+    }
+}
+
+impl WasmCacheItem for WasmType {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.param_types.write_cache(vec);
+        self.result_types.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let param_types = Box::read_cache(stream)?;
+        let result_types = Box::read_cache(stream)?;
+        Ok(Self {
+            param_types,
+            result_types,
+        })
+    }
+}
+
+impl WasmCacheItem for WasmLimit {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.min.write_cache(vec);
+        self.max.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let min = u32::read_cache(stream)?;
+        let max = u32::read_cache(stream)?;
+        Ok(Self { min, max })
+    }
+}
+
+impl WasmCacheItem for WasmImport {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.mod_name.write_cache(vec);
+        self.name.write_cache(vec);
+        match self.index {
+            WasmImportIndex::Type(index) => {
+                vec.push(0);
+                index.write_cache(vec);
+            }
+            WasmImportIndex::Memory(limit) => {
+                vec.push(2);
+                limit.write_cache(vec);
+            }
+        }
+        self.func_ref.write_cache(vec);
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let mod_name = String::read_cache(stream)?;
+        let name = String::read_cache(stream)?;
+        let index = match stream.read_byte()? {
+            0 => WasmImportIndex::Type(usize::read_cache(stream)?),
+            2 => WasmImportIndex::Memory(WasmLimit::read_cache(stream)?),
+            _ => return Err(WasmDecodeErrorKind::UnexpectedToken),
+        };
+        let func_ref = usize::read_cache(stream)?;
+        Ok(Self {
+            mod_name,
+            name,
+            index,
+            func_ref,
+        })
+    }
+}
+
+impl WasmCacheItem for WasmFunctionOrigin {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        match *self {
+            WasmFunctionOrigin::Internal => vec.push(0),
+            WasmFunctionOrigin::Export(index) => {
+                vec.push(1);
+                index.write_cache(vec);
+            }
+            WasmFunctionOrigin::Import(index) => {
+                vec.push(2);
+                index.write_cache(vec);
+            }
+        }
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        match stream.read_byte()? {
+            0 => Ok(WasmFunctionOrigin::Internal),
+            1 => usize::read_cache(stream).map(WasmFunctionOrigin::Export),
+            2 => usize::read_cache(stream).map(WasmFunctionOrigin::Import),
+            _ => Err(WasmDecodeErrorKind::UnexpectedToken),
+        }
+    }
+}
+
+impl WasmCacheItem for WasmCodeBlock {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.func_index.write_cache(vec);
+        self.file_position.write_cache(vec);
+        self.local_types.write_cache(vec);
+        self.max_stack.write_cache(vec);
+        self.flags.bits().write_cache(vec);
+        self.int_codes.write_cache(vec);
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let func_index = usize::read_cache(stream)?;
+        let file_position = usize::read_cache(stream)?;
+        let local_types = Box::read_cache(stream)?;
+        let max_stack = usize::read_cache(stream)?;
+        let flags = WasmBlockFlag::from_bits(usize::read_cache(stream)?)
+            .ok_or(WasmDecodeErrorKind::UnexpectedToken)?;
+        let int_codes = Box::read_cache(stream)?;
+        Ok(Self {
+            func_index,
+            file_position,
+            local_types,
+            max_stack,
+            flags,
+            int_codes,
+        })
+    }
+}
+
+impl WasmCacheItem for WasmTable {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.limit.write_cache(vec);
+        self.table.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let limit = WasmLimit::read_cache(stream)?;
+        let table = Vec::read_cache(stream)?;
+        Ok(Self { limit, table })
+    }
+}
+
+impl WasmCacheItem for WasmMemory {
+    /// Only the blocks that are not filled with zero are written.
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        const BLOCK_SIZE: usize = 256;
+        let memory = self.as_slice();
+        let mut segments = Vec::<(usize, usize)>::new();
+        for (index, block) in memory.chunks(BLOCK_SIZE).enumerate() {
+            if block.iter().all(|v| *v == 0) {
+                continue;
+            }
+            let start = index * BLOCK_SIZE;
+            let end = start + block.len();
+            match segments.last_mut() {
+                Some((_, last_end)) if *last_end == start => *last_end = end,
+                _ => segments.push((start, end)),
+            }
+        }
+
+        self.limit.write_cache(vec);
+        memory.len().write_cache(vec);
+        segments.len().write_cache(vec);
+        for (start, end) in segments {
+            start.write_cache(vec);
+            (end - start).write_cache(vec);
+            vec.extend_from_slice(&memory[start..end]);
+        }
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let limit = WasmLimit::read_cache(stream)?;
+        let size = usize::read_cache(stream)?;
+        if size % Self::PAGE_SIZE != 0 || size / Self::PAGE_SIZE > limit.min.max(limit.max) as usize
+        {
+            return Err(WasmDecodeErrorKind::OutOfMemory);
+        }
+        let mut data = Vec::new();
+        data.try_reserve(size)
+            .map_err(|_| WasmDecodeErrorKind::OutOfMemory)?;
+        data.resize(size, 0);
+
+        let n_segments = usize::read_cache(stream)?;
+        for _ in 0..n_segments {
+            let offset = usize::read_cache(stream)?;
+            let len = usize::read_cache(stream)?;
+            let src = stream.get_bytes(len)?;
+            let end = offset
+                .checked_add(len)
+                .ok_or(WasmDecodeErrorKind::StaleCache)?;
+            data.get_mut(offset..end)
+                .ok_or(WasmDecodeErrorKind::OutOfMemory)?
+                .copy_from_slice(src);
+        }
+
+        Ok(Self {
+            limit,
+            data: UnsafeCell::new(data),
+        })
+    }
+}
+
+impl WasmCacheItem for WasmGlobal {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.val_type.write_cache(vec);
+        self.is_mutable.write_cache(vec);
+        self.data.load(Ordering::Relaxed).write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let val_type = WasmValType::read_cache(stream)?;
+        let is_mutable = bool::read_cache(stream)?;
+        let data = AtomicU32::new(u32::read_cache(stream)?);
+        Ok(Self {
+            data,
+            val_type,
+            is_mutable,
+        })
+    }
+}
+
+impl WasmCacheItem for WasmExport {
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.name.write_cache(vec);
+        let (kind, index) = match self.index {
+            WasmExportIndex::Function(v) => (0u8, v),
+            WasmExportIndex::Table(v) => (1, v),
+            WasmExportIndex::Memory(v) => (2, v),
+            WasmExportIndex::Global(v) => (3, v),
+        };
+        vec.push(kind);
+        index.write_cache(vec);
+    }
+
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let name = String::read_cache(stream)?;
+        let kind = stream.read_byte()?;
+        let index = usize::read_cache(stream)?;
+        let index = match kind {
+            0 => WasmExportIndex::Function(index),
+            1 => WasmExportIndex::Table(index),
+            2 => WasmExportIndex::Memory(index),
+            3 => WasmExportIndex::Global(index),
+            _ => return Err(WasmDecodeErrorKind::UnexpectedToken),
+        };
+        Ok(Self { name, index })
+    }
+}
+
+impl WasmCacheItem for WasmName {
+    #[inline]
+    fn write_cache(&self, vec: &mut Vec<u8>) {
+        self.module.write_cache(vec);
+        self.functions.write_cache(vec);
+        self.globals.write_cache(vec);
+    }
+
+    #[inline]
+    fn read_cache(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorKind> {
+        let module = Option::read_cache(stream)?;
+        let functions = Vec::read_cache(stream)?;
+        let globals = Vec::read_cache(stream)?;
+        Ok(Self {
+            module,
+            functions,
+            globals,
+        })
+    }
+}
+
 struct WasmEndian;
 
 impl WasmEndian {
@@ -791,6 +1208,20 @@ impl Leb128Stream<'_> {
             }
         }
     }
+
+    pub fn write_signed(vec: &mut Vec<u8>, value: i64) {
+        let mut value = value;
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if (value == 0 && (byte & 0x40) == 0) || (value == -1 && (byte & 0x40) != 0) {
+                vec.push(byte);
+                break;
+            } else {
+                vec.push(0x80 | byte);
+            }
+        }
+    }
 }
 
 /// WebAssembly memory argument
@@ -882,7 +1313,7 @@ pub enum WasmValType {
 
 impl WasmValType {
     #[inline]
-    const fn from_u64(v: u64) -> Result<Self, WasmDecodeErrorKind> {
+    pub(crate) const fn from_u64(v: u64) -> Result<Self, WasmDecodeErrorKind> {
         match v {
             0x7F => Ok(WasmValType::I32),
             0x7E => Ok(WasmValType::I64),
@@ -1038,6 +1469,15 @@ impl WasmMemory {
         }
     }
 
+    /// Returns a memory object with no pages, for modules that do not declare a memory.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self {
+            limit: WasmLimit { min: 0, max: 0 },
+            data: UnsafeCell::new(Vec::new()),
+        }
+    }
+
     #[inline]
     pub const fn limit(&self) -> WasmLimit {
         self.limit
@@ -1226,14 +1666,14 @@ impl WasmMemory {
     #[inline]
     pub fn read_u16(&self, offset: u32, index: u32) -> Result<u16, WasmRuntimeErrorKind> {
         let slice = self.as_slice();
-        Self::effective_address(offset, index, slice.len() - 1)
+        Self::effective_address(offset, index, slice.len().saturating_sub(1))
             .map(|ea| unsafe { WasmEndian::read_u16(slice, ea) })
     }
 
     #[inline]
     pub fn write_u16(&self, offset: u32, index: u32, val: u16) -> Result<(), WasmRuntimeErrorKind> {
         let slice = self.as_mut_slice();
-        Self::effective_address(offset, index, slice.len() - 1).map(|ea| unsafe {
+        Self::effective_address(offset, index, slice.len().saturating_sub(1)).map(|ea| unsafe {
             WasmEndian::write_u16(slice, ea, val);
         })
     }
@@ -1241,14 +1681,14 @@ impl WasmMemory {
     #[inline]
     pub fn read_u32(&self, offset: u32, index: u32) -> Result<u32, WasmRuntimeErrorKind> {
         let slice = self.as_slice();
-        Self::effective_address(offset, index, slice.len() - 3)
+        Self::effective_address(offset, index, slice.len().saturating_sub(3))
             .map(|ea| unsafe { WasmEndian::read_u32(slice, ea) })
     }
 
     #[inline]
     pub fn write_u32(&self, offset: u32, index: u32, val: u32) -> Result<(), WasmRuntimeErrorKind> {
         let slice = self.as_mut_slice();
-        Self::effective_address(offset, index, slice.len() - 3).map(|ea| unsafe {
+        Self::effective_address(offset, index, slice.len().saturating_sub(3)).map(|ea| unsafe {
             WasmEndian::write_u32(slice, ea, val);
         })
     }
@@ -1256,14 +1696,14 @@ impl WasmMemory {
     #[inline]
     pub fn read_u64(&self, offset: u32, index: u32) -> Result<u64, WasmRuntimeErrorKind> {
         let slice = self.as_slice();
-        Self::effective_address(offset, index, slice.len() - 7)
+        Self::effective_address(offset, index, slice.len().saturating_sub(7))
             .map(|ea| unsafe { WasmEndian::read_u64(slice, ea) })
     }
 
     #[inline]
     pub fn write_u64(&self, offset: u32, index: u32, val: u64) -> Result<(), WasmRuntimeErrorKind> {
         let slice = self.as_mut_slice();
-        Self::effective_address(offset, index, slice.len() - 7).map(|ea| unsafe {
+        Self::effective_address(offset, index, slice.len().saturating_sub(7)).map(|ea| unsafe {
             WasmEndian::write_u64(slice, ea, val);
         })
     }
@@ -1626,6 +2066,8 @@ pub enum WasmDecodeErrorKind {
     NoMethod(String),
     /// Imported module does not exist.
     NoModule(String),
+    /// The precompiled cache does not match the module or the runtime.
+    StaleCache,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -2540,7 +2982,7 @@ impl WasmCodeBlock {
                                 position,
                                 opcode,
                                 WasmIntMnemonic::Return,
-                                StackLevel(value_stack.len() - 1),
+                                StackLevel(value_stack.len().saturating_sub(1)),
                             ));
                             break;
                         }
@@ -2601,7 +3043,7 @@ impl WasmCodeBlock {
                             position,
                             opcode,
                             WasmIntMnemonic::Return,
-                            StackLevel(value_stack.len() - 1),
+                            StackLevel(value_stack.len().saturating_sub(1)),
                         ));
                         // TODO: type check
                     }
@@ -4739,6 +5181,11 @@ impl StackOffset {
 pub struct LocalVarIndex(usize);
 
 impl LocalVarIndex {
+    #[inline]
+    pub const fn new(value: usize) -> Self {
+        Self(value)
+    }
+
     #[inline]
     pub const fn as_usize(&self) -> usize {
         self.0 as usize
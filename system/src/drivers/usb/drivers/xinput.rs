@@ -75,7 +75,7 @@ impl XInputDriver {
     ) {
         let addr = device.device().addr();
         let input = Arc::new(RwLock::new(GameInput::empty()));
-        let _handle = GameInputManager::connect_new_input(input.clone());
+        let handle = GameInputManager::connect_new_input(input.clone());
         let mut buffer = [0u8; 512];
         loop {
             match device
@@ -94,6 +94,9 @@ impl XInputDriver {
                 }
             }
         }
+        if let Some(handle) = handle {
+            GameInputManager::disconnect_input(handle);
+        }
     }
 }
 
@@ -186,6 +186,11 @@ impl FsDriver for DevFsDriver {
                 .map(|v| v.as_ref().into())
         }
     }
+
+    fn statfs(&self) -> Result<FsRawStatFs> {
+        // Device files have no storage
+        Ok(FsRawStatFs::new(0, 0))
+    }
 }
 
 struct ThisFsInodeEntry {
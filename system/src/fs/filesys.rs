@@ -224,13 +224,37 @@ impl FileManager {
         Ok(())
     }
 
-    pub fn read_dir(path: &str) -> Result<impl Iterator<Item = FsRawDirEntry>> {
+    pub fn read_dir(path: &str) -> Result<FsRawReadDir> {
         let (fs, dir) = Self::resolve_all(path)?;
+        let stat = fs.stat(dir).ok_or(ErrorKind::NotFound)?;
+        if !stat.file_type().is_dir() {
+            return Err(ErrorKind::NotADirectory.into());
+        }
         Ok(FsRawReadDir::new(fs, dir))
     }
 
     pub fn open(path: &str, options: &OpenOptions) -> Result<FsRawFileControlBlock> {
-        let (fs, inode) = Self::resolve_all(path)?;
+        let (fs, inode) = match Self::resolve_all(path) {
+            Ok(v) => {
+                if options.contains(OpenOptions::EXCL) {
+                    return Err(ErrorKind::AlreadyExists.into());
+                }
+                v
+            }
+            Err(err) => {
+                if err.kind() == ErrorKind::NotFound
+                    && (options.contains(OpenOptions::CREAT) || options.contains(OpenOptions::EXCL))
+                {
+                    let (fs, dir, lpc) = Self::resolve_parent(path)?;
+                    let Some(name) = lpc else {
+                        return Err(ErrorKind::NotFound.into());
+                    };
+                    let access_token = fs.creat(dir, &name)?;
+                    return Ok(FsRawFileControlBlock::new(access_token, options, false));
+                }
+                return Err(err);
+            }
+        };
 
         let Some(stat) = fs.stat(inode) else {
             return Err(ErrorKind::NotFound.into());
@@ -241,11 +265,16 @@ impl FileManager {
 
         let access_token = fs.open(inode)?;
 
-        Ok(FsRawFileControlBlock::new(
+        let mut fcb = FsRawFileControlBlock::new(
             access_token,
             options,
             stat.file_type().is_char_device() || stat.file_type().is_block_device(),
-        ))
+        );
+        if options.contains(OpenOptions::TRUNC) && options.contains(OpenOptions::WRITE) {
+            fcb.truncate(0)?;
+        }
+
+        Ok(fcb)
     }
 
     pub fn creat(path: &str) -> Result<FsRawFileControlBlock> {
@@ -298,6 +327,18 @@ impl FileManager {
         fs.unlink(dir, &name)
     }
 
+    pub fn rmdir(path: &str) -> Result<()> {
+        let stat = Self::stat(path)?;
+        if !stat.file_type().is_dir() {
+            return Err(ErrorKind::NotADirectory.into());
+        }
+        if Self::read_dir(path)?.next().is_some() {
+            return Err(ErrorKind::DirectoryNotEmpty.into());
+        }
+
+        Self::unlink(path)
+    }
+
     pub fn stat(path: &str) -> Result<FsRawMetaData> {
         let (fs, mut inode, lpc) = Self::resolve_parent(path)?;
         if let Some(lpc) = lpc {
@@ -347,6 +388,11 @@ impl FileManager {
         }
     }
 
+    pub fn statfs(path: &str) -> Result<FsRawStatFs> {
+        let (fs, _inode) = Self::resolve_all(path)?;
+        fs.statfs()
+    }
+
     pub fn mount_points<'a>() -> RwLockReadGuard<'a, BTreeMap<String, Arc<dyn FsDriver>>> {
         let shared = FileManager::shared();
         shared.mount_points.read().unwrap()
@@ -425,6 +471,11 @@ pub trait FsDriver {
     fn unlink(&self, _dir: INodeType, _name: &str) -> Result<()> {
         Err(ErrorKind::ReadOnlyFilesystem.into())
     }
+
+    /// Returns the capacity and usage of the file system
+    fn statfs(&self) -> Result<FsRawStatFs> {
+        Err(ErrorKind::Unsupported.into())
+    }
 }
 
 pub trait FsAccessToken {
@@ -459,6 +510,12 @@ impl FsRawReadDir {
     fn new(fs: Arc<dyn FsDriver>, dir: INodeType) -> Self {
         Self { fs, dir, index: 0 }
     }
+
+    /// Returns the next entry without advancing
+    #[inline]
+    pub fn peek(&self) -> Option<FsRawDirEntry> {
+        self.fs.as_ref().read_dir(self.dir, self.index)
+    }
 }
 
 impl Iterator for FsRawReadDir {
@@ -536,6 +593,36 @@ impl FsRawMetaData {
     }
 }
 
+pub struct FsRawStatFs {
+    total_bytes: u64,
+    free_bytes: u64,
+}
+
+impl FsRawStatFs {
+    #[inline]
+    pub const fn new(total_bytes: u64, free_bytes: u64) -> Self {
+        Self {
+            total_bytes,
+            free_bytes,
+        }
+    }
+
+    #[inline]
+    pub const fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    #[inline]
+    pub const fn free_bytes(&self) -> u64 {
+        self.free_bytes
+    }
+
+    #[inline]
+    pub const fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+}
+
 pub struct FsRawFileControlBlock {
     access_token: Arc<dyn FsAccessToken>,
     options: OpenOptions,
@@ -633,6 +720,9 @@ impl Write for FsRawFileControlBlock {
         if !self.options.contains(OpenOptions::WRITE) {
             return Err(ErrorKind::InvalidInput.into());
         }
+        if self.options.contains(OpenOptions::APPEND) && !self.is_device {
+            self.lseek(0, Whence::SeekEnd)?;
+        }
         self.access_token.write_data(self.file_pos, buf).map(|v| {
             self.file_pos += v as OffsetType;
             v
@@ -1,6 +1,6 @@
 // use crate::*;
 use super::*;
-use crate::sync::Mutex;
+use crate::{mem::MemoryManager, sync::Mutex};
 use core::{
     ops::DerefMut,
     sync::atomic::{AtomicUsize, Ordering},
@@ -99,6 +99,23 @@ impl RamFs {
         }
     }
 
+    /// Returns the total size of the files in the tree
+    fn used_bytes(entity: &ThisFsInodeEntity) -> usize {
+        match entity.content {
+            ThisFsContent::File(ref v) => v.estimated_size(),
+            ThisFsContent::Directory(ref v) => {
+                // Releases the lock before walking the children
+                let children = v
+                    .lock()
+                    .content
+                    .iter()
+                    .map(|v| v.entity.clone())
+                    .collect::<Vec<_>>();
+                children.iter().map(|v| Self::used_bytes(v)).sum()
+            }
+        }
+    }
+
     #[inline]
     fn compare_name(lhs: &str, rhs: &str) -> bool {
         lhs == rhs
@@ -263,6 +280,13 @@ impl FsDriver for RamFs {
             Ok(())
         }
     }
+
+    fn statfs(&self) -> Result<FsRawStatFs> {
+        // The capacity is the memory that is available now
+        let used = Self::used_bytes(&self.root) as u64;
+        let free = MemoryManager::free_memory_size() as u64;
+        Ok(FsRawStatFs::new(used + free, free))
+    }
 }
 
 struct ThisFsDirEntry {
@@ -402,13 +426,18 @@ impl ThisFsFile {
         } else {
             return Err(ErrorKind::InvalidInput.into());
         };
+        if length > FILE_SIZE_MAX {
+            return Err(ErrorKind::FilesystemQuotaExceeded.into());
+        }
         let mut content = self.content.lock().unwrap();
-        if content.len() <= length {
-            content.resize(length, 0);
-            Ok(())
-        } else {
-            Err(ErrorKind::InvalidInput.into())
+        if let Some(additional) = length.checked_sub(content.len()) {
+            if content.try_reserve(additional).is_err() {
+                return Err(ErrorKind::StorageFull.into());
+            }
         }
+        content.resize(length, 0);
+        self.estimated_size.store(length, Ordering::SeqCst);
+        Ok(())
     }
 }
 
@@ -5,7 +5,7 @@ use crate::{
     task::scheduler::{Priority, SpawnOption, Timer},
     *,
 };
-use alloc::slice;
+use alloc::{collections::VecDeque, slice};
 use core::{
     f64::consts::TAU,
     mem::transmute,
@@ -35,6 +35,9 @@ pub struct AudioManager {
 impl AudioManager {
     pub const DEFAULT_SAMPLE_RATE: FreqType = 44_100.0;
 
+    /// Time to wait for the hardware driver before falling back to the null driver
+    const DRIVER_TIMEOUT: Duration = Duration::from_millis(3000);
+
     #[inline]
     pub unsafe fn init() {
         assert_call_once!();
@@ -111,12 +114,20 @@ impl AudioManager {
     fn _audio_thread(_: usize) {
         let shared = Self::shared();
 
+        let deadline = Timer::new(Self::DRIVER_TIMEOUT);
         let driver = loop {
             Timer::sleep(Duration::from_millis(100));
             match Self::shared().audio_driver.lock().unwrap().clone() {
                 Some(v) => break v,
                 None => (),
             }
+            if deadline.is_expired() {
+                let driver = NullAudioDriver::new();
+                unsafe {
+                    Self::set_audio_driver(driver.clone());
+                }
+                break driver;
+            }
         };
         driver.set_master_volume(10);
 
@@ -188,6 +199,33 @@ pub trait AudioDriver {
     fn write_block(&self, data: &[u8]) -> Option<()>;
 }
 
+/// Audio driver that discards the samples, used when no audio hardware is present
+pub struct NullAudioDriver;
+
+impl NullAudioDriver {
+    /// Size of the buffer, 1024 stereo samples of 16bit
+    const SIZE_OF_BUFFER: usize = 4096;
+
+    #[inline]
+    pub fn new() -> Arc<dyn AudioDriver> {
+        Arc::new(Self) as Arc<dyn AudioDriver>
+    }
+}
+
+impl AudioDriver for NullAudioDriver {
+    fn set_master_volume(&self, _gain: usize) -> bool {
+        false
+    }
+
+    fn size_of_buffer(&self) -> usize {
+        Self::SIZE_OF_BUFFER
+    }
+
+    fn write_block(&self, _data: &[u8]) -> Option<()> {
+        Some(())
+    }
+}
+
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct AudioContextHandle(NonZeroUsize);
@@ -261,6 +299,24 @@ impl AudioContext {
         AudioNode::new(ctx, move |v| v * gain)
     }
 
+    /// Creates a source node that plays the samples queued to the stream
+    pub fn create_pcm_source(self: &Arc<Self>, stream: &Arc<PcmStream>) -> Box<AudioNode> {
+        let ctx = Arc::downgrade(self);
+        let stream = stream.clone();
+        let step = stream.sample_rate / AudioManager::DEFAULT_SAMPLE_RATE;
+        let mut time = 0.0;
+        AudioNode::new(ctx, move |data| {
+            let mut queue = stream.queue.lock().unwrap();
+            let result = queue.front().map(|v| data * v).unwrap_or(0.0);
+            time += step;
+            while time >= 1.0 {
+                queue.pop_front();
+                time -= 1.0;
+            }
+            result
+        })
+    }
+
     #[inline]
     pub fn destination(self: &Arc<Self>) -> Box<AudioNode> {
         AudioNode::closed(Arc::downgrade(self))
@@ -417,6 +473,46 @@ impl NoteControl {
     }
 }
 
+/// Queue of PCM samples written by applications
+pub struct PcmStream {
+    queue: Mutex<VecDeque<SampleType>>,
+    sample_rate: FreqType,
+    capacity: usize,
+}
+
+impl PcmStream {
+    pub const SAMPLE_RATE_MIN: FreqType = 8_000.0;
+    pub const SAMPLE_RATE_MAX: FreqType = 96_000.0;
+
+    /// Creates a stream that can queue samples for one second
+    #[inline]
+    pub fn new(sample_rate: FreqType) -> Option<Arc<Self>> {
+        if sample_rate < Self::SAMPLE_RATE_MIN || sample_rate > Self::SAMPLE_RATE_MAX {
+            return None;
+        }
+        let capacity = sample_rate as usize;
+        Some(Arc::new(Self {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            sample_rate,
+            capacity,
+        }))
+    }
+
+    #[inline]
+    pub const fn sample_rate(&self) -> FreqType {
+        self.sample_rate
+    }
+
+    /// Queues the samples as much as possible and returns the number of samples queued
+    pub fn write(&self, samples: impl Iterator<Item = SampleType>) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let len = self.capacity.saturating_sub(queue.len());
+        let old_len = queue.len();
+        queue.extend(samples.take(len));
+        queue.len() - old_len
+    }
+}
+
 pub struct PulseWaveOscillator {
     full_length: f64,
     pos_length: f64,
@@ -911,6 +911,7 @@ impl HidManager {
             shared.key_modifier.set(bit_position, !event.is_break());
         }
         let event = KeyEvent::new(usage, shared.key_modifier.value(), event.flags());
+        GameInputManager::send_key(event);
         WindowManager::post_key_event(event);
     }
 
@@ -1252,6 +1253,51 @@ impl GameInputManager {
         })
     }
 
+    /// Removes a disconnected input. If it was the current input,
+    /// the most recently connected input or the simulated input takes its place.
+    pub fn disconnect_input(handle: GameInputHandle) {
+        let shared = HidManager::shared();
+        let mut game_inputs = shared.game_inputs.write().unwrap();
+        game_inputs.remove(&handle);
+        let mut current = shared.current_game_inputs.write().unwrap();
+        if *current == Some(handle) {
+            *current = game_inputs.keys().next_back().map(|v| *v);
+        }
+    }
+
+    /// Returns the handles of all connected inputs.
+    pub fn inputs() -> Vec<GameInputHandle> {
+        let shared = HidManager::shared();
+        shared
+            .game_inputs
+            .read()
+            .unwrap()
+            .keys()
+            .map(|v| *v)
+            .collect()
+    }
+
+    /// Returns the current state of the specified input.
+    pub fn get(handle: GameInputHandle) -> Option<GameInput> {
+        let shared = HidManager::shared();
+        shared
+            .game_inputs
+            .read()
+            .unwrap()
+            .get(&handle)
+            .map(|v| v.read().unwrap().clone())
+    }
+
+    /// Returns the current state of the input simulated by the keyboard.
+    #[inline]
+    pub fn simulated_input() -> GameInput {
+        HidManager::shared()
+            .simulated_game_input
+            .read()
+            .unwrap()
+            .clone()
+    }
+
     pub fn send_key(event: KeyEvent) {
         let position = match event.usage() {
             Usage::NUMPAD_2 => Some(GameInputButtonType::DpadDown),
@@ -1332,6 +1378,26 @@ impl GameInput {
         self.bitmap
     }
 
+    #[inline]
+    pub const fn left_trigger(&self) -> u8 {
+        self.lt
+    }
+
+    #[inline]
+    pub const fn right_trigger(&self) -> u8 {
+        self.rt
+    }
+
+    #[inline]
+    pub const fn left_stick(&self) -> (i16, i16) {
+        (self.x1 as i16, self.y1 as i16)
+    }
+
+    #[inline]
+    pub const fn right_stick(&self) -> (i16, i16) {
+        (self.x2 as i16, self.y2 as i16)
+    }
+
     #[inline]
     pub fn copy_from(&mut self, other: &Self) {
         unsafe {
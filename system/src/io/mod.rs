@@ -2,6 +2,7 @@ pub mod audio;
 pub mod hid_mgr;
 pub mod image;
 pub mod screen;
+pub mod stdio;
 pub mod tty;
 
 pub mod emcon;
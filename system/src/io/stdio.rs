@@ -0,0 +1,274 @@
+//! Standard streams of processes
+//!
+//! Each process has the standard input, output and error inherited from its parent.
+//! They are connected to the terminal by default, and the shell connects them to files or pipes.
+
+use crate::{
+    fs::FsRawFileControlBlock,
+    sync::{semaphore::Semaphore, Mutex},
+    system::System,
+    task::scheduler::Scheduler,
+};
+use alloc::collections::VecDeque;
+use core::{
+    fmt::{self, Write as _},
+    sync::atomic::*,
+};
+use megstd::{
+    io::{ErrorKind, Read, Result, Write},
+    Arc, String, Vec,
+};
+
+static TTY_INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// Stream connected to the standard input, output or error of a process
+#[derive(Clone, Default)]
+pub enum StdStream {
+    /// Terminal of the system
+    #[default]
+    Tty,
+    /// File opened for redirection
+    File(Arc<Mutex<FsRawFileControlBlock>>),
+    /// Read end of a pipe
+    PipeReader(Arc<PipeReader>),
+    /// Write end of a pipe
+    PipeWriter(Arc<PipeWriter>),
+}
+
+impl StdStream {
+    /// Maximum length of a line read from the terminal
+    const MAX_LINE_LEN: usize = 250;
+
+    #[inline]
+    pub fn file(file: FsRawFileControlBlock) -> Self {
+        Self::File(Arc::new(Mutex::new(file)))
+    }
+
+    /// Creates a pipe, returns the read end and the write end.
+    ///
+    /// The writer blocks while the buffer of the pipe is full, and the reader blocks while it is empty.
+    /// The reader gets the end of the stream after all write ends are dropped,
+    /// and the writer fails with `BrokenPipe` after all read ends are dropped.
+    pub fn pipe() -> (Self, Self) {
+        let pipe = Arc::new(Pipe::new());
+        (
+            Self::PipeReader(Arc::new(PipeReader(pipe.clone()))),
+            Self::PipeWriter(Arc::new(PipeWriter(pipe))),
+        )
+    }
+
+    /// Returns the standard input of the current process
+    #[inline]
+    pub fn stdin() -> Self {
+        Scheduler::current_pid().stdio(0)
+    }
+
+    /// Returns the standard output of the current process
+    #[inline]
+    pub fn stdout() -> Self {
+        Scheduler::current_pid().stdio(1)
+    }
+
+    /// Returns the standard error of the current process
+    #[inline]
+    pub fn stderr() -> Self {
+        Scheduler::current_pid().stdio(2)
+    }
+
+    #[inline]
+    pub fn is_tty(&self) -> bool {
+        matches!(self, Self::Tty)
+    }
+
+    /// Wakes up the threads blocked on the stream, called when the process is requested to terminate
+    pub fn wake(&self) {
+        match self {
+            Self::Tty => System::stdout().wake_reader(),
+            Self::File(_) => (),
+            Self::PipeReader(pipe) => pipe.0.readable.signal(),
+            Self::PipeWriter(pipe) => pipe.0.writable.signal(),
+        }
+    }
+
+    /// Reads a line from the terminal when the buffered input runs out
+    fn read_tty(buf: &mut [u8]) -> Result<usize> {
+        let mut input = TTY_INPUT.lock().unwrap();
+        if input.is_empty() {
+            match System::stdout().read_line(Self::MAX_LINE_LEN) {
+                Ok(line) => {
+                    input.extend(line.bytes());
+                    input.push_back(b'\n');
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(input.len());
+        for (p, q) in buf.iter_mut().zip(input.drain(..len)) {
+            *p = q;
+        }
+        Ok(len)
+    }
+}
+
+impl Read for StdStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self {
+            Self::Tty => Self::read_tty(buf),
+            Self::File(file) => file.lock().unwrap().read(buf),
+            Self::PipeReader(pipe) => pipe.read(buf),
+            Self::PipeWriter(_) => Err(ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut chunk = [0; 256];
+        let mut total = 0;
+        loop {
+            let len = self.read(&mut chunk)?;
+            if len == 0 {
+                return Ok(total);
+            }
+            buf.extend_from_slice(&chunk[..len]);
+            total += len;
+        }
+    }
+}
+
+impl Write for StdStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Self::Tty => {
+                let _ = System::stdout().write_str(&String::from_utf8_lossy(buf));
+                Ok(buf.len())
+            }
+            Self::File(file) => file.lock().unwrap().write(buf),
+            Self::PipeWriter(pipe) => pipe.write(buf),
+            Self::PipeReader(_) => Err(ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::File(file) => file.lock().unwrap().flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Write for StdStream {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self {
+            Self::Tty => System::stdout().write_str(s),
+            _ => self.write_all(s.as_bytes()).map_err(|_| fmt::Error),
+        }
+    }
+}
+
+struct Pipe {
+    buffer: Mutex<VecDeque<u8>>,
+    /// Signalled when the data is written or the write end is closed
+    readable: Semaphore,
+    /// Signalled when the data is read or the read end is closed
+    writable: Semaphore,
+    is_reader_closed: AtomicBool,
+    is_writer_closed: AtomicBool,
+}
+
+impl Pipe {
+    /// Size of the buffer, the writer blocks when it is full
+    const CAPACITY: usize = 4096;
+
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            buffer: Mutex::new(VecDeque::new()),
+            readable: Semaphore::new(0),
+            writable: Semaphore::new(0),
+            is_reader_closed: AtomicBool::new(false),
+            is_writer_closed: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Read end of a pipe
+pub struct PipeReader(Arc<Pipe>);
+
+impl PipeReader {
+    /// Waits for the data, returns 0 if the write end has been closed and no data remains.
+    ///
+    /// Fails with `Interrupted` if the current process is requested to terminate.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            {
+                let mut buffer = self.0.buffer.lock().unwrap();
+                if !buffer.is_empty() {
+                    let len = buf.len().min(buffer.len());
+                    for (p, q) in buf.iter_mut().zip(buffer.drain(..len)) {
+                        *p = q;
+                    }
+                    drop(buffer);
+                    self.0.writable.signal();
+                    return Ok(len);
+                }
+                if self.0.is_writer_closed.load(Ordering::SeqCst) {
+                    return Ok(0);
+                }
+            }
+            if Scheduler::current_pid().is_terminating() {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            self.0.readable.wait();
+        }
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.0.is_reader_closed.store(true, Ordering::SeqCst);
+        self.0.writable.signal();
+    }
+}
+
+/// Write end of a pipe
+pub struct PipeWriter(Arc<Pipe>);
+
+impl PipeWriter {
+    /// Waits for the space in the buffer, returns the number of bytes written.
+    ///
+    /// Fails with `BrokenPipe` if the read end has been closed,
+    /// or `Interrupted` if the current process is requested to terminate.
+    pub fn write(&self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.0.is_reader_closed.load(Ordering::SeqCst) {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+            {
+                let mut buffer = self.0.buffer.lock().unwrap();
+                let len = buf.len().min(Pipe::CAPACITY - buffer.len());
+                if len > 0 {
+                    buffer.extend(&buf[..len]);
+                    drop(buffer);
+                    self.0.readable.signal();
+                    return Ok(len);
+                }
+            }
+            if Scheduler::current_pid().is_terminating() {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            self.0.writable.wait();
+        }
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.0.is_writer_closed.store(true, Ordering::SeqCst);
+        self.0.readable.signal();
+    }
+}
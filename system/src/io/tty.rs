@@ -1,6 +1,7 @@
 //! TeleTypewriter
 
-use alloc::{boxed::Box, string::String, vec::Vec};
+use crate::task::scheduler::{ProcessId, Scheduler};
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec::Vec};
 use core::{
     cell::UnsafeCell,
     fmt::Write,
@@ -31,57 +32,390 @@ pub trait TtyWrite: Write {
 
 pub trait TtyRead {
     fn read_async(&self) -> Pin<Box<dyn Future<Output = TtyReadResult> + '_>>;
+
+    /// Adds the process that Ctrl+C interrupts, Ctrl+C is read as an input while there are none
+    fn add_foreground_process(&self, _pid: ProcessId) {}
+
+    /// Removes the process added by `add_foreground_process`
+    fn remove_foreground_process(&self, _pid: ProcessId) {}
+
+    /// Records Ctrl+C pressed while no process is in the foreground, it is still read as an input
+    fn set_catch_interrupt(&self, _catch: bool) {}
+
+    /// Returns whether Ctrl+C has been caught since the last call
+    fn take_interrupt(&self) -> bool {
+        false
+    }
+
+    /// Wakes up the reader so that it sees the termination request of its process
+    fn wake_reader(&self) {}
 }
 
 pub trait Tty: TtyWrite + TtyRead {}
 
 impl dyn Tty {
+    /// Reads a line without the history and the completion.
     pub async fn read_line_async(&mut self, max_length: usize) -> Result<String, TtyError> {
-        let mut buffer: Vec<char> = Vec::with_capacity(max_length);
+        LineEditor::new(0)
+            .read_line_async(self, "", max_length, None)
+            .await
+    }
+
+    /// Reads a line, blocking the current thread until it is entered.
+    pub fn read_line(&mut self, max_length: usize) -> Result<String, TtyError> {
+        Scheduler::block_on(self.read_line_async(max_length))
+    }
+}
+
+/// Returns the number of cells that the character occupies on the terminal
+///
+/// Wide characters such as CJK ideographs take two cells,
+/// control characters and combining marks take none.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x00..=0x1F
+        | 0x7F..=0x9F
+        | 0x0300..=0x036F
+        | 0x200B..=0x200F
+        | 0xFE00..=0xFE0F
+        | 0xFEFF => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Completes the word at the cursor, receives the text before the word and the word itself,
+/// and returns the candidates that replace the word.
+pub type LineCompleter<'a> = &'a dyn Fn(&str, &str) -> Vec<String>;
+
+/// Line editor with the cursor movement, the history and the completion
+///
+/// | Key                | Action                             |
+/// |--------------------|------------------------------------|
+/// | Left, Ctrl+B       | Moves the cursor backward          |
+/// | Right, Ctrl+F      | Moves the cursor forward           |
+/// | Home, Ctrl+A       | Moves the cursor to the beginning  |
+/// | End, Ctrl+E        | Moves the cursor to the end        |
+/// | Backspace          | Deletes the character before       |
+/// | Delete             | Deletes the character at cursor    |
+/// | Ctrl+K             | Deletes to the end                 |
+/// | Ctrl+U             | Deletes to the beginning           |
+/// | Up, Ctrl+P         | Previous line in the history       |
+/// | Down, Ctrl+N       | Next line in the history           |
+/// | Tab                | Completes the word                 |
+/// | Ctrl+C             | Cancels                            |
+pub struct LineEditor {
+    history: Vec<String>,
+    max_history: usize,
+}
+
+impl LineEditor {
+    /// Creates a line editor that remembers up to `max_history` lines
+    #[inline]
+    pub const fn new(max_history: usize) -> Self {
+        Self {
+            history: Vec::new(),
+            max_history,
+        }
+    }
+
+    #[inline]
+    pub fn history(&self) -> &[String] {
+        self.history.as_slice()
+    }
+
+    /// Adds the line to the history, empty lines and repeated lines are ignored.
+    pub fn add_history(&mut self, line: &str) {
+        if self.max_history == 0
+            || line.trim().is_empty()
+            || self.history.last().map(|v| v.as_str()) == Some(line)
+        {
+            return;
+        }
+        if self.history.len() >= self.max_history {
+            self.history.remove(0);
+        }
+        self.history.push(line.to_owned());
+    }
+
+    #[inline]
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Prints the prompt and reads a line, the entered line is added to the history.
+    pub async fn read_line_async(
+        &mut self,
+        tty: &mut dyn Tty,
+        prompt: &str,
+        max_length: usize,
+        completer: Option<LineCompleter<'_>>,
+    ) -> Result<String, TtyError> {
+        let mut line = EditingLine::new(tty, prompt);
+        let mut history_index = self.history.len();
+        let mut pending = Vec::new();
         loop {
-            self.set_cursor_enabled(true);
-            match self.read_async().await {
-                Ok(c) => {
-                    self.set_cursor_enabled(false);
-                    match c {
-                        '\r' | '\n' => {
-                            self.write_str("\r\n").unwrap();
-                            break;
+            line.tty.set_cursor_enabled(true);
+            let c = match line.tty.read_async().await {
+                Ok(c) => c,
+                Err(TtyError::EndOfStream) => return Err(TtyError::EndOfStream),
+                Err(_) => continue,
+            };
+            line.tty.set_cursor_enabled(false);
+            match c {
+                '\r' | '\n' => {
+                    line.cursor = line.buffer.len();
+                    line.update_cursor();
+                    line.tty.write_str("\r\n").unwrap();
+                    break;
+                }
+                '\x03' => return Err(TtyError::EndOfStream),
+                '\x01' => line.move_to(0),
+                '\x05' => line.move_to(line.buffer.len()),
+                '\x02' => line.move_to(line.cursor.saturating_sub(1)),
+                '\x06' => line.move_to(line.cursor + 1),
+                '\x08' => {
+                    if line.cursor > 0 {
+                        line.cursor -= 1;
+                        line.buffer.remove(line.cursor);
+                        line.refresh();
+                    }
+                }
+                '\x7F' => {
+                    if line.cursor < line.buffer.len() {
+                        line.buffer.remove(line.cursor);
+                        line.refresh();
+                    }
+                }
+                '\x0B' => {
+                    line.buffer.truncate(line.cursor);
+                    line.refresh();
+                }
+                '\x15' => {
+                    line.buffer.drain(..line.cursor);
+                    line.cursor = 0;
+                    line.refresh();
+                }
+                '\x10' => {
+                    if history_index > 0 {
+                        if history_index == self.history.len() {
+                            pending = line.buffer.clone();
                         }
-                        '\x03' => return Err(TtyError::EndOfStream),
-                        '\x08' => match buffer.pop() {
-                            Some(c) => {
-                                if c < ' ' {
-                                    self.write_str("\x08\x08  \x08\x08").unwrap();
-                                } else {
-                                    self.write_str("\x08 \x08").unwrap();
-                                }
-                            }
-                            None => (),
-                        },
-                        _ => {
-                            if buffer.len() < max_length {
-                                if c < ' ' {
-                                    // Control char
-                                    self.write_char('^').unwrap();
-                                    self.write_char((c as u8 | 0x40) as char).unwrap();
-                                    buffer.push(c);
-                                } else if c < '\x7F' {
-                                    // Printable ascii
-                                    self.write_char(c).unwrap();
-                                    buffer.push(c);
-                                } else {
-                                    // TODO:
-                                }
-                            }
+                        history_index -= 1;
+                        line.replace(self.history[history_index].chars().collect());
+                    }
+                }
+                '\x0E' => {
+                    if history_index < self.history.len() {
+                        history_index += 1;
+                        match self.history.get(history_index) {
+                            Some(v) => line.replace(v.chars().collect()),
+                            None => line.replace(core::mem::take(&mut pending)),
                         }
                     }
                 }
-                Err(TtyError::EndOfStream) => return Err(TtyError::EndOfStream),
-                Err(_) => (),
+                '\t' => {
+                    if let Some(completer) = completer {
+                        line.complete(completer, max_length);
+                    }
+                }
+                _ => {
+                    if char_width(c) > 0 && line.buffer.len() < max_length {
+                        line.buffer.insert(line.cursor, c);
+                        line.cursor += 1;
+                        line.refresh();
+                    }
+                }
+            }
+        }
+        let result = line.buffer.iter().collect::<String>();
+        self.add_history(&result);
+        Ok(result)
+    }
+}
+
+/// Line being edited on the terminal
+struct EditingLine<'a> {
+    tty: &'a mut dyn Tty,
+    prompt: &'a str,
+    buffer: Vec<char>,
+    cursor: usize,
+    /// Position where the line begins, may be negative if the terminal has scrolled
+    origin: (isize, isize),
+    /// Number of cells drawn at the last refresh
+    drawn_cells: isize,
+}
+
+impl<'a> EditingLine<'a> {
+    fn new(tty: &'a mut dyn Tty, prompt: &'a str) -> Self {
+        tty.write_str(prompt).unwrap();
+        let origin = tty.cursor_position();
+        Self {
+            tty,
+            prompt,
+            buffer: Vec::new(),
+            cursor: 0,
+            origin,
+            drawn_cells: 0,
+        }
+    }
+
+    #[inline]
+    fn cols(&self) -> isize {
+        self.tty.dims().0.max(1)
+    }
+
+    /// Returns the position of the character at the index, wrapped in the same way as the terminal
+    fn position_of(&self, index: usize) -> (isize, isize) {
+        let cols = self.cols();
+        let (mut x, mut y) = self.origin;
+        for c in self.buffer.iter().take(index) {
+            let w = char_width(*c) as isize;
+            if x + w > cols {
+                x = 0;
+                y += 1;
+            }
+            x += w;
+        }
+        if x >= cols {
+            x = 0;
+            y += 1;
+        }
+        (x, y)
+    }
+
+    #[inline]
+    fn linear_position(&self, index: usize) -> isize {
+        let (x, y) = self.position_of(index);
+        y * self.cols() + x
+    }
+
+    /// Redraws the line from the beginning and moves the cursor
+    fn refresh(&mut self) {
+        let start = (0..self.buffer.len())
+            .find(|index| self.position_of(*index).1 >= 0)
+            .unwrap_or(self.buffer.len());
+        let (x, y) = self.position_of(start);
+        let start_cell = self.linear_position(0);
+        let end_cell = self.linear_position(self.buffer.len());
+        let new_cells = end_cell - start_cell;
+        let n_spaces = (self.drawn_cells - new_cells).max(0);
+
+        self.tty.set_cursor_position(x, y.max(0));
+        let mut sb = String::with_capacity(self.buffer.len() + n_spaces as usize);
+        sb.extend(&self.buffer[start..]);
+        for _ in 0..n_spaces {
+            sb.push(' ');
+        }
+        self.tty.write_str(sb.as_str()).unwrap();
+        self.drawn_cells = new_cells;
+
+        // Adjusts the origin if the terminal has scrolled
+        let cols = self.cols();
+        let expected = end_cell + n_spaces;
+        let (ax, ay) = self.tty.cursor_position();
+        let actual_y = if ax >= cols { ay + 1 } else { ay };
+        let scrolled = expected.div_euclid(cols) - actual_y;
+        if scrolled > 0 {
+            self.origin.1 -= scrolled;
+        }
+
+        self.update_cursor();
+    }
+
+    /// Moves the terminal cursor to the editing position
+    fn update_cursor(&mut self) {
+        let rows = self.tty.dims().1.max(1);
+        let (x, mut y) = self.position_of(self.cursor);
+        while y >= rows {
+            self.tty.set_cursor_position(0, rows - 1);
+            self.tty.write_str("\n").unwrap();
+            self.origin.1 -= 1;
+            y -= 1;
+        }
+        self.tty.set_cursor_position(x, y.max(0));
+    }
+
+    #[inline]
+    fn move_to(&mut self, index: usize) {
+        self.cursor = index.min(self.buffer.len());
+        self.update_cursor();
+    }
+
+    #[inline]
+    fn replace(&mut self, buffer: Vec<char>) {
+        self.buffer = buffer;
+        self.cursor = self.buffer.len();
+        self.refresh();
+    }
+
+    /// Completes the word before the cursor
+    fn complete(&mut self, completer: LineCompleter, max_length: usize) {
+        let word_start = self.buffer[..self.cursor]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map(|v| v + 1)
+            .unwrap_or(0);
+        let before = self.buffer[..word_start].iter().collect::<String>();
+        let word = self.buffer[word_start..self.cursor]
+            .iter()
+            .collect::<String>();
+        let mut candidates = completer(&before, &word);
+        candidates.sort();
+        candidates.dedup();
+
+        let mut completion = match candidates.first() {
+            Some(v) => v.chars().collect::<Vec<_>>(),
+            None => return,
+        };
+        for candidate in &candidates[1..] {
+            let len = completion
+                .iter()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| **a == *b)
+                .count();
+            completion.truncate(len);
+        }
+        if candidates.len() == 1 && completion.last() != Some(&'/') {
+            completion.push(' ');
+        }
+
+        let word_len = self.cursor - word_start;
+        if completion.len() > word_len || candidates.len() == 1 {
+            if self.buffer.len() - word_len + completion.len() <= max_length {
+                self.buffer
+                    .splice(word_start..self.cursor, completion.iter().copied());
+                self.cursor = word_start + completion.len();
+                self.refresh();
+            }
+        } else {
+            // Shows the candidates and prints the line again
+            self.cursor = self.buffer.len();
+            self.update_cursor();
+            self.tty.write_str("\r\n").unwrap();
+            for candidate in &candidates {
+                write!(self.tty, "{}  ", candidate).unwrap();
             }
+            self.tty.write_str("\r\n").unwrap();
+            self.tty.write_str(self.prompt).unwrap();
+            self.origin = self.tty.cursor_position();
+            self.drawn_cells = 0;
+            self.cursor = word_start + word_len;
+            self.refresh();
         }
-        Ok(buffer.as_slice().iter().clone().collect::<String>())
     }
 }
 
@@ -6,13 +6,62 @@
 #![no_main]
 
 extern crate alloc;
+use alloc::collections::VecDeque;
 use bootprot::*;
-use core::{fmt, fmt::Write, num::NonZeroU8};
+use core::{
+    fmt,
+    fmt::Write,
+    iter::Peekable,
+    num::NonZeroU8,
+    str::Chars,
+    sync::atomic::{AtomicBool, Ordering},
+};
 use kernel::{
-    drivers::pci, drivers::usb, fs::OpenOptions, fs::*, mem::*, rt::*, system::*,
-    task::scheduler::*, ui::window::WindowManager, user::userenv::UserEnv, *,
+    drivers::pci,
+    drivers::usb,
+    fs::OpenOptions,
+    fs::*,
+    io::{stdio::StdStream, tty::LineEditor},
+    mem::*,
+    rt::*,
+    sync::Mutex,
+    system::*,
+    task::scheduler::*,
+    ui::{
+        terminal::Terminal,
+        window::{WindowHandle, WindowManager},
+    },
+    user::{
+        editor::TextEditor,
+        launcher::{Handler, Launcher},
+        screenshot::Screenshot,
+        userenv::UserEnv,
+    },
+    *,
+};
+use megstd::{
+    drawing::ImageFormat,
+    io::{ErrorKind, Read, Write as _},
+    regex::Regex,
+    sys::megos::caps::*,
+    BTreeMap, Box, String, ToOwned, ToString, Vec,
 };
-use megstd::{io::Read, String, ToOwned, ToString, Vec};
+
+/// Prints to the standard output of the current process, which may be redirected by the shell.
+///
+/// Shadows the kernel macro that always prints to the system console.
+macro_rules! print {
+    ($($arg:tt)*) => {{
+        let _ = write!(StdStream::stdout(), $($arg)*);
+    }};
+}
+
+/// Prints a line to the standard output of the current process, see [`print!`]
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        let _ = writeln!(StdStream::stdout(), $($arg)*);
+    }};
+}
 
 /// Kernel entry point
 #[no_mangle]
@@ -22,137 +71,943 @@ unsafe fn _start(info: &BootInfo) -> ! {
 
 static mut MAIN: Shell = Shell::new();
 
+/// Snapshots of the shell for the commands of the pipelines running in the child processes
+static SUBSHELLS: Mutex<BTreeMap<ProcessId, Box<Shell>>> = Mutex::new(BTreeMap::new());
+
+/// Set when Ctrl+C interrupts the foreground job to stop the rest of the command line
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 pub struct Shell {
     path_ext: Vec<String>,
+    last_status: usize,
+    editor: LineEditor,
+    history_file: Option<String>,
+    /// Variables of the shell, not passed to the applications until exported
+    vars: BTreeMap<String, String>,
+    /// Positional parameters of the running script, `$0` is the path of the script
+    args: Vec<String>,
+    /// Set by `exit` to stop the running script
+    is_exiting: bool,
+    /// Runs a command of the pipeline in a child process, Ctrl+C is taken by the shell
+    is_subshell: bool,
+    jobs: Vec<Job>,
+}
+
+/// Command of the pipeline running in a child process with the snapshot of the shell
+struct PipelineStage {
+    args: Vec<String>,
+    shell: Shell,
+}
+
+/// Process running in the background
+struct Job {
+    id: usize,
+    pid: ProcessId,
+    cmdline: String,
 }
 
 enum ParsedCmdLine {
     Empty,
     InvalidQuote,
+    InvalidSyntax,
+    /// The block is not closed by `fi` or `done`
+    Incomplete,
+}
+
+/// Node of a parsed script
+enum ScriptNode {
+    /// Command line, which is expanded when it runs
+    Command(String),
+    /// `if COND; then BODY; [elif COND; then BODY;]... [else BODY;] fi`
+    If {
+        branches: Vec<(Vec<ScriptNode>, Vec<ScriptNode>)>,
+        otherwise: Vec<ScriptNode>,
+    },
+    /// `for NAME [in WORDS]; do BODY; done`, iterates the positional parameters without `in`
+    For {
+        name: String,
+        words: Option<String>,
+        body: Vec<ScriptNode>,
+    },
+    /// `while COND; do BODY; done`
+    While {
+        cond: Vec<ScriptNode>,
+        body: Vec<ScriptNode>,
+    },
+}
+
+/// Token of the command line
+enum CmdLineToken {
+    Word(String),
+    /// `|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;`
+    Semicolon,
+    /// `<`
+    RedirectIn,
+    /// `>`
+    RedirectOut,
+    /// `>>`
+    RedirectAppend,
+}
+
+/// Condition to run a pipeline after the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmdSequence {
+    /// At the beginning of the line or after `;`
+    Always,
+    /// After `&&`
+    IfSuccess,
+    /// After `||`
+    IfFailure,
+}
+
+/// Command in a pipeline with its redirections
+#[derive(Default)]
+struct ParsedCommand {
+    args: Vec<String>,
+    stdin: Option<String>,
+    /// Path of the output and whether to append to it
+    stdout: Option<(String, bool)>,
 }
 
 impl Shell {
+    const MAX_HISTORY: usize = 100;
+    /// Extension of the script files
+    const SCRIPT_EXT: &'static str = "sh";
+    /// Exit code of the processes terminated by `kill`
+    const EXIT_KILLED: usize = 143;
+
     const fn new() -> Self {
         Self {
             path_ext: Vec::new(),
+            last_status: 0,
+            editor: LineEditor::new(Self::MAX_HISTORY),
+            history_file: None,
+            vars: BTreeMap::new(),
+            args: Vec::new(),
+            is_exiting: false,
+            is_subshell: false,
+            jobs: Vec::new(),
         }
     }
 
+    /// Returns the shell of the current process, which is the snapshot for a stage of the pipeline
     fn shared<'a>() -> &'a mut Self {
+        if let Some(shell) = SUBSHELLS.lock().unwrap().get_mut(&Scheduler::current_pid()) {
+            // Each snapshot is only accessed by its own process and is boxed so it does not move
+            return unsafe { &mut *(shell.as_mut() as *mut Self) };
+        }
         unsafe { &mut MAIN }
     }
 
+    /// Copies the variables and the parameters for a command running in a child process,
+    /// the changes made by the command do not affect the shell
+    fn snapshot(&self) -> Self {
+        Self {
+            path_ext: self.path_ext.clone(),
+            last_status: self.last_status,
+            vars: self.vars.clone(),
+            args: self.args.clone(),
+            is_subshell: true,
+            ..Self::new()
+        }
+    }
+
     // Shell entry point
-    fn start() {
+    fn start(run_startup_script: usize) {
         let shared = Self::shared();
         for ext in RuntimeEnvironment::supported_extensions() {
             shared.path_ext.push(ext.to_string());
         }
+        shared.path_ext.push(Self::SCRIPT_EXT.to_owned());
+
+        if run_startup_script != 0 {
+            Self::exec_script_file(UserEnv::STARTUP_SCRIPT, &[UserEnv::STARTUP_SCRIPT]);
+        }
 
         Scheduler::spawn_async(Self::repl_main());
         Scheduler::perform_tasks();
     }
 
     async fn repl_main() {
+        let mut source = String::new();
         loop {
-            print!("# ");
-            if let Ok(cmdline) = System::stdout().read_line_async(120).await {
-                Self::exec_cmd(&cmdline);
+            if source.is_empty() {
+                Self::report_jobs();
             }
+            Self::load_history();
+            let shared = Self::shared();
+            let path_ext = shared.path_ext.clone();
+            let completer = |before: &str, word: &str| Self::complete(&path_ext, before, word);
+            // Continues reading lines until the blocks are closed
+            let prompt = if source.is_empty() { "# " } else { "> " };
+            match shared
+                .editor
+                .read_line_async(System::stdout(), prompt, 120, Some(&completer))
+                .await
+            {
+                Ok(cmdline) => {
+                    Self::save_history(&cmdline);
+                    source.push_str(&cmdline);
+                    source.push('\n');
+                    match Self::parse_script(&source) {
+                        Err(ParsedCmdLine::Incomplete) => continue,
+                        result => {
+                            INTERRUPTED.store(false, Ordering::SeqCst);
+                            // Ctrl+C stops the loops that run only the builtin commands
+                            let stdout = System::stdout();
+                            stdout.set_catch_interrupt(true);
+                            Self::exec_parsed_script(result);
+                            stdout.set_catch_interrupt(false);
+                        }
+                    }
+                }
+                Err(_) => (),
+            }
+            source.clear();
         }
     }
 
-    fn exec_cmd(cmdline: &str) {
-        match Self::parse_cmd(cmdline) {
-            Ok((cmd, args)) => {
-                let name = cmd.as_str();
-                let mut args = args.iter().map(|v| v.as_str()).collect::<Vec<&str>>();
-                match name {
-                    "clear" | "cls" | "reset" => System::stdout().reset().unwrap(),
-                    "exit" => println!("Feature not available"),
-                    "echo" => {
-                        let stdout = System::stdout();
-                        for (index, word) in args.iter().skip(1).enumerate() {
-                            if index > 0 {
-                                stdout.write_char(' ').unwrap();
-                            }
-                            stdout.write_str(word).unwrap();
+    /// Loads the history file when `HISTFILE` has been changed
+    fn load_history() {
+        let shared = Self::shared();
+        let history_file = Scheduler::current_pid().get_env("HISTFILE");
+        if history_file == shared.history_file {
+            return;
+        }
+        if let Some(path) = history_file.as_ref() {
+            let mut vec = Vec::new();
+            if let Ok(mut file) = FileManager::open(path, OpenOptions::new().read(true)) {
+                let _ = file.read_to_end(&mut vec);
+            }
+            // Lines in the file come before the lines entered in this session
+            let session = shared.editor.history().to_vec();
+            shared.editor.clear_history();
+            for line in String::from_utf8_lossy(&vec).lines() {
+                shared.editor.add_history(line);
+            }
+            for line in &session {
+                shared.editor.add_history(line);
+            }
+        }
+        shared.history_file = history_file;
+    }
+
+    /// Appends the line to the history file if `HISTFILE` is set
+    fn save_history(cmdline: &str) {
+        let shared = Self::shared();
+        let Some(path) = shared.history_file.as_ref() else {
+            return;
+        };
+        if cmdline.trim().is_empty() {
+            return;
+        }
+        match FileManager::open(
+            path,
+            OpenOptions::new().write(true).append(true).create(true),
+        ) {
+            Ok(mut file) => {
+                let mut line = cmdline.to_owned();
+                line.push('\n');
+                let _ = file.write(line.as_bytes());
+            }
+            Err(err) => println!("{}: {:?}", path, err.kind()),
+        }
+    }
+
+    /// Returns the candidates to complete the word,
+    /// commands and executables for the first word, and paths for the others.
+    fn complete(path_ext: &[String], before: &str, word: &str) -> Vec<String> {
+        let is_command = before.trim().is_empty();
+        let mut candidates = Vec::new();
+        if is_command && !word.contains('/') {
+            for command in &Self::COMMAND_TABLE {
+                if command.0.starts_with(word) {
+                    candidates.push(command.0.to_owned());
+                }
+            }
+        }
+
+        let (dir, prefix) = match word.rfind('/') {
+            Some(pos) => word.split_at(pos + 1),
+            None => ("", word),
+        };
+        if let Ok(read_dir) = FileManager::read_dir(dir) {
+            for entry in read_dir {
+                let name = entry.name();
+                if !name.starts_with(prefix) || name == "." || name == ".." {
+                    continue;
+                }
+                if entry.metadata().file_type().is_dir() {
+                    candidates.push(alloc::format!("{}{}/", dir, name));
+                } else if !is_command {
+                    candidates.push(alloc::format!("{}{}", dir, name));
+                } else if let Some((stem, ext)) = name.rsplit_once('.') {
+                    if path_ext.iter().any(|v| v == ext) {
+                        candidates.push(alloc::format!("{}{}", dir, stem));
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Splits the script into the statements and parses the blocks
+    fn parse_script(source: &str) -> Result<Vec<ScriptNode>, ParsedCmdLine> {
+        let mut statements = Self::split_statements(source)?;
+        let (nodes, _) = Self::parse_block(&mut statements, &[])?;
+        if nodes.is_empty() {
+            Err(ParsedCmdLine::Empty)
+        } else {
+            Ok(nodes)
+        }
+    }
+
+    /// Splits the script at semicolons and new lines outside the quotes, and removes the comments
+    fn split_statements(source: &str) -> Result<VecDeque<String>, ParsedCmdLine> {
+        // Comments begin only at the beginning of the words
+        fn is_word_start(sb: &str) -> bool {
+            sb.chars()
+                .next_back()
+                .map(|c| c.is_whitespace() || matches!(c, '|' | '&' | '<' | '>'))
+                .unwrap_or(true)
+        }
+
+        let mut statements = VecDeque::new();
+        let mut sb = String::new();
+        let mut quote = None;
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) => {
+                    if c == q {
+                        quote = None;
+                    }
+                    sb.push(c);
+                }
+                None => match c {
+                    '\'' | '\"' => {
+                        quote = Some(c);
+                        sb.push(c);
+                    }
+                    '#' if is_word_start(&sb) => while chars.next_if(|c| *c != '\n').is_some() {},
+                    ';' | '\n' => {
+                        let statement = sb.trim();
+                        if !statement.is_empty() {
+                            statements.push_back(statement.to_owned());
                         }
-                        stdout.write_str("\r\n").unwrap();
+                        sb.clear();
                     }
-                    "ver" => {
-                        println!(
-                            "{} v{} (codename {})",
-                            System::name(),
-                            System::version(),
-                            System::codename()
-                        )
-                    }
-                    "reboot" => {
-                        UserEnv::system_reset(false);
-                    }
-                    "shutdown" => {
-                        UserEnv::system_reset(true);
-                    }
-                    "uptime" => {
-                        let systime = System::system_time();
-                        let sec = systime.secs;
-                        // let time_s = sec % 60;
-                        let time_m = (sec / 60) % 60;
-                        let time_h = (sec / 3600) % 24;
-
-                        let uptime = Timer::monotonic();
-                        let sec = uptime.as_secs();
-                        let upt_s = sec % 60;
-                        let upt_m = (sec / 60) % 60;
-                        let upt_h = (sec / 3600) % 24;
-                        let upt_d = sec / 86400;
-
-                        if upt_d > 0 {
-                            println!(
-                                "{:02}:{:02} up {} days, {:02}:{:02}",
-                                time_h, time_m, upt_d, upt_h, upt_m
-                            );
-                        } else {
-                            println!(
-                                "{:02}:{:02} up {:02}:{:02}:{:02}",
-                                time_h, time_m, upt_h, upt_m, upt_s
-                            );
+                    _ => sb.push(c),
+                },
+            }
+        }
+        if quote.is_some() {
+            return Err(ParsedCmdLine::InvalidQuote);
+        }
+        let statement = sb.trim();
+        if !statement.is_empty() {
+            statements.push_back(statement.to_owned());
+        }
+        Ok(statements)
+    }
+
+    /// Parses the statements until one of the terminators,
+    /// returns the nodes and the terminator found.
+    ///
+    /// The command after `then`, `else` or `do` in the same statement is put back to the statements.
+    fn parse_block(
+        statements: &mut VecDeque<String>,
+        terminators: &[&'static str],
+    ) -> Result<(Vec<ScriptNode>, &'static str), ParsedCmdLine> {
+        let mut nodes = Vec::new();
+        while let Some(statement) = statements.pop_front() {
+            let (keyword, rest) = match statement.split_once(char::is_whitespace) {
+                Some((keyword, rest)) => (keyword, rest.trim_start()),
+                None => (statement.as_str(), ""),
+            };
+            if let Some(terminator) = terminators.iter().find(|v| **v == keyword) {
+                match *terminator {
+                    "fi" | "done" if !rest.is_empty() => return Err(ParsedCmdLine::InvalidSyntax),
+                    _ => (),
+                }
+                if !rest.is_empty() {
+                    statements.push_front(rest.to_owned());
+                }
+                return Ok((nodes, terminator));
+            }
+            match keyword {
+                "if" => {
+                    if !rest.is_empty() {
+                        statements.push_front(rest.to_owned());
+                    }
+                    let mut branches = Vec::new();
+                    let mut otherwise = Vec::new();
+                    loop {
+                        let (cond, _) = Self::parse_block(statements, &["then"])?;
+                        if cond.is_empty() {
+                            return Err(ParsedCmdLine::InvalidSyntax);
+                        }
+                        let (body, terminator) =
+                            Self::parse_block(statements, &["elif", "else", "fi"])?;
+                        branches.push((cond, body));
+                        match terminator {
+                            "elif" => (),
+                            "else" => {
+                                otherwise = Self::parse_block(statements, &["fi"])?.0;
+                                break;
+                            }
+                            _ => break,
                         }
                     }
-                    "ts" => {
-                        let mut sb = String::new();
-                        Scheduler::get_thread_statistics(&mut sb);
-                        print!("{}", sb.as_str());
+                    nodes.push(ScriptNode::If {
+                        branches,
+                        otherwise,
+                    });
+                }
+                "while" => {
+                    if !rest.is_empty() {
+                        statements.push_front(rest.to_owned());
                     }
-                    "open" | "ncst" => {
-                        let args = &args[1..];
-                        let name = args[0];
-                        Self::spawn(name, args, false);
+                    let (cond, _) = Self::parse_block(statements, &["do"])?;
+                    if cond.is_empty() {
+                        return Err(ParsedCmdLine::InvalidSyntax);
                     }
-                    _ => match Self::command(name) {
-                        Some(exec) => {
-                            exec(args.as_slice());
+                    let (body, _) = Self::parse_block(statements, &["done"])?;
+                    nodes.push(ScriptNode::While { cond, body });
+                }
+                "for" => {
+                    let (name, words) = match rest.split_once(char::is_whitespace) {
+                        Some((name, rest)) => {
+                            match rest.trim_start().split_once(char::is_whitespace) {
+                                Some(("in", words)) => (name, Some(words.to_owned())),
+                                _ if rest.trim() == "in" => (name, Some(String::new())),
+                                _ => return Err(ParsedCmdLine::InvalidSyntax),
+                            }
                         }
+                        None => (rest, None),
+                    };
+                    if !Self::is_var_name(name) {
+                        return Err(ParsedCmdLine::InvalidSyntax);
+                    }
+                    let name = name.to_owned();
+                    let (nodes_before_do, _) = Self::parse_block(statements, &["do"])?;
+                    if !nodes_before_do.is_empty() {
+                        return Err(ParsedCmdLine::InvalidSyntax);
+                    }
+                    let (body, _) = Self::parse_block(statements, &["done"])?;
+                    nodes.push(ScriptNode::For { name, words, body });
+                }
+                "then" | "elif" | "else" | "fi" | "do" | "done" => {
+                    return Err(ParsedCmdLine::InvalidSyntax)
+                }
+                _ => nodes.push(ScriptNode::Command(statement)),
+            }
+        }
+        if terminators.is_empty() {
+            Ok((nodes, ""))
+        } else {
+            Err(ParsedCmdLine::Incomplete)
+        }
+    }
+
+    /// Runs the parsed script or prints the error
+    fn exec_parsed_script(result: Result<Vec<ScriptNode>, ParsedCmdLine>) {
+        match result {
+            Ok(nodes) => Self::exec_nodes(&nodes),
+            Err(ParsedCmdLine::Empty) => (),
+            Err(err) => {
+                Self::print_error(err);
+                Self::shared().last_status = 1;
+            }
+        }
+    }
+
+    /// Runs the script file with the positional parameters, returns the exit status
+    fn exec_script_file(path: &str, args: &[&str]) -> usize {
+        let mut vec = Vec::new();
+        match FileManager::open(path, OpenOptions::new().read(true)) {
+            Ok(mut file) => {
+                if let Err(err) = file.read_to_end(&mut vec) {
+                    println!("{}: {:?}", path, err.kind());
+                    return 1;
+                }
+            }
+            Err(err) => {
+                println!("{}: {:?}", path, err.kind());
+                return 1;
+            }
+        }
+        let shared = Self::shared();
+        let saved_args = core::mem::replace(
+            &mut shared.args,
+            args.iter().map(|v| v.to_string()).collect(),
+        );
+        Self::exec_parsed_script(Self::parse_script(&String::from_utf8_lossy(&vec)));
+        let shared = Self::shared();
+        shared.args = saved_args;
+        shared.is_exiting = false;
+        shared.last_status
+    }
+
+    fn exec_nodes(nodes: &[ScriptNode]) {
+        for node in nodes {
+            if Self::is_stopping() {
+                break;
+            }
+            match node {
+                ScriptNode::Command(cmdline) => Self::exec_cmd(cmdline),
+                ScriptNode::If {
+                    branches,
+                    otherwise,
+                } => {
+                    let branch = branches.iter().find(|(cond, _)| {
+                        Self::exec_nodes(cond);
+                        Self::shared().last_status == 0
+                    });
+                    match branch {
+                        Some((_, body)) => Self::exec_nodes(body),
                         None => {
-                            if args.len() > 1 && args.last() == Some(&"&") {
-                                args.remove(args.len() - 1);
-                                Self::spawn(name, args.as_slice(), false);
-                            } else {
-                                Self::spawn(name, args.as_slice(), true);
+                            Self::shared().last_status = 0;
+                            Self::exec_nodes(otherwise);
+                        }
+                    }
+                }
+                ScriptNode::For { name, words, body } => {
+                    let words = match words {
+                        Some(words) => match Self::expand_words(words) {
+                            Ok(v) => v,
+                            Err(err) => {
+                                Self::print_error(err);
+                                Self::shared().last_status = 1;
+                                continue;
                             }
+                        },
+                        None => Self::shared().args.iter().skip(1).cloned().collect(),
+                    };
+                    Self::shared().last_status = 0;
+                    for word in words {
+                        if Self::is_stopping() {
+                            break;
                         }
-                    },
+                        Self::set_var(name, &word);
+                        Self::exec_nodes(body);
+                    }
+                }
+                ScriptNode::While { cond, body } => {
+                    loop {
+                        Self::exec_nodes(cond);
+                        if Self::is_stopping() || Self::shared().last_status != 0 {
+                            break;
+                        }
+                        Self::exec_nodes(body);
+                    }
+                    if !Self::is_stopping() {
+                        Self::shared().last_status = 0;
+                    }
                 }
             }
-            Err(ParsedCmdLine::Empty) => (),
-            Err(ParsedCmdLine::InvalidQuote) => {
+        }
+    }
+
+    /// Returns whether the script has to stop by `exit` or Ctrl+C
+    #[inline]
+    fn is_stopping() -> bool {
+        let shared = Self::shared();
+        if shared.is_subshell {
+            if Scheduler::current_pid().is_terminating() {
+                return true;
+            }
+        } else if System::stdout().take_interrupt() && !INTERRUPTED.swap(true, Ordering::SeqCst) {
+            println!("^C");
+        }
+        shared.is_exiting || INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    /// Expands the variables in the words of `for`
+    fn expand_words(words: &str) -> Result<Vec<String>, ParsedCmdLine> {
+        Self::tokenize(words)?
+            .into_iter()
+            .map(|token| match token {
+                CmdLineToken::Word(word) => Ok(word),
+                _ => Err(ParsedCmdLine::InvalidSyntax),
+            })
+            .collect()
+    }
+
+    fn print_error(err: ParsedCmdLine) {
+        match err {
+            ParsedCmdLine::Empty => (),
+            ParsedCmdLine::InvalidQuote => {
                 println!("Error: Invalid quote");
             }
+            ParsedCmdLine::InvalidSyntax => {
+                println!("Error: Invalid syntax");
+            }
+            ParsedCmdLine::Incomplete => {
+                println!("Error: Unexpected end of script");
+            }
+        }
+    }
+
+    #[inline]
+    fn is_var_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .map(|c| c == '_' || c.is_ascii_alphabetic())
+            .unwrap_or(false)
+            && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+    }
+
+    /// Returns the value of the variable, the shell variables take precedence over the environment
+    fn get_var(name: &str) -> Option<String> {
+        let shared = Self::shared();
+        match name {
+            "?" => Some(shared.last_status.to_string()),
+            "#" => Some(shared.args.len().saturating_sub(1).to_string()),
+            _ => match name.parse::<usize>() {
+                Ok(index) => shared.args.get(index).cloned(),
+                Err(_) => shared
+                    .vars
+                    .get(name)
+                    .cloned()
+                    .or_else(|| Scheduler::current_pid().get_env(name)),
+            },
+        }
+    }
+
+    /// Sets the variable, or the environment variable if it has been exported
+    fn set_var(name: &str, value: &str) {
+        let pid = Scheduler::current_pid();
+        if pid.get_env(name).is_some() {
+            pid.set_env(name, value);
+        } else {
+            Self::shared()
+                .vars
+                .insert(name.to_owned(), value.to_owned());
         }
     }
 
-    fn parse_cmd(cmdline: &str) -> Result<(String, Vec<String>), ParsedCmdLine> {
+    fn exec_cmd(cmdline: &str) {
+        match Self::parse_cmd(cmdline) {
+            Ok(list) => {
+                for (sequence, pipeline) in list {
+                    if Self::is_stopping() {
+                        break;
+                    }
+                    let last_status = Self::shared().last_status;
+                    match sequence {
+                        CmdSequence::IfSuccess if last_status != 0 => continue,
+                        CmdSequence::IfFailure if last_status == 0 => continue,
+                        _ => (),
+                    }
+                    Self::shared().last_status = Self::exec_pipeline(pipeline);
+                }
+            }
+            Err(ParsedCmdLine::Empty) => (),
+            Err(err) => {
+                Self::print_error(err);
+                Self::shared().last_status = 1;
+            }
+        }
+    }
+
+    /// Runs the commands of the pipeline concurrently, returns the status of the last command.
+    ///
+    /// The commands except the last one run in child processes connected with pipes,
+    /// each with a snapshot of the shell, and the last one runs in the shell itself,
+    /// so that builtin commands such as `cd` take effect.
+    fn exec_pipeline(mut pipeline: Vec<ParsedCommand>) -> usize {
+        let pid = Scheduler::current_pid();
+        let saved = pid.stdio_all();
+        let Some(last) = pipeline.pop() else {
+            return 0;
+        };
+        let mut stdin = saved[0].clone();
+        let mut children = Vec::new();
+        for command in pipeline {
+            let (reader, writer) = StdStream::pipe();
+            if let Some((stdin, stdout)) = Self::redirect(&command, stdin, writer) {
+                // The child process inherits the standard streams of the shell
+                pid.replace_stdio([stdin, stdout, saved[2].clone()]);
+                let name = command.args[0].clone();
+                let stage = PipelineStage {
+                    args: command.args,
+                    shell: Self::shared().snapshot(),
+                };
+                let arg = Box::into_raw(Box::new(stage)) as usize;
+                match SpawnOption::new().start_process(Self::start_pipeline_stage, arg, &name) {
+                    Some(child) => children.push(child),
+                    None => drop(unsafe { Box::from_raw(arg as *mut PipelineStage) }),
+                }
+                // Closes the write end of the pipe held by the shell
+                pid.replace_stdio(saved.clone());
+            }
+            stdin = reader;
+        }
+        let status = match Self::redirect(&last, stdin, saved[1].clone()) {
+            Some((stdin, stdout)) => {
+                pid.replace_stdio([stdin, stdout, saved[2].clone()]);
+                let args = last.args.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+                let status = Self::exec_command(&args);
+                // Closes the read end of the pipe, the writers fail with BrokenPipe
+                pid.replace_stdio(saved.clone());
+                status
+            }
+            None => 1,
+        };
+        for child in children {
+            child.join();
+        }
+        status
+    }
+
+    /// Entry point of the child process running a command of the pipeline
+    fn start_pipeline_stage(arg: usize) {
+        let stage = unsafe { Box::from_raw(arg as *mut PipelineStage) };
+        let PipelineStage { args, shell } = *stage;
+        let pid = Scheduler::current_pid();
+        SUBSHELLS.lock().unwrap().insert(pid, Box::new(shell));
+        let args = args.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+        let status = Self::exec_command(&args);
+        SUBSHELLS.lock().unwrap().remove(&pid);
+        pid.set_exit_code(status);
+    }
+
+    /// Opens the files of the redirections, or prints the error
+    fn redirect(
+        command: &ParsedCommand,
+        stdin: StdStream,
+        stdout: StdStream,
+    ) -> Option<(StdStream, StdStream)> {
+        let stdin = match command.stdin.as_ref() {
+            Some(path) => match FileManager::open(path, OpenOptions::new().read(true)) {
+                Ok(file) => StdStream::file(file),
+                Err(err) => {
+                    println!("{}: {:?}", path, err.kind());
+                    return None;
+                }
+            },
+            None => stdin,
+        };
+        let stdout = match command.stdout.as_ref() {
+            Some((path, append)) => match FileManager::open(
+                path,
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .append(*append)
+                    .truncate(!*append),
+            ) {
+                Ok(file) => StdStream::file(file),
+                Err(err) => {
+                    println!("{}: {:?}", path, err.kind());
+                    return None;
+                }
+            },
+            None => stdout,
+        };
+        Some((stdin, stdout))
+    }
+
+    /// Runs the builtin command or the application, returns the exit status
+    fn exec_command(args: &[&str]) -> usize {
+        let name = args[0];
+        let mut args = args.to_vec();
+        let mut status = 0;
+        if let Some((key, value)) = name.split_once('=') {
+            if args.len() == 1 && Self::is_var_name(key) {
+                Self::set_var(key, value);
+                return 0;
+            }
+        }
+        match name {
+            "clear" | "cls" | "reset" => System::stdout().reset().unwrap(),
+            "exit" => {
+                let shared = Self::shared();
+                if shared.args.is_empty() {
+                    println!("Feature not available");
+                } else {
+                    // Stops the running script
+                    shared.is_exiting = true;
+                    status = match args.get(1) {
+                        Some(v) => v.parse().unwrap_or(1),
+                        None => shared.last_status,
+                    };
+                }
+            }
+            "true" => (),
+            "false" => status = 1,
+            "test" | "[" => status = Self::cmd_test(&args),
+            "set" => status = Self::cmd_set(&args),
+            "jobs" => Self::cmd_jobs(),
+            "fg" => status = Self::cmd_fg(&args),
+            "bg" => status = Self::cmd_bg(&args),
+            "wait" => status = Self::cmd_wait(&args),
+            "kill" => status = Self::cmd_kill(&args),
+            "sh" | "source" | "." => match args.get(1) {
+                Some(path) => status = Self::exec_script_file(path, &args[1..]),
+                None => {
+                    println!("usage: {} SCRIPT [args...]", name);
+                    status = 1;
+                }
+            },
+            "echo" => {
+                println!("{}", args[1..].join(" "));
+            }
+            "ver" => {
+                println!(
+                    "{} v{} (codename {})",
+                    System::name(),
+                    System::version(),
+                    System::codename()
+                )
+            }
+            "reboot" => {
+                UserEnv::system_reset(false);
+            }
+            "shutdown" => {
+                UserEnv::system_reset(true);
+            }
+            "uptime" => {
+                let systime = System::system_time();
+                let sec = systime.secs;
+                // let time_s = sec % 60;
+                let time_m = (sec / 60) % 60;
+                let time_h = (sec / 3600) % 24;
+
+                let uptime = Timer::monotonic();
+                let sec = uptime.as_secs();
+                let upt_s = sec % 60;
+                let upt_m = (sec / 60) % 60;
+                let upt_h = (sec / 3600) % 24;
+                let upt_d = sec / 86400;
+
+                if upt_d > 0 {
+                    println!(
+                        "{:02}:{:02} up {} days, {:02}:{:02}",
+                        time_h, time_m, upt_d, upt_h, upt_m
+                    );
+                } else {
+                    println!(
+                        "{:02}:{:02} up {:02}:{:02}:{:02}",
+                        time_h, time_m, upt_h, upt_m, upt_s
+                    );
+                }
+            }
+            "ts" => {
+                let mut sb = String::new();
+                Scheduler::get_thread_statistics(&mut sb);
+                print!("{}", sb.as_str());
+            }
+            "open" | "ncst" => match args.get(1) {
+                Some(_) => status = Self::open(&args[1..]),
+                None => println!("usage: open file [args...]"),
+            },
+            "wdb" => match args.get(1) {
+                Some(name) => {
+                    status = Self::spawn(name, &args[1..], true, LaunchMode::Debug, None);
+                }
+                None => println!("usage: wdb app.wasm [args...]"),
+            },
+            "wprof" => match args.get(1) {
+                Some(name) => {
+                    status = Self::spawn(name, &args[1..], true, LaunchMode::Profile, None);
+                }
+                None => println!("usage: wprof app.wasm [args...]"),
+            },
+            "sandbox" => status = Self::cmd_sandbox(args.as_slice()),
+            _ => match Self::command(name) {
+                Some(exec) => status = exec(args.as_slice()),
+                None => {
+                    if args.len() > 1 && args.last() == Some(&"&") {
+                        args.remove(args.len() - 1);
+                        status =
+                            Self::spawn(name, args.as_slice(), false, LaunchMode::Normal, None);
+                    } else {
+                        status = Self::spawn(name, args.as_slice(), true, LaunchMode::Normal, None);
+                    }
+                }
+            },
+        }
+        status
+    }
+
+    /// Splits the command line into the pipelines to run in order
+    fn parse_cmd(cmdline: &str) -> Result<Vec<(CmdSequence, Vec<ParsedCommand>)>, ParsedCmdLine> {
+        let mut list = Vec::new();
+        let mut sequence = CmdSequence::Always;
+        let mut pipeline = Vec::new();
+        let mut command = ParsedCommand::default();
+        let mut tokens = Self::tokenize(cmdline)?.into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                CmdLineToken::Word(word) => command.args.push(word),
+                CmdLineToken::RedirectIn => match tokens.next() {
+                    Some(CmdLineToken::Word(path)) => command.stdin = Some(path),
+                    _ => return Err(ParsedCmdLine::InvalidSyntax),
+                },
+                CmdLineToken::RedirectOut | CmdLineToken::RedirectAppend => {
+                    let append = matches!(token, CmdLineToken::RedirectAppend);
+                    match tokens.next() {
+                        Some(CmdLineToken::Word(path)) => command.stdout = Some((path, append)),
+                        _ => return Err(ParsedCmdLine::InvalidSyntax),
+                    }
+                }
+                CmdLineToken::Pipe => {
+                    if command.args.is_empty() {
+                        return Err(ParsedCmdLine::InvalidSyntax);
+                    }
+                    pipeline.push(core::mem::take(&mut command));
+                }
+                CmdLineToken::Semicolon | CmdLineToken::And | CmdLineToken::Or => {
+                    if command.args.is_empty() {
+                        // Empty statements are allowed only between semicolons
+                        if matches!(token, CmdLineToken::Semicolon)
+                            && pipeline.is_empty()
+                            && command.stdin.is_none()
+                            && command.stdout.is_none()
+                        {
+                            continue;
+                        }
+                        return Err(ParsedCmdLine::InvalidSyntax);
+                    }
+                    pipeline.push(core::mem::take(&mut command));
+                    list.push((sequence, core::mem::take(&mut pipeline)));
+                    sequence = match token {
+                        CmdLineToken::And => CmdSequence::IfSuccess,
+                        CmdLineToken::Or => CmdSequence::IfFailure,
+                        _ => CmdSequence::Always,
+                    };
+                }
+            }
+        }
+        if command.args.is_empty() {
+            if !pipeline.is_empty()
+                || sequence != CmdSequence::Always
+                || command.stdin.is_some()
+                || command.stdout.is_some()
+            {
+                return Err(ParsedCmdLine::InvalidSyntax);
+            }
+        } else {
+            pipeline.push(command);
+            list.push((sequence, pipeline));
+        }
+        if list.is_empty() {
+            Err(ParsedCmdLine::Empty)
+        } else {
+            Ok(list)
+        }
+    }
+
+    fn tokenize(cmdline: &str) -> Result<Vec<CmdLineToken>, ParsedCmdLine> {
         enum CmdLinePhase {
             SkippingSpace,
             Token,
@@ -160,43 +1015,85 @@ impl Shell {
             DoubleQuote,
         }
 
-        if cmdline.len() == 0 {
-            return Err(ParsedCmdLine::Empty);
-        }
-
         let mut sb = String::new();
-        let mut args = Vec::new();
+        let mut tokens = Vec::new();
         let mut phase = CmdLinePhase::SkippingSpace;
-        for c in cmdline.chars() {
+        let mut chars = cmdline.chars().peekable();
+        while let Some(c) = chars.next() {
             match phase {
-                CmdLinePhase::SkippingSpace => match c {
-                    ' ' | '\t' | '\r' | '\n' => (),
-                    '\'' => {
-                        phase = CmdLinePhase::SingleQuote;
-                    }
-                    '\"' => {
-                        phase = CmdLinePhase::DoubleQuote;
-                    }
-                    _ => {
-                        sb.write_char(c).unwrap();
-                        phase = CmdLinePhase::Token;
+                CmdLinePhase::SkippingSpace | CmdLinePhase::Token => {
+                    if c == '$' {
+                        if let Some(value) = Self::expand_var(&mut chars)? {
+                            // Unquoted values are split into words
+                            for c in value.chars() {
+                                if c.is_whitespace() {
+                                    if let CmdLinePhase::Token = phase {
+                                        tokens.push(CmdLineToken::Word(core::mem::take(&mut sb)));
+                                        phase = CmdLinePhase::SkippingSpace;
+                                    }
+                                } else {
+                                    sb.push(c);
+                                    phase = CmdLinePhase::Token;
+                                }
+                            }
+                            continue;
+                        }
                     }
-                },
-                CmdLinePhase::Token => match c {
-                    ' ' | '\t' | '\r' | '\n' => {
-                        args.push(sb);
+                    let operator = match c {
+                        '|' if chars.next_if_eq(&'|').is_some() => Some(CmdLineToken::Or),
+                        '|' => Some(CmdLineToken::Pipe),
+                        '&' if chars.next_if_eq(&'&').is_some() => Some(CmdLineToken::And),
+                        ';' => Some(CmdLineToken::Semicolon),
+                        '<' => Some(CmdLineToken::RedirectIn),
+                        '>' if chars.next_if_eq(&'>').is_some() => {
+                            Some(CmdLineToken::RedirectAppend)
+                        }
+                        '>' => Some(CmdLineToken::RedirectOut),
+                        _ => None,
+                    };
+                    if let Some(operator) = operator {
+                        if let CmdLinePhase::Token = phase {
+                            tokens.push(CmdLineToken::Word(core::mem::take(&mut sb)));
+                        }
+                        tokens.push(operator);
                         phase = CmdLinePhase::SkippingSpace;
-                        sb = String::new();
+                        continue;
                     }
-                    _ => {
-                        sb.write_char(c).unwrap();
+                    match phase {
+                        CmdLinePhase::SkippingSpace => match c {
+                            ' ' | '\t' | '\r' | '\n' => (),
+                            '\'' => {
+                                phase = CmdLinePhase::SingleQuote;
+                            }
+                            '\"' => {
+                                phase = CmdLinePhase::DoubleQuote;
+                            }
+                            _ => {
+                                sb.write_char(c).unwrap();
+                                phase = CmdLinePhase::Token;
+                            }
+                        },
+                        _ => match c {
+                            ' ' | '\t' | '\r' | '\n' => {
+                                tokens.push(CmdLineToken::Word(core::mem::take(&mut sb)));
+                                phase = CmdLinePhase::SkippingSpace;
+                            }
+                            '\'' => {
+                                phase = CmdLinePhase::SingleQuote;
+                            }
+                            '\"' => {
+                                phase = CmdLinePhase::DoubleQuote;
+                            }
+                            _ => {
+                                sb.write_char(c).unwrap();
+                            }
+                        },
                     }
-                },
+                }
+                // The word continues after the closing quote
                 CmdLinePhase::SingleQuote => match c {
                     '\'' => {
-                        args.push(sb);
-                        phase = CmdLinePhase::SkippingSpace;
-                        sb = String::new();
+                        phase = CmdLinePhase::Token;
                     }
                     _ => {
                         sb.write_char(c).unwrap();
@@ -204,10 +1101,12 @@ impl Shell {
                 },
                 CmdLinePhase::DoubleQuote => match c {
                     '\"' => {
-                        args.push(sb);
-                        phase = CmdLinePhase::SkippingSpace;
-                        sb = String::new();
+                        phase = CmdLinePhase::Token;
                     }
+                    '$' => match Self::expand_var(&mut chars)? {
+                        Some(value) => sb.push_str(&value),
+                        None => sb.push(c),
+                    },
                     _ => {
                         sb.write_char(c).unwrap();
                     }
@@ -215,29 +1114,89 @@ impl Shell {
             }
         }
         match phase {
-            CmdLinePhase::SkippingSpace | CmdLinePhase::Token => (),
+            CmdLinePhase::SkippingSpace => (),
+            // Pushes the empty word of the empty quotes as well
+            CmdLinePhase::Token => tokens.push(CmdLineToken::Word(sb)),
             CmdLinePhase::SingleQuote | CmdLinePhase::DoubleQuote => {
                 return Err(ParsedCmdLine::InvalidQuote)
             }
         }
-        if sb.len() > 0 {
-            args.push(sb);
+        Ok(tokens)
+    }
+
+    /// Expands the variable after `$` such as `$NAME`, `${NAME}`, `$?`, `$#` and `$1`,
+    /// returns `None` if no variable name follows.
+    fn expand_var(chars: &mut Peekable<Chars>) -> Result<Option<String>, ParsedCmdLine> {
+        let mut name = String::new();
+        match chars.peek().copied() {
+            Some('{') => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(ParsedCmdLine::InvalidSyntax),
+                    }
+                }
+            }
+            Some(c) if c == '?' || c == '#' || c.is_ascii_digit() => {
+                chars.next();
+                name.push(c);
+            }
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                while let Some(c) = chars.next_if(|c| *c == '_' || c.is_ascii_alphanumeric()) {
+                    name.push(c);
+                }
+            }
+            _ => return Ok(None),
         }
-        if let Some(cmd) = args.get(0) {
-            Ok((cmd.to_owned(), args))
+        Ok(Some(Self::get_var(&name).unwrap_or_default()))
+    }
+
+    /// Opens the file with the associated application, the scripts and the commands run in the shell
+    fn open(argv: &[&str]) -> usize {
+        let path = argv[0];
+        let is_script = path
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| ext == Self::SCRIPT_EXT);
+        let handler = if is_script {
+            None
         } else {
-            Err(ParsedCmdLine::Empty)
+            Launcher::handler_for(path)
+        };
+        match handler {
+            Some(Handler::Runtime) | None => {
+                Self::spawn(path, argv, false, LaunchMode::Normal, None)
+            }
+            Some(Handler::App(app)) => {
+                let mut app_argv = Vec::from([app.as_str()]);
+                app_argv.extend_from_slice(argv);
+                Self::spawn(&app, &app_argv, false, LaunchMode::Normal, None)
+            }
+            Some(handler) => match Launcher::launch(&handler, path) {
+                Ok(_) => 0,
+                Err(err) => {
+                    println!("open: {}: {:?}", path, err.kind());
+                    1
+                }
+            },
         }
     }
 
-    fn spawn(name: &str, argv: &[&str], wait_until: bool) -> usize {
-        Self::spawn_main(name, argv, wait_until).unwrap_or_else(|| {
+    fn spawn(
+        name: &str,
+        argv: &[&str],
+        wait_until: bool,
+        mode: LaunchMode,
+        caps: Option<&CapabilityTable>,
+    ) -> usize {
+        Self::spawn_main(name, argv, wait_until, mode, caps).unwrap_or_else(|| {
             let mut sb = String::new();
             let shared = Self::shared();
             for ext in &shared.path_ext {
                 sb.clear();
                 write!(sb, "{}.{}", name, ext).unwrap();
-                match Self::spawn_main(sb.as_str(), argv, wait_until) {
+                match Self::spawn_main(sb.as_str(), argv, wait_until, mode, caps) {
                     Some(v) => return v,
                     None => (),
                 }
@@ -247,7 +1206,13 @@ impl Shell {
         })
     }
 
-    fn spawn_main(name: &str, argv: &[&str], wait_until: bool) -> Option<usize> {
+    fn spawn_main(
+        name: &str,
+        argv: &[&str],
+        wait_until: bool,
+        mode: LaunchMode,
+        caps: Option<&CapabilityTable>,
+    ) -> Option<usize> {
         FileManager::open(name, OpenOptions::new().read(true))
             .map(|mut fcb| {
                 let stat = fcb.fstat().unwrap();
@@ -255,6 +1220,14 @@ impl Shell {
                     println!("permission denied: {}", name);
                     return 1;
                 }
+                if name
+                    .rsplit_once('.')
+                    .map(|(_, ext)| ext == Self::SCRIPT_EXT)
+                    .unwrap_or(false)
+                {
+                    drop(fcb);
+                    return Self::exec_script_file(name, argv);
+                }
                 let file_size = stat.len() as usize;
                 if file_size > 0 {
                     let mut vec = Vec::with_capacity(file_size);
@@ -269,13 +1242,18 @@ impl Shell {
                     if let Some(mut loader) = RuntimeEnvironment::recognize(blob) {
                         loader.option().name = name.to_string();
                         loader.option().argv = argv.iter().map(|v| v.to_string()).collect();
+                        loader.option().mode = mode;
+                        loader.option().caps = caps.cloned();
                         match loader.load(blob) {
-                            Ok(_) => {
-                                let child = loader.invoke_start();
-                                if wait_until {
-                                    child.map(|thread| thread.join());
+                            Ok(_) => match loader.invoke_start() {
+                                Some(child) => {
+                                    if wait_until {
+                                        return Self::wait_foreground(child);
+                                    }
+                                    Self::add_job(child, argv);
                                 }
-                            }
+                                None => return 1,
+                            },
                             Err(_) => {
                                 println!("Load error");
                                 return 1;
@@ -293,7 +1271,186 @@ impl Shell {
             .ok()
     }
 
-    fn command(cmd: &str) -> Option<&'static fn(&[&str]) -> ()> {
+    /// Waits for the process in the foreground, Ctrl+C on the terminal interrupts it
+    fn wait_foreground(pid: ProcessId) -> usize {
+        let stdout = System::stdout();
+        stdout.add_foreground_process(pid);
+        let status = pid.join().unwrap_or(0);
+        stdout.remove_foreground_process(pid);
+        if status == Terminal::EXIT_INTERRUPTED {
+            println!("^C");
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+        status
+    }
+
+    fn add_job(pid: ProcessId, argv: &[&str]) {
+        let shared = Self::shared();
+        let id = (1..)
+            .find(|id| shared.jobs.iter().all(|job| job.id != *id))
+            .unwrap();
+        println!("[{}] {}", id, usize::from(pid));
+        shared.jobs.push(Job {
+            id,
+            pid,
+            cmdline: argv.join(" "),
+        });
+    }
+
+    /// Prints and removes the jobs that have finished
+    fn report_jobs() {
+        Self::shared().jobs.retain(|job| {
+            if job.pid.is_alive() {
+                return true;
+            }
+            match job.pid.join().unwrap_or(0) {
+                0 => println!("[{}] Done\t{}", job.id, job.cmdline),
+                Self::EXIT_KILLED => println!("[{}] Terminated\t{}", job.id, job.cmdline),
+                status => println!("[{}] Exit {}\t{}", job.id, status, job.cmdline),
+            }
+            false
+        });
+    }
+
+    /// Returns the index of the job specified by `%N` or `N`, or the last job if not specified
+    fn find_job(arg0: &str, spec: Option<&&str>) -> Option<usize> {
+        let shared = Self::shared();
+        let index = match spec {
+            Some(spec) => spec
+                .trim_start_matches('%')
+                .parse::<usize>()
+                .ok()
+                .and_then(|id| shared.jobs.iter().position(|job| job.id == id)),
+            None => shared.jobs.len().checked_sub(1),
+        };
+        if index.is_none() {
+            println!("{}: {}: No such job", arg0, spec.unwrap_or(&"current"));
+        }
+        index
+    }
+
+    fn cmd_jobs() {
+        Self::report_jobs();
+        for job in &Self::shared().jobs {
+            println!(
+                "[{}] {:3} Running\t{}",
+                job.id,
+                usize::from(job.pid),
+                job.cmdline
+            );
+        }
+    }
+
+    /// Moves the job to the foreground and waits for it
+    fn cmd_fg(argv: &[&str]) -> usize {
+        let Some(index) = Self::find_job(argv[0], argv.get(1)) else {
+            return 1;
+        };
+        let job = Self::shared().jobs.remove(index);
+        println!("{}", job.cmdline);
+        Self::wait_foreground(job.pid)
+    }
+
+    /// Jobs are never stopped, so this only checks that the job is running in the background
+    fn cmd_bg(argv: &[&str]) -> usize {
+        let Some(index) = Self::find_job(argv[0], argv.get(1)) else {
+            return 1;
+        };
+        let job = &Self::shared().jobs[index];
+        println!("[{}] {}", job.id, job.cmdline);
+        0
+    }
+
+    /// Waits for the jobs, or all jobs if not specified, returns the status of the last one
+    fn cmd_wait(argv: &[&str]) -> usize {
+        let shared = Self::shared();
+        let mut jobs = Vec::new();
+        if argv.len() > 1 {
+            for spec in &argv[1..] {
+                match Self::find_job(argv[0], Some(spec)) {
+                    Some(index) => jobs.push(shared.jobs.remove(index)),
+                    None => return 1,
+                }
+            }
+        } else {
+            jobs.append(&mut shared.jobs);
+        }
+        let mut status = 0;
+        for job in jobs {
+            status = job.pid.join().unwrap_or(0);
+        }
+        status
+    }
+
+    /// Terminates the processes specified by the process IDs or `%N` for the jobs
+    fn cmd_kill(argv: &[&str]) -> usize {
+        let mut argv = argv.iter();
+        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+
+        if argv.len() < 1 {
+            println!("usage: {} PID|%JOB...", arg0);
+            return 1;
+        }
+
+        let mut status = 0;
+        for arg in argv {
+            let pid = if arg.starts_with('%') {
+                Self::find_job(arg0, Some(arg)).map(|index| Self::shared().jobs[index].pid)
+            } else {
+                let pid = arg.parse::<usize>().ok().map(ProcessId::from);
+                if pid.is_none() {
+                    println!("{}: {}: Invalid process ID", arg0, arg);
+                }
+                pid
+            };
+            let Some(pid) = pid else {
+                status = 1;
+                continue;
+            };
+            if let Err(err) = Scheduler::terminate_process(pid, Self::EXIT_KILLED) {
+                println!("{}: {}: {:?}", arg0, arg, err.kind());
+                status = 1;
+            }
+        }
+        status
+    }
+
+    /// Starts the application with only the specified directories and devices granted
+    fn cmd_sandbox(argv: &[&str]) -> usize {
+        let mut argv = argv.iter();
+        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+
+        let mut caps = CapabilityTable::new();
+        let mut args = Vec::new();
+        while let Some(arg) = argv.next() {
+            let access = match *arg {
+                "-r" => Access::Read,
+                "-w" => Access::ReadWrite,
+                _ => {
+                    args.push(*arg);
+                    break;
+                }
+            };
+            match argv.next() {
+                Some(path) => caps.add(Capability::new(&FileManager::canonical_path(path), access)),
+                None => {
+                    args.clear();
+                    break;
+                }
+            }
+        }
+        args.extend(argv);
+
+        match args.first() {
+            Some(name) => Self::spawn(name, &args, true, LaunchMode::Normal, Some(&caps)),
+            None => {
+                println!("usage: {} [-r PATH] [-w PATH] app.wasm [args...]", arg0);
+                1
+            }
+        }
+    }
+
+    fn command(cmd: &str) -> Option<&'static fn(&[&str]) -> usize> {
         for command in &Self::COMMAND_TABLE {
             if command.0 == cmd {
                 return Some(&command.1);
@@ -302,18 +1459,32 @@ impl Shell {
         None
     }
 
-    const COMMAND_TABLE: [(&'static str, fn(&[&str]) -> (), &'static str); 17] = [
+    const COMMAND_TABLE: [(&'static str, fn(&[&str]) -> usize, &'static str); 31] = [
         ("cd", Self::cmd_cd, ""),
         ("mkdir", Self::cmd_mkdir, ""),
         ("rm", Self::cmd_rm, ""),
         ("mv", Self::cmd_mv, ""),
+        ("cp", Self::cmd_cp, "Copy files"),
         ("touch", Self::cmd_touch, ""),
         ("pwd", Self::cmd_pwd, ""),
+        ("env", Self::cmd_env, "Show environment variables"),
+        ("export", Self::cmd_export, ""),
+        ("unset", Self::cmd_unset, ""),
         ("ls", Self::cmd_ls, "Show directory"),
         ("cat", Self::cmd_cat, "Show file"),
         ("dir", Self::cmd_ls, ""),
         ("type", Self::cmd_cat, ""),
+        ("edit", Self::cmd_edit, "Edit text files"),
+        ("screenshot", Self::cmd_screenshot, "Capture the screen"),
+        ("head", Self::cmd_head, ""),
+        ("tail", Self::cmd_tail, ""),
+        ("wc", Self::cmd_wc, ""),
+        ("grep", Self::cmd_grep, "Search files for text"),
+        ("find", Self::cmd_find, "Find files"),
+        ("hexdump", Self::cmd_hexdump, ""),
         ("stat", Self::cmd_stat, ""),
+        ("du", Self::cmd_du, ""),
+        ("df", Self::cmd_df, "Show disk usage"),
         ("mount", Self::cmd_mount, ""),
         ("ps", Self::cmd_ps, ""),
         ("lspci", Self::cmd_lspci, "Show List of PCI Devices"),
@@ -322,108 +1493,466 @@ impl Shell {
         ("help", Self::cmd_help, ""),
     ];
 
-    fn cmd_help(_: &[&str]) {
+    fn cmd_help(_: &[&str]) -> usize {
         for cmd in &Self::COMMAND_TABLE {
             if cmd.2.len() > 0 {
                 println!("{}\t{}", cmd.0, cmd.2);
             }
         }
+        0
     }
 
-    fn cmd_cd(argv: &[&str]) {
+    fn cmd_cd(argv: &[&str]) -> usize {
         match FileManager::chdir(argv.get(1).unwrap_or(&"/")) {
-            Ok(_) => (),
+            Ok(_) => 0,
             Err(err) => {
                 println!("{:?}", err.kind());
+                1
+            }
+        }
+    }
+
+    fn cmd_env(_: &[&str]) -> usize {
+        for (key, value) in Scheduler::current_pid().environ() {
+            println!("{}={}", key, value);
+        }
+        0
+    }
+
+    fn cmd_export(argv: &[&str]) -> usize {
+        let mut argv = argv.iter();
+        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+
+        if argv.len() < 1 {
+            println!("usage: {} NAME=VALUE", arg0);
+            return 1;
+        };
+
+        let shared = Self::shared();
+        let pid = Scheduler::current_pid();
+        let mut status = 0;
+        for arg in argv {
+            match arg.split_once('=') {
+                Some((key, value)) if key.len() > 0 => {
+                    shared.vars.remove(key);
+                    pid.set_env(key, value);
+                }
+                // Exports the shell variable
+                _ => match shared.vars.remove(*arg) {
+                    Some(value) => pid.set_env(arg, &value),
+                    None => {
+                        println!("{}: {}: Invalid variable", arg0, arg);
+                        status = 1;
+                    }
+                },
+            }
+        }
+        status
+    }
+
+    /// Shows the shell variables, or sets them without exporting
+    fn cmd_set(argv: &[&str]) -> usize {
+        let mut argv = argv.iter();
+        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+
+        if argv.len() < 1 {
+            for (key, value) in &Self::shared().vars {
+                println!("{}={}", key, value);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in argv {
+            match arg.split_once('=') {
+                Some((key, value)) if Self::is_var_name(key) => Self::set_var(key, value),
+                _ => {
+                    println!("{}: {}: Invalid variable", arg0, arg);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Evaluates the expression, returns 0 if it is true, 1 if false, or 2 if invalid
+    fn cmd_test(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let mut argv = &argv[1..];
+        if arg0 == "[" {
+            match argv.split_last() {
+                Some((&"]", rest)) => argv = rest,
+                _ => {
+                    println!("{}: Missing ]", arg0);
+                    return 2;
+                }
+            }
+        }
+        let (negate, argv) = match argv {
+            ["!", rest @ ..] => (true, rest),
+            _ => (false, argv),
+        };
+        let file_type = |path: &str| FileManager::stat(path).map(|v| v.file_type()).ok();
+        let result = match argv {
+            [] => false,
+            [s] => !s.is_empty(),
+            ["-n", s] => !s.is_empty(),
+            ["-z", s] => s.is_empty(),
+            ["-e", path] => file_type(path).is_some(),
+            ["-f", path] => file_type(path).map(|v| v.is_file()).unwrap_or(false),
+            ["-d", path] => file_type(path).map(|v| v.is_dir()).unwrap_or(false),
+            [lhs, "=", rhs] => lhs == rhs,
+            [lhs, "!=", rhs] => lhs != rhs,
+            [lhs, op, rhs] => {
+                let (Ok(lhs), Ok(rhs)) = (lhs.parse::<isize>(), rhs.parse::<isize>()) else {
+                    println!("{}: Integer expected", arg0);
+                    return 2;
+                };
+                match *op {
+                    "-eq" => lhs == rhs,
+                    "-ne" => lhs != rhs,
+                    "-lt" => lhs < rhs,
+                    "-le" => lhs <= rhs,
+                    "-gt" => lhs > rhs,
+                    "-ge" => lhs >= rhs,
+                    _ => {
+                        println!("{}: {}: Unknown operator", arg0, op);
+                        return 2;
+                    }
+                }
             }
+            _ => {
+                println!("{}: Invalid expression", arg0);
+                return 2;
+            }
+        };
+        (result == negate) as usize
+    }
+
+    fn cmd_unset(argv: &[&str]) -> usize {
+        let mut argv = argv.iter();
+        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+
+        if argv.len() < 1 {
+            println!("usage: {} NAME", arg0);
+            return 1;
+        };
+
+        let shared = Self::shared();
+        let pid = Scheduler::current_pid();
+        for key in argv {
+            shared.vars.remove(*key);
+            pid.remove_env(key);
         }
+        0
     }
 
-    fn cmd_mkdir(argv: &[&str]) {
+    fn cmd_mkdir(argv: &[&str]) -> usize {
         let mut argv = argv.iter();
         let arg0 = unsafe { argv.next().unwrap_unchecked() };
 
-        if argv.len() < 1 {
-            println!("usage: {} directory_name", arg0);
-            return;
-        };
+        if argv.len() < 1 {
+            println!("usage: {} directory_name", arg0);
+            return 1;
+        };
+
+        let mut status = 0;
+        for path in argv {
+            match FileManager::mkdir(path) {
+                Ok(_) => (),
+                Err(err) => {
+                    println!("{}: {}: {:?}", arg0, path, err.kind());
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Removes the files, `-r` removes the directories with their contents,
+    /// and `-f` ignores the files that do not exist
+    fn cmd_rm(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, paths)) = Self::parse_flags(arg0, &argv[1..], "rf") else {
+            return 1;
+        };
+        if paths.is_empty() {
+            println!("usage: {} [-rf] file...", arg0);
+            return 1;
+        }
+
+        let mut status = 0;
+        for path in paths {
+            let result = if flags.contains('r') {
+                Self::remove_all(path)
+            } else {
+                FileManager::unlink(path)
+            };
+            match result {
+                Ok(_) => (),
+                Err(err) if flags.contains('f') && err.kind() == ErrorKind::NotFound => (),
+                Err(err) => {
+                    println!("{}: {}: {:?}", arg0, path, err.kind());
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    fn remove_all(path: &str) -> megstd::io::Result<()> {
+        if FileManager::stat(path)?.file_type().is_dir() {
+            for name in Self::dir_entries(path)? {
+                Self::remove_all(&Self::join_path(path, &name))?;
+            }
+        }
+        FileManager::unlink(path)
+    }
+
+    /// Copies the files, `-r` copies the directories with their contents
+    fn cmd_cp(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, paths)) = Self::parse_flags(arg0, &argv[1..], "r") else {
+            return 1;
+        };
+        let Some((target, sources)) = paths.split_last().filter(|v| !v.1.is_empty()) else {
+            println!("usage: {} [-r] source... target", arg0);
+            return 1;
+        };
+
+        let target_is_dir = FileManager::stat(target)
+            .map(|v| v.file_type().is_dir())
+            .unwrap_or(false);
+        if sources.len() > 1 && !target_is_dir {
+            println!("{}: {}: {:?}", arg0, target, ErrorKind::NotADirectory);
+            return 1;
+        }
+
+        let mut status = 0;
+        for source in sources {
+            let target = if target_is_dir {
+                match FileManager::canonical_path_components(source).pop() {
+                    Some(name) => Self::join_path(target, &name),
+                    None => {
+                        println!("{}: {}: {:?}", arg0, source, ErrorKind::InvalidInput);
+                        status = 1;
+                        continue;
+                    }
+                }
+            } else {
+                target.to_string()
+            };
+            if let Err(err) = Self::copy(source, &target, flags.contains('r')) {
+                println!("{}: {}: {:?}", arg0, source, err.kind());
+                status = 1;
+            }
+        }
+        status
+    }
+
+    fn copy(source: &str, target: &str, recursive: bool) -> megstd::io::Result<()> {
+        let source_path = FileManager::canonical_path(source);
+        let target_path = FileManager::canonical_path(target);
 
-        for path in argv {
-            match FileManager::mkdir(path) {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("{}: {}: {:?}", arg0, path, err.kind());
+        if !FileManager::stat(source)?.file_type().is_dir() {
+            if source_path == target_path {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            let mut reader = FileManager::open(source, OpenOptions::new().read(true))?;
+            let mut writer = FileManager::open(
+                target,
+                OpenOptions::new().write(true).create(true).truncate(true),
+            )?;
+            let mut buf = Vec::new();
+            buf.resize(0x10000, 0);
+            loop {
+                let size = reader.read(buf.as_mut_slice())?;
+                if size == 0 {
+                    return Ok(());
+                }
+                let mut written = 0;
+                while written < size {
+                    written += writer.write(&buf[written..size])?;
                 }
             }
         }
+
+        if !recursive {
+            return Err(ErrorKind::IsADirectory.into());
+        }
+        // The directory cannot be copied into itself, the root is an ancestor of everything
+        let is_inside = source_path == "/"
+            || target_path == source_path
+            || target_path
+                .strip_prefix(source_path.as_str())
+                .is_some_and(|v| v.starts_with('/'));
+        if is_inside {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        match FileManager::mkdir(target) {
+            Ok(_) => (),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => (),
+            Err(err) => return Err(err),
+        }
+        for name in Self::dir_entries(source)? {
+            Self::copy(
+                &Self::join_path(source, &name),
+                &Self::join_path(target, &name),
+                true,
+            )?;
+        }
+        Ok(())
     }
 
-    fn cmd_rm(argv: &[&str]) {
-        let mut argv = argv.iter();
-        let arg0 = unsafe { argv.next().unwrap_unchecked() };
+    /// Returns the names in the directory, except `.` and `..`
+    fn dir_entries(path: &str) -> megstd::io::Result<Vec<String>> {
+        Ok(FileManager::read_dir(path)?
+            .map(|v| v.name().to_owned())
+            .filter(|v| v != "." && v != "..")
+            .collect())
+    }
 
-        if argv.len() < 1 {
-            println!("usage: {} file", arg0);
-            return;
-        };
+    fn join_path(dir: &str, name: &str) -> String {
+        if dir.ends_with('/') {
+            format!("{}{}", dir, name)
+        } else {
+            format!("{}/{}", dir, name)
+        }
+    }
 
-        for path in argv {
-            match FileManager::unlink(path) {
-                Ok(_) => (),
-                Err(err) => {
-                    println!("{}: {}: {:?}", arg0, path, err.kind());
+    /// Splits the leading options such as `-rf` from the operands,
+    /// returns `None` if there is an option not in `available`
+    fn parse_flags<'a>(
+        arg0: &str,
+        args: &'a [&'a str],
+        available: &str,
+    ) -> Option<(String, &'a [&'a str])> {
+        let mut flags = String::new();
+        let mut args = args;
+        while let Some((arg, rest)) = args.split_first() {
+            if *arg == "--" {
+                args = rest;
+                break;
+            }
+            let Some(chars) = arg.strip_prefix('-').filter(|v| !v.is_empty()) else {
+                break;
+            };
+            for ch in chars.chars() {
+                if !available.contains(ch) {
+                    println!("{}: -{}: Invalid option", arg0, ch);
+                    return None;
                 }
+                flags.push(ch);
             }
+            args = rest;
         }
+        Some((flags, args))
     }
 
-    fn cmd_mv(argv: &[&str]) {
+    fn cmd_mv(argv: &[&str]) -> usize {
         let mut argv = argv.iter();
         let arg0 = unsafe { argv.next().unwrap_unchecked() };
 
         if argv.len() < 2 {
             println!("usage: {} source target", arg0);
-            return;
+            return 1;
         };
 
         let old_path = argv.next().unwrap();
         let new_path = argv.next().unwrap();
         match FileManager::rename(old_path, new_path) {
-            Ok(_) => (),
+            Ok(_) => 0,
             Err(err) => {
                 println!("{}: {} to {}: {:?}", arg0, old_path, new_path, err.kind());
+                1
             }
         }
     }
 
-    fn cmd_touch(argv: &[&str]) {
+    fn cmd_touch(argv: &[&str]) -> usize {
         let mut argv = argv.iter();
         let arg0 = unsafe { argv.next().unwrap_unchecked() };
 
         if argv.len() < 1 {
             println!("usage: {} file", arg0);
-            return;
+            return 1;
         };
 
+        let mut status = 0;
         for path in argv {
             match FileManager::creat(path) {
                 Ok(_) => (),
                 Err(err) => {
                     println!("{}: {}: {:?}", arg0, path, err.kind());
+                    status = 1;
                 }
             }
         }
+        status
+    }
+
+    fn cmd_edit(argv: &[&str]) -> usize {
+        if argv.len() > 2 {
+            println!("usage: {} [file]", argv[0]);
+            return 1;
+        }
+        match TextEditor::open(argv.get(1).copied()) {
+            Some(_) => 0,
+            None => 1,
+        }
+    }
+
+    fn cmd_screenshot(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, args)) = Self::parse_flags(arg0, &argv[1..], "q") else {
+            return 1;
+        };
+        let format = if flags.contains('q') {
+            ImageFormat::Qoi
+        } else {
+            ImageFormat::Png
+        };
+        let bitmap = match args {
+            [] => Screenshot::capture_screen(),
+            [window] => {
+                let Some(bitmap) = window
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(WindowHandle::new)
+                    .and_then(Screenshot::capture_window)
+                else {
+                    println!("{}: {}: No such window", arg0, window);
+                    return 1;
+                };
+                bitmap
+            }
+            _ => {
+                println!("usage: {} [-q] [window]", arg0);
+                return 1;
+            }
+        };
+        match Screenshot::save(&bitmap, format) {
+            Ok(path) => {
+                println!("{}", path);
+                0
+            }
+            Err(err) => {
+                println!("{}: {:?}", arg0, err.kind());
+                1
+            }
+        }
     }
 
-    fn cmd_pwd(_argv: &[&str]) {
+    fn cmd_pwd(_argv: &[&str]) -> usize {
         println!("{}", Scheduler::current_pid().cwd());
+        0
     }
 
-    fn cmd_sysctl(argv: &[&str]) {
+    fn cmd_sysctl(argv: &[&str]) -> usize {
         if argv.len() < 2 {
             println!("usage: sysctl command [options]");
             println!("memory:\tShow memory information");
-            return;
+            return 1;
         }
 
         fn print_cpu_type(device: &DeviceInfo, new_line: bool) {
@@ -508,18 +2037,19 @@ impl Shell {
             }
             _ => {
                 println!("Unknown command: {}", subcmd);
-                return;
+                return 1;
             }
         }
+        0
     }
 
-    fn cmd_ls(args: &[&str]) {
+    fn cmd_ls(args: &[&str]) -> usize {
         let path = args.get(1).unwrap_or(&"");
         let dir = match FileManager::read_dir(path) {
             Ok(v) => v,
             Err(err) => {
                 println!("{:?}", err.kind());
-                return;
+                return 1;
             }
         };
 
@@ -545,6 +2075,13 @@ impl Shell {
             .collect::<Vec<_>>();
         files.sort_by(|a, b| a.0.cmp(&b.0));
 
+        if !StdStream::stdout().is_tty() {
+            for (name, suffix, _) in files {
+                println!("{}{}", name, suffix);
+            }
+            return 0;
+        }
+
         let item_len = files.iter().fold(0, |acc, v| acc.max(v.0.len())) + 2;
         let width = stdout.dims().0 as usize;
         let items_per_line = width / item_len;
@@ -566,50 +2103,409 @@ impl Shell {
             }
         }
         println!("");
+        0
     }
 
-    fn cmd_cat(args: &[&str]) {
+    /// Concatenates the files, or copies the standard input if no files are specified
+    fn cmd_cat(args: &[&str]) -> usize {
         let arg0 = args[0];
-        let len = 0x10000;
-        let mut sb = Vec::with_capacity(len);
-        sb.resize(len, 0);
-        for path in args.iter().skip(1) {
-            let mut file = match FileManager::open(path, OpenOptions::new().read(true)) {
-                Ok(v) => v,
-                Err(err) => {
-                    println!("{}: {}: {:?}", arg0, path, err.kind());
-                    continue;
-                }
+        let mut stdout = StdStream::stdout();
+        let mut sb = Vec::new();
+        sb.resize(0x10000, 0);
+        let mut status = 0;
+        for path in Self::input_paths(args) {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                status = 1;
+                continue;
             };
             loop {
                 match file.read(sb.as_mut_slice()) {
                     Ok(0) => break,
                     Ok(size) => {
-                        for b in &sb[..size] {
-                            System::stdout().write_char(*b as char).unwrap();
+                        if stdout.write_all(&sb[..size]).is_err() {
+                            return 1;
+                        }
+                    }
+                    Err(err) => {
+                        println!("{}: {}: {:?}", arg0, path, err.kind());
+                        status = 1;
+                        break;
+                    }
+                }
+            }
+        }
+        status
+    }
+
+    /// Returns the operands, or `-` for the standard input if there are none
+    fn input_paths<'a>(args: &'a [&'a str]) -> &'a [&'a str] {
+        if args.len() > 1 {
+            &args[1..]
+        } else {
+            &["-"]
+        }
+    }
+
+    /// Opens the file to read, or the standard input if the path is `-`
+    fn open_input(arg0: &str, path: &str) -> Option<StdStream> {
+        if path == "-" {
+            return Some(StdStream::stdin());
+        }
+        match FileManager::open(path, OpenOptions::new().read(true)) {
+            Ok(v) => Some(StdStream::file(v)),
+            Err(err) => {
+                println!("{}: {}: {:?}", arg0, path, err.kind());
+                None
+            }
+        }
+    }
+
+    /// Reads the stream line by line until the function returns false
+    fn read_lines<F>(file: &mut StdStream, mut f: F) -> megstd::io::Result<()>
+    where
+        F: FnMut(usize, &str) -> bool,
+    {
+        let mut buf = Vec::new();
+        buf.resize(0x1000, 0);
+        let mut line = Vec::new();
+        let mut line_no = 0;
+        loop {
+            let size = file.read(buf.as_mut_slice())?;
+            if size == 0 {
+                if !line.is_empty() {
+                    f(line_no + 1, &String::from_utf8_lossy(&line));
+                }
+                return Ok(());
+            }
+            for &byte in &buf[..size] {
+                if byte == b'\n' {
+                    line_no += 1;
+                    if !f(line_no, &String::from_utf8_lossy(&line)) {
+                        return Ok(());
+                    }
+                    line.clear();
+                } else {
+                    line.push(byte);
+                }
+            }
+        }
+    }
+
+    /// Prints the lines that match the regular expression,
+    /// returns 0 if any line matches, 1 if none, or 2 if an error occurs
+    fn cmd_grep(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, args)) = Self::parse_flags(arg0, &argv[1..], "ivnclq") else {
+            return 2;
+        };
+        let Some((pattern, paths)) = args.split_first() else {
+            println!("usage: {} [-ivnclq] pattern [file...]", arg0);
+            return 2;
+        };
+        let regex = match Regex::new(pattern) {
+            Ok(v) => v.ignore_case(flags.contains('i')),
+            Err(err) => {
+                println!("{}: {}: {:?}", arg0, pattern, err);
+                return 2;
+            }
+        };
+        let paths = if paths.is_empty() { &["-"][..] } else { paths };
+        let show_name = paths.len() > 1;
+        let invert = flags.contains('v');
+        let quiet = flags.contains('q');
+        let count_only = flags.contains('c');
+        let name_only = flags.contains('l');
+
+        let mut has_matched = false;
+        let mut has_error = false;
+        for path in paths {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                has_error = true;
+                continue;
+            };
+            let mut count = 0;
+            let result = Self::read_lines(&mut file, |line_no, line| {
+                if regex.is_match(line) == invert {
+                    return true;
+                }
+                count += 1;
+                if quiet || name_only {
+                    return false;
+                }
+                if !count_only {
+                    if show_name {
+                        print!("{}:", path);
+                    }
+                    if flags.contains('n') {
+                        print!("{}:", line_no);
+                    }
+                    println!("{}", line);
+                }
+                true
+            });
+            if let Err(err) = result {
+                println!("{}: {}: {:?}", arg0, path, err.kind());
+                has_error = true;
+            }
+            if count > 0 {
+                has_matched = true;
+                if quiet {
+                    break;
+                }
+            }
+            if count_only && !name_only && !quiet {
+                if show_name {
+                    print!("{}:", path);
+                }
+                println!("{}", count);
+            }
+            if name_only && count > 0 {
+                println!("{}", path);
+            }
+        }
+
+        if has_error && !(quiet && has_matched) {
+            2
+        } else if has_matched {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Prints the first lines of the files
+    fn cmd_head(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((count, paths)) = Self::parse_line_count(arg0, &argv[1..]) else {
+            return 1;
+        };
+        let paths = if paths.is_empty() { &["-"][..] } else { paths };
+        let mut status = 0;
+        for (index, path) in paths.iter().enumerate() {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                status = 1;
+                continue;
+            };
+            if paths.len() > 1 {
+                println!("{}==> {} <==", if index > 0 { "\n" } else { "" }, path);
+            }
+            if count == 0 {
+                continue;
+            }
+            let result = Self::read_lines(&mut file, |line_no, line| {
+                println!("{}", line);
+                line_no < count
+            });
+            if let Err(err) = result {
+                println!("{}: {}: {:?}", arg0, path, err.kind());
+                status = 1;
+            }
+        }
+        status
+    }
+
+    /// Prints the last lines of the files
+    fn cmd_tail(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((count, paths)) = Self::parse_line_count(arg0, &argv[1..]) else {
+            return 1;
+        };
+        let paths = if paths.is_empty() { &["-"][..] } else { paths };
+        let mut status = 0;
+        for (index, path) in paths.iter().enumerate() {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                status = 1;
+                continue;
+            };
+            if paths.len() > 1 {
+                println!("{}==> {} <==", if index > 0 { "\n" } else { "" }, path);
+            }
+            let mut lines = VecDeque::new();
+            let result = Self::read_lines(&mut file, |_, line| {
+                if count > 0 {
+                    if lines.len() >= count {
+                        lines.pop_front();
+                    }
+                    lines.push_back(line.to_owned());
+                }
+                true
+            });
+            for line in lines {
+                println!("{}", line);
+            }
+            if let Err(err) = result {
+                println!("{}: {}: {:?}", arg0, path, err.kind());
+                status = 1;
+            }
+        }
+        status
+    }
+
+    /// Parses `-n COUNT` or `-COUNT` for head and tail, the default is 10 lines
+    fn parse_line_count<'a>(arg0: &str, args: &'a [&'a str]) -> Option<(usize, &'a [&'a str])> {
+        let result = match args {
+            ["-n", count, rest @ ..] => count.parse().ok().map(|v| (v, rest)),
+            [option, rest @ ..] if option.len() > 1 && option.starts_with('-') => {
+                option[1..].parse().ok().map(|v| (v, rest))
+            }
+            _ => Some((10, args)),
+        };
+        if result.is_none() {
+            println!("usage: {} [-n count] [file...]", arg0);
+        }
+        result
+    }
+
+    /// Counts the lines, words and bytes of the files
+    fn cmd_wc(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, paths)) = Self::parse_flags(arg0, &argv[1..], "lwc") else {
+            return 1;
+        };
+        let paths = if paths.is_empty() { &["-"][..] } else { paths };
+        let flags = if flags.is_empty() {
+            "lwc"
+        } else {
+            flags.as_str()
+        };
+        let print_counts = |counts: &[usize; 3], name: &str| {
+            for (flag, count) in "lwc".chars().zip(counts) {
+                if flags.contains(flag) {
+                    print!("{:8}", count);
+                }
+            }
+            if name == "-" {
+                println!("");
+            } else {
+                println!(" {}", name);
+            }
+        };
+
+        let mut status = 0;
+        let mut total = [0; 3];
+        let mut buf = Vec::new();
+        buf.resize(0x10000, 0);
+        for path in paths {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                status = 1;
+                continue;
+            };
+            let mut counts = [0; 3];
+            let mut in_word = false;
+            loop {
+                match file.read(buf.as_mut_slice()) {
+                    Ok(0) => break,
+                    Ok(size) => {
+                        for &byte in &buf[..size] {
+                            if byte == b'\n' {
+                                counts[0] += 1;
+                            }
+                            if byte.is_ascii_whitespace() {
+                                in_word = false;
+                            } else if !in_word {
+                                in_word = true;
+                                counts[1] += 1;
+                            }
+                        }
+                        counts[2] += size;
+                    }
+                    Err(err) => {
+                        println!("{}: {}: {:?}", arg0, path, err.kind());
+                        status = 1;
+                        break;
+                    }
+                }
+            }
+            print_counts(&counts, path);
+            for (sum, count) in total.iter_mut().zip(counts) {
+                *sum += count;
+            }
+        }
+        if paths.len() > 1 {
+            print_counts(&total, "total");
+        }
+        status
+    }
+
+    /// Dumps the files in hexadecimal and ASCII
+    fn cmd_hexdump(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let mut status = 0;
+        let mut offset = 0;
+        let mut line = Vec::with_capacity(16);
+        let mut buf = Vec::new();
+        buf.resize(0x1000, 0);
+        for path in Self::input_paths(argv) {
+            let Some(mut file) = Self::open_input(arg0, path) else {
+                status = 1;
+                continue;
+            };
+            loop {
+                match file.read(buf.as_mut_slice()) {
+                    Ok(0) => break,
+                    Ok(size) => {
+                        for &byte in &buf[..size] {
+                            line.push(byte);
+                            if line.len() == 16 {
+                                Self::print_hex_line(offset, &line);
+                                offset += line.len();
+                                line.clear();
+                            }
                         }
                     }
                     Err(err) => {
                         println!("{}: {}: {:?}", arg0, path, err.kind());
+                        status = 1;
                         break;
                     }
                 }
             }
-            // System::stdout().write_str("\r\n").unwrap();
         }
+        if !line.is_empty() {
+            Self::print_hex_line(offset, &line);
+            offset += line.len();
+        }
+        if offset > 0 {
+            println!("{:08x}", offset);
+        }
+        status
+    }
+
+    fn print_hex_line(offset: usize, bytes: &[u8]) {
+        let mut sb = String::new();
+        write!(sb, "{:08x} ", offset).unwrap();
+        for index in 0..16 {
+            if index == 8 {
+                sb.push(' ');
+            }
+            match bytes.get(index) {
+                Some(byte) => write!(sb, " {:02x}", byte).unwrap(),
+                None => sb.push_str("   "),
+            }
+        }
+        sb.push_str("  |");
+        sb.extend(bytes.iter().map(|&v| {
+            if (0x20..0x7F).contains(&v) {
+                v as char
+            } else {
+                '.'
+            }
+        }));
+        sb.push('|');
+        println!("{}", sb);
     }
 
-    fn cmd_stat(args: &[&str]) {
+    fn cmd_stat(args: &[&str]) -> usize {
         if args.len() < 2 {
             println!("stat PATH...");
-            return;
+            return 1;
         };
         for path in args.iter().skip(1) {
             let stat = match FileManager::stat(path) {
                 Ok(v) => v,
                 Err(err) => {
                     println!("stat: {}: {:?}", path, err.kind());
-                    return;
+                    return 1;
                 }
             };
             println!(
@@ -620,9 +2516,10 @@ impl Shell {
                 FileManager::canonical_path(path),
             )
         }
+        0
     }
 
-    fn cmd_mount(_argv: &[&str]) {
+    fn cmd_mount(_argv: &[&str]) -> usize {
         let mount_points = FileManager::mount_points();
         let mut keys = mount_points.keys().collect::<Vec<_>>();
         keys.sort();
@@ -636,22 +2533,185 @@ impl Shell {
                 mount_point.description()
             );
         }
+        0
+    }
+
+    /// Prints the capacity and usage of the mounted file systems
+    fn cmd_df(_argv: &[&str]) -> usize {
+        let mount_points = FileManager::mount_points();
+        let mut keys = mount_points.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        println!("Filesystem    Size  Used Avail Use% Mounted on");
+        for key in keys {
+            let mount_point = mount_points.get(key).unwrap();
+            let Ok(statfs) = mount_point.statfs() else {
+                continue;
+            };
+            let mut sb = String::new();
+            for bytes in [
+                statfs.total_bytes(),
+                statfs.used_bytes(),
+                statfs.free_bytes(),
+            ] {
+                Self::format_bytes(&mut sb, bytes as usize).unwrap();
+                sb.push(' ');
+            }
+            match (statfs.used_bytes() * 100).checked_div(statfs.total_bytes()) {
+                Some(percent) => write!(sb, "{:3}%", percent).unwrap(),
+                None => sb.push_str("   -"),
+            }
+            println!("{:12} {} {}", mount_point.device_name(), sb, key);
+        }
+        0
+    }
+
+    /// Prints the total size of the files in the directories, `-s` prints only the totals
+    fn cmd_du(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let Some((flags, paths)) = Self::parse_flags(arg0, &argv[1..], "s") else {
+            return 1;
+        };
+        let paths = if paths.is_empty() { &["."][..] } else { paths };
+        let mut status = 0;
+        for path in paths {
+            match FileManager::stat(path) {
+                Ok(stat) => {
+                    let summarize = flags.contains('s') || !stat.file_type().is_dir();
+                    let total = Self::disk_usage(path, &stat, !summarize);
+                    if summarize {
+                        Self::print_disk_usage(total, path);
+                    }
+                }
+                Err(err) => {
+                    println!("{}: {}: {:?}", arg0, path, err.kind());
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Returns the total size of the files under the path, and prints the subtotals if needed
+    fn disk_usage(path: &str, metadata: &FsRawMetaData, print_dirs: bool) -> u64 {
+        let file_type = metadata.file_type();
+        if file_type.is_file() {
+            return metadata.len() as u64;
+        } else if !file_type.is_dir() {
+            return 0;
+        }
+        let mut total = 0;
+        if let Ok(dir) = FileManager::read_dir(path) {
+            for entry in dir {
+                if entry.name() == "." || entry.name() == ".." {
+                    continue;
+                }
+                let path = Self::join_path(path, entry.name());
+                total += Self::disk_usage(&path, entry.metadata(), print_dirs);
+            }
+        }
+        if print_dirs {
+            Self::print_disk_usage(total, path);
+        }
+        total
+    }
+
+    fn print_disk_usage(bytes: u64, path: &str) {
+        let mut sb = String::new();
+        Self::format_bytes(&mut sb, bytes as usize).unwrap();
+        println!("{}\t{}", sb, path);
+    }
+
+    /// Prints the files under the directories that match `-name PATTERN` and `-type f|d`
+    fn cmd_find(argv: &[&str]) -> usize {
+        let arg0 = argv[0];
+        let mut roots = Vec::new();
+        let mut name_pattern = None;
+        let mut type_filter = None;
+        let mut iter = argv.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match *arg {
+                "-name" | "-iname" => match iter.next().map(|v| Regex::from_glob(v)) {
+                    Some(Ok(regex)) => name_pattern = Some(regex.ignore_case(*arg == "-iname")),
+                    _ => {
+                        println!("{}: {}: Invalid pattern", arg0, arg);
+                        return 1;
+                    }
+                },
+                "-type" => match iter.next() {
+                    Some(&"f") => type_filter = Some(false),
+                    Some(&"d") => type_filter = Some(true),
+                    _ => {
+                        println!("{}: {}: Invalid file type", arg0, arg);
+                        return 1;
+                    }
+                },
+                _ if arg.starts_with('-') => {
+                    println!("usage: {} [path...] [-name pattern] [-type f|d]", arg0);
+                    return 1;
+                }
+                _ => roots.push(*arg),
+            }
+        }
+        if roots.is_empty() {
+            roots.push(".");
+        }
+
+        let mut status = 0;
+        for root in roots {
+            match FileManager::stat(root) {
+                Ok(stat) => Self::walk_tree(root, &stat, &mut |path, metadata| {
+                    let name = path
+                        .trim_end_matches('/')
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or(path);
+                    if name_pattern.as_ref().is_none_or(|v| v.is_match(name))
+                        && type_filter.is_none_or(|v| v == metadata.file_type().is_dir())
+                    {
+                        println!("{}", path);
+                    }
+                }),
+                Err(err) => {
+                    println!("{}: {}: {:?}", arg0, root, err.kind());
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Calls the function for the path, and for everything under it if it is a directory
+    fn walk_tree(path: &str, metadata: &FsRawMetaData, f: &mut dyn FnMut(&str, &FsRawMetaData)) {
+        f(path, metadata);
+        if !metadata.file_type().is_dir() {
+            return;
+        }
+        if let Ok(dir) = FileManager::read_dir(path) {
+            for entry in dir {
+                if entry.name() == "." || entry.name() == ".." {
+                    continue;
+                }
+                Self::walk_tree(&Self::join_path(path, entry.name()), entry.metadata(), f);
+            }
+        }
     }
 
-    fn cmd_ps(_argv: &[&str]) {
+    fn cmd_ps(_argv: &[&str]) -> usize {
         let mut sb = String::new();
         Scheduler::print_statistics(&mut sb);
         print!("{}", sb.as_str());
+        0
     }
 
-    fn cmd_lsusb(argv: &[&str]) {
+    fn cmd_lsusb(argv: &[&str]) -> usize {
         if let Some(addr) = argv.get(1).and_then(|v| v.parse::<NonZeroU8>().ok()) {
             let addr = usb::UsbAddress::from(addr);
             let device = match usb::UsbManager::device_by_addr(addr) {
                 Some(v) => v,
                 None => {
                     println!("Error: Device not found");
-                    return;
+                    return 1;
                 }
             };
 
@@ -707,6 +2767,7 @@ impl Shell {
         } else {
             Self::print_usb_device(0, None);
         }
+        0
     }
 
     fn print_usb_device(level: usize, parent: Option<usb::UsbAddress>) {
@@ -728,7 +2789,7 @@ impl Shell {
         }
     }
 
-    fn cmd_lspci(_argv: &[&str]) {
+    fn cmd_lspci(_argv: &[&str]) -> usize {
         // let _opt_all = argv.len() > 1;
         for device in drivers::pci::Pci::devices() {
             let addr = device.address();
@@ -744,6 +2805,7 @@ impl Shell {
                 class_string,
             );
         }
+        0
     }
 
     fn find_pci_class_string(cc: pci::PciClass) -> &'static str {
@@ -869,7 +2931,6 @@ impl Shell {
         }
     }
 
-    #[allow(dead_code)]
     fn format_bytes(sb: &mut dyn fmt::Write, val: usize) -> core::fmt::Result {
         let kb = (val >> 10) & 0x3FF;
         let mb = (val >> 20) & 0x3FF;
@@ -9,6 +9,7 @@ pub enum Icons {
     Close,
     Delete,
     Error,
+    Folder,
     Info,
     Menu,
     Usb,
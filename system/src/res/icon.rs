@@ -38,6 +38,9 @@ impl IconManager {
             r::Icons::Error => ImageLoader::load(
                 include_bytes!("../../../assets/material-design-icons/ic_error_black_24dp.png")
             ),
+            r::Icons::Folder => ImageLoader::load(
+                include_bytes!("../../../assets/material-design-icons/ic_folder_black_24dp.png")
+            ),
             r::Icons::Menu => ImageLoader::load(
                 include_bytes!("../../../assets/material-design-icons/ic_menu_black_24dp.png")
             ),
@@ -4,7 +4,10 @@ use super::*;
 use crate::{
     arch::cpu::LegacySyscallContext,
     fs::*,
-    io::audio::{AudioContext, FreqType, NoteControl, NoteOnParams, OscType},
+    io::{
+        audio::{AudioContext, FreqType, NoteControl, NoteOnParams, OscType},
+        stdio::StdStream,
+    },
     mem::MemoryManager,
     ui::window::*,
 };
@@ -27,6 +30,9 @@ static mut HOE_MANAGER: UnsafeCell<HoeManager> = UnsafeCell::new(HoeManager::new
 pub fn hoe_syscall(regs: &mut LegacySyscallContext) {
     match Scheduler::current_personality().and_then(|v| v.get::<Hoe>().ok()) {
         Some(hoe) => {
+            if Scheduler::current_pid().is_terminating() {
+                hoe.exit();
+            }
             hoe._syscall(regs);
         }
         None => todo!(),
@@ -182,16 +188,19 @@ impl Hoe {
         match regs.edx {
             1 => {
                 // putchar(eax)
-                System::stdout().write_char(regs.eax as u8 as char).unwrap();
+                let _ = StdStream::stdout().write_char(regs.eax as u8 as char);
             }
             2 => {
                 // putstring(ebx)
-                self.load_cstring(regs.ebx).map(|text| print!("{}", text));
+                if let Some(text) = self.load_cstring(regs.ebx) {
+                    let _ = write!(StdStream::stdout(), "{}", text);
+                }
             }
             3 => {
                 // putstring(ebx, ecx)
-                self.load_string(regs.ebx, regs.ecx)
-                    .map(|text| print!("{}", text));
+                if let Some(text) = self.load_string(regs.ebx, regs.ecx) {
+                    let _ = write!(StdStream::stdout(), "{}", text);
+                }
             }
             4 => {
                 // Exit
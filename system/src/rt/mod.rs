@@ -3,7 +3,10 @@
 use crate::{task::scheduler::*, *};
 use alloc::{boxed::Box, string::String, string::*, vec::Vec};
 use core::{cell::UnsafeCell, ffi::c_void};
-use megstd::uuid::{Identify, Uuid};
+use megstd::{
+    sys::megos::caps::CapabilityTable,
+    uuid::{Identify, Uuid},
+};
 
 pub mod arle;
 
@@ -69,8 +72,10 @@ impl RuntimeEnvironment {
         None
     }
 
+    /// Terminates the current thread, reporting the exit code to the parent of the process
     #[inline]
-    pub fn exit(_exit_code: usize) -> ! {
+    pub fn exit(exit_code: usize) -> ! {
+        Scheduler::current_pid().set_exit_code(exit_code);
         Scheduler::exit();
     }
 }
@@ -133,6 +138,26 @@ pub trait BinaryLoader {
 pub struct LoadedImageOption {
     pub name: String,
     pub argv: Vec<String>,
+    pub mode: LaunchMode,
+    /// Capabilities granted by the launcher, overrides the manifest of the image
+    pub caps: Option<CapabilityTable>,
+}
+
+/// How to start the loaded image, runtimes that do not support the mode start it normally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    Normal,
+    /// Start under the interactive debugger
+    Debug,
+    /// Start with the sampling profiler
+    Profile,
+}
+
+impl Default for LaunchMode {
+    #[inline]
+    fn default() -> Self {
+        Self::Normal
+    }
 }
 
 /// Contextual data for legacy applications
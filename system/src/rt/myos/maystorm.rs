@@ -1,26 +1,108 @@
 use super::*;
 use crate::{
     fs::*,
+    io::{audio::*, stdio::StdStream},
     sync::Mutex,
-    ui::theme::Theme,
+    task::channel::ChannelHandle,
+    ui::{clipboard::*, theme::Theme},
     *,
     {io::hid_mgr::*, ui::text::*, ui::window::*},
 };
+use alloc::format;
 use byteorder::*;
 use core::{
-    alloc::Layout, intrinsics::transmute, num::NonZeroU32, sync::atomic::*, time::Duration,
+    alloc::Layout,
+    intrinsics::transmute,
+    num::{NonZeroU32, NonZeroUsize},
+    sync::atomic::*,
+    time::Duration,
 };
 use megstd::{
     drawing::*,
     io::{Read, Write},
     rand::*,
+    sys::megos::{
+        audio::{osc, OsNoteParams},
+        caps::*,
+        channel,
+        clipboard::{self, BITMAP_HEADER_SIZE},
+        error,
+        fs::{ioctl, OsStat, STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO},
+        game::{self, OsGameEvent, OsGameState},
+        heap::OsHeapStats,
+        window::{back_buffer, OsWindowMessage},
+    },
     Arc, BTreeMap,
 };
-use wasm::{intr::*, *};
+use wasm::{cache::*, debug::*, heap::*, intr::*, profile::*, *};
+
+/// Debugger console on the standard output of the system
+struct TtyDebugConsole;
+
+impl core::fmt::Write for TtyDebugConsole {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        System::stdout().write_str(s)
+    }
+}
+
+impl WasmDebugConsole for TtyDebugConsole {
+    fn read_line(&mut self) -> Option<String> {
+        System::stdout().read_line(120).ok()
+    }
+}
+
+/// Samples the call stack of the application at timer intervals
+struct TimerSampler {
+    profiler: WasmProfiler,
+    request: Arc<AtomicBool>,
+    termination: Arc<AtomicBool>,
+}
+
+impl TimerSampler {
+    const INTERVAL: Duration = Duration::from_millis(1);
+
+    fn new(termination: Arc<AtomicBool>) -> Self {
+        let sampler = Self {
+            profiler: WasmProfiler::new(),
+            request: Arc::new(AtomicBool::new(false)),
+            termination,
+        };
+        sampler.schedule();
+        sampler
+    }
+
+    fn schedule(&self) {
+        TimerEvent::flag(Timer::new(Self::INTERVAL), self.request.clone()).schedule();
+    }
+}
+
+impl WasmDebugHook for TimerSampler {
+    fn should_break(&mut self, _func_index: usize, _position: usize) -> bool {
+        if self.request.swap(false, Ordering::Acquire) {
+            self.profiler.sample();
+            self.schedule();
+        }
+        self.termination.load(Ordering::Relaxed)
+    }
+
+    fn on_break(&mut self, _frame: &WasmDebugFrame) -> WasmDebugAction {
+        // Breaks only when the process is requested to terminate
+        WasmDebugAction::Abort
+    }
+
+    fn on_enter(&mut self, func_index: usize) {
+        self.profiler.on_enter(func_index);
+    }
+
+    fn on_leave(&mut self, func_index: usize) {
+        self.profiler.on_leave(func_index);
+    }
+}
 
 pub struct MyosBinaryLoader {
     loader: WasmLoader,
     lio: LoadedImageOption,
+    manifest: Option<CapabilityTable>,
 }
 
 impl MyosBinaryLoader {
@@ -28,6 +110,52 @@ impl MyosBinaryLoader {
         Self {
             loader: WasmLoader::new(),
             lio: LoadedImageOption::default(),
+            manifest: None,
+        }
+    }
+
+    /// Directory of precompiled modules
+    const CACHE_DIR: &'static str = "/var/cache/wasm";
+
+    /// Returns whether apps are denied the access to the path regardless of their capabilities.
+    ///
+    /// The precompiled modules are run without validation, so no app can access the cache
+    /// or create, remove and rename its ancestors to replace it.
+    fn is_reserved_path(path: &str, access: Access) -> bool {
+        let is_cache = path
+            .strip_prefix(Self::CACHE_DIR)
+            .is_some_and(|v| v.is_empty() || v.starts_with('/'));
+        let is_ancestor = path == "/"
+            || Self::CACHE_DIR
+                .strip_prefix(path)
+                .is_some_and(|v| v.starts_with('/'));
+        is_cache || (access == Access::ReadWrite && is_ancestor)
+    }
+
+    fn resolve(mod_name: &str, name: &str, type_ref: &WasmType) -> ImportResult<WasmDynFunc> {
+        let signature = type_ref.signature();
+        match mod_name {
+            MyosRuntime::MOD_NAME => match (name, signature.as_str()) {
+                ("svc0", "ii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc1", "iii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc2", "iiii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc3", "iiiii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc4", "iiiiii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc5", "iiiiiii") => ImportResult::Ok(MyosRuntime::syscall),
+                ("svc6", "iiiiiiii") => ImportResult::Ok(MyosRuntime::syscall),
+                _ => ImportResult::NoMethod,
+            },
+            _ => ImportResult::NoModule,
+        }
+    }
+
+    /// Writes the precompiled module so that the next launch can skip the code generation.
+    fn write_cache(&self, path: &str, blob: &[u8]) {
+        let _ = FileManager::mkdir2(Self::CACHE_DIR);
+        let _ = FileManager::unlink(path);
+        if let Ok(mut file) = FileManager::creat(path) {
+            let cache = WasmCache::encode(self.loader.module(), blob);
+            let _ = file.write_all(&cache);
         }
     }
 
@@ -46,35 +174,45 @@ impl BinaryLoader for MyosBinaryLoader {
     }
 
     fn load(&mut self, blob: &[u8]) -> Result<(), ()> {
-        self.loader
-            .load(blob, |mod_name, name, type_ref| {
-                let signature = type_ref.signature();
-                match mod_name {
-                    MyosRuntime::MOD_NAME => match (name, signature.as_str()) {
-                        ("svc0", "ii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc1", "iii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc2", "iiii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc3", "iiiii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc4", "iiiiii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc5", "iiiiiii") => ImportResult::Ok(MyosRuntime::syscall),
-                        ("svc6", "iiiiiiii") => ImportResult::Ok(MyosRuntime::syscall),
-                        _ => ImportResult::NoMethod,
-                    },
-                    _ => ImportResult::NoModule,
-                }
-            })
-            .map_err(|v| {
-                println!("Load error: {:?}", v);
-                ()
-            })
+        if let Some(manifest) = WasmLoader::custom_section(blob, SECTION_NAME) {
+            self.manifest = Some(CapabilityTable::from_manifest(manifest).ok_or_else(|| {
+                println!("Load error: bad manifest");
+            })?);
+        }
+
+        let cache_path = format!("{}/{}", Self::CACHE_DIR, WasmCache::file_name(blob));
+        if let Ok(mut file) = FileManager::open(&cache_path, OpenOptions::new().read(true)) {
+            let mut cache = Vec::new();
+            if file.read_to_end(&mut cache).is_ok()
+                && self.loader.load_cache(&cache, blob, Self::resolve).is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        self.loader.load(blob, Self::resolve).map_err(|v| {
+            println!("Load error: {:?}", v);
+            ()
+        })?;
+
+        self.write_cache(&cache_path, blob);
+        Ok(())
     }
 
     fn invoke_start(self: Box<Self>) -> Option<ProcessId> {
         match self.loader.module().func(MyosRuntime::ENTRY_FUNC_NAME) {
             Ok(_) => {
                 let module = self.loader.into_module();
+                // Without the manifest, no files are accessible
+                let caps = self.lio.caps.clone().or(self.manifest).unwrap_or_default();
                 SpawnOption::new()
-                    .personality(MyosRuntime::new(module))
+                    .personality(MyosRuntime::new(
+                        module,
+                        self.lio.name.clone(),
+                        self.lio.argv.clone(),
+                        caps,
+                        self.lio.mode,
+                    ))
                     .start_process(Self::start, 0, self.lio.name.as_ref())
             }
             Err(err) => {
@@ -90,11 +228,18 @@ pub struct MyosRuntime {
     module: WasmModule,
     next_handle: AtomicUsize,
     windows: Mutex<BTreeMap<usize, UnsafeCell<OsWindow>>>,
-    files: Mutex<Vec<Option<Arc<Mutex<FsRawFileControlBlock>>>>>,
+    files: Mutex<Vec<Option<OsFileHandle>>>,
+    audio: Mutex<BTreeMap<usize, OsAudioHandle>>,
+    game_inputs: Mutex<Vec<u32>>,
     rng32: XorShift32,
     key_buffer: Mutex<Vec<KeyEvent>>,
-    malloc: Mutex<SimpleAllocator>,
+    heap: Mutex<WasmHeap>,
     has_to_exit: AtomicBool,
+    exit_code: AtomicUsize,
+    name: String,
+    argv: Vec<String>,
+    caps: CapabilityTable,
+    mode: LaunchMode,
 }
 
 unsafe impl Identify for MyosRuntime {
@@ -110,6 +255,8 @@ impl Personality for MyosRuntime {
 
     fn on_exit(self: Box<Self>) {
         self.windows.lock().unwrap().clear();
+        self.audio.lock().unwrap().clear();
+        self.files.lock().unwrap().clear();
     }
 }
 
@@ -120,19 +267,62 @@ impl MyosRuntime {
 
     const SIZE_KEYBUFFER: usize = 32;
 
-    fn new(module: WasmModule) -> PersonalityContext {
+    /// Maximum number of pages of the linear memory, which is 128MB
+    const MAX_MEMORY_PAGES: usize = 2048;
+
+    const MAX_CLIPBOARD_BITMAP_SIZE: isize = 4096;
+
+    /// Exit code of the application killed by writing to a closed pipe, like SIGPIPE
+    const EXIT_BROKEN_PIPE: usize = 141;
+
+    fn new(
+        module: WasmModule,
+        name: String,
+        argv: Vec<String>,
+        caps: CapabilityTable,
+        mode: LaunchMode,
+    ) -> PersonalityContext {
         PersonalityContext::new(Self {
             module,
             next_handle: AtomicUsize::new(1),
             windows: Mutex::new(BTreeMap::new()),
-            files: Mutex::new(Vec::new()),
+            files: Mutex::new(OsFileHandle::stdio()),
+            audio: Mutex::new(BTreeMap::new()),
+            game_inputs: Mutex::new(Self::game_input_ids()),
             rng32: XorShift32::default(),
             key_buffer: Mutex::new(Vec::with_capacity(Self::SIZE_KEYBUFFER)),
-            malloc: Mutex::new(SimpleAllocator::default()),
+            heap: Mutex::new(WasmHeap::new(Self::MAX_MEMORY_PAGES)),
             has_to_exit: AtomicBool::new(false),
+            exit_code: AtomicUsize::new(0),
+            name,
+            argv,
+            caps,
+            mode,
         })
     }
 
+    /// Returns whether the application has to exit by closing the window or by the termination request
+    #[inline]
+    fn has_to_exit(&self) -> bool {
+        self.has_to_exit.load(Ordering::Relaxed) || Scheduler::current_pid().is_terminating()
+    }
+
+    /// Ends the application if it has written to a pipe whose read end has been closed,
+    /// so that the writer of a pipeline such as `yes | head` does not run forever.
+    fn check_broken_pipe<T>(
+        &self,
+        result: &megstd::io::Result<T>,
+    ) -> Result<(), WasmRuntimeErrorKind> {
+        match result {
+            Err(err) if err.kind() == megstd::io::ErrorKind::BrokenPipe => {
+                self.exit_code
+                    .store(Self::EXIT_BROKEN_PIPE, Ordering::SeqCst);
+                Err(WasmRuntimeErrorKind::Exit)
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn next_handle(&self) -> usize {
         let result = 1 + self.next_handle.load(Ordering::SeqCst);
         self.next_handle.swap(result, Ordering::SeqCst)
@@ -147,15 +337,65 @@ impl MyosRuntime {
             }
         };
 
-        match function.invoke(&[]) {
+        if let Some(memory) = self.module.memory(0) {
+            Self::update_memory_usage(memory);
+        }
+
+        let termination = Scheduler::current_pid()
+            .termination_flag()
+            .unwrap_or_default();
+        let result = match self.mode {
+            LaunchMode::Normal => function.invoke_with_abort_flag(&[], &termination),
+            LaunchMode::Debug => {
+                let mut debugger = WasmDebugger::new(TtyDebugConsole);
+                function.invoke_with_debugger(&[], &mut debugger)
+            }
+            LaunchMode::Profile => {
+                let mut sampler = TimerSampler::new(termination);
+                let result = function.invoke_with_debugger(&[], &mut sampler);
+                self.write_profile(&sampler.profiler);
+                result
+            }
+        };
+
+        match result {
             Ok(_v) => (),
             Err(err) => match err.kind() {
                 WasmRuntimeErrorKind::Exit => (),
-                _ => println!("error: {:?}", err),
+                _ => {
+                    println!("error: {:?}", err);
+                    RuntimeEnvironment::exit(1);
+                }
             },
         }
 
-        RuntimeEnvironment::exit(0);
+        RuntimeEnvironment::exit(self.exit_code.load(Ordering::SeqCst));
+    }
+
+    /// Writes the folded stacks to `/var/` and prints the functions with the most samples.
+    fn write_profile(&self, profiler: &WasmProfiler) {
+        let lpc = self.name.rsplit('/').next().unwrap_or(&self.name);
+        let stem = lpc.split('.').next().unwrap_or(lpc);
+        let path = format!("/var/{}.folded", stem);
+
+        let _ = FileManager::unlink(&path);
+        match FileManager::creat(&path) {
+            Ok(mut file) => {
+                let _ = profiler.write_folded(&self.module, &mut file);
+            }
+            Err(err) => {
+                println!("{}: {:?}", path, err.kind());
+                return;
+            }
+        }
+
+        let n_samples = profiler.n_samples();
+        println!("{} samples written to {}", n_samples, path);
+        for (func_index, hits) in profiler.self_hits().into_iter().take(10) {
+            let mut name = String::new();
+            let _ = WasmProfiler::write_function_name(&self.module, func_index, &mut name);
+            println!("{:6} {:3}% {}", hits, hits * 100 / n_samples, name);
+        }
     }
 
     fn syscall(
@@ -184,12 +424,17 @@ impl MyosRuntime {
             .get_u32()
             .map(|v| unsafe { transmute::<u32, Function>(v) })?;
 
-        if self.has_to_exit.load(Ordering::Relaxed) {
+        if self.has_to_exit() {
             return Err(WasmRuntimeErrorKind::Exit);
         }
 
+        // The module may have grown its memory by itself since the last call
+        Self::update_memory_usage(memory);
+
         match func_no {
             Function::Exit => {
+                let exit_code = params.get_usize().unwrap_or(0);
+                self.exit_code.store(exit_code, Ordering::SeqCst);
                 return Err(WasmRuntimeErrorKind::Exit);
             }
 
@@ -220,17 +465,113 @@ impl MyosRuntime {
             }
 
             Function::PrintString => {
-                params.get_string(memory).map(|s| print!("{}", s));
+                if let Some(s) = params.get_string(memory) {
+                    self.check_broken_pipe(&StdStream::stdout().write_all(s.as_bytes()))?;
+                }
+            }
+
+            Function::MkDir => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::ReadWrite)
+                        .and_then(|path| FileManager::mkdir(&path).map(|_| 0)),
+                );
+            }
+            Function::RmDir => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::ReadWrite)
+                        .and_then(|path| FileManager::rmdir(&path).map(|_| 0)),
+                );
+            }
+            Function::ChDir => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::Read)
+                        .and_then(|path| FileManager::chdir(&path).map(|_| 0)),
+                );
+            }
+            Function::GetCwd => {
+                let buf = params.get_buffer(memory)?;
+                let cwd = FileManager::canonical_path(".");
+                let len = cwd.len().min(buf.len());
+                buf[..len].copy_from_slice(&cwd.as_bytes()[..len]);
+                return Ok(WasmValue::from(cwd.len() as u32));
+            }
+            Function::Unlink => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::ReadWrite).and_then(|path| {
+                        let stat = FileManager::stat(&path)?;
+                        if stat.file_type().is_dir() {
+                            Err(megstd::io::ErrorKind::IsADirectory.into())
+                        } else {
+                            FileManager::unlink(&path).map(|_| 0)
+                        }
+                    }),
+                );
+            }
+            Function::Rename => {
+                let old_path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let new_path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(old_path, Access::ReadWrite)
+                        .and_then(|old_path| {
+                            self.resolve_path(new_path, Access::ReadWrite)
+                                .map(|new_path| (old_path, new_path))
+                        })
+                        .and_then(|(old_path, new_path)| {
+                            FileManager::rename(&old_path, &new_path).map(|_| 0)
+                        }),
+                );
+            }
+            Function::Stat => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::Read)
+                        .and_then(|path| FileManager::stat(&path))
+                        .and_then(|stat| Self::encode_stat(&stat, buf)),
+                );
             }
 
             Function::Open => {
                 let path = params
                     .get_string(memory)
                     .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
-                let _options = params.get_u32()?;
+                let mut options = OpenOptions::from_bits_truncate(params.get_u32()?);
+                if options.contains(OpenOptions::APPEND) {
+                    options.write(true);
+                }
+                if !options.contains(OpenOptions::WRITE) {
+                    options.read(true);
+                }
+                let access = if options.contains(OpenOptions::WRITE)
+                    || options.contains(OpenOptions::TRUNC)
+                    || options.contains(OpenOptions::CREAT)
+                {
+                    Access::ReadWrite
+                } else {
+                    Access::Read
+                };
                 return Self::encode_io_result(
-                    FileManager::open(path, OpenOptions::new().read(true))
-                        .and_then(|file| self.alloc_file(file)),
+                    self.resolve_path(path, access)
+                        .and_then(|path| FileManager::open(&path, &options))
+                        .and_then(|file| self.alloc_file(OsFileHandle::file(file))),
                 );
             }
             Function::Close => {
@@ -238,14 +579,16 @@ impl MyosRuntime {
                 self.close_file(handle);
             }
             Function::Read => {
-                let file = params.get_file(self)?;
+                let mut stream = params.get_stream(self)?;
                 let buf = params.get_buffer(memory)?;
-                return Self::encode_io_result(file.lock().unwrap().read(buf));
+                return Self::encode_io_result(stream.read(buf));
             }
             Function::Write => {
-                let file = params.get_file(self)?;
+                let mut stream = params.get_stream(self)?;
                 let buf = params.get_buffer(memory)?;
-                return Self::encode_io_result(file.lock().unwrap().write(buf));
+                let result = stream.write(buf);
+                self.check_broken_pipe(&result)?;
+                return Self::encode_io_result(result);
             }
             Function::LSeek => {
                 let file = params.get_file(self)?;
@@ -259,6 +602,62 @@ impl MyosRuntime {
                         .map(|v| v as usize),
                 );
             }
+            Function::IoCtl => {
+                let file = params.get_file(self)?;
+                let request = params.get_usize()?;
+                let arg = params.get_buffer(memory)?;
+                let mut file = file.lock().unwrap();
+                let result = match request {
+                    ioctl::FSTAT => match file.fstat() {
+                        Some(stat) => Self::encode_stat(&stat, arg),
+                        None => Err(megstd::io::ErrorKind::NotFound.into()),
+                    },
+                    ioctl::SET_LEN => {
+                        let len = arg
+                            .get(..8)
+                            .map(LE::read_u64)
+                            .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                        file.truncate(len as OffsetType).map(|_| 0)
+                    }
+                    ioctl::FLUSH => file.flush().map(|_| 0),
+                    _ => Err(megstd::io::ErrorKind::Unsupported.into()),
+                };
+                return Self::encode_io_result(result);
+            }
+            Function::OpenDir => {
+                let path = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(
+                    self.resolve_path(path, Access::Read)
+                        .and_then(|path| FileManager::read_dir(&path))
+                        .and_then(|dir| self.alloc_file(OsFileHandle::dir(dir))),
+                );
+            }
+            Function::ReadDir => {
+                let dir = params.get_dir(self)?;
+                let name = params.get_buffer(memory)?;
+                let stat = params.get_buffer(memory)?;
+                let mut dir = dir.lock().unwrap();
+                // Leaves the entry whose name does not fit so that the caller can retry
+                if let Some(len) = dir.peek().map(|v| v.name().len()) {
+                    if len > name.len() {
+                        return Self::encode_io_result(Ok(len));
+                    }
+                }
+                let Some(entry) = dir.next() else {
+                    return Ok(WasmValue::from(0u32));
+                };
+                let entry_name = entry.name().as_bytes();
+                let result = match name.get_mut(..entry_name.len()) {
+                    Some(name) => {
+                        name.copy_from_slice(entry_name);
+                        Self::encode_stat(entry.metadata(), stat).map(|_| entry_name.len())
+                    }
+                    None => Err(megstd::io::ErrorKind::InvalidInput.into()),
+                };
+                return Self::encode_io_result(result);
+            }
 
             Function::NewWindow => {
                 let title = params.get_string(memory).unwrap_or("");
@@ -385,6 +784,74 @@ impl MyosRuntime {
                 ));
             }
 
+            Function::GetMessage => {
+                let window = params.get_window(self)?.native();
+                let buf = params.get_buffer(memory)?;
+                if buf.len() < OsWindowMessage::SIZE {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+                while let Some(message) = window.wait_message() {
+                    if let Some(message) = Self::translate_message(window, message) {
+                        message.encode(buf);
+                        return Ok(WasmValue::from(1u32));
+                    }
+                }
+                return Ok(WasmValue::I32(0));
+            }
+            Function::PeekMessage => {
+                let window = params.get_window(self)?.native();
+                let buf = params.get_buffer(memory)?;
+                if buf.len() < OsWindowMessage::SIZE {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+                while let Some(message) = window.read_message() {
+                    if let Some(message) = Self::translate_message(window, message) {
+                        message.encode(buf);
+                        return Ok(WasmValue::from(1u32));
+                    }
+                }
+                return Ok(WasmValue::I32(0));
+            }
+            Function::SetTimer => {
+                let window = params.get_window(self)?.native();
+                let timer_id = params.get_usize()?;
+                let ms = params.get_u32()?;
+                window.create_timer(timer_id, Duration::from_millis(ms as u64));
+            }
+
+            Function::SetBackBuffer => {
+                let window = params.get_window(self)?;
+                let base = params.get_usize()?;
+                let size = params.get_size()?;
+                let format = params.get_u32()?;
+                if base == 0 {
+                    window.back_buffer = None;
+                    return Ok(WasmValue::I32(0));
+                }
+                let back_buffer = OsBackBuffer { base, size, format };
+                let content_size = window.content_rect().size();
+                if size.width <= 0
+                    || size.height <= 0
+                    || size.width > content_size.width
+                    || size.height > content_size.height
+                    || back_buffer.with_bitmap(memory, |_| ()).is_err()
+                {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+                window.back_buffer = Some(back_buffer);
+            }
+            Function::Present => {
+                let window = params.get_window(self)?;
+                let origin = params.get_point()?;
+                let size = params.get_size()?;
+                let back_buffer = window
+                    .back_buffer
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                back_buffer.with_bitmap(memory, |src| {
+                    window.present(Rect { origin, size }, src);
+                })?;
+            }
+
             Function::Blt8 => {
                 let window = params.get_window(self)?;
                 let origin = params.get_point()?;
@@ -448,20 +915,279 @@ impl MyosRuntime {
                 let layout = Layout::from_size_align(size, align)
                     .map_err(|_| WasmRuntimeErrorKind::InvalidParameter)?;
 
-                return self.alloc(memory, layout).map(|v| WasmValue::from(v.get()));
+                let result = self.heap.lock().unwrap().alloc(memory, layout);
+                Self::update_memory_usage(memory);
+                return Ok(WasmValue::from(result.map(|v| v.get()).unwrap_or(0)));
             }
 
             Function::Dealloc => {
+                // Freeing an unknown block is ignored, as the previous allocator did
                 let base = params.get_u32()?;
-                let size = params.get_usize()?;
-                let align = params.get_usize()?;
-                let layout = Layout::from_size_align(size, align)
-                    .map_err(|_| WasmRuntimeErrorKind::InvalidParameter)?;
+                let _ = self.heap.lock().unwrap().dealloc(base);
+            }
+
+            Function::HeapStats => {
+                let buf = params.get_buffer(memory)?;
+                let stats = self.heap.lock().unwrap().stats(memory);
+                let stats = OsHeapStats {
+                    memory_size: stats.memory_size as u32,
+                    memory_limit: stats.memory_limit as u32,
+                    heap_size: stats.heap_size as u32,
+                    used: stats.used as u32,
+                    n_allocs: stats.n_allocs as u32,
+                    n_free_blocks: stats.n_free_blocks as u32,
+                    largest_free: stats.largest_free as u32,
+                };
+                if stats.encode(buf).is_none() {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+            }
+
+            Function::Args => {
+                let buf = params.get_buffer(memory)?;
+                let len = Self::write_strings(buf, self.argv.iter().map(|v| v.as_str()));
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::Environ => {
+                let buf = params.get_buffer(memory)?;
+                let environ = Scheduler::current_pid()
+                    .environ()
+                    .into_iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>();
+                let len = Self::write_strings(buf, environ.iter().map(|v| v.as_str()));
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::SetEnv => {
+                let key = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let value = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                if !Self::is_valid_env_key(key) || value.contains('\0') {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+                Scheduler::current_pid().set_env(key, value);
+            }
+            Function::UnsetEnv => {
+                let key = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                if !Self::is_valid_env_key(key) {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+                Scheduler::current_pid().remove_env(key);
+            }
+
+            Function::AudioNewContext => {
+                let handle = self.next_handle();
+                self.audio
+                    .lock()
+                    .unwrap()
+                    .insert(handle, OsAudioHandle::Context(AudioContext::new()));
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioNoteOn => {
+                let ctx = params.get_audio_context(self)?;
+                let note = params
+                    .get_buffer(memory)
+                    .ok()
+                    .and_then(|v| OsNoteParams::decode(v))
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let Some(note) = Self::note_on(&ctx, &note) else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                };
+                let handle = self.next_handle();
+                self.audio
+                    .lock()
+                    .unwrap()
+                    .insert(handle, OsAudioHandle::Note(note));
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioOpenStream => {
+                let ctx = params.get_audio_context(self)?;
+                let sample_rate = params.get_u32()? as FreqType;
+                let Some(stream) = PcmStream::new(sample_rate) else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                };
+                let mut source = ctx.create_pcm_source(&stream);
+                source.connect(ctx.destination());
+                let Ok(note) = source.start() else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                };
+                let handle = self.next_handle();
+                self.audio
+                    .lock()
+                    .unwrap()
+                    .insert(handle, OsAudioHandle::Stream(stream, note));
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioWrite => {
+                let stream = params.get_audio_stream(self)?;
+                let buf = params.get_buffer(memory)?;
+                let len = stream.write(
+                    buf.chunks_exact(2)
+                        .map(|v| LE::read_i16(v) as SampleType / i16::MAX as SampleType),
+                );
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::AudioClose => {
+                let handle = params.get_usize()?;
+                self.audio.lock().unwrap().remove(&handle);
+            }
 
-                println!("dealloc {:08x} {:?}", base, layout);
-                memory.write_bytes(base as usize, 0xCC, size)?;
+            Function::GameEnumInputs => {
+                let buf = params.get_buffer(memory)?;
+                let ids = Self::game_input_ids();
+                for (chunk, id) in buf.chunks_exact_mut(4).zip(ids.iter()) {
+                    LE::write_u32(chunk, *id);
+                }
+                return Ok(WasmValue::from(ids.len() as u32));
+            }
+            Function::GameGetState => {
+                let id = params.get_u32()?;
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(
+                    Self::game_input(id)
+                        .ok_or(megstd::io::ErrorKind::NotFound.into())
+                        .and_then(|input| Self::encode_game_state(&input, buf)),
+                );
+            }
+            Function::GamePollEvent => {
+                let buf = params.get_buffer(memory)?;
+                if buf.len() < OsGameEvent::SIZE {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+                let current = Self::game_input_ids();
+                let mut known = self.game_inputs.lock().unwrap();
+                let event = if let Some(index) = known.iter().position(|v| !current.contains(v)) {
+                    OsGameEvent {
+                        kind: game::event::DISCONNECTED,
+                        id: known.remove(index),
+                    }
+                } else if let Some(id) = current.into_iter().find(|v| !known.contains(v)) {
+                    known.push(id);
+                    OsGameEvent {
+                        kind: game::event::CONNECTED,
+                        id,
+                    }
+                } else {
+                    return Ok(WasmValue::I32(0));
+                };
+                event.encode(buf);
+                return Ok(WasmValue::from(1u32));
+            }
 
-                self.malloc.lock().unwrap().dealloc(base, layout);
+            Function::ClipboardFormat => {
+                let format = match Clipboard::get() {
+                    None => clipboard::format::EMPTY,
+                    Some(ClipboardData::Text(_)) => clipboard::format::TEXT,
+                    Some(ClipboardData::Bitmap(_)) => clipboard::format::BITMAP32,
+                };
+                return Ok(WasmValue::from(format));
+            }
+            Function::ClipboardGetText => {
+                let buf = params.get_buffer(memory)?;
+                let Some(text) = Clipboard::text() else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::NotFound.into()));
+                };
+                let len = buf.len().min(text.len());
+                buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+                return Ok(WasmValue::from(text.len() as u32));
+            }
+            Function::ClipboardSetText => {
+                let text = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                Clipboard::set_text(text);
+            }
+            Function::ClipboardGetBitmap => {
+                let buf = params.get_buffer(memory)?;
+                let Some(bitmap) = Clipboard::bitmap() else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::NotFound.into()));
+                };
+                let size = bitmap.size();
+                let len = BITMAP_HEADER_SIZE + size.width as usize * size.height as usize * 4;
+                if buf.len() >= len {
+                    LE::write_u32(&mut buf[0..4], size.width as u32);
+                    LE::write_u32(&mut buf[4..8], size.height as u32);
+                    let pixels = &mut buf[BITMAP_HEADER_SIZE..len];
+                    for y in 0..size.height {
+                        for x in 0..size.width {
+                            let index = (y * size.width + x) as usize * 4;
+                            let color = bitmap.get_pixel(Point::new(x, y)).unwrap_or_default();
+                            LE::write_u32(&mut pixels[index..index + 4], color.argb());
+                        }
+                    }
+                }
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::ClipboardSetBitmap => {
+                let size = params.get_size()?;
+                let buf = params.get_buffer(memory)?;
+                let len = size.width as usize * size.height as usize;
+                if size.width <= 0
+                    || size.height <= 0
+                    || size.width > Self::MAX_CLIPBOARD_BITMAP_SIZE
+                    || size.height > Self::MAX_CLIPBOARD_BITMAP_SIZE
+                    || buf.len() < len * 4
+                {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::InvalidInput.into()));
+                }
+                let pixels = buf[..len * 4]
+                    .chunks_exact(4)
+                    .map(|v| TrueColor::from_argb(LE::read_u32(v)))
+                    .collect::<Vec<_>>();
+                Clipboard::set_bitmap(OwnedBitmap32::from_vec(pixels, size));
+            }
+
+            Function::ChannelCreate => {
+                let name = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let capacity = params.get_usize()?;
+                return Self::encode_io_result(
+                    ChannelHandle::create(name, capacity).map(|v| v.as_usize()),
+                );
+            }
+            Function::ChannelConnect => {
+                let name = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                return Self::encode_io_result(ChannelHandle::connect(name).map(|v| v.as_usize()));
+            }
+            Function::ChannelSend => {
+                let Some(handle) = params.get_channel()? else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::NotFound.into()));
+                };
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(handle.send(buf).map(|_| 0));
+            }
+            Function::ChannelReceive => {
+                let Some(handle) = params.get_channel()? else {
+                    return Self::encode_io_result(Err(megstd::io::ErrorKind::NotFound.into()));
+                };
+                let buf = params.get_buffer(memory)?;
+                let flags = params.get_u32()?;
+                let message = if (flags & channel::flags::NONBLOCK) != 0 {
+                    handle
+                        .try_receive()
+                        .and_then(|v| v.ok_or(megstd::io::ErrorKind::WouldBlock.into()))
+                } else {
+                    handle.receive()
+                };
+                return Self::encode_io_result(message.map(|message| {
+                    // The rest of the message is discarded if the buffer is too small
+                    let len = buf.len().min(message.len());
+                    buf[..len].copy_from_slice(&message[..len]);
+                    message.len()
+                }));
+            }
+            Function::ChannelClose => {
+                if let Some(handle) = params.get_channel()? {
+                    handle.close();
+                }
             }
 
             #[allow(unreachable_patterns)]
@@ -476,18 +1202,115 @@ impl MyosRuntime {
     ) -> Result<WasmValue, WasmRuntimeErrorKind> {
         match val {
             Ok(v) => Ok((v as u32).into()),
-            Err(_err) => {
-                // TODO
-                Ok((-1).into())
+            Err(err) => Ok((error::encode(err.kind()) as i32).into()),
+        }
+    }
+
+    /// Starts the note with the envelope, returns `None` if the parameters are out of range
+    fn note_on(ctx: &Arc<AudioContext>, note: &OsNoteParams) -> Option<NoteControl> {
+        let freq = note.freq as FreqType / 1000.0;
+        if !(FREQ_MIN..=FREQ_MAX).contains(&freq) {
+            return None;
+        }
+        let osc_type = match note.osc_type {
+            osc::SINE => OscType::Sine,
+            osc::SQUARE => OscType::Square,
+            osc::PULSE => OscType::Pulse((note.duty_cycle.min(1000) as f64 / 1000.0).max(0.001)),
+            osc::SAWTOOTH => OscType::Sawtooth,
+            osc::TRIANGLE => OscType::Triangle,
+            _ => return None,
+        };
+        let gain = note.gain.min(1000) as SampleType / 1000.0;
+        let attack = note.attack as f64 / 1000.0;
+        let decay = note.decay as f64 / 1000.0;
+        let sustain = note.sustain.min(1000) as f64 / 1000.0;
+
+        let mut osc = ctx.create_oscillator(freq, osc_type);
+        osc.connect(ctx.destination());
+        let mut envelope = NoteOnParams::new(Arc::downgrade(ctx), attack, decay, sustain);
+        envelope.connect(osc);
+        let mut gain = ctx.create_gain(gain);
+        gain.connect(envelope);
+        gain.start().ok()
+    }
+
+    /// Resolves the path in the application and checks it against the capabilities
+    fn resolve_path(&self, path: &str, access: Access) -> Result<String, megstd::io::Error> {
+        let path = FileManager::canonical_path(path);
+        if MyosBinaryLoader::is_reserved_path(&path, access) {
+            return Err(megstd::io::ErrorKind::PermissionDenied.into());
+        }
+        self.caps.check(&path, access)?;
+        Ok(path)
+    }
+
+    fn encode_stat(stat: &FsRawMetaData, buf: &mut [u8]) -> Result<usize, megstd::io::Error> {
+        OsStat::new(stat.file_type(), stat.len() as u64)
+            .encode(buf)
+            .map(|_| 0)
+            .ok_or(megstd::io::ErrorKind::InvalidInput.into())
+    }
+
+    /// Returns the ids of the connected game inputs, the simulated input comes first.
+    fn game_input_ids() -> Vec<u32> {
+        core::iter::once(game::SIMULATED_INPUT)
+            .chain(
+                GameInputManager::inputs()
+                    .into_iter()
+                    .map(|handle| handle.0.get() as u32),
+            )
+            .collect()
+    }
+
+    fn game_input(id: u32) -> Option<GameInput> {
+        match id {
+            game::CURRENT_INPUT => Some(GameInputManager::current_input()),
+            game::SIMULATED_INPUT => Some(GameInputManager::simulated_input()),
+            _ => GameInputManager::get(GameInputHandle(NonZeroUsize::new(id as usize)?)),
+        }
+    }
+
+    fn encode_game_state(input: &GameInput, buf: &mut [u8]) -> Result<usize, megstd::io::Error> {
+        let (x1, y1) = input.left_stick();
+        let (x2, y2) = input.right_stick();
+        OsGameState {
+            buttons: input.buttons(),
+            lt: input.left_trigger(),
+            rt: input.right_trigger(),
+            x1,
+            y1,
+            x2,
+            y2,
+        }
+        .encode(buf)
+        .map(|_| 0)
+        .ok_or(megstd::io::ErrorKind::InvalidInput.into())
+    }
+
+    /// Writes the strings terminated by NUL as much as the buffer allows, returns the total length.
+    fn write_strings<'a>(buf: &mut [u8], iter: impl Iterator<Item = &'a str>) -> usize {
+        let mut len = 0;
+        for s in iter {
+            for byte in s.bytes().chain(core::iter::once(0)) {
+                if let Some(p) = buf.get_mut(len) {
+                    *p = byte;
+                }
+                len += 1;
             }
         }
+        len
+    }
+
+    #[inline]
+    fn is_valid_env_key(key: &str) -> bool {
+        !key.is_empty() && !key.contains(|c| c == '=' || c == '\0')
     }
 
-    fn alloc_file(&self, file: FsRawFileControlBlock) -> Result<usize, megstd::io::Error> {
+    fn alloc_file(&self, file: OsFileHandle) -> Result<usize, megstd::io::Error> {
         let mut vec = self.files.lock().unwrap();
         for (handle, entry) in vec.iter_mut().enumerate() {
             if entry.is_none() {
-                *entry = Some(Arc::new(Mutex::new(file)));
+                *entry = Some(file);
                 return Ok(handle);
             }
         }
@@ -495,7 +1318,7 @@ impl MyosRuntime {
         if handle >= Self::MAX_FILES {
             return Err(megstd::io::ErrorKind::OutOfMemory.into());
         }
-        vec.push(Some(Arc::new(Mutex::new(file))));
+        vec.push(Some(file));
         Ok(handle)
     }
 
@@ -506,44 +1329,16 @@ impl MyosRuntime {
         }
     }
 
-    fn alloc(
-        &self,
-        memory: &WasmMemory,
-        layout: Layout,
-    ) -> Result<NonZeroU32, WasmRuntimeErrorKind> {
-        let mut malloc = self.malloc.lock().unwrap();
-
-        if let Some(result) = malloc.alloc(layout) {
-            println!("alloc1 {:?} => {:08x}", layout, result);
-            return Ok(result);
-        } else {
-            let min_alloc = WasmMemory::PAGE_SIZE;
-            let delta = (((layout.size() + min_alloc - 1) / min_alloc) * min_alloc
-                / WasmMemory::PAGE_SIZE) as i32;
-            let new_page = memory.grow(delta);
-            if new_page > 0 {
-                println!("grow {} => {}", delta, new_page);
-                malloc.append_block(
-                    new_page as u32 * WasmMemory::PAGE_SIZE as u32,
-                    delta as u32 * WasmMemory::PAGE_SIZE as u32,
-                );
-            } else {
-                return Err(WasmRuntimeErrorKind::OutOfMemory);
-            }
-
-            let result = match malloc.alloc(layout) {
-                Some(v) => v.get(),
-                None => 0,
-            };
-            println!("alloc2 {:?} => {:08x}", layout, result);
-            NonZeroU32::new(result).ok_or(WasmRuntimeErrorKind::OutOfMemory)
-        }
+    /// Reports the size of the linear memory as the memory usage of the process
+    #[inline]
+    fn update_memory_usage(memory: &WasmMemory) {
+        Scheduler::current_pid().set_memory_usage(memory.size() as usize * WasmMemory::PAGE_SIZE);
     }
 
     fn wait_key(&self, window: WindowHandle) -> Result<Option<char>, WasmRuntimeErrorKind> {
         while let Some(message) = window.wait_message() {
             self.process_message(window, message);
-            if self.has_to_exit.load(Ordering::Relaxed) {
+            if self.has_to_exit() {
                 return Err(WasmRuntimeErrorKind::Exit);
             }
 
@@ -573,6 +1368,53 @@ impl MyosRuntime {
         }
     }
 
+    /// Converts the message for `GetMessage` and `PeekMessage`,
+    /// returns `None` if the message is not passed to the application.
+    ///
+    /// Unlike `WaitChar`, `Close` is left to the application.
+    fn translate_message(window: WindowHandle, message: WindowMessage) -> Option<OsWindowMessage> {
+        use megstd::sys::megos::window::message::*;
+        let mouse = |kind, event: MouseEvent| {
+            OsWindowMessage::new(
+                kind,
+                [
+                    event.x as i32 as u32,
+                    event.y as i32 as u32,
+                    event.buttons.bits() as u32 | (event.event_buttons.bits() as u32) << 8,
+                ],
+            )
+        };
+        let message = match message {
+            WindowMessage::Nop => return None,
+            WindowMessage::Close => OsWindowMessage::new(CLOSE, [0; 3]),
+            WindowMessage::Back => OsWindowMessage::new(BACK, [0; 3]),
+            WindowMessage::Draw => {
+                window.handle_default_message(message);
+                OsWindowMessage::new(DRAW, [0; 3])
+            }
+            WindowMessage::Activated => OsWindowMessage::new(ACTIVATED, [0; 3]),
+            WindowMessage::Deactivated => OsWindowMessage::new(DEACTIVATED, [0; 3]),
+            WindowMessage::Key(event) => {
+                // Also posts the converted `Char` message
+                window.handle_default_message(message);
+                OsWindowMessage::new(KEY, [event.0.get(), 0, 0])
+            }
+            WindowMessage::Char(c) => OsWindowMessage::new(CHAR, [c as u32, 0, 0]),
+            WindowMessage::MouseMove(event) => mouse(MOUSE_MOVE, event),
+            WindowMessage::MouseDown(event) => mouse(MOUSE_DOWN, event),
+            WindowMessage::MouseUp(event) => mouse(MOUSE_UP, event),
+            WindowMessage::MouseEnter(event) => mouse(MOUSE_ENTER, event),
+            WindowMessage::MouseLeave(event) => mouse(MOUSE_LEAVE, event),
+            WindowMessage::Resized(size) => {
+                OsWindowMessage::new(RESIZED, [size.width as u32, size.height as u32, 0])
+            }
+            WindowMessage::Timer(timer_id) => OsWindowMessage::new(TIMER, [timer_id as u32, 0, 0]),
+            WindowMessage::User(value) => OsWindowMessage::new(USER, [value as u32, 0, 0]),
+            WindowMessage::ClipboardChanged => OsWindowMessage::new(CLIPBOARD_CHANGED, [0; 3]),
+        };
+        Some(message)
+    }
+
     fn process_message(&self, window: WindowHandle, message: WindowMessage) {
         match message {
             WindowMessage::Close => {
@@ -644,6 +1486,13 @@ impl ParamsDecoder<'_> {
         Ok(MemArg::new(base, len))
     }
 
+    /// Returns the channel if it is opened by the current process
+    #[inline]
+    fn get_channel(&mut self) -> Result<Option<ChannelHandle>, WasmRuntimeErrorKind> {
+        self.get_usize()
+            .map(|v| ChannelHandle::from_raw(Scheduler::current_pid(), v))
+    }
+
     #[inline]
     fn get_buffer<'a>(
         &mut self,
@@ -760,13 +1609,98 @@ impl ParamsDecoder<'_> {
         rt: &MyosRuntime,
     ) -> Result<Arc<Mutex<FsRawFileControlBlock>>, WasmRuntimeErrorKind> {
         let handle = self.get_usize()?;
-        rt.files
-            .lock()
-            .unwrap()
-            .get(handle)
-            .and_then(|v| v.as_ref())
-            .map(|v| v.clone())
-            .ok_or(WasmRuntimeErrorKind::InvalidParameter)
+        match rt.files.lock().unwrap().get(handle) {
+            Some(Some(OsFileHandle::File(file))) => Ok(file.clone()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    /// Returns the file or the standard stream to read or write
+    fn get_stream(&mut self, rt: &MyosRuntime) -> Result<StdStream, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.files.lock().unwrap().get(handle) {
+            Some(Some(OsFileHandle::File(file))) => Ok(StdStream::File(file.clone())),
+            Some(Some(OsFileHandle::Std(fd))) => Ok(Scheduler::current_pid().stdio(*fd)),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    fn get_dir(
+        &mut self,
+        rt: &MyosRuntime,
+    ) -> Result<Arc<Mutex<FsRawReadDir>>, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.files.lock().unwrap().get(handle) {
+            Some(Some(OsFileHandle::Dir(dir))) => Ok(dir.clone()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    fn get_audio_context(
+        &mut self,
+        rt: &MyosRuntime,
+    ) -> Result<Arc<AudioContext>, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.audio.lock().unwrap().get(&handle) {
+            Some(OsAudioHandle::Context(ctx)) => Ok(ctx.clone()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    fn get_audio_stream(
+        &mut self,
+        rt: &MyosRuntime,
+    ) -> Result<Arc<PcmStream>, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.audio.lock().unwrap().get(&handle) {
+            Some(OsAudioHandle::Stream(stream, _)) => Ok(stream.clone()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+}
+
+/// Audio contexts, notes and streams of the application
+enum OsAudioHandle {
+    Context(Arc<AudioContext>),
+    Note(NoteControl),
+    Stream(Arc<PcmStream>, NoteControl),
+}
+
+impl Drop for OsAudioHandle {
+    fn drop(&mut self) {
+        match self {
+            OsAudioHandle::Context(_) => (),
+            OsAudioHandle::Note(note) | OsAudioHandle::Stream(_, note) => note.stop(),
+        }
+    }
+}
+
+/// Files and directories opened by the application
+enum OsFileHandle {
+    File(Arc<Mutex<FsRawFileControlBlock>>),
+    Dir(Arc<Mutex<FsRawReadDir>>),
+    /// Standard stream of the process
+    Std(usize),
+}
+
+impl OsFileHandle {
+    /// Returns the handles of the standard input, output and error
+    #[inline]
+    fn stdio() -> Vec<Option<Self>> {
+        [STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO]
+            .into_iter()
+            .map(|fd| Some(Self::Std(fd)))
+            .collect()
+    }
+
+    #[inline]
+    fn file(file: FsRawFileControlBlock) -> Self {
+        Self::File(Arc::new(Mutex::new(file)))
+    }
+
+    #[inline]
+    fn dir(dir: FsRawReadDir) -> Self {
+        Self::Dir(Arc::new(Mutex::new(dir)))
     }
 }
 
@@ -874,6 +1808,7 @@ struct OsWindow {
     native: WindowHandle,
     handle: usize,
     draw_region: Coordinates,
+    back_buffer: Option<OsBackBuffer>,
 }
 
 impl OsWindow {
@@ -883,6 +1818,7 @@ impl OsWindow {
             native,
             handle,
             draw_region: Coordinates::void(),
+            back_buffer: None,
         }
     }
 
@@ -928,169 +1864,64 @@ impl OsWindow {
         let _ = self.native.draw_in_rect(rect, f);
         self.add_region(rect);
     }
-}
 
-impl Drop for OsWindow {
-    #[inline]
-    fn drop(&mut self) {
-        self.native.close();
+    /// Copies the rectangle of the back buffer to the window and shows it immediately
+    fn present(&self, rect: Rect, src: &BitmapRef) {
+        let size = src.size();
+        let left = rect.min_x().max(0);
+        let top = rect.min_y().max(0);
+        let right = rect.max_x().min(size.width);
+        let bottom = rect.max_y().min(size.height);
+        if left >= right || top >= bottom {
+            return;
+        }
+        let rect = Rect::new(left, top, right - left, bottom - top);
+        let _ = self.native.draw_in_rect(rect, |bitmap| {
+            bitmap.blt(src, Point::default(), rect);
+        });
+        self.native.invalidate_rect(rect);
     }
 }
 
-pub struct SimpleAllocator {
-    data: Vec<SimpleFreePair>,
-    strategy: AllocationStrategy,
+/// Bitmap in the linear memory registered by `SetBackBuffer`
+///
+/// Only the offset is kept, because the memory may be moved when it grows.
+#[derive(Debug, Clone, Copy)]
+struct OsBackBuffer {
+    base: usize,
+    size: Size,
+    format: u32,
 }
 
-impl SimpleAllocator {
-    const MIN_MASK: u32 = 0x0000_000F;
-
-    #[inline]
-    pub const fn new(strategy: AllocationStrategy) -> Self {
-        Self {
-            data: Vec::new(),
-            strategy,
-        }
-    }
-
-    fn merge(&mut self, new_data: Option<SimpleFreePair>) {
-        if let Some(new_data) = new_data {
-            self.data.push(new_data);
-        }
-        self.data.sort_by_key(|v| v.base);
-
-        let mut do_retry = false;
-        let mut retry_index = 0;
-        loop {
-            for index in retry_index..self.data.len() - 1 {
-                let current = unsafe { self.data.get_unchecked(index) };
-                let next = unsafe { self.data.get_unchecked(index + 1) };
-                if current.next_base() == next.base {
-                    let next = self.data.remove(index + 1);
-                    self.data[index].size += next.size;
-                    retry_index = index;
-                    do_retry = true;
-                    break;
-                }
-            }
-            if do_retry == false {
-                break;
-            }
-        }
-    }
-
-    pub fn append_block(&mut self, base: u32, size: u32) {
-        self.merge(Some(SimpleFreePair::new(base, size)));
-    }
-
-    pub fn alloc(&mut self, layout: Layout) -> Option<NonZeroU32> {
-        let layout_align = u32::max(layout.align() as u32, Self::MIN_MASK + 1);
-        let layout_mask = layout_align - 1;
-        let min_alloc =
-            (u32::max(layout_align, layout.size() as u32) + Self::MIN_MASK) & !Self::MIN_MASK;
-        let max_alloc = (min_alloc + layout_mask) & layout_mask;
-
-        let mut result = 0;
-        let mut extend = Vec::new();
-        match self.strategy {
-            AllocationStrategy::FirstFit => {
-                for pair in &mut self.data {
-                    if (pair.base & layout_mask) == 0 && pair.size >= min_alloc {
-                        result = pair.base;
-                        pair.size -= min_alloc;
-                        pair.base += min_alloc;
-                        break;
-                    } else if pair.size >= max_alloc {
-                        let redundant = pair.base & layout_mask;
-                        extend.push(SimpleFreePair::new(pair.base, redundant));
-                        pair.base -= redundant;
-                        pair.size -= redundant;
-
-                        result = pair.base;
-                        pair.size -= min_alloc;
-                        pair.base += min_alloc;
-                        break;
-                    }
-                }
-            }
-            AllocationStrategy::BestFit => todo!(),
-        }
-        if extend.len() > 0 {
-            self.data.extend_from_slice(extend.as_slice());
-            self.merge(None);
-        }
-
-        NonZeroU32::new(result)
-    }
-
-    pub fn dealloc(&mut self, base: u32, layout: Layout) {
-        let layout_align = u32::max(layout.align() as u32, Self::MIN_MASK + 1);
-        // let layout_mask = layout_align - 1;
-        let min_alloc =
-            (u32::max(layout_align, layout.size() as u32) + Self::MIN_MASK) & !Self::MIN_MASK;
-
-        let new_pair = SimpleFreePair::new(base, min_alloc);
-
-        let mut cursor = None;
-        for (index, pair) in self.data.iter_mut().enumerate() {
-            if new_pair.next_base() == pair.base {
-                pair.base = new_pair.base;
-                pair.size += min_alloc;
-                cursor = Some(index);
-                break;
-            } else if pair.next_base() == base {
-                pair.size += min_alloc;
-                cursor = Some(index);
-                break;
+impl OsBackBuffer {
+    /// Calls the function with the bitmap, fails if it is outside the memory
+    fn with_bitmap<F, R>(&self, memory: &WasmMemory, f: F) -> Result<R, WasmRuntimeErrorKind>
+    where
+        F: FnOnce(&BitmapRef) -> R,
+    {
+        let len = self.size.width as usize * self.size.height as usize;
+        match self.format {
+            back_buffer::INDEXED8 => {
+                let slice = memory.slice(self.base, len)?;
+                let bitmap = BitmapRef8::from_bytes(slice, self.size);
+                Ok(f(&BitmapRef::from(&bitmap)))
             }
-        }
-
-        if let Some(index) = cursor {
-            if index < self.data.len() - 1 {
-                let current = unsafe { self.data.get_unchecked(index) };
-                let next = unsafe { self.data.get_unchecked(index + 1) };
-                if current.next_base() == next.base {
-                    let next = self.data.remove(index + 1);
-                    self.data[index].size += next.size;
+            back_buffer::ARGB32 => {
+                if (self.base & 3) != 0 {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
                 }
+                let slice = memory.read_u32_array(self.base, len)?;
+                let bitmap = BitmapRef32::from_bytes(slice, self.size);
+                Ok(f(&BitmapRef::from(&bitmap)))
             }
-        } else {
-            self.merge(Some(new_pair));
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
         }
-        for data in &self.data {
-            println!("DATA {:08x} {}", data.base, data.size);
-        }
-    }
-}
-
-impl Default for SimpleAllocator {
-    #[inline]
-    fn default() -> Self {
-        Self::new(AllocationStrategy::FirstFit)
     }
 }
 
-#[non_exhaustive]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum AllocationStrategy {
-    FirstFit,
-    BestFit,
-}
-
-#[derive(Clone, Copy)]
-struct SimpleFreePair {
-    base: u32,
-    size: u32,
-}
-
-impl SimpleFreePair {
-    #[inline]
-    pub const fn new(base: u32, size: u32) -> Self {
-        Self { base, size }
-    }
-
+impl Drop for OsWindow {
     #[inline]
-    pub const fn next_base(&self) -> u32 {
-        self.base + self.size
+    fn drop(&mut self) {
+        self.native.close();
     }
 }
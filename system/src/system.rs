@@ -66,7 +66,7 @@ impl System {
     }
 
     /// Initialize the system
-    pub unsafe fn init(info: &BootInfo, f: fn() -> ()) -> ! {
+    pub unsafe fn init(info: &BootInfo, f: fn(usize)) -> ! {
         assert_call_once!();
 
         let shared = SYSTEM.get_mut();
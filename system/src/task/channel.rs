@@ -0,0 +1,290 @@
+//! Named message channels between processes
+//!
+//! A channel is a bounded queue of byte messages registered under a unique name.
+//! The process that creates the channel owns it, and other processes connect to it by name.
+//! Any handle of the channel can send and receive messages.
+//! When the owner closes its handle or exits, the name is released and the channel is closed,
+//! the remaining messages can still be received but no more messages can be sent.
+
+use super::scheduler::*;
+use crate::sync::{fifo::ConcurrentFifo, semaphore::*, RwLock};
+use core::{num::NonZeroUsize, pin::Pin, sync::atomic::*};
+use megstd::{
+    io::{ErrorKind, Result},
+    Arc, BTreeMap, String, ToOwned, Vec,
+};
+
+static CHANNEL_MANAGER: ChannelManager = ChannelManager::new();
+
+/// Handle of a channel opened by a process
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChannelHandle(NonZeroUsize);
+
+impl ChannelHandle {
+    /// Maximum size of a message
+    pub const MAX_MESSAGE_SIZE: usize = 4096;
+    /// Maximum number of messages that a channel can hold
+    pub const MAX_CAPACITY: usize = 256;
+    /// Maximum length of the name of a channel
+    pub const MAX_NAME_LEN: usize = 64;
+
+    /// Creates a new channel owned by the current process
+    pub fn create(name: &str, capacity: usize) -> Result<Self> {
+        if name.is_empty()
+            || name.len() > Self::MAX_NAME_LEN
+            || capacity == 0
+            || capacity > Self::MAX_CAPACITY
+        {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let shared = ChannelManager::shared();
+        let channel = {
+            let mut names = shared.names.write().unwrap();
+            if names.contains_key(name) {
+                return Err(ErrorKind::AlreadyExists.into());
+            }
+            let channel = Arc::new(ChannelData::new(name, capacity));
+            names.insert(name.to_owned(), channel.clone());
+            channel
+        };
+        Ok(shared.add_handle(channel, true))
+    }
+
+    /// Connects the current process to the channel with the specified name
+    pub fn connect(name: &str) -> Result<Self> {
+        let shared = ChannelManager::shared();
+        let channel = shared
+            .names
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or(ErrorKind::NotFound)?;
+        Ok(shared.add_handle(channel, false))
+    }
+
+    /// Returns the handle if it is opened by the specified process
+    pub fn from_raw(pid: ProcessId, val: usize) -> Option<Self> {
+        let handle = Self(NonZeroUsize::new(val)?);
+        ChannelManager::shared()
+            .handles
+            .read()
+            .unwrap()
+            .get(&handle)
+            .and_then(|entry| (entry.pid == pid).then_some(handle))
+    }
+
+    #[inline]
+    pub const fn as_usize(&self) -> usize {
+        self.0.get()
+    }
+
+    #[inline]
+    fn channel(&self) -> Result<Arc<ChannelData>> {
+        ChannelManager::shared()
+            .handles
+            .read()
+            .unwrap()
+            .get(self)
+            .map(|entry| entry.channel.clone())
+            .ok_or(ErrorKind::NotFound.into())
+    }
+
+    /// Returns the name of the channel
+    pub fn name(&self) -> Option<String> {
+        self.channel().ok().map(|v| v.name.clone())
+    }
+
+    /// Sends the message, fails with `WouldBlock` if the channel is full
+    pub fn send(&self, message: &[u8]) -> Result<()> {
+        if message.len() > Self::MAX_MESSAGE_SIZE {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        self.channel()?.send(message.to_vec())
+    }
+
+    /// Receives the next message if available
+    ///
+    /// Returns `Ok(None)` if the channel is empty, or `BrokenPipe` if the channel is empty and closed.
+    pub fn try_receive(&self) -> Result<Option<Vec<u8>>> {
+        self.channel()?.try_receive()
+    }
+
+    /// Waits for the next message
+    ///
+    /// Fails with `Interrupted` if the current process is requested to terminate.
+    pub fn receive(&self) -> Result<Vec<u8>> {
+        let channel = self.channel()?;
+        loop {
+            if let Some(message) = channel.try_receive()? {
+                return Ok(message);
+            }
+            if Scheduler::current_pid().is_terminating() {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            channel.sem.wait();
+        }
+    }
+
+    /// Waits for the next message asynchronously
+    ///
+    /// Fails with `Interrupted` if the current process is requested to terminate.
+    pub async fn receive_async(&self) -> Result<Vec<u8>> {
+        let channel = self.channel()?;
+        loop {
+            if let Some(message) = channel.try_receive()? {
+                return Ok(message);
+            }
+            if Scheduler::current_pid().is_terminating() {
+                return Err(ErrorKind::Interrupted.into());
+            }
+            channel.async_sem.clone().wait().await;
+        }
+    }
+
+    /// Closes the handle
+    #[inline]
+    pub fn close(self) {
+        ChannelManager::shared().remove_handles(|handle, _| *handle == self);
+    }
+}
+
+/// Registry of the channels and their handles
+pub struct ChannelManager {
+    names: RwLock<BTreeMap<String, Arc<ChannelData>>>,
+    handles: RwLock<BTreeMap<ChannelHandle, ChannelEntry>>,
+}
+
+impl ChannelManager {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            names: RwLock::new(BTreeMap::new()),
+            handles: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    #[inline]
+    fn shared<'a>() -> &'a Self {
+        &CHANNEL_MANAGER
+    }
+
+    fn add_handle(&self, channel: Arc<ChannelData>, is_owner: bool) -> ChannelHandle {
+        static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(1);
+        let handle =
+            ChannelHandle(NonZeroUsize::new(NEXT_HANDLE.fetch_add(1, Ordering::SeqCst)).unwrap());
+        let entry = ChannelEntry {
+            pid: Scheduler::current_pid(),
+            channel,
+            is_owner,
+        };
+        self.handles.write().unwrap().insert(handle, entry);
+        handle
+    }
+
+    fn remove_handles<F>(&self, mut f: F)
+    where
+        F: FnMut(&ChannelHandle, &ChannelEntry) -> bool,
+    {
+        let mut removed = Vec::new();
+        self.handles.write().unwrap().retain(|handle, entry| {
+            if f(handle, entry) {
+                if entry.is_owner {
+                    removed.push(entry.channel.clone());
+                }
+                false
+            } else {
+                true
+            }
+        });
+        if !removed.is_empty() {
+            let mut names = self.names.write().unwrap();
+            for channel in removed {
+                names.remove(&channel.name);
+                channel.close();
+            }
+        }
+    }
+
+    /// Wakes up the threads of the process waiting for the messages,
+    /// called when the process is requested to terminate
+    pub fn wake_process(pid: ProcessId) {
+        for entry in Self::shared().handles.read().unwrap().values() {
+            if entry.pid == pid {
+                entry.channel.wake();
+            }
+        }
+    }
+
+    /// Closes all handles opened by the process, called when the process exits
+    #[inline]
+    pub fn remove_process(pid: ProcessId) {
+        Self::shared().remove_handles(|_, entry| entry.pid == pid);
+    }
+}
+
+struct ChannelEntry {
+    pid: ProcessId,
+    channel: Arc<ChannelData>,
+    is_owner: bool,
+}
+
+struct ChannelData {
+    name: String,
+    queue: ConcurrentFifo<Vec<u8>>,
+    sem: Semaphore,
+    async_sem: Pin<Arc<AsyncSemaphore>>,
+    is_closed: AtomicBool,
+}
+
+impl ChannelData {
+    #[inline]
+    fn new(name: &str, capacity: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            queue: ConcurrentFifo::with_capacity(capacity),
+            sem: Semaphore::new(0),
+            async_sem: AsyncSemaphore::new(0),
+            is_closed: AtomicBool::new(false),
+        }
+    }
+
+    fn send(&self, message: Vec<u8>) -> Result<()> {
+        if self.is_closed.load(Ordering::SeqCst) {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        self.queue
+            .enqueue(message)
+            .map_err(|_| ErrorKind::WouldBlock)?;
+        self.wake();
+        Ok(())
+    }
+
+    fn try_receive(&self) -> Result<Option<Vec<u8>>> {
+        match self.queue.dequeue() {
+            Some(message) => Ok(Some(message)),
+            None => {
+                if self.is_closed.load(Ordering::SeqCst) {
+                    // Passes the wakeup on to the other receivers waiting for the channel
+                    self.wake();
+                    Err(ErrorKind::BrokenPipe.into())
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn close(&self) {
+        self.is_closed.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    #[inline]
+    fn wake(&self) {
+        self.sem.signal();
+        self.async_sem.signal();
+    }
+}
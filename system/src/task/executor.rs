@@ -107,6 +107,34 @@ impl Wake for TaskWaker {
     }
 }
 
+/// Wakes a thread blocked in [`Scheduler::block_on`](super::scheduler::Scheduler::block_on)
+pub(super) struct ThreadWaker {
+    sem: Semaphore,
+}
+
+impl ThreadWaker {
+    pub(super) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            sem: Semaphore::new(0),
+        })
+    }
+
+    #[inline]
+    pub(super) fn wait(&self) {
+        self.sem.wait();
+    }
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.sem.signal();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.sem.signal();
+    }
+}
+
 struct TaskQueue {
     queue: ConcurrentFifo<TaskId>,
     sem: Semaphore,
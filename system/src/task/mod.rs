@@ -1,5 +1,6 @@
 //! Task scheduler
 
+pub mod channel;
 pub mod executor;
 pub mod scheduler;
 
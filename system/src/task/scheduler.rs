@@ -1,6 +1,11 @@
-use super::{executor::Executor, *};
+use super::{
+    channel::ChannelManager,
+    executor::{Executor, ThreadWaker},
+    *,
+};
 use crate::{
     arch::cpu::*,
+    io::stdio::StdStream,
     rt::PersonalityContext,
     sync::{
         atomic::{AtomicFlags, AtomicWrapper},
@@ -10,15 +15,15 @@ use crate::{
         LockResult, Mutex, RwLock, RwLockReadGuard,
     },
     system::*,
-    ui::window::{WindowHandle, WindowMessage},
+    ui::window::{WindowHandle, WindowManager, WindowMessage},
     *,
 };
 // use alloc::format;
 use core::{
     cell::UnsafeCell, ffi::c_void, fmt, intrinsics::transmute, num::*, ops::*, sync::atomic::*,
-    time::Duration,
+    task::Waker, time::Duration,
 };
-use megstd::{string::*, Arc, BTreeMap, Box, String, ToOwned, Vec};
+use megstd::{io::ErrorKind, string::*, Arc, BTreeMap, Box, String, ToOwned, Vec};
 
 const THRESHOLD_BUSY_THREAD: usize = 750;
 const THRESHOLD_ENTER_SAVING: usize = 500;
@@ -112,6 +117,8 @@ impl Scheduler {
             Priority::Idle,
             "idle",
             "/",
+            BTreeMap::new(),
+            Default::default(),
         ));
 
         let num_of_active_cpus = System::current_device().num_of_logical_cpus();
@@ -566,6 +573,8 @@ impl Scheduler {
                 options.priority.unwrap_or_default(),
                 name,
                 current_pid.cwd().as_str(),
+                current_pid.environ(),
+                current_pid.stdio_all(),
             );
             let pid = child.pid;
             ProcessPool::shared().add(child);
@@ -613,11 +622,49 @@ impl Scheduler {
         Self::exit();
     }
 
+    /// Runs a future to completion, blocking the current thread while it is pending.
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let thread_waker = ThreadWaker::new();
+        let waker = Waker::from(thread_waker.clone());
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(v) => return v,
+                Poll::Pending => thread_waker.wait(),
+            }
+        }
+    }
+
     pub fn exit() -> ! {
         let thread = Self::current_thread_data();
         thread.exit();
     }
 
+    /// Requests the process to terminate with the exit code.
+    ///
+    /// The threads of the process exit at their next system call or window message,
+    /// and their personalities release the windows and files in `on_exit`.
+    /// The threads blocked on the standard streams or the channels are woken up to exit.
+    /// The processes of the kernel cannot be terminated.
+    pub fn terminate_process(pid: ProcessId, exit_code: usize) -> megstd::io::Result<()> {
+        let process = pid.get().ok_or(ErrorKind::NotFound)?;
+        if process.parent == ProcessId(0) {
+            return Err(ErrorKind::PermissionDenied.into());
+        }
+        if !process.is_terminating.swap(true, Ordering::SeqCst) {
+            process.exit_code.store(exit_code, Ordering::SeqCst);
+            // Wakes up the threads waiting for the window messages
+            WindowManager::post_to_process(pid, WindowMessage::Close);
+            // Wakes up the threads waiting for the standard streams or the channels
+            for stream in pid.stdio_all() {
+                stream.wake();
+            }
+            ChannelManager::wake_process(pid);
+        }
+        Ok(())
+    }
+
     pub fn get_idle_statistics(vec: &mut Vec<u32>) {
         vec.clear();
         for thread in ThreadPool::shared().data.lock().values() {
@@ -630,7 +677,7 @@ impl Scheduler {
 
     pub fn print_statistics(sb: &mut impl fmt::Write) {
         let max_load = 1000 * System::current_device().num_of_logical_cpus() as u32;
-        writeln!(sb, "PID P #TH %CPU TIME     NAME").unwrap();
+        writeln!(sb, "PID P #TH %CPU TIME      MEM NAME").unwrap();
         for process in ProcessPool::shared().read().unwrap().values() {
             let process = process.clone();
             if process.pid == ProcessId(0) {
@@ -666,6 +713,15 @@ impl Scheduler {
                 write!(sb, " {:02}:{:02}.{:02}", min, sec, dsec,).unwrap();
             }
 
+            let memory = process.memory_usage.load(Ordering::Relaxed);
+            if memory == 0 {
+                write!(sb, "    -").unwrap();
+            } else if memory < 1024 * 1024 {
+                write!(sb, " {:3}K", (memory + 1023) / 1024).unwrap();
+            } else {
+                write!(sb, " {:3}M", (memory + 1024 * 1024 - 1) / (1024 * 1024)).unwrap();
+            }
+
             writeln!(sb, " {}", process.name(),).unwrap();
         }
     }
@@ -1190,6 +1246,7 @@ enum TimerType {
     Async(Pin<Arc<AsyncSemaphore>>),
     OneShot(ThreadHandle),
     Window(WindowHandle, usize),
+    Flag(Arc<AtomicBool>),
 }
 
 #[allow(dead_code)]
@@ -1218,6 +1275,15 @@ impl TimerEvent {
         }
     }
 
+    /// Sets the flag when the timer expires, which can be polled without blocking.
+    #[inline]
+    pub fn flag(timer: Timer, flag: Arc<AtomicBool>) -> Self {
+        Self {
+            timer,
+            timer_type: TimerType::Flag(flag),
+        }
+    }
+
     #[inline]
     pub fn is_alive(&self) -> bool {
         self.timer.is_alive()
@@ -1237,6 +1303,7 @@ impl TimerEvent {
                     .is_valid()
                     .map(|v| v.post(WindowMessage::Timer(timer_id)).unwrap());
             }
+            TimerType::Flag(flag) => flag.store(true, Ordering::Release),
         }
     }
 }
@@ -1329,13 +1396,18 @@ impl From<Priority> for Quantum {
 #[derive(Default)]
 struct ProcessPool {
     data: RwLock<BTreeMap<ProcessId, Arc<ProcessContextData>>>,
+    exit_codes: SpinMutex<BTreeMap<ProcessId, usize>>,
 }
 
 impl ProcessPool {
+    /// Maximum number of exit codes kept for processes that have not been joined yet
+    const MAX_EXIT_CODES: usize = 64;
+
     #[inline]
     const fn new() -> Self {
         Self {
             data: RwLock::new(BTreeMap::new()),
+            exit_codes: SpinMutex::new(BTreeMap::new()),
         }
     }
 
@@ -1357,6 +1429,20 @@ impl ProcessPool {
         self.data.write().unwrap().remove(&handle);
     }
 
+    /// Keeps the exit code of a terminated process until its parent joins it
+    fn store_exit_code(&self, handle: ProcessId, exit_code: usize) {
+        let mut exit_codes = self.exit_codes.lock();
+        exit_codes.insert(handle, exit_code);
+        while exit_codes.len() > Self::MAX_EXIT_CODES {
+            exit_codes.pop_first();
+        }
+    }
+
+    #[inline]
+    fn take_exit_code(&self, handle: ProcessId) -> Option<usize> {
+        self.exit_codes.lock().remove(&handle)
+    }
+
     #[inline]
     fn read(&self) -> LockResult<RwLockReadGuard<BTreeMap<ProcessId, Arc<ProcessContextData>>>> {
         self.data.read()
@@ -1423,9 +1509,49 @@ impl ProcessId {
         ProcessPool::shared().get(*self)
     }
 
+    /// Waits for the process to terminate and returns its exit code
     #[inline]
-    pub fn join(&self) {
+    pub fn join(&self) -> Option<usize> {
         self.get().map(|t| t.sem.wait());
+        ProcessPool::shared().take_exit_code(*self)
+    }
+
+    /// Sets the size of the memory used by the process, such as the linear memory of wasm
+    #[inline]
+    pub fn set_memory_usage(&self, size: usize) {
+        if let Some(process) = self.get() {
+            process.memory_usage.store(size, Ordering::Relaxed);
+        }
+    }
+
+    /// Sets the exit code reported to the parent when the process terminates,
+    /// which is ignored after the process is requested to terminate
+    #[inline]
+    pub fn set_exit_code(&self, exit_code: usize) {
+        if let Some(process) = self.get() {
+            if !process.is_terminating.load(Ordering::SeqCst) {
+                process.exit_code.store(exit_code, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Returns whether the process has been requested to terminate by [`Scheduler::terminate_process`]
+    #[inline]
+    pub fn is_terminating(&self) -> bool {
+        self.get()
+            .map(|v| v.is_terminating.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+
+    /// Returns the flag that is set when the process is requested to terminate
+    #[inline]
+    pub fn termination_flag(&self) -> Option<Arc<AtomicBool>> {
+        self.get().map(|v| v.is_terminating.clone())
     }
 
     pub fn cwd(&self) -> String {
@@ -1439,6 +1565,61 @@ impl ProcessId {
         self.get()
             .map(|v| *v.cwd.write().unwrap() = path.to_owned());
     }
+
+    /// Returns a copy of the environment variables of the process
+    pub fn environ(&self) -> BTreeMap<String, String> {
+        self.get()
+            .map(|v| v.environ.read().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn get_env(&self, key: &str) -> Option<String> {
+        self.get()
+            .and_then(|v| v.environ.read().unwrap().get(key).cloned())
+    }
+
+    #[inline]
+    pub fn set_env(&self, key: &str, value: &str) {
+        self.get().map(|v| {
+            v.environ
+                .write()
+                .unwrap()
+                .insert(key.to_owned(), value.to_owned())
+        });
+    }
+
+    #[inline]
+    pub fn remove_env(&self, key: &str) {
+        self.get().map(|v| v.environ.write().unwrap().remove(key));
+    }
+
+    /// Returns the standard stream of the process, 0 for input, 1 for output and 2 for error
+    #[inline]
+    pub fn stdio(&self, fd: usize) -> StdStream {
+        self.get()
+            .and_then(|v| v.stdio.read().unwrap().get(fd).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Returns the standard input, output and error of the process
+    #[inline]
+    pub fn stdio_all(&self) -> [StdStream; 3] {
+        self.get()
+            .map(|v| v.stdio.read().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the standard streams of the process, returns the previous ones.
+    ///
+    /// The processes started after this inherit the new streams.
+    #[inline]
+    pub fn replace_stdio(&self, stdio: [StdStream; 3]) -> [StdStream; 3] {
+        match self.get() {
+            Some(v) => core::mem::replace(&mut *v.stdio.write().unwrap(), stdio),
+            None => stdio,
+        }
+    }
 }
 
 impl From<ProcessId> for usize {
@@ -1448,6 +1629,13 @@ impl From<ProcessId> for usize {
     }
 }
 
+impl From<usize> for ProcessId {
+    #[inline]
+    fn from(val: usize) -> Self {
+        Self(val)
+    }
+}
+
 #[allow(dead_code)]
 struct ProcessContextData {
     name: String,
@@ -1462,12 +1650,24 @@ struct ProcessContextData {
     cpu_time: AtomicUsize,
     load0: AtomicU32,
     load: AtomicU32,
+    memory_usage: AtomicUsize,
 
     cwd: RwLock<String>,
+    environ: RwLock<BTreeMap<String, String>>,
+    stdio: RwLock<[StdStream; 3]>,
+    exit_code: AtomicUsize,
+    is_terminating: Arc<AtomicBool>,
 }
 
 impl ProcessContextData {
-    fn new(parent: ProcessId, priority: Priority, name: &str, cwd: &str) -> ProcessContextData {
+    fn new(
+        parent: ProcessId,
+        priority: Priority,
+        name: &str,
+        cwd: &str,
+        environ: BTreeMap<String, String>,
+        stdio: [StdStream; 3],
+    ) -> ProcessContextData {
         let pid = Self::next_pid();
         Self {
             name: name.to_owned(),
@@ -1480,7 +1680,12 @@ impl ProcessContextData {
             cpu_time: AtomicUsize::new(0),
             load0: AtomicU32::new(0),
             load: AtomicU32::new(0),
+            memory_usage: AtomicUsize::new(0),
             cwd: RwLock::new(cwd.to_owned()),
+            environ: RwLock::new(environ),
+            stdio: RwLock::new(stdio),
+            exit_code: AtomicUsize::new(0),
+            is_terminating: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -1495,6 +1700,10 @@ impl ProcessContextData {
     }
 
     fn exit(&self) {
+        ProcessPool::shared().store_exit_code(self.pid, self.exit_code.load(Ordering::SeqCst));
+        ChannelManager::remove_process(self.pid);
+        // Closes the pipes connected to the process
+        *self.stdio.write().unwrap() = Default::default();
         self.sem.signal();
         ProcessPool::shared().remove(self.pid);
     }
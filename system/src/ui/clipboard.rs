@@ -0,0 +1,90 @@
+//! System Clipboard shared by windows, terminals and applications
+
+use crate::{sync::RwLock, ui::window::*, *};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use megstd::{drawing::*, Arc, String, ToOwned};
+
+static CLIPBOARD: Clipboard = Clipboard::new();
+
+/// Typed data in the clipboard
+#[derive(Clone)]
+pub enum ClipboardData {
+    /// UTF-8 text
+    Text(Arc<String>),
+    /// ARGB32 bitmap
+    Bitmap(Arc<OwnedBitmap32>),
+}
+
+pub struct Clipboard {
+    data: RwLock<Option<ClipboardData>>,
+    sequence: AtomicUsize,
+}
+
+impl Clipboard {
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            data: RwLock::new(None),
+            sequence: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn shared<'a>() -> &'a Self {
+        &CLIPBOARD
+    }
+
+    /// Returns the current data of the clipboard
+    #[inline]
+    pub fn get() -> Option<ClipboardData> {
+        Self::shared().data.read().unwrap().clone()
+    }
+
+    /// Replaces the data of the clipboard,
+    /// then notifies all windows with `WindowMessage::ClipboardChanged`
+    pub fn set(data: Option<ClipboardData>) {
+        let shared = Self::shared();
+        *shared.data.write().unwrap() = data;
+        shared.sequence.fetch_add(1, Ordering::SeqCst);
+        WindowManager::broadcast(WindowMessage::ClipboardChanged);
+    }
+
+    #[inline]
+    pub fn set_text(text: &str) {
+        Self::set(Some(ClipboardData::Text(Arc::new(text.to_owned()))));
+    }
+
+    #[inline]
+    pub fn set_bitmap(bitmap: OwnedBitmap32) {
+        Self::set(Some(ClipboardData::Bitmap(Arc::new(bitmap))));
+    }
+
+    #[inline]
+    pub fn clear() {
+        Self::set(None);
+    }
+
+    /// Returns the text if the clipboard contains text
+    #[inline]
+    pub fn text() -> Option<Arc<String>> {
+        match Self::get() {
+            Some(ClipboardData::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the bitmap if the clipboard contains a bitmap
+    #[inline]
+    pub fn bitmap() -> Option<Arc<OwnedBitmap32>> {
+        match Self::get() {
+            Some(ClipboardData::Bitmap(bitmap)) => Some(bitmap),
+            _ => None,
+        }
+    }
+
+    /// Number of changes since the system started
+    #[inline]
+    pub fn sequence() -> usize {
+        Self::shared().sequence.load(Ordering::SeqCst)
+    }
+}
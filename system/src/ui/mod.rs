@@ -1,5 +1,6 @@
 //! User Interface modules (windows, terminals, ...)
 
+pub mod clipboard;
 pub mod font;
 pub mod terminal;
 pub mod text;
@@ -1,4 +1,10 @@
-use crate::{io::tty::*, ui::font::*, ui::window::*, *};
+use crate::{
+    io::{hid_mgr::*, tty::*},
+    sync::Mutex,
+    task::scheduler::{ProcessId, Scheduler},
+    ui::{clipboard::*, font::*, window::*},
+    *,
+};
 use alloc::boxed::Box;
 use core::{
     fmt::Write,
@@ -7,7 +13,7 @@ use core::{
     sync::atomic::{AtomicUsize, Ordering},
     task::{Context, Poll},
 };
-use megstd::drawing::*;
+use megstd::{drawing::*, io::hid::Usage, Arc, BTreeMap, String, Vec};
 
 const DEFAULT_INSETS: EdgeInsets = EdgeInsets::new(0, 0, 0, 0);
 // const DEFAULT_ATTRIBUTE: u8 = 0x07;
@@ -19,6 +25,10 @@ static TA: TerminalAgent = TerminalAgent::new();
 
 struct TerminalAgent {
     n_instances: AtomicUsize,
+    /// Foreground processes of the terminal windows
+    foreground: Mutex<BTreeMap<WindowHandle, Vec<ProcessId>>>,
+    /// Terminal windows catching Ctrl+C, and whether it has been caught
+    catching: Mutex<BTreeMap<WindowHandle, bool>>,
 }
 
 impl TerminalAgent {
@@ -26,6 +36,8 @@ impl TerminalAgent {
     const fn new() -> Self {
         Self {
             n_instances: AtomicUsize::new(0),
+            foreground: Mutex::new(BTreeMap::new()),
+            catching: Mutex::new(BTreeMap::new()),
         }
     }
 
@@ -69,9 +81,13 @@ pub struct Terminal {
     is_cursor_enabled: bool,
     font_cache: Option<OwnedBitmap32>,
     palette: [TrueColor; 16],
+    text: Arc<Mutex<TerminalText>>,
 }
 
 impl Terminal {
+    /// Exit code of the foreground process interrupted by Ctrl+C
+    pub const EXIT_INTERRUPTED: usize = 130;
+
     pub const DEFAULT_PALETTE: [TrueColor; 16] = [
         TrueColor::BLACK,
         TrueColor::BLUE,
@@ -133,6 +149,7 @@ impl Terminal {
             is_cursor_enabled: true,
             font_cache: Self::_fill_cache(&font),
             palette,
+            text: Arc::new(Mutex::new(TerminalText::new(cols, rows))),
         }
     }
 
@@ -182,7 +199,38 @@ impl Terminal {
             is_cursor_enabled: true,
             font_cache: Self::_fill_cache(&font),
             palette,
+            text: Arc::new(Mutex::new(TerminalText::new(cols, rows))),
+        }
+    }
+
+    /// Terminates the foreground processes of the terminal window by Ctrl+C,
+    /// returns `false` if the key should be posted to the window.
+    ///
+    /// Without the foreground processes, Ctrl+C is recorded if the terminal is catching it.
+    pub fn handle_interrupt_key(window: WindowHandle, event: KeyEvent) -> bool {
+        let modifier = event.modifier();
+        if event.usage() != Usage::KEY_C
+            || !modifier.has_ctrl()
+            || modifier.has_shift()
+            || modifier.has_alt()
+        {
+            return false;
+        }
+        let shared = TerminalAgent::shared();
+        let Some(pids) = shared.foreground.lock().unwrap().get(&window).cloned() else {
+            if let Some(caught) = shared.catching.lock().unwrap().get_mut(&window) {
+                *caught |= !event.is_break();
+            }
+            return false;
+        };
+        if !event.is_break() {
+            for pid in pids {
+                let _ = Scheduler::terminate_process(pid, Self::EXIT_INTERRUPTED);
+            }
+            // Wakes up the process if it is reading the terminal
+            let _ = window.post(WindowMessage::Draw);
         }
+        true
     }
 
     fn _fill_cache(_font: &FontDescriptor) -> Option<OwnedBitmap32> {
@@ -228,6 +276,7 @@ impl Terminal {
             })
             .unwrap();
         self.window.set_needs_display();
+        self.text.lock().unwrap().scroll_up();
     }
 
     fn put_char(&mut self, c: char) -> Option<Rect> {
@@ -258,10 +307,19 @@ impl Terminal {
                 None
             }
             _ => {
+                let width = char_width(c);
+                if width == 0 {
+                    return None;
+                }
                 let w = self.font.em_width();
                 let h = self.font.line_height();
 
-                if self.x >= self.cols {
+                let mut padding = None;
+                if self.x + width > self.cols {
+                    if self.x < self.cols {
+                        // Wide characters do not fit in the last column
+                        padding = self.put_char(' ');
+                    }
                     self.x = 0;
                     self.y += 1;
                 }
@@ -273,7 +331,7 @@ impl Terminal {
                 let rect = Rect::new(
                     self.insets.left + self.x as isize * w,
                     self.insets.top + self.y as isize * h,
-                    w,
+                    w * width as isize,
                     h,
                 );
                 self.window
@@ -295,9 +353,15 @@ impl Terminal {
                         }
                     })
                     .unwrap();
-
-                self.x += 1;
-                Some(rect)
+                self.text.lock().unwrap().put(self.x, self.y, c, width);
+
+                self.x += width;
+                match padding.and_then(|v| Coordinates::from_rect(v).ok()) {
+                    Some(padding) => Coordinates::from_rect(rect)
+                        .ok()
+                        .map(|v| (v + padding).into()),
+                    None => Some(rect),
+                }
             }
         }
     }
@@ -363,8 +427,46 @@ impl TtyRead for Terminal {
     ) -> core::pin::Pin<Box<dyn core::future::Future<Output = TtyReadResult> + '_>> {
         Box::pin(ConsoleReader {
             window: self.window,
+            text: self.text.clone(),
         })
     }
+
+    fn add_foreground_process(&self, pid: ProcessId) {
+        let mut foreground = TerminalAgent::shared().foreground.lock().unwrap();
+        foreground.entry(self.window).or_default().push(pid);
+    }
+
+    fn remove_foreground_process(&self, pid: ProcessId) {
+        let mut foreground = TerminalAgent::shared().foreground.lock().unwrap();
+        if let Some(pids) = foreground.get_mut(&self.window) {
+            pids.retain(|v| *v != pid);
+            if pids.is_empty() {
+                foreground.remove(&self.window);
+            }
+        }
+    }
+
+    fn set_catch_interrupt(&self, catch: bool) {
+        let mut catching = TerminalAgent::shared().catching.lock().unwrap();
+        if catch {
+            catching.insert(self.window, false);
+        } else {
+            catching.remove(&self.window);
+        }
+    }
+
+    fn take_interrupt(&self) -> bool {
+        TerminalAgent::shared()
+            .catching
+            .lock()
+            .unwrap()
+            .get_mut(&self.window)
+            .is_some_and(|caught| core::mem::replace(caught, false))
+    }
+
+    fn wake_reader(&self) {
+        let _ = self.window.post(WindowMessage::Draw);
+    }
 }
 
 impl TtyWrite for Terminal {
@@ -375,6 +477,7 @@ impl TtyWrite for Terminal {
                 bitmap.fill_rect(bitmap.bounds(), self.bg_color);
             })
             .unwrap();
+        self.text.lock().unwrap().clear();
         self.set_cursor_position(0, 0);
         self.window.set_needs_display();
         Ok(())
@@ -427,8 +530,106 @@ impl TtyWrite for Terminal {
 
 impl Tty for Terminal {}
 
+/// Characters on the screen, kept to copy them to the clipboard
+struct TerminalText {
+    cols: usize,
+    rows: usize,
+    chars: Vec<char>,
+}
+
+impl TerminalText {
+    #[inline]
+    fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            chars: alloc::vec![' '; cols * rows],
+        }
+    }
+
+    /// Puts the character, the following cells of a wide character are filled with NUL
+    #[inline]
+    fn put(&mut self, x: usize, y: usize, c: char, width: usize) {
+        if y < self.rows {
+            for index in x..(x + width).min(self.cols) {
+                self.chars[y * self.cols + index] = if index == x { c } else { '\0' };
+            }
+        }
+    }
+
+    #[inline]
+    fn scroll_up(&mut self) {
+        self.chars.rotate_left(self.cols);
+        let len = self.chars.len();
+        self.chars[len - self.cols..].fill(' ');
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.chars.fill(' ');
+    }
+
+    /// Returns the lines without trailing spaces and empty lines at the end
+    fn to_string(&self) -> String {
+        let mut result = String::new();
+        for line in self.chars.chunks(self.cols.max(1)) {
+            let line = line.iter().filter(|v| **v != '\0').collect::<String>();
+            result.push_str(line.trim_end());
+            result.push('\n');
+        }
+        let len = result.trim_end().len();
+        result.truncate(len);
+        result
+    }
+}
+
 struct ConsoleReader {
     window: WindowHandle,
+    text: Arc<Mutex<TerminalText>>,
+}
+
+impl ConsoleReader {
+    /// Handles Ctrl+Shift+C and Ctrl+Shift+V, returns `false` for other keys
+    fn handle_clipboard_key(&self, event: KeyEvent) -> bool {
+        let modifier = event.modifier();
+        if event.is_break() || !modifier.has_ctrl() || !modifier.has_shift() {
+            return false;
+        }
+        match event.usage() {
+            Usage::KEY_C => {
+                let text = self.text.lock().unwrap().to_string();
+                Clipboard::set_text(&text);
+                true
+            }
+            Usage::KEY_V => {
+                if let Some(text) = Clipboard::text() {
+                    for c in text.chars() {
+                        if self.window.post(WindowMessage::Char(c)).is_err() {
+                            break;
+                        }
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Converts the editing keys that have no characters to the control characters for the line editor
+    fn editing_key_to_char(event: KeyEvent) -> Option<char> {
+        if event.is_break() {
+            return None;
+        }
+        match event.usage() {
+            Usage::KEY_HOME => Some('\x01'),
+            Usage::KEY_LEFT_ARROW => Some('\x02'),
+            Usage::KEY_END => Some('\x05'),
+            Usage::KEY_RIGHT_ARROW => Some('\x06'),
+            Usage::KEY_DOWN_ARROW => Some('\x0E'),
+            Usage::KEY_UP_ARROW => Some('\x10'),
+            _ => None,
+        }
+    }
 }
 
 impl Future for ConsoleReader {
@@ -436,11 +637,20 @@ impl Future for ConsoleReader {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         loop {
+            // The process requested to terminate by Ctrl+C or kill gets the end of the stream
+            if Scheduler::current_pid().is_terminating() {
+                return Poll::Ready(Err(TtyError::EndOfStream));
+            }
             match self.window.poll_message(cx) {
                 Poll::Ready(v) => {
                     if let Some(message) = v {
                         match message {
                             WindowMessage::Char(c) => return Poll::Ready(Ok(c)),
+                            WindowMessage::Key(event) if self.handle_clipboard_key(event) => (),
+                            WindowMessage::Key(event) => match Self::editing_key_to_char(event) {
+                                Some(c) => return Poll::Ready(Ok(c)),
+                                None => self.window.handle_default_message(message),
+                            },
                             _ => self.window.handle_default_message(message),
                         }
                     }
@@ -1,4 +1,4 @@
-use super::{font::*, text::*, theme::Theme};
+use super::{font::*, terminal::Terminal, text::*, theme::Theme};
 use crate::{
     io::{hid_mgr::*, screen::Screen},
     res::icon::IconManager,
@@ -6,7 +6,7 @@ use crate::{
     sync::RwLock,
     sync::{fifo::*, semaphore::*, spinlock::SpinMutex},
     task::scheduler::*,
-    user::userenv::UserEnv,
+    user::{screenshot::Screenshot, userenv::UserEnv},
     *,
 };
 use core::{
@@ -259,8 +259,11 @@ impl WindowManager<'_> {
                 while let Some(event) = shared.system_event.dequeue() {
                     match event {
                         WindowSystemEvent::Key(w, e) => {
-                            w.post(WindowMessage::Key(e)).unwrap();
+                            if !Terminal::handle_interrupt_key(w, e) {
+                                w.post(WindowMessage::Key(e)).unwrap();
+                            }
                         }
+                        WindowSystemEvent::Screenshot(w) => Screenshot::spawn(w),
                     }
                 }
             }
@@ -665,7 +668,7 @@ impl WindowManager<'_> {
         }
     }
 
-    fn window_at_point(point: Point) -> WindowHandle {
+    pub fn window_at_point(point: Point) -> WindowHandle {
         let shared = WindowManager::shared();
         let window_orders = shared.window_orders.read().unwrap();
         for handle in window_orders.iter().rev().skip(1) {
@@ -814,6 +817,16 @@ impl WindowManager<'_> {
         {
             // ctrl alt del
             UserEnv::system_reset(false);
+        } else if event.usage() == Usage::KEY_PRINT_SCREEN {
+            // Alt+PrintScreen captures the active window only
+            if !event.is_break() {
+                let window = event
+                    .modifier()
+                    .has_alt()
+                    .then(|| shared.active.get())
+                    .flatten();
+                let _ = Self::post_system_event(WindowSystemEvent::Screenshot(window));
+            }
         } else if let Some(window) = shared.active.get() {
             Self::post_system_event(WindowSystemEvent::Key(window, event)).unwrap();
         }
@@ -965,6 +978,43 @@ impl WindowManager<'_> {
         Self::while_hiding_pointer(|| shared.root.draw_into(bitmap, rect));
     }
 
+    /// Posts the message to all windows that have a message queue
+    pub fn broadcast(message: WindowMessage) {
+        let Some(shared) = Self::shared_opt() else {
+            return;
+        };
+        let windows = shared
+            .window_pool
+            .read()
+            .unwrap()
+            .keys()
+            .map(|v| *v)
+            .collect::<Vec<_>>();
+        for window in windows {
+            let _ = window.post(message);
+        }
+    }
+
+    /// Posts the message to all windows of the process
+    pub fn post_to_process(pid: ProcessId, message: WindowMessage) {
+        let Some(shared) = Self::shared_opt() else {
+            return;
+        };
+        let windows = shared
+            .window_pool
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|window| {
+                let window = unsafe { &*window.clone().as_ref().get() };
+                (window.pid == pid).then_some(window.handle)
+            })
+            .collect::<Vec<_>>();
+        for window in windows {
+            let _ = window.post(message);
+        }
+    }
+
     pub fn get_statistics(sb: &mut String) {
         let shared = Self::shared();
 
@@ -1219,6 +1269,10 @@ impl RawWindow {
     fn set_frame(&mut self, new_frame: Rect) {
         let old_frame = self.frame;
         if old_frame != new_frame {
+            if old_frame.size() != new_frame.size() {
+                let content_size = new_frame.bounds().insets_by(self.content_insets).size();
+                let _ = self.handle.post(WindowMessage::Resized(content_size));
+            }
             let old_frame = self.shadow_frame();
             self.frame = new_frame;
             if self.attributes.contains(WindowAttributes::VISIBLE) {
@@ -2624,6 +2678,10 @@ pub enum WindowMessage {
     MouseUp(MouseEvent),
     MouseEnter(MouseEvent),
     MouseLeave(MouseEvent),
+    /// The size of the content area has been changed
+    Resized(Size),
+    /// The data of the clipboard has been changed
+    ClipboardChanged,
     /// Timer event
     Timer(usize),
     /// User Defined
@@ -2635,6 +2693,8 @@ pub enum WindowMessage {
 pub enum WindowSystemEvent {
     /// Raw Keyboard event
     Key(WindowHandle, KeyEvent),
+    /// Captures the screen, or the window if specified
+    Screenshot(Option<WindowHandle>),
 }
 
 pub struct AnimatedProp {
@@ -0,0 +1,909 @@
+//! Text Editor
+
+use crate::{
+    fs::*,
+    io::{hid_mgr::*, tty::char_width},
+    task::scheduler::*,
+    ui::{clipboard::Clipboard, font::*, text::*, window::*},
+    *,
+};
+use alloc::{boxed::Box, format};
+use megstd::{
+    drawing::*,
+    io::{
+        hid::{MouseButton, Usage},
+        ErrorKind, Read, Write,
+    },
+    String, ToString, Vec,
+};
+
+const TAB_WIDTH: usize = 4;
+const DEFAULT_COLS: usize = 80;
+const DEFAULT_ROWS: usize = 25;
+const PADDING_LEFT: isize = 4;
+
+const FG_COLOR: Color = Color::BLACK;
+const BG_COLOR: Color = Color::WHITE;
+const SELECTION_COLOR: Color = Color::from_rgb(0xB3D7FF);
+const STATUS_FG_COLOR: Color = Color::DARK_GRAY;
+const STATUS_BG_COLOR: Color = Color::from_rgb(0xEEEEEE);
+
+/// Windowed text editor for UTF-8 files
+pub struct TextEditor {
+    window: WindowHandle,
+    font: FontDescriptor,
+    path: Option<String>,
+    buffer: TextBuffer,
+    /// Saves the lines with CR LF if the file was loaded so
+    is_crlf: bool,
+    cols: usize,
+    rows: usize,
+    /// The first line on the screen
+    top: usize,
+    /// The first display column on the screen
+    left: usize,
+    prompt: Option<Prompt>,
+    message: Option<String>,
+    find_text: String,
+    title: String,
+    is_dragging: bool,
+    /// Set by the first close request while there are unsaved changes
+    is_closing: bool,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+enum PromptKind {
+    Find,
+    Replace,
+    ReplaceWith(String),
+    SaveAs,
+}
+
+impl TextEditor {
+    /// Opens the file in a new editor process, or an empty document if the path is `None`
+    pub fn open(path: Option<&str>) -> Option<ProcessId> {
+        let path = Box::new(path.map(FileManager::canonical_path));
+        let arg = Box::into_raw(path) as usize;
+        let pid = SpawnOption::new().start_process(Self::_start, arg, "edit");
+        if pid.is_none() {
+            drop(unsafe { Box::from_raw(arg as *mut Option<String>) });
+        }
+        pid
+    }
+
+    fn _start(arg: usize) {
+        let path = unsafe { Box::from_raw(arg as *mut Option<String>) };
+        Self::new(*path).run();
+    }
+
+    fn new(path: Option<String>) -> Self {
+        let font =
+            FontDescriptor::new(FontFamily::Monospace, 14).unwrap_or(FontManager::monospace_font());
+
+        let mut message = None;
+        let mut text = String::new();
+        if let Some(path) = path.as_ref() {
+            match Self::read_file(path) {
+                Ok(blob) => {
+                    text = match String::from_utf8(blob) {
+                        Ok(v) => v,
+                        Err(err) => {
+                            message = Some("Invalid UTF-8 sequences are replaced".to_string());
+                            String::from_utf8_lossy(err.as_bytes()).into_owned()
+                        }
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {
+                    message = Some("New file".to_string());
+                }
+                Err(err) => message = Some(format!("Cannot open: {:?}", err.kind())),
+            }
+        }
+        let is_crlf = text.contains("\r\n");
+        if is_crlf {
+            text = text.replace("\r\n", "\n");
+        }
+
+        let cols = DEFAULT_COLS;
+        let rows = DEFAULT_ROWS;
+        let window = RawWindowBuilder::new()
+            .size(Size::new(
+                font.em_width() * cols as isize + PADDING_LEFT * 2,
+                font.line_height() * (rows + 1) as isize,
+            ))
+            .bg_color(BG_COLOR)
+            .build("Text Editor");
+
+        Self {
+            window,
+            font,
+            path,
+            buffer: TextBuffer::new(&text),
+            is_crlf,
+            cols,
+            rows,
+            top: 0,
+            left: 0,
+            prompt: None,
+            message,
+            find_text: String::new(),
+            title: String::new(),
+            is_dragging: false,
+            is_closing: false,
+        }
+    }
+
+    fn run(&mut self) {
+        self.refresh();
+        self.window.show();
+
+        while let Some(message) = self.window.wait_message() {
+            match message {
+                WindowMessage::Close => {
+                    if self.buffer.is_modified() && !self.is_closing {
+                        self.is_closing = true;
+                        self.message =
+                            Some("There are unsaved changes, close again to discard".to_string());
+                        self.refresh();
+                    } else {
+                        self.window.close();
+                    }
+                }
+                WindowMessage::Key(event) => {
+                    if !self.handle_key(event) {
+                        self.window.handle_default_message(message);
+                    }
+                }
+                WindowMessage::Char(c) => self.handle_char(c),
+                WindowMessage::MouseDown(event) => {
+                    if event.event_buttons().contains(MouseButton::PRIMARY) {
+                        let pos = self.pos_at(event.point());
+                        self.buffer.move_to(pos, false);
+                        self.is_dragging = true;
+                        self.refresh();
+                    }
+                }
+                WindowMessage::MouseMove(event) => {
+                    if self.is_dragging && event.buttons().contains(MouseButton::PRIMARY) {
+                        let pos = self.pos_at(event.point());
+                        self.buffer.move_to(pos, true);
+                        self.refresh();
+                    }
+                }
+                WindowMessage::MouseUp(_) => self.is_dragging = false,
+                _ => self.window.handle_default_message(message),
+            }
+        }
+    }
+
+    /// Handles the keys that have no characters and the shortcuts,
+    /// returns `false` if the key should be converted to a character.
+    fn handle_key(&mut self, event: KeyEvent) -> bool {
+        if event.is_break() || self.prompt.is_some() {
+            return false;
+        }
+        let modifier = event.modifier();
+        let shift = modifier.has_shift();
+        let page = self.rows as isize - 1;
+        if modifier.has_ctrl() {
+            match event.usage() {
+                Usage::KEY_A => self.buffer.select_all(),
+                Usage::KEY_C => self.copy(),
+                Usage::KEY_X => {
+                    self.copy();
+                    self.buffer.delete_selection();
+                }
+                Usage::KEY_V => {
+                    if let Some(text) = Clipboard::text() {
+                        self.buffer.insert(&text.replace("\r\n", "\n"));
+                    }
+                }
+                Usage::KEY_Z => self.buffer.undo(),
+                Usage::KEY_Y => self.buffer.redo(),
+                Usage::KEY_S if shift => self.start_prompt(PromptKind::SaveAs),
+                Usage::KEY_S => self.save(),
+                Usage::KEY_F => self.start_prompt(PromptKind::Find),
+                Usage::KEY_G => self.find_next(),
+                Usage::KEY_H => self.start_prompt(PromptKind::Replace),
+                Usage::KEY_HOME => self.buffer.move_to(TextPos::default(), shift),
+                Usage::KEY_END => self.buffer.move_to(self.buffer.end_pos(), shift),
+                _ => return false,
+            }
+        } else {
+            match event.usage() {
+                Usage::KEY_LEFT_ARROW => self.buffer.move_left(shift),
+                Usage::KEY_RIGHT_ARROW => self.buffer.move_right(shift),
+                Usage::KEY_UP_ARROW => self.buffer.move_vertical(-1, shift),
+                Usage::KEY_DOWN_ARROW => self.buffer.move_vertical(1, shift),
+                Usage::KEY_PAGE_UP => self.buffer.move_vertical(-page, shift),
+                Usage::KEY_PAGE_DOWN => self.buffer.move_vertical(page, shift),
+                Usage::KEY_HOME => self.buffer.move_home(shift),
+                Usage::KEY_END => self.buffer.move_end(shift),
+                Usage::DELETE => self.buffer.delete(),
+                Usage::KEY_F3 => self.find_next(),
+                _ => return false,
+            }
+        }
+        self.is_closing = false;
+        self.refresh();
+        true
+    }
+
+    fn handle_char(&mut self, c: char) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match c {
+                '\r' | '\n' => self.confirm_prompt(),
+                '\x08' => {
+                    prompt.input.pop();
+                }
+                '\x1B' => self.prompt = None,
+                _ if c.is_control() => return,
+                _ => prompt.input.push(c),
+            }
+            self.refresh();
+            return;
+        }
+        match c {
+            '\r' | '\n' => {
+                // Keeps the indentation of the current line
+                let line = self.buffer.line(self.buffer.cursor.line);
+                let mut text = String::from("\n");
+                text.extend(line.iter().take_while(|v| **v == ' ' || **v == '\t'));
+                self.buffer.insert(&text);
+            }
+            '\x08' => self.buffer.backspace(),
+            '\x7F' => self.buffer.delete(),
+            '\x1B' => self.buffer.move_to(self.buffer.cursor, false),
+            '\t' => self.buffer.insert("\t"),
+            _ if c.is_control() => return,
+            _ => {
+                let mut buf = [0; 4];
+                self.buffer.insert(c.encode_utf8(&mut buf));
+            }
+        }
+        self.is_closing = false;
+        self.refresh();
+    }
+
+    fn copy(&self) {
+        if let Some(text) = self.buffer.selected_text() {
+            Clipboard::set_text(&text);
+        }
+    }
+
+    fn start_prompt(&mut self, kind: PromptKind) {
+        let input = match kind {
+            PromptKind::Find | PromptKind::Replace => self
+                .buffer
+                .selected_text()
+                .filter(|v| !v.contains('\n'))
+                .unwrap_or_else(|| self.find_text.clone()),
+            PromptKind::ReplaceWith(_) => String::new(),
+            PromptKind::SaveAs => self.path.clone().unwrap_or_default(),
+        };
+        self.prompt = Some(Prompt { kind, input });
+    }
+
+    fn confirm_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::Find => {
+                self.find_text = prompt.input;
+                self.find_next();
+            }
+            PromptKind::Replace => {
+                if !prompt.input.is_empty() {
+                    self.find_text = prompt.input.clone();
+                    self.start_prompt(PromptKind::ReplaceWith(prompt.input));
+                }
+            }
+            PromptKind::ReplaceWith(find_text) => {
+                let count = self.buffer.replace_all(&find_text, &prompt.input);
+                self.message = Some(format!("{} replaced", count));
+            }
+            PromptKind::SaveAs => {
+                if !prompt.input.is_empty() {
+                    self.path = Some(FileManager::canonical_path(&prompt.input));
+                    self.save();
+                }
+            }
+        }
+    }
+
+    fn find_next(&mut self) {
+        if self.find_text.is_empty() {
+            return self.start_prompt(PromptKind::Find);
+        }
+        let from = self.buffer.cursor;
+        match self.buffer.find(&self.find_text, from) {
+            Some((start, end)) => {
+                self.buffer.move_to(start, false);
+                self.buffer.move_to(end, true);
+            }
+            None => self.message = Some(format!("Not found: {}", self.find_text)),
+        }
+    }
+
+    fn save(&mut self) {
+        let Some(path) = self.path.clone() else {
+            return self.start_prompt(PromptKind::SaveAs);
+        };
+        let mut text = self.buffer.text();
+        if self.is_crlf {
+            text = text.replace('\n', "\r\n");
+        }
+        match Self::write_file(&path, text.as_bytes()) {
+            Ok(_) => {
+                self.buffer.mark_saved();
+                self.message = Some(format!("Saved {} bytes", text.len()));
+            }
+            Err(err) => self.message = Some(format!("Cannot save: {:?}", err.kind())),
+        }
+    }
+
+    fn read_file(path: &str) -> megstd::io::Result<Vec<u8>> {
+        let mut file = FileManager::open(path, OpenOptions::new().read(true))?;
+        let mut vec = Vec::new();
+        file.read_to_end(&mut vec)?;
+        Ok(vec)
+    }
+
+    fn write_file(path: &str, bytes: &[u8]) -> megstd::io::Result<()> {
+        let mut file = FileManager::open(
+            path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )?;
+        let mut written = 0;
+        while written < bytes.len() {
+            match file.write(&bytes[written..])? {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                size => written += size,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the position of the text at the point in the window
+    fn pos_at(&self, point: Point) -> TextPos {
+        let line_height = self.font.line_height();
+        let em_width = self.font.em_width();
+        let line = (self.top as isize + point.y.div_euclid(line_height)).max(0) as usize;
+        let line = line.min(self.buffer.line_count() - 1);
+        let x = (point.x - PADDING_LEFT + em_width / 2).div_euclid(em_width);
+        let x = (self.left as isize + x).max(0) as usize;
+        TextPos::new(line, col_at_display(self.buffer.line(line), x))
+    }
+
+    /// Scrolls to the cursor, updates the title and redraws the window
+    fn refresh(&mut self) {
+        let cursor = self.buffer.cursor;
+        if cursor.line < self.top {
+            self.top = cursor.line;
+        } else if cursor.line >= self.top + self.rows {
+            self.top = cursor.line + 1 - self.rows;
+        }
+        let x = display_col(self.buffer.line(cursor.line), cursor.col);
+        if x < self.left {
+            self.left = x;
+        } else if x >= self.left + self.cols {
+            self.left = x + 1 - self.cols;
+        }
+
+        let name = self
+            .path
+            .as_ref()
+            .and_then(|v| v.rsplit('/').next())
+            .unwrap_or("Untitled");
+        let modified = if self.buffer.is_modified() { "*" } else { "" };
+        let title = format!("{}{} - Text Editor", name, modified);
+        if self.title != title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
+
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        let line_height = self.font.line_height();
+        let em_width = self.font.em_width();
+        let selection = self.buffer.selection();
+        let is_selected =
+            |pos: TextPos| selection.is_some_and(|(start, end)| start <= pos && pos < end);
+        let status = match self.prompt.as_ref() {
+            Some(prompt) => match prompt.kind {
+                PromptKind::Find => format!("Find: {}_", prompt.input),
+                PromptKind::Replace => format!("Replace: {}_", prompt.input),
+                PromptKind::ReplaceWith(ref find_text) => {
+                    format!("Replace {} with: {}_", find_text, prompt.input)
+                }
+                PromptKind::SaveAs => format!("Save as: {}_", prompt.input),
+            },
+            None => match self.message.take() {
+                Some(message) => message,
+                None => format!(
+                    "Ln {}, Col {}",
+                    self.buffer.cursor.line + 1,
+                    self.buffer.cursor.col + 1
+                ),
+            },
+        };
+
+        self.window.draw(|bitmap| {
+            bitmap.fill_rect(bitmap.bounds(), BG_COLOR);
+
+            let right = self.left + self.cols;
+            for row in 0..self.rows {
+                let index = self.top + row;
+                if index >= self.buffer.line_count() {
+                    break;
+                }
+                let line = self.buffer.line(index);
+                let y = row as isize * line_height;
+                let mut x = 0;
+                for (col, &c) in line.iter().enumerate() {
+                    let width = display_width(c, x);
+                    if x + width > right {
+                        break;
+                    }
+                    if x >= self.left {
+                        let rect = Rect::new(
+                            PADDING_LEFT + (x - self.left) as isize * em_width,
+                            y,
+                            width as isize * em_width,
+                            line_height,
+                        );
+                        if is_selected(TextPos::new(index, col)) {
+                            bitmap.fill_rect(rect, SELECTION_COLOR);
+                        }
+                        if !c.is_control() {
+                            self.font.draw_char(c, bitmap, rect.origin(), FG_COLOR);
+                        }
+                    }
+                    x += width;
+                }
+                // The selected line break
+                if x >= self.left && x < right && is_selected(TextPos::new(index, line.len())) {
+                    let rect = Rect::new(
+                        PADDING_LEFT + (x - self.left) as isize * em_width,
+                        y,
+                        em_width,
+                        line_height,
+                    );
+                    bitmap.fill_rect(rect, SELECTION_COLOR);
+                }
+            }
+
+            let cursor = self.buffer.cursor;
+            let x = display_col(self.buffer.line(cursor.line), cursor.col);
+            if self.prompt.is_none() && cursor.line >= self.top && x >= self.left {
+                let rect = Rect::new(
+                    PADDING_LEFT + (x - self.left) as isize * em_width,
+                    (cursor.line - self.top) as isize * line_height,
+                    2,
+                    line_height,
+                );
+                bitmap.fill_rect(rect, FG_COLOR);
+            }
+
+            let rect = Rect::new(
+                0,
+                self.rows as isize * line_height,
+                bitmap.width() as isize,
+                line_height,
+            );
+            bitmap.fill_rect(rect, STATUS_BG_COLOR);
+            AttributedString::new()
+                .font(&self.font)
+                .color(STATUS_FG_COLOR)
+                .middle_left()
+                .text(&status)
+                .draw_text(
+                    bitmap,
+                    rect.insets_by(EdgeInsets::new(0, PADDING_LEFT, 0, PADDING_LEFT)),
+                    1,
+                );
+        });
+    }
+}
+
+/// Position in the text, the column is counted in characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct TextPos {
+    line: usize,
+    col: usize,
+}
+
+impl TextPos {
+    #[inline]
+    const fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+}
+
+/// Change of the text that can be undone
+struct TextChange {
+    start: TextPos,
+    removed: String,
+    inserted: String,
+    /// The cursor before the change
+    cursor: TextPos,
+}
+
+/// Lines of the text with the cursor, the selection and the undo history
+struct TextBuffer {
+    lines: Vec<Vec<char>>,
+    cursor: TextPos,
+    /// The other end of the selection
+    anchor: Option<TextPos>,
+    /// Display column kept while moving up and down
+    goal_x: Option<usize>,
+    undo_stack: Vec<TextChange>,
+    redo_stack: Vec<TextChange>,
+    /// Length of the undo stack when saved, `None` if undo cannot go back to the saved text
+    saved_len: Option<usize>,
+}
+
+impl TextBuffer {
+    fn new(text: &str) -> Self {
+        Self {
+            lines: text.split('\n').map(|v| v.chars().collect()).collect(),
+            cursor: TextPos::default(),
+            anchor: None,
+            goal_x: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            saved_len: Some(0),
+        }
+    }
+
+    fn text(&self) -> String {
+        self.text_in(TextPos::default(), self.end_pos())
+    }
+
+    #[inline]
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[inline]
+    fn line(&self, index: usize) -> &[char] {
+        &self.lines[index]
+    }
+
+    fn end_pos(&self) -> TextPos {
+        let line = self.lines.len() - 1;
+        TextPos::new(line, self.lines[line].len())
+    }
+
+    #[inline]
+    fn is_modified(&self) -> bool {
+        self.saved_len != Some(self.undo_stack.len())
+    }
+
+    #[inline]
+    fn mark_saved(&mut self) {
+        self.saved_len = Some(self.undo_stack.len());
+    }
+
+    /// Returns the ordered range of the selection if it is not empty
+    fn selection(&self) -> Option<(TextPos, TextPos)> {
+        let anchor = self.anchor?;
+        if anchor < self.cursor {
+            Some((anchor, self.cursor))
+        } else if anchor > self.cursor {
+            Some((self.cursor, anchor))
+        } else {
+            None
+        }
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        self.selection()
+            .map(|(start, end)| self.text_in(start, end))
+    }
+
+    fn text_in(&self, start: TextPos, end: TextPos) -> String {
+        let mut text = String::new();
+        for index in start.line..=end.line {
+            let line = &self.lines[index];
+            let first = if index == start.line { start.col } else { 0 };
+            let last = if index == end.line {
+                end.col
+            } else {
+                line.len()
+            };
+            text.extend(&line[first..last]);
+            if index < end.line {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Returns the position after the text inserted at the position
+    fn pos_after(start: TextPos, text: &str) -> TextPos {
+        match text.rsplit_once('\n') {
+            Some((_, last)) => TextPos::new(
+                start.line + text.matches('\n').count(),
+                last.chars().count(),
+            ),
+            None => TextPos::new(start.line, start.col + text.chars().count()),
+        }
+    }
+
+    /// Replaces the range with the text without recording, returns the end of the inserted text
+    fn replace(&mut self, start: TextPos, end: TextPos, text: &str) -> TextPos {
+        let tail = self.lines[end.line].split_off(end.col);
+        self.lines[start.line].truncate(start.col);
+        self.lines.drain(start.line + 1..=end.line);
+
+        let mut segments = text.split('\n');
+        if let Some(first) = segments.next() {
+            self.lines[start.line].extend(first.chars());
+        }
+        let new_lines = segments.map(|v| v.chars().collect::<Vec<_>>());
+        self.lines.splice(start.line + 1..start.line + 1, new_lines);
+
+        let end = Self::pos_after(start, text);
+        self.lines[end.line].extend(tail);
+        end
+    }
+
+    /// Replaces the range with the text as a change that can be undone
+    fn edit(&mut self, start: TextPos, end: TextPos, text: &str) {
+        let removed = self.text_in(start, end);
+        if removed.is_empty() && text.is_empty() {
+            return;
+        }
+        let cursor = self.cursor;
+        self.cursor = self.replace(start, end, text);
+        self.anchor = None;
+        self.goal_x = None;
+
+        self.redo_stack.clear();
+        if self.saved_len.is_some_and(|v| v > self.undo_stack.len()) {
+            self.saved_len = None;
+        }
+        // Typing continues the last change unless the text has been saved in between
+        if removed.is_empty() && !text.contains('\n') && !self.is_saved_point() {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if last.removed.is_empty()
+                    && !last.inserted.contains('\n')
+                    && Self::pos_after(last.start, &last.inserted) == start
+                {
+                    last.inserted.push_str(text);
+                    return;
+                }
+            }
+        }
+        self.undo_stack.push(TextChange {
+            start,
+            removed,
+            inserted: text.to_string(),
+            cursor,
+        });
+    }
+
+    #[inline]
+    fn is_saved_point(&self) -> bool {
+        self.saved_len == Some(self.undo_stack.len())
+    }
+
+    fn undo(&mut self) {
+        let Some(change) = self.undo_stack.pop() else {
+            return;
+        };
+        let end = Self::pos_after(change.start, &change.inserted);
+        self.replace(change.start, end, &change.removed);
+        self.cursor = change.cursor;
+        self.anchor = None;
+        self.goal_x = None;
+        self.redo_stack.push(change);
+    }
+
+    fn redo(&mut self) {
+        let Some(change) = self.redo_stack.pop() else {
+            return;
+        };
+        let end = Self::pos_after(change.start, &change.removed);
+        self.cursor = self.replace(change.start, end, &change.inserted);
+        self.anchor = None;
+        self.goal_x = None;
+        self.undo_stack.push(change);
+    }
+
+    /// Replaces the selection or inserts at the cursor
+    fn insert(&mut self, text: &str) {
+        let (start, end) = self.selection().unwrap_or((self.cursor, self.cursor));
+        self.edit(start, end, text);
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.edit(start, end, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn backspace(&mut self) {
+        if !self.delete_selection() {
+            if let Some(prev) = self.prev_pos(self.cursor) {
+                self.edit(prev, self.cursor, "");
+            }
+        }
+    }
+
+    fn delete(&mut self) {
+        if !self.delete_selection() {
+            if let Some(next) = self.next_pos(self.cursor) {
+                self.edit(self.cursor, next, "");
+            }
+        }
+    }
+
+    fn prev_pos(&self, pos: TextPos) -> Option<TextPos> {
+        if pos.col > 0 {
+            Some(TextPos::new(pos.line, pos.col - 1))
+        } else if pos.line > 0 {
+            Some(TextPos::new(pos.line - 1, self.lines[pos.line - 1].len()))
+        } else {
+            None
+        }
+    }
+
+    fn next_pos(&self, pos: TextPos) -> Option<TextPos> {
+        if pos.col < self.lines[pos.line].len() {
+            Some(TextPos::new(pos.line, pos.col + 1))
+        } else if pos.line + 1 < self.lines.len() {
+            Some(TextPos::new(pos.line + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    /// Moves the cursor, extends the selection if `select` is `true`
+    fn move_to(&mut self, pos: TextPos, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = pos;
+        self.goal_x = None;
+    }
+
+    fn move_left(&mut self, select: bool) {
+        let pos = match self.selection() {
+            Some((start, _)) if !select => start,
+            _ => self.prev_pos(self.cursor).unwrap_or(self.cursor),
+        };
+        self.move_to(pos, select);
+    }
+
+    fn move_right(&mut self, select: bool) {
+        let pos = match self.selection() {
+            Some((_, end)) if !select => end,
+            _ => self.next_pos(self.cursor).unwrap_or(self.cursor),
+        };
+        self.move_to(pos, select);
+    }
+
+    fn move_vertical(&mut self, delta: isize, select: bool) {
+        let goal_x = self
+            .goal_x
+            .unwrap_or_else(|| display_col(&self.lines[self.cursor.line], self.cursor.col));
+        let line = (self.cursor.line as isize + delta).clamp(0, self.lines.len() as isize - 1);
+        let line = line as usize;
+        self.move_to(
+            TextPos::new(line, col_at_display(&self.lines[line], goal_x)),
+            select,
+        );
+        self.goal_x = Some(goal_x);
+    }
+
+    fn move_home(&mut self, select: bool) {
+        self.move_to(TextPos::new(self.cursor.line, 0), select);
+    }
+
+    fn move_end(&mut self, select: bool) {
+        let line = self.cursor.line;
+        self.move_to(TextPos::new(line, self.lines[line].len()), select);
+    }
+
+    fn select_all(&mut self) {
+        self.move_to(TextPos::default(), false);
+        self.move_to(self.end_pos(), true);
+    }
+
+    /// Finds the text after the position, wraps around at the end
+    fn find(&self, text: &str, from: TextPos) -> Option<(TextPos, TextPos)> {
+        let pattern = text.chars().collect::<Vec<_>>();
+        if pattern.is_empty() || pattern.contains(&'\n') {
+            return None;
+        }
+        let count = self.lines.len();
+        (0..=count).find_map(|index| {
+            let line = (from.line + index) % count;
+            let start = if index == 0 { from.col } else { 0 };
+            find_chars(&self.lines[line], &pattern, start).map(|col| {
+                (
+                    TextPos::new(line, col),
+                    TextPos::new(line, col + pattern.len()),
+                )
+            })
+        })
+    }
+
+    /// Replaces all occurrences of the text as one change, returns the number of them
+    fn replace_all(&mut self, text: &str, with: &str) -> usize {
+        let pattern = text.chars().collect::<Vec<_>>();
+        if pattern.is_empty() || pattern.contains(&'\n') {
+            return 0;
+        }
+        let mut count = 0;
+        let mut result = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                result.push('\n');
+            }
+            let mut col = 0;
+            while let Some(found) = find_chars(line, &pattern, col) {
+                result.extend(&line[col..found]);
+                result.push_str(with);
+                col = found + pattern.len();
+                count += 1;
+            }
+            result.extend(&line[col..]);
+        }
+        if count > 0 {
+            let cursor = self.cursor;
+            self.edit(TextPos::default(), self.end_pos(), &result);
+            let line = cursor.line.min(self.lines.len() - 1);
+            let col = cursor.col.min(self.lines[line].len());
+            self.move_to(TextPos::new(line, col), false);
+        }
+        count
+    }
+}
+
+fn find_chars(line: &[char], pattern: &[char], from: usize) -> Option<usize> {
+    line.get(from..)?
+        .windows(pattern.len())
+        .position(|v| v == pattern)
+        .map(|v| v + from)
+}
+
+/// Returns the width of the character at the display column
+fn display_width(c: char, x: usize) -> usize {
+    match c {
+        '\t' => TAB_WIDTH - x % TAB_WIDTH,
+        _ => char_width(c).max(1),
+    }
+}
+
+/// Returns the display column of the position in the line
+fn display_col(line: &[char], col: usize) -> usize {
+    line[..col].iter().fold(0, |x, &c| x + display_width(c, x))
+}
+
+/// Returns the position of the character at the display column in the line
+fn col_at_display(line: &[char], target: usize) -> usize {
+    let mut x = 0;
+    for (col, &c) in line.iter().enumerate() {
+        x += display_width(c, x);
+        if x > target {
+            return col;
+        }
+    }
+    line.len()
+}
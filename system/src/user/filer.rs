@@ -0,0 +1,617 @@
+//! File Manager Window
+
+use super::launcher::Launcher;
+use crate::{
+    fs::*,
+    io::hid_mgr::*,
+    r,
+    res::icon::IconManager,
+    rt::*,
+    sync::Mutex,
+    task::scheduler::*,
+    ui::{font::*, text::*, window::*},
+    *,
+};
+use alloc::{boxed::Box, format};
+use core::time::Duration;
+use megstd::{
+    drawing::*,
+    io::{
+        hid::{MouseButton, Usage},
+        ErrorKind,
+    },
+    BTreeMap, String, ToString, Vec,
+};
+
+const ROW_HEIGHT: isize = 28;
+const DEFAULT_ROWS: usize = 14;
+const WINDOW_WIDTH: isize = 480;
+const PADDING: isize = 4;
+const ICON_SIZE: isize = 24;
+const DOUBLE_CLICK: Duration = Duration::from_millis(500);
+/// Distance of the pointer to start dragging the item
+const DRAG_THRESHOLD: isize = 4;
+/// `WindowMessage::User` to reload the directory
+const USER_RELOAD: usize = 1;
+
+const FG_COLOR: Color = Color::BLACK;
+const BG_COLOR: Color = Color::WHITE;
+const SELECTION_COLOR: Color = Color::from_rgb(0xB3D7FF);
+const DROP_TARGET_COLOR: Color = Color::from_rgb(0xFFE0B2);
+const BAR_FG_COLOR: Color = Color::DARK_GRAY;
+const BAR_BG_COLOR: Color = Color::from_rgb(0xEEEEEE);
+
+/// Current directories of the file manager windows, where the items are dropped
+static DROP_TARGETS: Mutex<BTreeMap<WindowHandle, String>> = Mutex::new(BTreeMap::new());
+
+/// File manager window to browse and organize the files
+pub struct Filer {
+    window: WindowHandle,
+    font: FontDescriptor,
+    folder_icon: Option<OperationalBitmap>,
+    app_icon: Option<OperationalBitmap>,
+    path: String,
+    entries: Vec<Entry>,
+    selected: Option<usize>,
+    /// The first entry on the screen
+    top: usize,
+    rows: usize,
+    prompt: Option<Prompt>,
+    message: Option<String>,
+    /// The entry and the time of the last click to detect the double click
+    last_click: Option<(usize, Duration)>,
+    drag: Option<Drag>,
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    len: u64,
+}
+
+struct Drag {
+    index: usize,
+    origin: Point,
+    is_moved: bool,
+    /// The directory entry under the pointer in this window
+    target: Option<usize>,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+enum PromptKind {
+    Rename(String),
+    Delete(String),
+    NewFolder,
+}
+
+impl Filer {
+    /// Opens the directory in a new file manager process
+    pub fn open(path: &str) -> Option<ProcessId> {
+        let path = Box::new(FileManager::canonical_path(path));
+        let arg = Box::into_raw(path) as usize;
+        let pid = SpawnOption::new().start_process(Self::_start, arg, "files");
+        if pid.is_none() {
+            drop(unsafe { Box::from_raw(arg as *mut String) });
+        }
+        pid
+    }
+
+    fn _start(arg: usize) {
+        let path = unsafe { Box::from_raw(arg as *mut String) };
+        Self::new(*path).run();
+    }
+
+    fn new(path: String) -> Self {
+        let font = FontManager::ui_font();
+        let rows = DEFAULT_ROWS;
+        let window = RawWindowBuilder::new()
+            .size(Size::new(WINDOW_WIDTH, ROW_HEIGHT * (rows + 2) as isize))
+            .bg_color(BG_COLOR)
+            .build("Files");
+
+        let mut filer = Self {
+            window,
+            font,
+            folder_icon: IconManager::mask(r::Icons::Folder),
+            app_icon: IconManager::mask(r::Icons::Apps),
+            path,
+            entries: Vec::new(),
+            selected: None,
+            top: 0,
+            rows,
+            prompt: None,
+            message: None,
+            last_click: None,
+            drag: None,
+        };
+        filer.reload();
+        filer
+    }
+
+    fn run(&mut self) {
+        self.refresh();
+        self.window.show();
+
+        while let Some(message) = self.window.wait_message() {
+            match message {
+                WindowMessage::Close => self.window.close(),
+                WindowMessage::Key(event) => {
+                    if !self.handle_key(event) {
+                        self.window.handle_default_message(message);
+                    }
+                }
+                WindowMessage::Char(c) => self.handle_char(c),
+                WindowMessage::MouseDown(event) => self.mouse_down(event),
+                WindowMessage::MouseMove(event) => self.mouse_move(event),
+                WindowMessage::MouseUp(event) => self.mouse_up(event),
+                WindowMessage::User(USER_RELOAD) => {
+                    self.reload();
+                    self.refresh();
+                }
+                _ => self.window.handle_default_message(message),
+            }
+        }
+
+        DROP_TARGETS.lock().unwrap().remove(&self.window);
+    }
+
+    /// Reads the current directory again, keeping the selected name
+    fn reload(&mut self) {
+        let selected_name = self
+            .selected
+            .and_then(|v| self.entries.get(v))
+            .map(|v| v.name.clone());
+        self.load(selected_name);
+    }
+
+    /// Reads the current directory and selects the name
+    fn load(&mut self, selected_name: Option<String>) {
+        self.entries.clear();
+        match FileManager::read_dir(&self.path) {
+            Ok(read_dir) => {
+                for entry in read_dir {
+                    let name = entry.name();
+                    if name == "." || name == ".." {
+                        continue;
+                    }
+                    self.entries.push(Entry {
+                        name: name.to_string(),
+                        is_dir: entry.metadata().file_type().is_dir(),
+                        len: entry.metadata().len() as u64,
+                    });
+                }
+            }
+            Err(err) => self.message = Some(format!("{}: {:?}", self.path, err.kind())),
+        }
+        self.entries
+            .sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        if self.path != "/" {
+            self.entries.insert(
+                0,
+                Entry {
+                    name: "..".to_string(),
+                    is_dir: true,
+                    len: 0,
+                },
+            );
+        }
+
+        self.selected = selected_name
+            .and_then(|name| self.entries.iter().position(|v| v.name == name))
+            .or_else(|| (!self.entries.is_empty()).then_some(0));
+        self.window.set_title(&self.path);
+        DROP_TARGETS
+            .lock()
+            .unwrap()
+            .insert(self.window, self.path.clone());
+    }
+
+    fn change_dir(&mut self, path: String) {
+        let old_path = core::mem::replace(&mut self.path, path);
+        self.top = 0;
+        // Selects the directory that was left when going up
+        let name = old_path
+            .strip_prefix(&self.child_path(""))
+            .filter(|v| !v.contains('/'))
+            .map(|v| v.to_string());
+        self.load(name);
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        match name {
+            ".." => FileManager::canonical_path(&format!("{}/..", self.path)),
+            _ if self.path == "/" => format!("/{}", name),
+            _ => format!("{}/{}", self.path, name),
+        }
+    }
+
+    fn open_entry(&mut self, index: usize) {
+        let Some(entry) = self.entries.get(index) else {
+            return;
+        };
+        let path = self.child_path(&entry.name);
+        if entry.is_dir {
+            self.change_dir(path);
+        } else if let Err(err) = Launcher::open(&path) {
+            self.message = Some(format!("Cannot open {}: {:?}", entry.name, err.kind()));
+        }
+    }
+
+    /// Returns the selected entry except `..`
+    fn selected_name(&self) -> Option<String> {
+        self.selected
+            .and_then(|v| self.entries.get(v))
+            .filter(|v| v.name != "..")
+            .map(|v| v.name.clone())
+    }
+
+    fn handle_key(&mut self, event: KeyEvent) -> bool {
+        if event.is_break() || self.prompt.is_some() {
+            return false;
+        }
+        let modifier = event.modifier();
+        let page = self.rows as isize - 1;
+        match event.usage() {
+            Usage::KEY_UP_ARROW if modifier.has_alt() => self.go_up(),
+            Usage::KEY_UP_ARROW => self.move_selection(-1),
+            Usage::KEY_DOWN_ARROW => self.move_selection(1),
+            Usage::KEY_PAGE_UP => self.move_selection(-page),
+            Usage::KEY_PAGE_DOWN => self.move_selection(page),
+            Usage::KEY_HOME => self.move_selection(isize::MIN),
+            Usage::KEY_END => self.move_selection(isize::MAX),
+            Usage::KEY_F2 => {
+                if let Some(name) = self.selected_name() {
+                    self.prompt = Some(Prompt {
+                        input: name.clone(),
+                        kind: PromptKind::Rename(name),
+                    });
+                }
+            }
+            Usage::KEY_F5 => self.reload(),
+            Usage::DELETE => {
+                if let Some(name) = self.selected_name() {
+                    self.prompt = Some(Prompt {
+                        input: String::new(),
+                        kind: PromptKind::Delete(name),
+                    });
+                }
+            }
+            Usage::KEY_N if modifier.has_ctrl() && modifier.has_shift() => {
+                self.prompt = Some(Prompt {
+                    input: String::new(),
+                    kind: PromptKind::NewFolder,
+                });
+            }
+            Usage::KEY_N if modifier.has_ctrl() => {
+                Self::open(&self.path);
+            }
+            _ => return false,
+        }
+        self.refresh();
+        true
+    }
+
+    fn handle_char(&mut self, c: char) {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match c {
+                '\r' | '\n' => self.confirm_prompt(),
+                '\x08' => {
+                    prompt.input.pop();
+                }
+                '\x1B' => self.prompt = None,
+                _ if c.is_control() => return,
+                _ => prompt.input.push(c),
+            }
+            self.refresh();
+            return;
+        }
+        match c {
+            '\r' | '\n' => {
+                if let Some(index) = self.selected {
+                    self.open_entry(index);
+                }
+            }
+            '\x08' => self.go_up(),
+            _ => return,
+        }
+        self.refresh();
+    }
+
+    fn go_up(&mut self) {
+        if self.path != "/" {
+            self.change_dir(self.child_path(".."));
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let index = self.selected.unwrap_or(0) as isize;
+        let index = index
+            .saturating_add(delta)
+            .clamp(0, self.entries.len() as isize - 1);
+        self.selected = Some(index as usize);
+    }
+
+    fn confirm_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        let result = match prompt.kind {
+            PromptKind::Rename(name) => {
+                if prompt.input.is_empty() || prompt.input.contains('/') {
+                    return;
+                }
+                let new_path = self.child_path(&prompt.input);
+                if FileManager::stat(&new_path).is_ok() {
+                    Err(ErrorKind::AlreadyExists.into())
+                } else {
+                    FileManager::rename(&self.child_path(&name), &new_path)
+                        .map(|_| self.load(Some(prompt.input)))
+                }
+            }
+            PromptKind::Delete(name) => {
+                if !prompt.input.eq_ignore_ascii_case("y") {
+                    return;
+                }
+                let path = self.child_path(&name);
+                match FileManager::stat(&path) {
+                    Ok(stat) if stat.file_type().is_dir() => FileManager::rmdir(&path),
+                    Ok(_) => FileManager::unlink(&path),
+                    Err(err) => Err(err),
+                }
+            }
+            PromptKind::NewFolder => {
+                if prompt.input.is_empty() || prompt.input.contains('/') {
+                    return;
+                }
+                FileManager::mkdir(&self.child_path(&prompt.input))
+                    .map(|_| self.load(Some(prompt.input)))
+            }
+        };
+        match result {
+            Ok(_) => self.reload(),
+            Err(err) => self.message = Some(format!("Error: {:?}", err.kind())),
+        }
+    }
+
+    /// Returns the index of the entry at the point in the window
+    fn entry_at(&self, point: Point) -> Option<usize> {
+        let y = point.y - ROW_HEIGHT;
+        if y < 0 || y >= ROW_HEIGHT * self.rows as isize {
+            return None;
+        }
+        let index = self.top + (y / ROW_HEIGHT) as usize;
+        (index < self.entries.len()).then_some(index)
+    }
+
+    fn mouse_down(&mut self, event: MouseEvent) {
+        if !event.event_buttons().contains(MouseButton::PRIMARY) {
+            return;
+        }
+        let Some(index) = self.entry_at(event.point()) else {
+            return;
+        };
+        let now = Timer::monotonic();
+        let is_double_click = self.last_click.is_some_and(|(last_index, last_time)| {
+            last_index == index && now - last_time < DOUBLE_CLICK
+        });
+        self.selected = Some(index);
+        if is_double_click {
+            self.last_click = None;
+            self.open_entry(index);
+        } else {
+            self.last_click = Some((index, now));
+            self.drag = Some(Drag {
+                index,
+                origin: event.point(),
+                is_moved: false,
+                target: None,
+            });
+        }
+        self.refresh();
+    }
+
+    fn mouse_move(&mut self, event: MouseEvent) {
+        let point = event.point();
+        let target = self.entry_at(point).filter(|&v| {
+            self.entries[v].is_dir && self.drag.as_ref().is_some_and(|d| d.index != v)
+        });
+        let Some(drag) = self.drag.as_mut() else {
+            return;
+        };
+        if !drag.is_moved {
+            if (point.x - drag.origin.x).abs() < DRAG_THRESHOLD
+                && (point.y - drag.origin.y).abs() < DRAG_THRESHOLD
+            {
+                return;
+            }
+            if self.entries[drag.index].name == ".." {
+                self.drag = None;
+                return;
+            }
+            drag.is_moved = true;
+        }
+        if drag.target != target {
+            drag.target = target;
+            self.refresh();
+        }
+    }
+
+    fn mouse_up(&mut self, event: MouseEvent) {
+        let Some(drag) = self.drag.take() else {
+            return;
+        };
+        if !drag.is_moved {
+            return;
+        }
+        let name = self.entries[drag.index].name.clone();
+        let source = self.child_path(&name);
+
+        // Drops into the directory in this window or into another file manager window
+        let mut target_window = None;
+        let target_dir = match drag.target {
+            Some(index) => Some(self.child_path(&self.entries[index].name)),
+            None => {
+                let origin = self
+                    .window
+                    .frame()
+                    .insets_by(self.window.content_insets())
+                    .origin();
+                let point = event.point();
+                let target = WindowManager::window_at_point(Point::new(
+                    origin.x + point.x,
+                    origin.y + point.y,
+                ));
+                if target != self.window {
+                    target_window = Some(target);
+                    DROP_TARGETS.lock().unwrap().get(&target).cloned()
+                } else {
+                    None
+                }
+            }
+        };
+        if let Some(target_dir) = target_dir {
+            match FileManager::rename(&source, &target_dir) {
+                Ok(_) => {
+                    if let Some(target) = target_window {
+                        let _ = target.post(WindowMessage::User(USER_RELOAD));
+                    }
+                }
+                Err(err) => self.message = Some(format!("Cannot move {}: {:?}", name, err.kind())),
+            }
+            self.reload();
+        }
+        self.refresh();
+    }
+
+    /// Scrolls to the selected entry and redraws the window
+    fn refresh(&mut self) {
+        if let Some(selected) = self.selected {
+            if selected < self.top {
+                self.top = selected;
+            } else if selected >= self.top + self.rows {
+                self.top = selected + 1 - self.rows;
+            }
+        }
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        let status = match self.prompt.as_ref() {
+            Some(prompt) => match prompt.kind {
+                PromptKind::Rename(ref name) => format!("Rename {} to: {}_", name, prompt.input),
+                PromptKind::Delete(ref name) => {
+                    format!("Delete {}? (y/n): {}_", name, prompt.input)
+                }
+                PromptKind::NewFolder => format!("New folder: {}_", prompt.input),
+            },
+            None => match self.message.take() {
+                Some(message) => message,
+                None => format!("{} items", self.entries.len()),
+            },
+        };
+        let drop_target = self.drag.as_ref().and_then(|v| v.target);
+
+        self.window.draw(|bitmap| {
+            bitmap.fill_rect(bitmap.bounds(), BG_COLOR);
+            let width = bitmap.width() as isize;
+
+            let rect = Rect::new(0, 0, width, ROW_HEIGHT);
+            bitmap.fill_rect(rect, BAR_BG_COLOR);
+            self.draw_label(bitmap, rect, &self.path, BAR_FG_COLOR, false);
+
+            for row in 0..self.rows {
+                let index = self.top + row;
+                let Some(entry) = self.entries.get(index) else {
+                    break;
+                };
+                let rect = Rect::new(0, ROW_HEIGHT * (row as isize + 1), width, ROW_HEIGHT);
+                if drop_target == Some(index) {
+                    bitmap.fill_rect(rect, DROP_TARGET_COLOR);
+                } else if self.selected == Some(index) {
+                    bitmap.fill_rect(rect, SELECTION_COLOR);
+                }
+
+                let icon_rect = Rect::new(
+                    PADDING,
+                    rect.min_y() + (ROW_HEIGHT - ICON_SIZE) / 2,
+                    ICON_SIZE,
+                    ICON_SIZE,
+                );
+                let is_app = entry
+                    .name
+                    .rsplit_once('.')
+                    .map(|(_, ext)| RuntimeEnvironment::supported_extensions().any(|v| v == ext))
+                    .unwrap_or(false);
+                let icon = if entry.is_dir {
+                    self.folder_icon.as_ref()
+                } else if is_app {
+                    self.app_icon.as_ref()
+                } else {
+                    None
+                };
+                match icon {
+                    Some(icon) => icon.draw_to(bitmap, icon_rect.origin(), icon.bounds(), FG_COLOR),
+                    None => {
+                        // Page of the document
+                        let page = icon_rect.insets_by(EdgeInsets::new(3, 5, 3, 5));
+                        bitmap.draw_rect(page, FG_COLOR);
+                    }
+                }
+
+                let rect = rect.insets_by(EdgeInsets::new(0, PADDING * 2 + ICON_SIZE, 0, PADDING));
+                self.draw_label(bitmap, rect, &entry.name, FG_COLOR, false);
+                if !entry.is_dir {
+                    self.draw_label(bitmap, rect, &format_size(entry.len), BAR_FG_COLOR, true);
+                }
+            }
+
+            let rect = Rect::new(0, ROW_HEIGHT * (self.rows as isize + 1), width, ROW_HEIGHT);
+            bitmap.fill_rect(rect, BAR_BG_COLOR);
+            self.draw_label(bitmap, rect, &status, BAR_FG_COLOR, false);
+        });
+    }
+
+    fn draw_label(
+        &self,
+        bitmap: &mut BitmapRefMut,
+        rect: Rect,
+        text: &str,
+        color: Color,
+        align_right: bool,
+    ) {
+        let ats = AttributedString::new().font(&self.font).color(color);
+        let ats = if align_right {
+            ats.middle_right()
+        } else {
+            ats.middle_left()
+        };
+        ats.text(text).draw_text(
+            bitmap,
+            rect.insets_by(EdgeInsets::new(0, PADDING, 0, PADDING)),
+            1,
+        );
+    }
+}
+
+fn format_size(len: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if len < 1024 {
+        return format!("{} bytes", len);
+    }
+    let mut value = len;
+    let mut unit = 0;
+    while value >= 1024 * 1024 && unit + 1 < UNITS.len() {
+        value >>= 10;
+        unit += 1;
+    }
+    // One decimal place
+    let tenths = (value * 10 + 512) >> 10;
+    format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+}
@@ -0,0 +1,209 @@
+//! File Type Associations
+
+use super::{
+    editor::TextEditor,
+    filer::Filer,
+    viewer::{ImageViewer, IMAGE_EXTENSIONS},
+};
+use crate::{fs::*, rt::*, task::scheduler::*, *};
+use alloc::borrow::ToOwned;
+use megstd::{
+    io::{ErrorKind, Read, Result},
+    String, ToString, Vec,
+};
+
+/// Extensions of the text files opened by the editor without the configuration
+const DEFAULT_TEXT_EXTENSIONS: [&str; 10] = [
+    "txt", "md", "sh", "log", "cfg", "ini", "json", "toml", "rs", "c",
+];
+
+/// Size of the head of the file to test the magic bytes
+const HEAD_SIZE: usize = 512;
+
+/// Application that opens the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Handler {
+    /// Runs the file itself with the runtime that recognizes it
+    Runtime,
+    /// Text editor
+    TextEditor,
+    /// File manager for the directories
+    Filer,
+    /// Image viewer
+    ImageViewer,
+    /// Application that takes the path of the file as the argument
+    App(String),
+}
+
+impl Handler {
+    fn parse(name: &str) -> Self {
+        match name {
+            "run" => Self::Runtime,
+            "edit" => Self::TextEditor,
+            "files" => Self::Filer,
+            "image" => Self::ImageViewer,
+            _ => Self::App(name.to_owned()),
+        }
+    }
+}
+
+enum Pattern {
+    Extension(String),
+    Magic(Vec<u8>),
+}
+
+impl Pattern {
+    /// Parses `.ext` or `magic:` followed by the hexadecimal bytes
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(ext) = s.strip_prefix('.') {
+            return (!ext.is_empty()).then(|| Self::Extension(ext.to_ascii_lowercase()));
+        }
+        let hex = s.strip_prefix("magic:")?;
+        if hex.is_empty() || hex.len() % 2 != 0 {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|v| u8::from_str_radix(hex.get(v..v + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()
+            .map(Self::Magic)
+    }
+}
+
+/// Registry of the file type associations used to open the files
+///
+/// The rules are read from [`Launcher::CONFIG_PATH`] on each lookup, one rule per line:
+///
+/// ```text
+/// # pattern       handler
+/// .txt            edit
+/// magic:52494646  /apps/player.wasm
+/// ```
+///
+/// The pattern is the extension or the magic bytes at the beginning of the file, and the handler is
+/// `run` (the binary runtimes), `edit` (the text editor), `files` (the file manager),
+/// `image` (the image viewer) or the path of the application.
+/// The magic bytes take precedence over the extensions.
+pub struct Launcher;
+
+impl Launcher {
+    pub const CONFIG_PATH: &'static str = "/etc/assoc";
+
+    /// Returns the handler of the file, `None` if the file does not exist or is not supported
+    pub fn handler_for(path: &str) -> Option<Handler> {
+        let stat = FileManager::stat(path).ok()?;
+        if stat.file_type().is_dir() {
+            return Some(Handler::Filer);
+        }
+        let head = Self::read_head(path).ok()?;
+        let ext = path
+            .rsplit_once('/')
+            .map_or(path, |(_, name)| name)
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+
+        let rules = Self::rules();
+        rules
+            .iter()
+            .find(
+                |(pattern, _)| matches!(pattern, Pattern::Magic(magic) if head.starts_with(magic)),
+            )
+            .or_else(|| {
+                rules.iter().find(|(pattern, _)| {
+                    matches!(pattern, Pattern::Extension(v) if Some(v) == ext.as_ref())
+                })
+            })
+            .map(|(_, handler)| handler.clone())
+            .or_else(|| {
+                // Text files without the association, the last character may be cut off
+                let is_text = match core::str::from_utf8(&head) {
+                    Ok(_) => true,
+                    Err(err) => err.error_len().is_none(),
+                };
+                (is_text && !head.contains(&0)).then_some(Handler::TextEditor)
+            })
+    }
+
+    /// Opens the file with the associated application
+    pub fn open(path: &str) -> Result<ProcessId> {
+        let handler = Self::handler_for(path).ok_or(ErrorKind::Unsupported)?;
+        Self::launch(&handler, path)
+    }
+
+    /// Opens the file with the handler
+    pub fn launch(handler: &Handler, path: &str) -> Result<ProcessId> {
+        let pid = match handler {
+            Handler::Runtime => return Self::start_app(path, &[path]),
+            Handler::App(app) => return Self::start_app(app, &[app.as_str(), path]),
+            Handler::TextEditor => TextEditor::open(Some(path)),
+            Handler::Filer => Filer::open(path),
+            Handler::ImageViewer => ImageViewer::open(path),
+        };
+        pid.ok_or(ErrorKind::Other.into())
+    }
+
+    /// Loads the application and starts it with the arguments
+    pub fn start_app(path: &str, argv: &[&str]) -> Result<ProcessId> {
+        let mut file = FileManager::open(path, OpenOptions::new().read(true))?;
+        let mut blob = Vec::new();
+        file.read_to_end(&mut blob)?;
+        if blob.is_empty() {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        let mut loader = RuntimeEnvironment::recognize(&blob).ok_or(ErrorKind::InvalidData)?;
+        loader.option().name = path.to_string();
+        loader.option().argv = argv.iter().map(|v| v.to_string()).collect();
+        loader
+            .load(&blob)
+            .map_err(|_| megstd::io::Error::from(ErrorKind::InvalidData))?;
+        loader.invoke_start().ok_or(ErrorKind::Other.into())
+    }
+
+    /// Returns the rules of the configuration file followed by the default rules
+    fn rules() -> Vec<(Pattern, Handler)> {
+        let mut rules = Vec::new();
+        if let Ok(mut file) = FileManager::open(Self::CONFIG_PATH, OpenOptions::new().read(true)) {
+            let mut blob = Vec::new();
+            if file.read_to_end(&mut blob).is_ok() {
+                for line in String::from_utf8_lossy(&blob).lines() {
+                    let mut words = line.split_whitespace();
+                    let (Some(pattern), Some(handler)) = (words.next(), words.next()) else {
+                        continue;
+                    };
+                    if pattern.starts_with('#') {
+                        continue;
+                    }
+                    if let Some(pattern) = Pattern::parse(pattern) {
+                        rules.push((pattern, Handler::parse(handler)));
+                    }
+                }
+            }
+        }
+
+        for ext in RuntimeEnvironment::supported_extensions() {
+            rules.push((Pattern::Extension(ext.clone()), Handler::Runtime));
+        }
+        for ext in DEFAULT_TEXT_EXTENSIONS {
+            rules.push((Pattern::Extension(ext.to_string()), Handler::TextEditor));
+        }
+        for ext in IMAGE_EXTENSIONS {
+            rules.push((Pattern::Extension(ext.to_string()), Handler::ImageViewer));
+        }
+        rules
+    }
+
+    fn read_head(path: &str) -> Result<Vec<u8>> {
+        let mut file = FileManager::open(path, OpenOptions::new().read(true))?;
+        let mut head = Vec::new();
+        head.resize(HEAD_SIZE, 0);
+        let mut len = 0;
+        while len < HEAD_SIZE {
+            match file.read(&mut head[len..])? {
+                0 => break,
+                size => len += size,
+            }
+        }
+        head.truncate(len);
+        Ok(head)
+    }
+}
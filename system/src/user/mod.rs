@@ -1 +1,6 @@
+pub mod editor;
+pub mod filer;
+pub mod launcher;
+pub mod screenshot;
 pub mod userenv;
+pub mod viewer;
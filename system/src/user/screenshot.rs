@@ -0,0 +1,99 @@
+//! Screen Capture
+
+use crate::{fs::*, task::scheduler::*, ui::window::*, *};
+use alloc::format;
+use megstd::{
+    drawing::*,
+    io::{ErrorKind, Result, Write},
+    String,
+};
+
+/// Captures the screen or the window and saves it as an image file
+pub struct Screenshot;
+
+impl Screenshot {
+    /// Directory where the captured images are saved
+    pub const DIR: &'static str = "/home";
+    const FILE_PREFIX: &'static str = "screenshot";
+
+    /// Captures the whole screen
+    pub fn capture_screen() -> OwnedBitmap32 {
+        let bounds = WindowManager::main_screen_bounds();
+        let mut bitmap = OwnedBitmap32::new(bounds.size(), TrueColor::TRANSPARENT);
+        WindowManager::save_screen_to(bitmap.as_mut(), bounds);
+        Self::make_opaque(&mut bitmap);
+        bitmap
+    }
+
+    /// Captures the frame of the window, including the windows in front of it
+    pub fn capture_window(window: WindowHandle) -> Option<OwnedBitmap32> {
+        let window = window.is_valid()?;
+        let frame = window.frame();
+        let bounds = WindowManager::main_screen_bounds();
+        let mut screen = OwnedBitmap32::new(bounds.size(), TrueColor::TRANSPARENT);
+        WindowManager::while_hiding_pointer(|| {
+            window.draw_into(screen.as_mut(), frame.size().into())
+        });
+
+        let mut bitmap = OwnedBitmap32::new(frame.size(), TrueColor::TRANSPARENT);
+        bitmap
+            .as_mut()
+            .blt(screen.as_ref(), Point::default(), frame);
+        Self::make_opaque(&mut bitmap);
+        Some(bitmap)
+    }
+
+    /// Saves the image as a new file in [`Screenshot::DIR`] and returns the path
+    pub fn save(bitmap: &OwnedBitmap32, format: ImageFormat) -> Result<String> {
+        let path = (1..)
+            .map(|v| {
+                format!(
+                    "{}/{}-{}.{}",
+                    Self::DIR,
+                    Self::FILE_PREFIX,
+                    v,
+                    format.extension()
+                )
+            })
+            .find(|path| FileManager::stat(path).is_err())
+            .unwrap();
+
+        let bytes = format.encode(bitmap.as_ref());
+        let mut file = FileManager::creat(&path)?;
+        let mut written = 0;
+        while written < bytes.len() {
+            match file.write(&bytes[written..])? {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                size => written += size,
+            }
+        }
+        Ok(path)
+    }
+
+    /// Captures the screen, or the window if specified, in a new process
+    /// so that the caller is not blocked by encoding and writing the file
+    pub fn spawn(window: Option<WindowHandle>) {
+        let arg = window.map_or(0, |v| v.as_usize());
+        SpawnOption::new().start_process(Self::_start, arg, "screenshot");
+    }
+
+    fn _start(arg: usize) {
+        let bitmap = match WindowHandle::new(arg) {
+            Some(window) => Self::capture_window(window),
+            None => Some(Self::capture_screen()),
+        };
+        if let Some(bitmap) = bitmap {
+            match Self::save(&bitmap, ImageFormat::Png) {
+                Ok(path) => log!("Screenshot saved: {}", path),
+                Err(err) => log!("Screenshot failed: {:?}", err.kind()),
+            }
+        }
+    }
+
+    /// The composed screen may contain the translucent pixels of the shadows
+    fn make_opaque(bitmap: &mut OwnedBitmap32) {
+        for pixel in bitmap.slice_mut() {
+            *pixel = pixel.with_opacity(Alpha8::OPAQUE);
+        }
+    }
+}
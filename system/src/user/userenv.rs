@@ -28,7 +28,10 @@ static mut BG_TERMINAL: Option<WindowHandle> = None;
 pub struct UserEnv;
 
 impl UserEnv {
-    pub fn start(f: fn()) {
+    /// Path of the script that the shell runs at boot
+    pub const STARTUP_SCRIPT: &'static str = "/etc/rc";
+
+    pub fn start(f: fn(usize)) {
         assert_call_once!();
 
         // sync::semaphore::Semaphore::new(0).wait();
@@ -195,7 +198,7 @@ impl UserEnv {
     }
 
     fn _main(f: usize) {
-        let f: fn() = unsafe { transmute(f) };
+        let f: fn(usize) = unsafe { transmute(f) };
         Scheduler::spawn_async(slpash_task(f));
         Scheduler::perform_tasks();
     }
@@ -222,7 +225,7 @@ enum ShutdownCommand {
 }
 
 #[allow(dead_code)]
-async fn slpash_task(f: fn()) {
+async fn slpash_task(f: fn(usize)) {
     if IS_GUI_BOOT {
         if let Some(window) = unsafe { BG_TERMINAL.take() } {
             window.close();
@@ -314,7 +317,7 @@ async fn slpash_task(f: fn()) {
 }
 
 #[allow(dead_code)]
-async fn shell_launcher(f: fn()) {
+async fn shell_launcher(f: fn(usize)) {
     if IS_GUI_BOOT {
         Timer::sleep_async(Duration::from_millis(500)).await;
 
@@ -328,7 +331,11 @@ async fn shell_launcher(f: fn()) {
 
         // Scheduler::spawn_async(clock_task());
     }
-    SpawnOption::new().start_process(unsafe { core::mem::transmute(f) }, 0, "shell");
+    // The shell runs the startup script before the first prompt
+    let run_startup_script = FileManager::stat(UserEnv::STARTUP_SCRIPT)
+        .map(|stat| stat.file_type().is_file())
+        .unwrap_or(false);
+    SpawnOption::new().start_process(f, run_startup_script as usize, "shell");
 }
 
 #[allow(dead_code)]
@@ -454,7 +461,7 @@ async fn activity_monitor_main() {
     let graph_main_color3 = Color::LIGHT_GRAY;
     let margin = EdgeInsets::new(0, 0, 0, 0);
 
-    let width = 260;
+    let width = 290;
     let height = 180;
     let screen_bounds = WindowManager::user_screen_bounds();
     let window = RawWindowBuilder::new()
@@ -0,0 +1,481 @@
+//! Image Viewer Window
+
+use crate::{
+    fs::*,
+    io::{hid_mgr::*, image::ImageLoader},
+    task::scheduler::*,
+    ui::{font::*, text::*, window::*},
+    *,
+};
+use alloc::{boxed::Box, format};
+use core::time::Duration;
+use megstd::{
+    drawing::*,
+    io::{
+        hid::{MouseButton, Usage},
+        Read,
+    },
+    String, ToString, Vec,
+};
+
+/// Extensions of the images that [`ImageLoader`] can decode
+pub const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "qoi", "mpic", "bmp", "dib"];
+
+const BAR_HEIGHT: isize = 28;
+const PADDING: isize = 4;
+const MIN_WIDTH: isize = 320;
+const MIN_HEIGHT: isize = 240;
+const ZOOM_STEP: f64 = 1.25;
+const MIN_SCALE: f64 = 1.0 / 32.0;
+const MAX_SCALE: f64 = 32.0;
+/// Distance to scroll with the arrow keys in the screen pixels
+const PAN_STEP: f64 = 32.0;
+const SLIDESHOW_INTERVAL: Duration = Duration::from_secs(3);
+const TIMER_SLIDESHOW: usize = 1;
+
+const BG_COLOR: Color = Color::from_rgb(0x333333);
+const BAR_FG_COLOR: Color = Color::DARK_GRAY;
+const BAR_BG_COLOR: Color = Color::from_rgb(0xEEEEEE);
+
+/// Image viewer window with the images in the same directory
+pub struct ImageViewer {
+    window: WindowHandle,
+    font: FontDescriptor,
+    paths: Vec<String>,
+    index: usize,
+    image: Option<OwnedBitmap32>,
+    rotation: Rotation,
+    /// Magnification of the image, `None` to fit the image to the window
+    scale: Option<f64>,
+    /// The point of the rotated image at the center of the view
+    center: (f64, f64),
+    message: Option<String>,
+    is_slideshow: bool,
+    /// The last position of the pointer while dragging the image
+    drag: Option<Point>,
+}
+
+impl ImageViewer {
+    /// Opens the image in a new image viewer process
+    pub fn open(path: &str) -> Option<ProcessId> {
+        let path = Box::new(FileManager::canonical_path(path));
+        let arg = Box::into_raw(path) as usize;
+        let pid = SpawnOption::new().start_process(Self::_start, arg, "image");
+        if pid.is_none() {
+            drop(unsafe { Box::from_raw(arg as *mut String) });
+        }
+        pid
+    }
+
+    /// Returns whether the file name has the extension of the supported images
+    pub fn is_image(name: &str) -> bool {
+        name.rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+    }
+
+    fn _start(arg: usize) {
+        let path = unsafe { Box::from_raw(arg as *mut String) };
+        Self::new(*path).run();
+    }
+
+    fn new(path: String) -> Self {
+        // Images in the same directory to go to the next or the previous one
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", &path));
+        let mut paths = Vec::new();
+        if let Ok(read_dir) = FileManager::read_dir(if dir.is_empty() { "/" } else { dir }) {
+            for entry in read_dir {
+                if !entry.metadata().file_type().is_dir() && Self::is_image(entry.name()) {
+                    paths.push(format!("{}/{}", dir, entry.name()));
+                }
+            }
+        }
+        paths.sort();
+        let index = match paths
+            .iter()
+            .position(|v| v.rsplit_once('/').map(|v| v.1) == Some(name))
+        {
+            Some(index) => index,
+            None => {
+                paths.insert(0, path.clone());
+                0
+            }
+        };
+
+        // The window shows the image in actual size as far as the screen allows
+        let image = Self::read_image(&paths[index]);
+        let (width, height) = image
+            .as_ref()
+            .map_or((0, 0), |v| (v.width() as isize, v.height() as isize));
+        let screen = WindowManager::user_screen_bounds();
+        let size = Size::new(
+            width.clamp(MIN_WIDTH, MIN_WIDTH.max(screen.width() * 4 / 5)),
+            (height + BAR_HEIGHT).clamp(MIN_HEIGHT, MIN_HEIGHT.max(screen.height() * 4 / 5)),
+        );
+        let window = RawWindowBuilder::new()
+            .size(size)
+            .bg_color(BG_COLOR)
+            .build("Image");
+
+        let mut viewer = Self {
+            window,
+            font: FontManager::ui_font(),
+            paths,
+            index,
+            image: None,
+            rotation: Rotation::Default,
+            scale: None,
+            center: (0.0, 0.0),
+            message: None,
+            is_slideshow: false,
+            drag: None,
+        };
+        viewer.set_image(index, image);
+        viewer
+    }
+
+    fn run(&mut self) {
+        self.draw();
+        self.window.show();
+
+        while let Some(message) = self.window.wait_message() {
+            match message {
+                WindowMessage::Close => self.window.close(),
+                WindowMessage::Key(event) => {
+                    if !self.handle_key(event) {
+                        self.window.handle_default_message(message);
+                    }
+                }
+                WindowMessage::Char(c) => self.handle_char(c),
+                WindowMessage::MouseDown(event) => {
+                    if event.event_buttons().contains(MouseButton::PRIMARY) {
+                        self.drag = Some(event.point());
+                    }
+                }
+                WindowMessage::MouseMove(event) => {
+                    if let Some(last) = self.drag {
+                        if event.buttons().contains(MouseButton::PRIMARY) {
+                            let point = event.point();
+                            self.pan(last.x - point.x, last.y - point.y);
+                            self.drag = Some(point);
+                            self.draw();
+                        } else {
+                            self.drag = None;
+                        }
+                    }
+                }
+                WindowMessage::MouseUp(_) => self.drag = None,
+                WindowMessage::Timer(TIMER_SLIDESHOW) => {
+                    if self.is_slideshow {
+                        self.go_to((self.index + 1) % self.paths.len());
+                        self.window
+                            .create_timer(TIMER_SLIDESHOW, SLIDESHOW_INTERVAL);
+                        self.draw();
+                    }
+                }
+                _ => self.window.handle_default_message(message),
+            }
+        }
+    }
+
+    fn read_image(path: &str) -> Result<OwnedBitmap32, String> {
+        let mut file = FileManager::open(path, OpenOptions::new().read(true))
+            .map_err(|err| format!("{:?}", err.kind()))?;
+        let mut blob = Vec::new();
+        file.read_to_end(&mut blob)
+            .map_err(|err| format!("{:?}", err.kind()))?;
+        ImageLoader::load(&blob).map_err(|err| format!("{:?}", err))
+    }
+
+    /// Shows the image and resets the view
+    fn set_image(&mut self, index: usize, image: Result<OwnedBitmap32, String>) {
+        self.index = index;
+        self.rotation = Rotation::Default;
+        self.scale = None;
+        self.image = match image {
+            Ok(image) => Some(image),
+            Err(err) => {
+                self.message = Some(format!("{}: {}", self.name(), err));
+                None
+            }
+        };
+        let (width, height) = self.rotated_size();
+        self.center = (width as f64 / 2.0, height as f64 / 2.0);
+        self.window.set_title(self.name());
+    }
+
+    fn go_to(&mut self, index: usize) {
+        if index < self.paths.len() {
+            let image = Self::read_image(&self.paths[index]);
+            self.set_image(index, image);
+        }
+    }
+
+    fn name(&self) -> &str {
+        let path = &self.paths[self.index];
+        path.rsplit_once('/').map_or(path, |v| v.1)
+    }
+
+    fn handle_key(&mut self, event: KeyEvent) -> bool {
+        if event.is_break() {
+            return false;
+        }
+        let last = self.paths.len() - 1;
+        match event.usage() {
+            Usage::KEY_LEFT_ARROW if self.can_pan() => self.pan_by(-PAN_STEP, 0.0),
+            Usage::KEY_RIGHT_ARROW if self.can_pan() => self.pan_by(PAN_STEP, 0.0),
+            Usage::KEY_UP_ARROW if self.can_pan() => self.pan_by(0.0, -PAN_STEP),
+            Usage::KEY_DOWN_ARROW if self.can_pan() => self.pan_by(0.0, PAN_STEP),
+            Usage::KEY_LEFT_ARROW | Usage::KEY_UP_ARROW | Usage::KEY_PAGE_UP => {
+                self.go_to(self.index.checked_sub(1).unwrap_or(last))
+            }
+            Usage::KEY_RIGHT_ARROW | Usage::KEY_DOWN_ARROW | Usage::KEY_PAGE_DOWN => {
+                self.go_to(if self.index < last { self.index + 1 } else { 0 })
+            }
+            Usage::KEY_HOME => self.go_to(0),
+            Usage::KEY_END => self.go_to(last),
+            _ => return false,
+        }
+        self.draw();
+        true
+    }
+
+    fn handle_char(&mut self, c: char) {
+        match c {
+            '+' | '=' => self.zoom(ZOOM_STEP),
+            '-' => self.zoom(1.0 / ZOOM_STEP),
+            '0' | 'f' => {
+                self.scale = None;
+                let (width, height) = self.rotated_size();
+                self.center = (width as f64 / 2.0, height as f64 / 2.0);
+            }
+            '1' => self.scale = Some(1.0),
+            'r' => self.rotate(self.rotation.succ()),
+            'l' | 'R' => self.rotate(self.rotation.succ().succ().succ()),
+            ' ' => {
+                let last = self.paths.len() - 1;
+                self.go_to(if self.index < last { self.index + 1 } else { 0 });
+            }
+            '\x08' => self.go_to(self.index.checked_sub(1).unwrap_or(self.paths.len() - 1)),
+            's' => {
+                self.is_slideshow = !self.is_slideshow;
+                if self.is_slideshow {
+                    self.window
+                        .create_timer(TIMER_SLIDESHOW, SLIDESHOW_INTERVAL);
+                }
+            }
+            '\x1B' => self.is_slideshow = false,
+            'd' => self.set_desktop(),
+            _ => return,
+        }
+        self.clamp_center();
+        self.draw();
+    }
+
+    /// Size of the image after the rotation
+    fn rotated_size(&self) -> (usize, usize) {
+        let Some(image) = self.image.as_ref() else {
+            return (0, 0);
+        };
+        match self.rotation {
+            Rotation::Default | Rotation::UpsideDown => (image.width(), image.height()),
+            Rotation::ClockWise | Rotation::CounterClockWise => (image.height(), image.width()),
+        }
+    }
+
+    fn view_size(&self) -> Size {
+        let size = self.window.content_size();
+        Size::new(size.width, (size.height - BAR_HEIGHT).max(0))
+    }
+
+    /// Returns the current magnification, including the one that fits the image to the window
+    fn current_scale(&self) -> f64 {
+        if let Some(scale) = self.scale {
+            return scale;
+        }
+        let (width, height) = self.rotated_size();
+        let view = self.view_size();
+        if width == 0 || height == 0 || view.width <= 0 || view.height <= 0 {
+            return 1.0;
+        }
+        (view.width as f64 / width as f64).min(view.height as f64 / height as f64)
+    }
+
+    fn zoom(&mut self, factor: f64) {
+        let scale = self.current_scale() * factor;
+        self.scale = Some(scale.clamp(MIN_SCALE, MAX_SCALE));
+    }
+
+    fn rotate(&mut self, rotation: Rotation) {
+        let (width, height) = self.rotated_size();
+        let (x, y) = self.center;
+        // Keeps the same point of the image at the center
+        self.center = match (usize::from(rotation) + 4 - usize::from(self.rotation)) % 4 {
+            1 => (height as f64 - y, x),
+            2 => (width as f64 - x, height as f64 - y),
+            3 => (y, width as f64 - x),
+            _ => (x, y),
+        };
+        self.rotation = rotation;
+    }
+
+    /// Returns whether the image is larger than the window
+    fn can_pan(&self) -> bool {
+        let (width, height) = self.rotated_size();
+        let scale = self.current_scale();
+        let view = self.view_size();
+        width as f64 * scale > view.width as f64 || height as f64 * scale > view.height as f64
+    }
+
+    /// Scrolls the image by the distance in the screen pixels
+    fn pan(&mut self, dx: isize, dy: isize) {
+        self.pan_by(dx as f64, dy as f64);
+    }
+
+    fn pan_by(&mut self, dx: f64, dy: f64) {
+        let scale = self.current_scale();
+        self.center.0 += dx / scale;
+        self.center.1 += dy / scale;
+        self.clamp_center();
+    }
+
+    /// Keeps the image in the window where it is larger than the window
+    fn clamp_center(&mut self) {
+        let (width, height) = self.rotated_size();
+        let scale = self.current_scale();
+        let view = self.view_size();
+        let clamp = |center: f64, length: usize, view: isize| {
+            let half = view as f64 / scale / 2.0;
+            let length = length as f64;
+            if half * 2.0 >= length {
+                length / 2.0
+            } else {
+                center.clamp(half, length - half)
+            }
+        };
+        self.center = (
+            clamp(self.center.0, width, view.width),
+            clamp(self.center.1, height, view.height),
+        );
+    }
+
+    /// Sets the rotated image as the desktop wallpaper
+    fn set_desktop(&mut self) {
+        let Some(image) = self.image.as_ref() else {
+            return;
+        };
+        let (width, height) = self.rotated_size();
+        let src = image.slice();
+        let stride = image.stride();
+        let mut vec = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (x, y) = self.source_point(x, y);
+                vec.push(src[x + y * stride]);
+            }
+        }
+        let bitmap = OwnedBitmap32::from_vec(vec, Size::new(width as isize, height as isize));
+        WindowManager::set_desktop_bitmap(&BitmapRef::from(bitmap.as_ref()));
+        self.message = Some(format!("{} is set as the desktop", self.name()));
+    }
+
+    /// Converts the point of the rotated image to the point of the original image
+    fn source_point(&self, x: usize, y: usize) -> (usize, usize) {
+        let Some(image) = self.image.as_ref() else {
+            return (x, y);
+        };
+        let (width, height) = (image.width(), image.height());
+        match self.rotation {
+            Rotation::Default => (x, y),
+            Rotation::ClockWise => (y, height - 1 - x),
+            Rotation::UpsideDown => (width - 1 - x, height - 1 - y),
+            Rotation::CounterClockWise => (width - 1 - y, x),
+        }
+    }
+
+    /// Renders the visible part of the image with the nearest neighbor
+    fn render(&self, size: Size) -> Option<OwnedBitmap32> {
+        let image = self.image.as_ref()?;
+        let (width, height) = self.rotated_size();
+        let scale = self.current_scale();
+        let (view_width, view_height) = (size.width.max(0) as usize, size.height.max(0) as usize);
+        if view_width == 0 || view_height == 0 {
+            return None;
+        }
+
+        // The point of the rotated image at each column and row of the view
+        let map = |view: usize, center: f64, length: usize| {
+            (0..view)
+                .map(|v| {
+                    let p = center + (v as f64 + 0.5 - view as f64 / 2.0) / scale;
+                    (p >= 0.0 && p < length as f64).then(|| p as usize)
+                })
+                .collect::<Vec<_>>()
+        };
+        let columns = map(view_width, self.center.0, width);
+        let rows = map(view_height, self.center.1, height);
+
+        let bg = BG_COLOR.into_true_color();
+        let src = image.slice();
+        let stride = image.stride();
+        let mut vec = Vec::with_capacity(view_width * view_height);
+        for row in rows.iter() {
+            for column in columns.iter() {
+                vec.push(match (column, row) {
+                    (Some(x), Some(y)) => {
+                        let (x, y) = self.source_point(*x, *y);
+                        bg.blend_draw(src[x + y * stride])
+                    }
+                    _ => bg,
+                });
+            }
+        }
+        Some(OwnedBitmap32::from_vec(vec, size))
+    }
+
+    fn draw(&mut self) {
+        let status = match self.message.take() {
+            Some(message) => message,
+            None => {
+                let mut status = match self.image.as_ref() {
+                    Some(image) => format!(
+                        "{} x {}  {}%",
+                        image.width(),
+                        image.height(),
+                        (self.current_scale() * 100.0 + 0.5) as usize
+                    ),
+                    None => "No image".to_string(),
+                };
+                if self.paths.len() > 1 {
+                    status.push_str(&format!("  [{}/{}]", self.index + 1, self.paths.len()));
+                }
+                if self.is_slideshow {
+                    status.push_str("  Slideshow");
+                }
+                status
+            }
+        };
+        let view_size = self.view_size();
+        let view = self.render(view_size);
+
+        self.window.draw(|bitmap| {
+            bitmap.fill_rect(bitmap.bounds(), BG_COLOR);
+            if let Some(view) = view.as_ref() {
+                bitmap.blt(view.as_ref(), Point::default(), view.bounds());
+            }
+
+            let rect = Rect::new(0, view_size.height, bitmap.width() as isize, BAR_HEIGHT);
+            bitmap.fill_rect(rect, BAR_BG_COLOR);
+            AttributedString::new()
+                .font(&self.font)
+                .color(BAR_FG_COLOR)
+                .middle_left()
+                .text(&status)
+                .draw_text(
+                    bitmap,
+                    rect.insets_by(EdgeInsets::new(0, PADDING, 0, PADDING)),
+                    1,
+                );
+        });
+    }
+}
@@ -0,0 +1,306 @@
+// Offscreen window surface for the host runtime
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// Indexed color that is treated as transparent
+pub const KEY_COLOR: u8 = 0xFF;
+
+/// Packed color value that represents transparency
+const PACKED_TRANSPARENT: u32 = 0x100;
+
+/// The standard 256-color palette (16 VGA colors followed by a 6x6x6 color cube)
+pub fn palette(index: u8) -> u32 {
+    const BASE_COLORS: [u32; 16] = [
+        0xFF000000, 0xFF0000AA, 0xFF00AA00, 0xFF00AAAA, 0xFFAA0000, 0xFFAA00AA, 0xFFAA5500,
+        0xFFAAAAAA, 0xFF555555, 0xFF5555FF, 0xFF55FF55, 0xFF55FFFF, 0xFFFF5555, 0xFFFF55FF,
+        0xFFFFFF55, 0xFFFFFFFF,
+    ];
+    match index {
+        0..=15 => BASE_COLORS[index as usize],
+        16..=231 => {
+            let index = index as u32 - 16;
+            let r = index % 6;
+            let g = index / 6 % 6;
+            let b = index / 36;
+            0xFF000000 | (r * 0x33) << 16 | (g * 0x33) << 8 | (b * 0x33)
+        }
+        _ => 0,
+    }
+}
+
+/// Decodes a packed color into ARGB32, returns `None` if it is transparent.
+pub fn unpack_color(packed: u32) -> Option<u32> {
+    match packed {
+        PACKED_TRANSPARENT => None,
+        0..=0xFF => match packed as u8 {
+            KEY_COLOR => None,
+            index => Some(palette(index)),
+        },
+        argb => (argb >= 0x0100_0000).then_some(argb),
+    }
+}
+
+/// A simple ARGB32 bitmap
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, bg_color: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![bg_color; width * height],
+        }
+    }
+
+    #[inline]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn set_pixel(&mut self, x: isize, y: isize, color: u32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+        *pixel = Self::blend(*pixel, color);
+    }
+
+    /// Replaces the pixel without blending
+    #[inline]
+    pub fn put_pixel(&mut self, x: isize, y: isize, color: u32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        self.pixels[y as usize * self.width + x as usize] = color;
+    }
+
+    /// Alpha blending of two ARGB32 colors
+    fn blend(lhs: u32, rhs: u32) -> u32 {
+        let alpha = rhs >> 24;
+        match alpha {
+            0xFF => rhs,
+            0 => lhs,
+            _ => {
+                let mix = |shift: u32| {
+                    let l = (lhs >> shift) & 0xFF;
+                    let r = (rhs >> shift) & 0xFF;
+                    ((l * (255 - alpha) + r * alpha) / 255) << shift
+                };
+                (lhs & 0xFF000000) | mix(16) | mix(8) | mix(0)
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: isize, y: isize, width: isize, height: isize, color: u32) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+
+    pub fn draw_rect(&mut self, x: isize, y: isize, width: isize, height: isize, color: u32) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y + 1, 1, height - 2, color);
+        self.fill_rect(x + width - 1, y + 1, 1, height - 2, color);
+    }
+
+    pub fn fill_round_rect(
+        &mut self,
+        x: isize,
+        y: isize,
+        width: isize,
+        height: isize,
+        radius: isize,
+        color: u32,
+    ) {
+        let radius = radius.min(width / 2).min(height / 2).max(0);
+        for dy in 0..height {
+            let inset = Self::round_inset(dy, height, radius);
+            self.fill_rect(x + inset, y + dy, width - inset * 2, 1, color);
+        }
+    }
+
+    pub fn draw_round_rect(
+        &mut self,
+        x: isize,
+        y: isize,
+        width: isize,
+        height: isize,
+        radius: isize,
+        color: u32,
+    ) {
+        let radius = radius.min(width / 2).min(height / 2).max(0);
+        for dy in 0..height {
+            let inset = Self::round_inset(dy, height, radius);
+            if dy == 0 || dy == height - 1 {
+                self.fill_rect(x + inset, y + dy, width - inset * 2, 1, color);
+            } else {
+                self.set_pixel(x + inset, y + dy, color);
+                self.set_pixel(x + width - inset - 1, y + dy, color);
+            }
+        }
+    }
+
+    /// Horizontal inset of a rounded corner at the specified line
+    fn round_inset(dy: isize, height: isize, radius: isize) -> isize {
+        let d = if dy < radius {
+            radius - dy
+        } else if dy >= height - radius {
+            dy - (height - radius) + 1
+        } else {
+            return 0;
+        };
+        let r2 = radius * radius;
+        let mut inset = radius;
+        while inset > 0 && (radius - inset) * (radius - inset) + d * d <= r2 {
+            inset -= 1;
+        }
+        inset
+    }
+
+    pub fn draw_line(&mut self, x1: isize, y1: isize, x2: isize, y2: isize, color: u32) {
+        let dx = (x2 - x1).abs();
+        let dy = -(y2 - y1).abs();
+        let sx = if x1 < x2 { 1 } else { -1 };
+        let sy = if y1 < y2 { 1 } else { -1 };
+        let mut e = dx + dy;
+        let (mut x, mut y) = (x1, y1);
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x2 && y == y2 {
+                break;
+            }
+            let e2 = e * 2;
+            if e2 >= dy {
+                e += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                e += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draws a placeholder glyph for each character, since the host has no fonts.
+    pub fn draw_string(&mut self, x: isize, y: isize, text: &str, color: u32) {
+        const GLYPH_WIDTH: isize = 8;
+        const GLYPH_HEIGHT: isize = 16;
+        let mut cursor = x;
+        let mut line = y;
+        for ch in text.chars() {
+            match ch {
+                '\n' => {
+                    cursor = x;
+                    line += GLYPH_HEIGHT;
+                }
+                ' ' | '\t' => cursor += GLYPH_WIDTH,
+                _ => {
+                    self.draw_rect(cursor + 1, line + 3, GLYPH_WIDTH - 2, 11, color);
+                    cursor += GLYPH_WIDTH;
+                }
+            }
+        }
+    }
+
+    pub fn blt32(&mut self, x: isize, y: isize, width: usize, height: usize, src: &[u32]) {
+        for (dy, row) in src.chunks(width.max(1)).take(height).enumerate() {
+            for (dx, color) in row.iter().enumerate() {
+                self.set_pixel(x + dx as isize, y + dy as isize, *color);
+            }
+        }
+    }
+
+    pub fn blt8(&mut self, x: isize, y: isize, width: usize, height: usize, src: &[u8]) {
+        for (dy, row) in src.chunks(width.max(1)).take(height).enumerate() {
+            for (dx, index) in row.iter().enumerate() {
+                if *index != KEY_COLOR {
+                    self.set_pixel(x + dx as isize, y + dy as isize, palette(*index));
+                }
+            }
+        }
+    }
+
+    /// Draws a 1bpp bitmap, each dot is scaled by `scale`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn blt1(
+        &mut self,
+        x: isize,
+        y: isize,
+        width: usize,
+        height: usize,
+        stride: usize,
+        src: &[u8],
+        color: u32,
+        scale: usize,
+    ) {
+        let scale = scale.max(1) as isize;
+        for dy in 0..height {
+            for dx in 0..width {
+                let Some(data) = src.get(dy * stride + dx / 8) else {
+                    continue;
+                };
+                if (data & (0x80 >> (dx & 7))) != 0 {
+                    self.fill_rect(
+                        x + dx as isize * scale,
+                        y + dy as isize * scale,
+                        scale,
+                        scale,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Writes this canvas as a PNG image.
+    pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for argb in &self.pixels {
+            data.extend_from_slice(&[(argb >> 16) as u8, (argb >> 8) as u8, *argb as u8, 0xFF]);
+        }
+        writer.write_image_data(&data)
+    }
+}
+
+/// Blends `color` over an ARGB32 bitmap stored in little-endian bytes (BlendRect)
+pub fn blend_rect(
+    bytes: &mut [u8],
+    stride: usize,
+    x: isize,
+    y: isize,
+    width: isize,
+    height: isize,
+    color: u32,
+) {
+    let rows = bytes.len() / (stride * 4).max(1);
+    for y in y.max(0)..(y + height).min(rows as isize) {
+        for x in x.max(0)..(x + width).min(stride as isize) {
+            let offset = (y as usize * stride + x as usize) * 4;
+            let pixel = &mut bytes[offset..offset + 4];
+            let lhs = u32::from_le_bytes(pixel.try_into().unwrap());
+            pixel.copy_from_slice(&Canvas::blend(lhs, color).to_le_bytes());
+        }
+    }
+}
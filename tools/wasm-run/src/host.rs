@@ -0,0 +1,1635 @@
+// Host implementation of the megos-canary system calls
+
+use crate::canvas::{self, Canvas};
+use byteorder::*;
+use std::{
+    alloc::Layout,
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+    iter::Peekable,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use wasm::{debug::*, heap::*, intr::*, *};
+
+thread_local! {
+    static RUNTIME: RefCell<Option<HostRuntime>> = const { RefCell::new(None) };
+}
+
+/// System call numbers, must match `megstd::sys::megos::svc::Function`
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Function {
+    Exit = 0,
+    PrintString,
+    Monotonic,
+    Time,
+    Usleep,
+    GetSystemInfo,
+    NewWindow,
+    CloseWindow,
+    BeginDraw,
+    EndDraw,
+    DrawString,
+    FillRect,
+    DrawRect,
+    DrawLine,
+    Blt8,
+    Blt1,
+    Blt32,
+    BlendRect,
+    WaitChar,
+    ReadChar,
+    DrawShape,
+    GetMessage,
+    PeekMessage,
+    SetTimer,
+    SetBackBuffer,
+    Present,
+
+    Rand = 100,
+    Srand,
+    Alloc,
+    Dealloc,
+    MkDir,
+    RmDir,
+    ChDir,
+    Open,
+    Close,
+    Read,
+    Write,
+    LSeek,
+    IoCtl,
+    Unlink,
+    OpenDir,
+    ReadDir,
+    Stat,
+    Rename,
+    GetCwd,
+    Args,
+    Environ,
+    SetEnv,
+    UnsetEnv,
+    AudioNewContext,
+    AudioNoteOn,
+    AudioOpenStream,
+    AudioWrite,
+    AudioClose,
+    GameEnumInputs,
+    GameGetState,
+    GamePollEvent,
+    ClipboardFormat,
+    ClipboardGetText,
+    ClipboardSetText,
+    ClipboardGetBitmap,
+    ClipboardSetBitmap,
+    ChannelCreate,
+    ChannelConnect,
+    ChannelSend,
+    ChannelReceive,
+    ChannelClose,
+    HeapStats,
+}
+
+impl Function {
+    fn from_u32(val: u32) -> Option<Self> {
+        const TABLE1: [Function; 26] = [
+            Function::Exit,
+            Function::PrintString,
+            Function::Monotonic,
+            Function::Time,
+            Function::Usleep,
+            Function::GetSystemInfo,
+            Function::NewWindow,
+            Function::CloseWindow,
+            Function::BeginDraw,
+            Function::EndDraw,
+            Function::DrawString,
+            Function::FillRect,
+            Function::DrawRect,
+            Function::DrawLine,
+            Function::Blt8,
+            Function::Blt1,
+            Function::Blt32,
+            Function::BlendRect,
+            Function::WaitChar,
+            Function::ReadChar,
+            Function::DrawShape,
+            Function::GetMessage,
+            Function::PeekMessage,
+            Function::SetTimer,
+            Function::SetBackBuffer,
+            Function::Present,
+        ];
+        const TABLE2: [Function; 42] = [
+            Function::Rand,
+            Function::Srand,
+            Function::Alloc,
+            Function::Dealloc,
+            Function::MkDir,
+            Function::RmDir,
+            Function::ChDir,
+            Function::Open,
+            Function::Close,
+            Function::Read,
+            Function::Write,
+            Function::LSeek,
+            Function::IoCtl,
+            Function::Unlink,
+            Function::OpenDir,
+            Function::ReadDir,
+            Function::Stat,
+            Function::Rename,
+            Function::GetCwd,
+            Function::Args,
+            Function::Environ,
+            Function::SetEnv,
+            Function::UnsetEnv,
+            Function::AudioNewContext,
+            Function::AudioNoteOn,
+            Function::AudioOpenStream,
+            Function::AudioWrite,
+            Function::AudioClose,
+            Function::GameEnumInputs,
+            Function::GameGetState,
+            Function::GamePollEvent,
+            Function::ClipboardFormat,
+            Function::ClipboardGetText,
+            Function::ClipboardSetText,
+            Function::ClipboardGetBitmap,
+            Function::ClipboardSetBitmap,
+            Function::ChannelCreate,
+            Function::ChannelConnect,
+            Function::ChannelSend,
+            Function::ChannelReceive,
+            Function::ChannelClose,
+            Function::HeapStats,
+        ];
+        match val {
+            0..=99 => TABLE1.get(val as usize).copied(),
+            _ => TABLE2.get(val as usize - Function::Rand as usize).copied(),
+        }
+    }
+}
+
+/// Options of `Open`, must match `megstd::sys::fcntl::OpenOptions`
+mod open_flags {
+    pub const READ: u32 = 0x0001;
+    pub const WRITE: u32 = 0x0002;
+    pub const APPEND: u32 = 0x0010;
+    pub const TRUNC: u32 = 0x0020;
+    pub const CREAT: u32 = 0x0040;
+    pub const EXCL: u32 = 0x0080;
+}
+
+/// Access rights granted by a capability, must match `megstd::sys::megos::caps::Access`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Read,
+    ReadWrite,
+}
+
+/// A directory or device in the application namespace that the application is allowed to access
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub path: PathBuf,
+    pub access: Access,
+}
+
+impl Capability {
+    /// Name of the custom section in which the application declares its capabilities
+    pub const SECTION_NAME: &'static str = "megos.caps";
+
+    /// Parses the contents of the manifest section, see `megstd::sys::megos::caps`
+    pub fn parse_manifest(manifest: &[u8]) -> Option<Vec<Self>> {
+        let mut result = Vec::new();
+        for line in std::str::from_utf8(manifest).ok()?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (access, path) = line.split_once(char::is_whitespace)?;
+            let access = match access {
+                "r" => Access::Read,
+                "rw" => Access::ReadWrite,
+                _ => return None,
+            };
+            let path = path.trim();
+            if !path.starts_with('/') {
+                return None;
+            }
+            result.push(Self {
+                path: PathBuf::from(path),
+                access,
+            });
+        }
+        Some(result)
+    }
+}
+
+/// Options for the host runtime
+#[derive(Debug, Clone, Default)]
+pub struct HostOption {
+    /// Root directory of the files visible to the application
+    pub sandbox: PathBuf,
+    /// Directory to write window contents as PNG frames
+    pub frames: Option<PathBuf>,
+    /// Run the application under the interactive debugger
+    pub debug: bool,
+    /// Arguments of the application, the first one is the name of the application
+    pub args: Vec<String>,
+    /// Environment variables of the application
+    pub env: BTreeMap<String, String>,
+    /// Capabilities of the application, nothing in the sandbox is accessible by default
+    pub caps: Vec<Capability>,
+}
+
+/// Host runtime for MEG-OS wasm applications
+pub struct HostRuntime {
+    option: HostOption,
+    cwd: PathBuf,
+    started_at: Instant,
+    next_handle: usize,
+    windows: BTreeMap<usize, HostWindow>,
+    files: Vec<Option<HostFile>>,
+    audio: BTreeMap<usize, HostAudio>,
+    clipboard: Option<HostClipboard>,
+    channel_names: BTreeMap<String, Rc<RefCell<HostChannel>>>,
+    channels: BTreeMap<usize, HostChannelHandle>,
+    rng32: u32,
+    heap: WasmHeap,
+    exit_code: i32,
+}
+
+impl HostRuntime {
+    const MAX_FILES: usize = 20;
+    /// Maximum number of pages of the linear memory, must match the kernel
+    const MAX_MEMORY_PAGES: usize = 2048;
+    const SIZE_HEAP_STATS: usize = 28;
+    const SIZE_STAT: usize = 16;
+    const SIZE_NOTE_PARAMS: usize = 28;
+    const SIZE_GAME_STATE: usize = 12;
+    const SIZE_WINDOW_MESSAGE: usize = 16;
+    const MESSAGE_CLOSE: u32 = 1;
+    const MESSAGE_CHAR: u32 = 7;
+    const MESSAGE_TIMER: u32 = 13;
+    const BACK_BUFFER_INDEXED8: u32 = 8;
+    const BACK_BUFFER_ARGB32: u32 = 32;
+    /// Id of the input simulated by the keyboard, which is the only input of the host
+    const GAME_SIMULATED_INPUT: u32 = 0;
+    const GAME_CURRENT_INPUT: u32 = u32::MAX;
+    const CLIPBOARD_EMPTY: u32 = 0;
+    const CLIPBOARD_TEXT: u32 = 1;
+    const CLIPBOARD_BITMAP32: u32 = 2;
+    const CLIPBOARD_BITMAP_HEADER_SIZE: usize = 8;
+    const MAX_CLIPBOARD_BITMAP_SIZE: usize = 4096;
+    const CHANNEL_MAX_MESSAGE_SIZE: usize = 4096;
+    const CHANNEL_MAX_CAPACITY: usize = 256;
+    const CHANNEL_MAX_NAME_LEN: usize = 64;
+    pub const MOD_NAME: &'static str = "megos-canary";
+    pub const ENTRY_FUNC_NAME: &'static str = "_start";
+
+    /// MEG-OS version reported to applications
+    const VERSION: u32 = 0x000C_0000;
+    const OPTION_CHAR_NONE: u32 = 0x110000;
+    const DEFAULT_BG_COLOR: u32 = 0xFFFFFFFF;
+
+    pub fn new(option: HostOption) -> Self {
+        Self {
+            option,
+            cwd: PathBuf::from("/"),
+            started_at: Instant::now(),
+            next_handle: 1,
+            windows: BTreeMap::new(),
+            files: vec![
+                Some(HostFile::Stdin),
+                Some(HostFile::Stdout),
+                Some(HostFile::Stderr),
+            ],
+            audio: BTreeMap::new(),
+            clipboard: None,
+            channel_names: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            rng32: 2463534242,
+            heap: WasmHeap::new(Self::MAX_MEMORY_PAGES),
+            exit_code: 0,
+        }
+    }
+
+    /// Resolves imports of the application
+    pub fn resolve(mod_name: &str, name: &str, type_ref: &WasmType) -> ImportResult<WasmDynFunc> {
+        let signature = type_ref.signature();
+        match mod_name {
+            Self::MOD_NAME => match (name, signature.as_str()) {
+                ("svc0", "ii") => ImportResult::Ok(Self::syscall),
+                ("svc1", "iii") => ImportResult::Ok(Self::syscall),
+                ("svc2", "iiii") => ImportResult::Ok(Self::syscall),
+                ("svc3", "iiiii") => ImportResult::Ok(Self::syscall),
+                ("svc4", "iiiiii") => ImportResult::Ok(Self::syscall),
+                ("svc5", "iiiiiii") => ImportResult::Ok(Self::syscall),
+                ("svc6", "iiiiiiii") => ImportResult::Ok(Self::syscall),
+                _ => ImportResult::NoMethod,
+            },
+            _ => ImportResult::NoModule,
+        }
+    }
+
+    /// Runs the application and returns its exit code
+    pub fn run(self, module: &WasmModule) -> i32 {
+        let function = match module.func(Self::ENTRY_FUNC_NAME) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("error: {:?}", err);
+                return 1;
+            }
+        };
+
+        let debug = self.option.debug;
+        RUNTIME.with(|v| *v.borrow_mut() = Some(self));
+
+        let result = if debug {
+            let mut debugger = WasmDebugger::new(StdioDebugConsole);
+            function.invoke_with_debugger(&[], &mut debugger)
+        } else {
+            function.invoke(&[])
+        };
+        let result = match result {
+            Ok(_v) => 0,
+            Err(err) => match err.kind() {
+                WasmRuntimeErrorKind::Exit => 0,
+                _ => {
+                    eprintln!("error: {:?}", err);
+                    1
+                }
+            },
+        };
+
+        RUNTIME
+            .with(|v| v.borrow_mut().take())
+            .map(|v| if result == 0 { v.exit_code } else { result })
+            .unwrap_or(result)
+    }
+
+    fn syscall(
+        module: &WasmModule,
+        params: &[WasmUnsafeValue],
+    ) -> Result<WasmValue, WasmRuntimeErrorKind> {
+        RUNTIME.with(|v| {
+            v.borrow_mut()
+                .as_mut()
+                .ok_or(WasmRuntimeErrorKind::NotSupprted)
+                .and_then(|rt| rt.dispatch_syscall(module, params))
+        })
+    }
+
+    fn dispatch_syscall(
+        &mut self,
+        module: &WasmModule,
+        params: &[WasmUnsafeValue],
+    ) -> Result<WasmValue, WasmRuntimeErrorKind> {
+        let mut params = ParamsDecoder::new(params);
+        let memory = module.memory(0).ok_or(WasmRuntimeErrorKind::OutOfMemory)?;
+        let func_no = params
+            .get_u32()
+            .map(Function::from_u32)?
+            .ok_or(WasmRuntimeErrorKind::NotSupprted)?;
+
+        match func_no {
+            Function::Exit => {
+                self.exit_code = params.get_i32().unwrap_or_default();
+                return Err(WasmRuntimeErrorKind::Exit);
+            }
+
+            Function::Monotonic => {
+                return Ok(WasmValue::I32(self.started_at.elapsed().as_micros() as i32));
+            }
+            Function::Time => {
+                let sub_func_no = params.get_usize()?;
+                if sub_func_no == 0 {
+                    let time = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    return Ok(WasmValue::from((time.as_secs() % 86400) as u32));
+                }
+            }
+            Function::Usleep => {
+                let us = params.get_u32()? as u64;
+                thread::sleep(Duration::from_micros(us));
+            }
+
+            Function::GetSystemInfo => {
+                let sub_func_no = params.get_usize()?;
+                if sub_func_no == 0 {
+                    return Ok(WasmValue::from(Self::VERSION));
+                }
+            }
+
+            Function::PrintString => {
+                if let Some(s) = params.get_string(memory) {
+                    print!("{}", s);
+                    let _ = io::stdout().flush();
+                }
+            }
+
+            Function::MkDir => {
+                let path = self.app_path(params.get_string(memory))?;
+                let result = self
+                    .grant(&path, Access::ReadWrite)
+                    .and_then(fs::create_dir);
+                return Self::encode_io_result(result.map(|_| 0));
+            }
+            Function::RmDir => {
+                let path = self.app_path(params.get_string(memory))?;
+                let result = self
+                    .grant(&path, Access::ReadWrite)
+                    .and_then(fs::remove_dir);
+                return Self::encode_io_result(result.map(|_| 0));
+            }
+            Function::ChDir => {
+                let path = self.app_path(params.get_string(memory))?;
+                let result = match self.grant(&path, Access::Read).and_then(fs::metadata) {
+                    Ok(metadata) if metadata.is_dir() => {
+                        self.cwd = path;
+                        Ok(0)
+                    }
+                    Ok(_) => Err(io::ErrorKind::NotADirectory.into()),
+                    Err(err) => Err(err),
+                };
+                return Self::encode_io_result(result);
+            }
+            Function::GetCwd => {
+                let buf = params.get_buffer(memory)?;
+                let cwd = self.cwd.to_string_lossy();
+                let len = cwd.len().min(buf.len());
+                buf[..len].copy_from_slice(&cwd.as_bytes()[..len]);
+                return Ok(WasmValue::from(cwd.len() as u32));
+            }
+            Function::Unlink => {
+                let path = self.app_path(params.get_string(memory))?;
+                let result = self
+                    .grant(&path, Access::ReadWrite)
+                    .and_then(fs::remove_file);
+                return Self::encode_io_result(result.map(|_| 0));
+            }
+            Function::Rename => {
+                let old_path = self.app_path(params.get_string(memory))?;
+                let new_path = self.app_path(params.get_string(memory))?;
+                let result = self
+                    .grant(&old_path, Access::ReadWrite)
+                    .and_then(|old_path| {
+                        self.grant(&new_path, Access::ReadWrite)
+                            .and_then(|new_path| fs::rename(old_path, new_path))
+                    });
+                return Self::encode_io_result(result.map(|_| 0));
+            }
+            Function::Stat => {
+                let path = self.app_path(params.get_string(memory))?;
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(
+                    self.grant(&path, Access::Read)
+                        .and_then(fs::metadata)
+                        .and_then(|metadata| Self::encode_stat(&metadata, buf)),
+                );
+            }
+
+            Function::Open => {
+                let path = self.app_path(params.get_string(memory))?;
+                let options = params.get_u32().unwrap_or_default();
+                let access = if Self::is_writable(options) {
+                    Access::ReadWrite
+                } else {
+                    Access::Read
+                };
+                let result = self
+                    .grant(&path, access)
+                    .and_then(|path| self.open_options(options).open(path))
+                    .and_then(|file| self.alloc_file(HostFile::File(file)));
+                return Self::encode_io_result(result);
+            }
+            Function::Close => {
+                let handle = params.get_usize()?;
+                self.close_file(handle);
+            }
+            Function::Read => {
+                let stream = params.get_stream(self)?;
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(stream.read(buf));
+            }
+            Function::Write => {
+                let stream = params.get_stream(self)?;
+                let buf = params.get_buffer(memory)?;
+                return Self::encode_io_result(stream.write(buf));
+            }
+            Function::LSeek => {
+                let file = params.get_file(self)?;
+                let offset = params.get_i32()? as i64;
+                let pos = match params.get_usize()? {
+                    0 => SeekFrom::Start(offset.max(0) as u64),
+                    1 => SeekFrom::Current(offset),
+                    2 => SeekFrom::End(offset),
+                    _ => return Err(WasmRuntimeErrorKind::InvalidParameter),
+                };
+                return Self::encode_io_result(file.seek(pos).map(|v| v as usize));
+            }
+            Function::IoCtl => {
+                const FSTAT: usize = 1;
+                const SET_LEN: usize = 2;
+                const FLUSH: usize = 3;
+
+                let file = params.get_file(self)?;
+                let request = params.get_usize()?;
+                let arg = params.get_buffer(memory)?;
+                let result = match request {
+                    FSTAT => file
+                        .metadata()
+                        .and_then(|metadata| Self::encode_stat(&metadata, arg)),
+                    SET_LEN => {
+                        let len = arg
+                            .get(..8)
+                            .map(LE::read_u64)
+                            .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                        file.set_len(len).map(|_| 0)
+                    }
+                    FLUSH => file.flush().map(|_| 0),
+                    _ => Err(io::ErrorKind::Unsupported.into()),
+                };
+                return Self::encode_io_result(result);
+            }
+            Function::OpenDir => {
+                let path = self.app_path(params.get_string(memory))?;
+                let result = self
+                    .grant(&path, Access::Read)
+                    .and_then(fs::read_dir)
+                    .and_then(|dir| self.alloc_file(HostFile::Dir(dir.peekable())));
+                return Self::encode_io_result(result);
+            }
+            Function::ReadDir => {
+                let dir = params.get_dir(self)?;
+                let name = params.get_buffer(memory)?;
+                let stat = params.get_buffer(memory)?;
+                // Leaves the entry whose name does not fit so that the caller can retry
+                if let Some(Ok(entry)) = dir.peek() {
+                    let len = entry.file_name().to_string_lossy().len();
+                    if len > name.len() {
+                        return Self::encode_io_result(Ok(len));
+                    }
+                }
+                let result = match dir.next() {
+                    Some(Ok(entry)) => entry.metadata().and_then(|metadata| {
+                        let entry_name = entry.file_name();
+                        let entry_name = entry_name.to_string_lossy();
+                        let entry_name = entry_name.as_bytes();
+                        let name = name
+                            .get_mut(..entry_name.len())
+                            .ok_or(io::ErrorKind::InvalidInput)?;
+                        name.copy_from_slice(entry_name);
+                        Self::encode_stat(&metadata, stat).map(|_| entry_name.len())
+                    }),
+                    Some(Err(err)) => Err(err),
+                    None => Ok(0),
+                };
+                return Self::encode_io_result(result);
+            }
+
+            Function::NewWindow => {
+                let title = params.get_string(memory).unwrap_or("");
+                let width = params.get_usize()?;
+                let height = params.get_usize()?;
+                let bg_color = params
+                    .get_u32()
+                    .ok()
+                    .and_then(canvas::unpack_color)
+                    .unwrap_or(Self::DEFAULT_BG_COLOR);
+                let _window_option = params.get_u32().unwrap_or_default();
+
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                let window = HostWindow::new(handle, title, Canvas::new(width, height, bg_color));
+                self.windows.insert(handle, window);
+                return Ok(WasmValue::I32(handle as i32));
+            }
+            Function::CloseWindow => {
+                let handle = params.get_usize()?;
+                self.windows.remove(&handle);
+            }
+            Function::BeginDraw => {
+                let window = params.get_window(self)?;
+                return Ok(WasmValue::from(window.handle as u32));
+            }
+            Function::EndDraw => {
+                let frames = self.option.frames.clone();
+                let window = params.get_window(self)?;
+                if let Some(frames) = frames {
+                    window.save_frame(&frames);
+                }
+            }
+
+            Function::DrawString => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let text = params.get_string(memory).unwrap_or("");
+                if let Some(color) = params.get_color()? {
+                    window.canvas.draw_string(x, y, text, color);
+                }
+            }
+            Function::FillRect => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (width, height) = params.get_point()?;
+                if let Some(color) = params.get_color()? {
+                    window.canvas.fill_rect(x, y, width, height, color);
+                }
+            }
+            Function::DrawRect => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (width, height) = params.get_point()?;
+                if let Some(color) = params.get_color()? {
+                    window.canvas.draw_rect(x, y, width, height, color);
+                }
+            }
+            Function::DrawLine => {
+                let window = params.get_window(self)?;
+                let (x1, y1) = params.get_point()?;
+                let (x2, y2) = params.get_point()?;
+                if let Some(color) = params.get_color()? {
+                    window.canvas.draw_line(x1, y1, x2, y2, color);
+                }
+            }
+            Function::DrawShape => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (width, height) = params.get_point()?;
+                let offset = params.get_usize()?;
+                let shape = memory.slice(offset, 12)?;
+                let radius = LE::read_u32(&shape[0..4]) as isize;
+                let bg_color = canvas::unpack_color(LE::read_u32(&shape[4..8]));
+                let border_color = canvas::unpack_color(LE::read_u32(&shape[8..12]));
+                if let Some(color) = bg_color {
+                    window
+                        .canvas
+                        .fill_round_rect(x, y, width, height, radius, color);
+                }
+                if let Some(color) = border_color {
+                    window
+                        .canvas
+                        .draw_round_rect(x, y, width, height, radius, color);
+                }
+            }
+            Function::WaitChar => {
+                let _ = params.get_window(self)?;
+                return match Self::read_stdin_char() {
+                    Some(c) => Ok(WasmValue::I32(c as i32)),
+                    None => Err(WasmRuntimeErrorKind::Exit),
+                };
+            }
+            Function::ReadChar => {
+                let _ = params.get_window(self)?;
+                return Ok(WasmValue::from(Self::OPTION_CHAR_NONE));
+            }
+            Function::GetMessage => {
+                // Pending timers are delivered first, then characters from the standard input
+                let window = params.get_window(self)?;
+                let buf = params.get_buffer(memory)?;
+                if buf.len() < Self::SIZE_WINDOW_MESSAGE {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+                let message = match window.next_timer(None) {
+                    Some((timer_id, deadline)) => {
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                        [Self::MESSAGE_TIMER, timer_id as u32]
+                    }
+                    None => match Self::read_stdin_char() {
+                        Some(c) => [Self::MESSAGE_CHAR, c as u32],
+                        None => [Self::MESSAGE_CLOSE, 0],
+                    },
+                };
+                Self::encode_message(buf, message);
+                return Ok(WasmValue::from(1u32));
+            }
+            Function::PeekMessage => {
+                let window = params.get_window(self)?;
+                let buf = params.get_buffer(memory)?;
+                if buf.len() < Self::SIZE_WINDOW_MESSAGE {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+                if let Some((timer_id, _)) = window.next_timer(Some(Instant::now())) {
+                    Self::encode_message(buf, [Self::MESSAGE_TIMER, timer_id as u32]);
+                    return Ok(WasmValue::from(1u32));
+                }
+            }
+            Function::SetBackBuffer => {
+                let window = params.get_window(self)?;
+                let base = params.get_usize()?;
+                let width = params.get_usize()?;
+                let height = params.get_usize()?;
+                let format = params.get_u32()?;
+                if base == 0 {
+                    window.back_buffer = None;
+                    return Ok(WasmValue::I32(0));
+                }
+                let back_buffer = HostBackBuffer {
+                    base,
+                    width,
+                    height,
+                    format,
+                };
+                if width == 0
+                    || height == 0
+                    || width > window.canvas.width()
+                    || height > window.canvas.height()
+                    || back_buffer.pixels(memory).is_err()
+                {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                }
+                window.back_buffer = Some(back_buffer);
+            }
+            Function::Present => {
+                let frames = self.option.frames.clone();
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (width, height) = params.get_point()?;
+                let back_buffer = window
+                    .back_buffer
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let pixels = back_buffer.pixels(memory)?;
+                let left = x.max(0) as usize;
+                let top = y.max(0) as usize;
+                let right = ((x + width).max(0) as usize).min(back_buffer.width);
+                let bottom = ((y + height).max(0) as usize).min(back_buffer.height);
+                for y in top..bottom {
+                    for x in left..right {
+                        let color = pixels[y * back_buffer.width + x];
+                        window.canvas.put_pixel(x as isize, y as isize, color);
+                    }
+                }
+                if let Some(frames) = frames {
+                    window.save_frame(&frames);
+                }
+            }
+            Function::SetTimer => {
+                let window = params.get_window(self)?;
+                let timer_id = params.get_usize()?;
+                let ms = params.get_u32()?;
+                window
+                    .timers
+                    .push((timer_id, Instant::now() + Duration::from_millis(ms as u64)));
+            }
+
+            Function::Blt8 => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (base, width, height) = params.get_bitmap(memory)?;
+                let src = memory.slice(base, width * height)?;
+                window.canvas.blt8(x, y, width, height, src);
+            }
+            Function::Blt32 => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let (base, width, height) = params.get_bitmap(memory)?;
+                let src = memory.slice(base, width * height * 4)?;
+                let src = src.chunks_exact(4).map(LE::read_u32).collect::<Vec<_>>();
+                window.canvas.blt32(x, y, width, height, &src);
+            }
+            Function::BlendRect => {
+                let (base, width, height) = params.get_bitmap(memory)?;
+                let (x, y) = params.get_point()?;
+                let (w, h) = params.get_point()?;
+                let color = params.get_u32()?;
+                let bitmap = unsafe { memory.slice_mut(base, width * height * 4)? };
+                canvas::blend_rect(bitmap, width, x, y, w, h, color);
+            }
+            Function::Blt1 => {
+                let window = params.get_window(self)?;
+                let (x, y) = params.get_point()?;
+                let base = params.get_usize()?;
+                let header = memory.slice(base, 16)?;
+                let width = LE::read_u32(&header[0..4]) as usize;
+                let height = LE::read_u32(&header[4..8]) as usize;
+                let stride = LE::read_u32(&header[8..12]) as usize;
+                let base = LE::read_u32(&header[12..16]) as usize;
+                let src = memory.slice(base, stride * height)?;
+                let color = params.get_color()?;
+                let mode = params.get_usize()?;
+                if let Some(color) = color {
+                    window
+                        .canvas
+                        .blt1(x, y, width, height, stride, src, color, mode);
+                }
+            }
+
+            Function::Rand => {
+                let mut x = self.rng32;
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                self.rng32 = x;
+                return Ok(WasmValue::from(x));
+            }
+            Function::Srand => {
+                let seed = params.get_u32()?;
+                if seed != 0 {
+                    self.rng32 = seed;
+                }
+            }
+
+            Function::Alloc => {
+                let size = params.get_usize()?;
+                let align = params.get_usize()?;
+                let layout = Layout::from_size_align(size, align)
+                    .map_err(|_| WasmRuntimeErrorKind::InvalidParameter)?;
+                let result = self.heap.alloc(memory, layout);
+                return Ok(WasmValue::from(result.map(|v| v.get()).unwrap_or(0)));
+            }
+            Function::Dealloc => {
+                let base = params.get_u32()?;
+                if !self.heap.dealloc(base) {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+            }
+            Function::HeapStats => {
+                let buf = params.get_buffer(memory)?;
+                let Some(buf) = buf.get_mut(..Self::SIZE_HEAP_STATS) else {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                };
+                let stats = self.heap.stats(memory);
+                for (chunk, value) in buf.chunks_exact_mut(4).zip([
+                    stats.memory_size,
+                    stats.memory_limit,
+                    stats.heap_size,
+                    stats.used,
+                    stats.n_allocs,
+                    stats.n_free_blocks,
+                    stats.largest_free,
+                ]) {
+                    chunk.copy_from_slice(&(value as u32).to_le_bytes());
+                }
+            }
+
+            Function::AudioNewContext => {
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.audio.insert(handle, HostAudio::Context);
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioNoteOn => {
+                params.get_audio_context(self)?;
+                let params = params.get_buffer(memory)?;
+                if params.len() < Self::SIZE_NOTE_PARAMS {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                }
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.audio.insert(handle, HostAudio::Note);
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioOpenStream => {
+                params.get_audio_context(self)?;
+                let sample_rate = params.get_u32()?;
+                if !(8_000..=96_000).contains(&sample_rate) {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                }
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.audio.insert(
+                    handle,
+                    HostAudio::Stream {
+                        sample_rate,
+                        end: Instant::now(),
+                    },
+                );
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::AudioWrite => {
+                let handle = params.get_usize()?;
+                let buf = params.get_buffer(memory)?;
+                let Some(HostAudio::Stream { sample_rate, end }) = self.audio.get_mut(&handle)
+                else {
+                    return Err(WasmRuntimeErrorKind::InvalidParameter);
+                };
+                // Samples are discarded at the rate they would be played, up to one second queued
+                let now = Instant::now();
+                *end = (*end).max(now);
+                let queued = ((*end - now).as_secs_f64() * *sample_rate as f64) as usize;
+                let len = (buf.len() / 2).min((*sample_rate as usize).saturating_sub(queued));
+                *end += Duration::from_secs_f64(len as f64 / *sample_rate as f64);
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::AudioClose => {
+                let handle = params.get_usize()?;
+                self.audio.remove(&handle);
+            }
+
+            Function::GameEnumInputs => {
+                let buf = params.get_buffer(memory)?;
+                if let Some(chunk) = buf.get_mut(..4) {
+                    chunk.copy_from_slice(&Self::GAME_SIMULATED_INPUT.to_le_bytes());
+                }
+                return Ok(WasmValue::from(1u32));
+            }
+            Function::GameGetState => {
+                let id = params.get_u32()?;
+                let buf = params.get_buffer(memory)?;
+                if id != Self::GAME_SIMULATED_INPUT && id != Self::GAME_CURRENT_INPUT {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                }
+                // The host does not read the keyboard, so nothing is pressed
+                let Some(buf) = buf.get_mut(..Self::SIZE_GAME_STATE) else {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                };
+                buf.fill(0);
+            }
+            Function::GamePollEvent => {
+                // No inputs are connected or disconnected on the host
+            }
+
+            Function::ClipboardFormat => {
+                let format = match self.clipboard {
+                    None => Self::CLIPBOARD_EMPTY,
+                    Some(HostClipboard::Text(_)) => Self::CLIPBOARD_TEXT,
+                    Some(HostClipboard::Bitmap { .. }) => Self::CLIPBOARD_BITMAP32,
+                };
+                return Ok(WasmValue::from(format));
+            }
+            Function::ClipboardGetText => {
+                let buf = params.get_buffer(memory)?;
+                let Some(HostClipboard::Text(text)) = &self.clipboard else {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                };
+                let len = buf.len().min(text.len());
+                buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+                return Ok(WasmValue::from(text.len() as u32));
+            }
+            Function::ClipboardSetText => {
+                let text = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                self.clipboard = Some(HostClipboard::Text(text.to_owned()));
+            }
+            Function::ClipboardGetBitmap => {
+                let buf = params.get_buffer(memory)?;
+                let Some(HostClipboard::Bitmap {
+                    width,
+                    height,
+                    pixels,
+                }) = &self.clipboard
+                else {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                };
+                let len = Self::CLIPBOARD_BITMAP_HEADER_SIZE + pixels.len() * 4;
+                if let Some(buf) = buf.get_mut(..len) {
+                    let (header, data) = buf.split_at_mut(Self::CLIPBOARD_BITMAP_HEADER_SIZE);
+                    header[0..4].copy_from_slice(&(*width as u32).to_le_bytes());
+                    header[4..8].copy_from_slice(&(*height as u32).to_le_bytes());
+                    for (chunk, pixel) in data.chunks_exact_mut(4).zip(pixels) {
+                        chunk.copy_from_slice(&pixel.to_le_bytes());
+                    }
+                }
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::ClipboardSetBitmap => {
+                let width = params.get_usize()?;
+                let height = params.get_usize()?;
+                let buf = params.get_buffer(memory)?;
+                let len = width * height;
+                if width == 0
+                    || height == 0
+                    || width > Self::MAX_CLIPBOARD_BITMAP_SIZE
+                    || height > Self::MAX_CLIPBOARD_BITMAP_SIZE
+                    || buf.len() < len * 4
+                {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                }
+                let pixels = buf[..len * 4]
+                    .chunks_exact(4)
+                    .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                    .collect();
+                self.clipboard = Some(HostClipboard::Bitmap {
+                    width,
+                    height,
+                    pixels,
+                });
+            }
+
+            Function::ChannelCreate => {
+                let name = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let capacity = params.get_usize()?;
+                if name.is_empty()
+                    || name.len() > Self::CHANNEL_MAX_NAME_LEN
+                    || capacity == 0
+                    || capacity > Self::CHANNEL_MAX_CAPACITY
+                {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                }
+                if self.channel_names.contains_key(name) {
+                    return Self::encode_io_result(Err(io::ErrorKind::AlreadyExists.into()));
+                }
+                let channel = Rc::new(RefCell::new(HostChannel {
+                    capacity,
+                    queue: VecDeque::new(),
+                    is_closed: false,
+                }));
+                self.channel_names.insert(name.to_owned(), channel.clone());
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.channels.insert(
+                    handle,
+                    HostChannelHandle {
+                        name: Some(name.to_owned()),
+                        channel,
+                    },
+                );
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::ChannelConnect => {
+                let name = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let Some(channel) = self.channel_names.get(name).cloned() else {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                };
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.channels.insert(
+                    handle,
+                    HostChannelHandle {
+                        name: None,
+                        channel,
+                    },
+                );
+                return Ok(WasmValue::from(handle as u32));
+            }
+            Function::ChannelSend => {
+                let handle = params.get_usize()?;
+                let buf = params.get_buffer(memory)?;
+                let Some(handle) = self.channels.get(&handle) else {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                };
+                let mut channel = handle.channel.borrow_mut();
+                if buf.len() > Self::CHANNEL_MAX_MESSAGE_SIZE {
+                    return Self::encode_io_result(Err(io::ErrorKind::InvalidInput.into()));
+                }
+                if channel.is_closed {
+                    return Self::encode_io_result(Err(io::ErrorKind::BrokenPipe.into()));
+                }
+                if channel.queue.len() >= channel.capacity {
+                    return Self::encode_io_result(Err(io::ErrorKind::WouldBlock.into()));
+                }
+                channel.queue.push_back(buf.to_vec());
+            }
+            Function::ChannelReceive => {
+                let handle = params.get_usize()?;
+                let buf = params.get_buffer(memory)?;
+                let _flags = params.get_u32()?;
+                let Some(handle) = self.channels.get(&handle) else {
+                    return Self::encode_io_result(Err(io::ErrorKind::NotFound.into()));
+                };
+                let mut channel = handle.channel.borrow_mut();
+                let Some(message) = channel.queue.pop_front() else {
+                    // There are no other processes on the host that could send a message,
+                    // so waiting for one would never end
+                    let kind = if channel.is_closed {
+                        io::ErrorKind::BrokenPipe
+                    } else {
+                        io::ErrorKind::WouldBlock
+                    };
+                    return Self::encode_io_result(Err(kind.into()));
+                };
+                // The rest of the message is discarded if the buffer is too small
+                let len = buf.len().min(message.len());
+                buf[..len].copy_from_slice(&message[..len]);
+                return Ok(WasmValue::from(message.len() as u32));
+            }
+            Function::ChannelClose => {
+                let handle = params.get_usize()?;
+                if let Some(HostChannelHandle {
+                    name: Some(name),
+                    channel,
+                }) = self.channels.remove(&handle)
+                {
+                    self.channel_names.remove(&name);
+                    channel.borrow_mut().is_closed = true;
+                }
+            }
+
+            Function::Args => {
+                let buf = params.get_buffer(memory)?;
+                let len = Self::write_strings(buf, self.option.args.iter().map(|v| v.as_str()));
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::Environ => {
+                let buf = params.get_buffer(memory)?;
+                let environ = self
+                    .option
+                    .env
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>();
+                let len = Self::write_strings(buf, environ.iter().map(|v| v.as_str()));
+                return Ok(WasmValue::from(len as u32));
+            }
+            Function::SetEnv => {
+                let key = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                let value = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                if !Self::is_valid_env_key(key) || value.contains('\0') {
+                    return Ok((-1).into());
+                }
+                self.option.env.insert(key.to_owned(), value.to_owned());
+            }
+            Function::UnsetEnv => {
+                let key = params
+                    .get_string(memory)
+                    .ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+                if !Self::is_valid_env_key(key) {
+                    return Ok((-1).into());
+                }
+                self.option.env.remove(key);
+            }
+        }
+
+        Ok(WasmValue::I32(0))
+    }
+
+    fn encode_io_result(val: io::Result<usize>) -> Result<WasmValue, WasmRuntimeErrorKind> {
+        match val {
+            Ok(v) => Ok((v as u32).into()),
+            Err(err) => Ok(Self::encode_error(err.kind()).into()),
+        }
+    }
+
+    /// Encodes the error as a negative result, must match `megstd::sys::megos::error`
+    fn encode_error(kind: io::ErrorKind) -> i32 {
+        const ERROR_KINDS: [io::ErrorKind; 14] = [
+            io::ErrorKind::Other,
+            io::ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::AlreadyExists,
+            io::ErrorKind::NotADirectory,
+            io::ErrorKind::IsADirectory,
+            io::ErrorKind::DirectoryNotEmpty,
+            io::ErrorKind::InvalidInput,
+            io::ErrorKind::InvalidData,
+            io::ErrorKind::UnexpectedEof,
+            io::ErrorKind::OutOfMemory,
+            io::ErrorKind::Unsupported,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::BrokenPipe,
+        ];
+        let index = ERROR_KINDS.iter().position(|v| *v == kind).unwrap_or(0);
+        -1 - index as i32
+    }
+
+    /// Writes the strings terminated by NUL as much as the buffer allows, returns the total length.
+    fn write_strings<'a>(buf: &mut [u8], iter: impl Iterator<Item = &'a str>) -> usize {
+        let mut len = 0;
+        for s in iter {
+            for byte in s.bytes().chain(std::iter::once(0)) {
+                if let Some(p) = buf.get_mut(len) {
+                    *p = byte;
+                }
+                len += 1;
+            }
+        }
+        len
+    }
+
+    fn is_valid_env_key(key: &str) -> bool {
+        !key.is_empty() && !key.contains(['=', '\0'])
+    }
+
+    /// Returns whether `Open` with the options modifies the file
+    fn is_writable(options: u32) -> bool {
+        use open_flags::*;
+        (options & (WRITE | APPEND | TRUNC | CREAT)) != 0
+    }
+
+    fn open_options(&self, options: u32) -> OpenOptions {
+        use open_flags::*;
+
+        let mut result = OpenOptions::new();
+        let write = (options & (WRITE | APPEND)) != 0;
+        result
+            .read((options & READ) != 0 || !write)
+            .write(write)
+            .append((options & APPEND) != 0)
+            .truncate((options & TRUNC) != 0);
+        if (options & CREAT) != 0 {
+            if (options & EXCL) != 0 {
+                result.create_new(true);
+            } else {
+                result.create(true);
+            }
+        }
+        result
+    }
+
+    /// Writes the metadata in the layout of `megstd::sys::megos::fs::OsStat`
+    fn encode_stat(metadata: &fs::Metadata, buf: &mut [u8]) -> io::Result<usize> {
+        let buf = buf
+            .get_mut(..Self::SIZE_STAT)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        let file_type = metadata.file_type();
+        let file_type: u32 = if file_type.is_dir() {
+            2
+        } else if file_type.is_symlink() {
+            3
+        } else {
+            1
+        };
+        LE::write_u32(&mut buf[..4], file_type);
+        LE::write_u32(&mut buf[4..8], 0);
+        LE::write_u64(&mut buf[8..], metadata.len());
+        Ok(0)
+    }
+
+    /// Returns the absolute path in the application
+    fn app_path(&self, path: Option<&str>) -> Result<PathBuf, WasmRuntimeErrorKind> {
+        let path = path.ok_or(WasmRuntimeErrorKind::InvalidParameter)?;
+        let mut result = PathBuf::from("/");
+        for component in self.cwd.join(path).components() {
+            match component {
+                Component::Normal(v) => result.push(v),
+                Component::ParentDir => {
+                    result.pop();
+                }
+                _ => (),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Maps the path in the application into the sandbox directory
+    ///
+    /// Fails with `PermissionDenied` unless a capability grants the access to the path,
+    /// the most specific capability wins.
+    fn grant(&self, path: &Path, access: Access) -> io::Result<PathBuf> {
+        let granted = self
+            .option
+            .caps
+            .iter()
+            .filter(|v| path.starts_with(&v.path))
+            .max_by_key(|v| v.path.components().count())
+            .map(|v| v.access);
+        if !granted.is_some_and(|v| v >= access) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        let mut result = self.option.sandbox.clone();
+        result.extend(path.components().skip(1));
+        Ok(result)
+    }
+
+    fn alloc_file(&mut self, file: HostFile) -> io::Result<usize> {
+        for (handle, entry) in self.files.iter_mut().enumerate() {
+            if entry.is_none() {
+                *entry = Some(file);
+                return Ok(handle);
+            }
+        }
+        let handle = self.files.len();
+        if handle >= Self::MAX_FILES {
+            return Err(io::ErrorKind::OutOfMemory.into());
+        }
+        self.files.push(Some(file));
+        Ok(handle)
+    }
+
+    fn close_file(&mut self, handle: usize) {
+        if let Some(entry) = self.files.get_mut(handle) {
+            *entry = None;
+        }
+    }
+
+    /// Writes the kind and the first parameter of the window message
+    fn encode_message(buf: &mut [u8], message: [u32; 2]) {
+        buf[..Self::SIZE_WINDOW_MESSAGE].fill(0);
+        LE::write_u32(&mut buf[0..4], message[0]);
+        LE::write_u32(&mut buf[4..8], message[1]);
+    }
+
+    fn read_stdin_char() -> Option<char> {
+        let mut buf = [0u8; 4];
+        let mut stdin = io::stdin().lock();
+        stdin.read_exact(&mut buf[..1]).ok()?;
+        let len = match buf[0] {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        stdin.read_exact(&mut buf[1..len]).ok()?;
+        std::str::from_utf8(&buf[..len])
+            .ok()
+            .and_then(|v| v.chars().next())
+    }
+}
+
+/// A window rendered into an offscreen canvas
+struct HostWindow {
+    handle: usize,
+    title: String,
+    canvas: Canvas,
+    frame_no: usize,
+    timers: Vec<(usize, Instant)>,
+    back_buffer: Option<HostBackBuffer>,
+}
+
+impl HostWindow {
+    #[inline]
+    fn new(handle: usize, title: &str, canvas: Canvas) -> Self {
+        Self {
+            handle,
+            title: title.to_owned(),
+            canvas,
+            frame_no: 0,
+            timers: Vec::new(),
+            back_buffer: None,
+        }
+    }
+
+    /// Removes the timer that expires first, if it expires by `until`
+    fn next_timer(&mut self, until: Option<Instant>) -> Option<(usize, Instant)> {
+        let index = (0..self.timers.len()).min_by_key(|&i| self.timers[i].1)?;
+        if until.is_some_and(|until| self.timers[index].1 > until) {
+            return None;
+        }
+        Some(self.timers.remove(index))
+    }
+
+    /// Writes the current contents to `<dir>/<handle>_<frame>.png`
+    fn save_frame(&mut self, dir: &Path) {
+        if self.canvas.width() == 0 || self.canvas.height() == 0 {
+            return;
+        }
+        let path = dir.join(format!("{}_{:05}.png", self.handle, self.frame_no));
+        self.frame_no += 1;
+        if let Err(err) = fs::create_dir_all(dir)
+            .map_err(png::EncodingError::from)
+            .and_then(|_| self.canvas.save_png(&path))
+        {
+            eprintln!("{}: {}: {}", self.title, path.display(), err);
+        }
+    }
+}
+
+/// Bitmap in the linear memory registered by `SetBackBuffer`
+#[derive(Debug, Clone, Copy)]
+struct HostBackBuffer {
+    base: usize,
+    width: usize,
+    height: usize,
+    format: u32,
+}
+
+impl HostBackBuffer {
+    /// Reads the pixels as ARGB32 colors
+    fn pixels(&self, memory: &WasmMemory) -> Result<Vec<u32>, WasmRuntimeErrorKind> {
+        let len = self.width * self.height;
+        match self.format {
+            HostRuntime::BACK_BUFFER_INDEXED8 => Ok(memory
+                .slice(self.base, len)?
+                .iter()
+                .map(|v| canvas::palette(*v))
+                .collect()),
+            HostRuntime::BACK_BUFFER_ARGB32 => Ok(memory
+                .slice(self.base, len * 4)?
+                .chunks_exact(4)
+                .map(LE::read_u32)
+                .collect()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+}
+
+struct ParamsDecoder<'a> {
+    params: &'a [WasmUnsafeValue],
+    index: usize,
+}
+
+impl<'a> ParamsDecoder<'a> {
+    #[inline]
+    const fn new(params: &'a [WasmUnsafeValue]) -> Self {
+        Self { params, index: 0 }
+    }
+}
+
+impl ParamsDecoder<'_> {
+    #[inline]
+    fn get_u32(&mut self) -> Result<u32, WasmRuntimeErrorKind> {
+        let result = self
+            .params
+            .get(self.index)
+            .ok_or(WasmRuntimeErrorKind::InvalidParameter)
+            .map(|v| unsafe { v.get_u32() })?;
+        self.index += 1;
+        Ok(result)
+    }
+
+    #[inline]
+    fn get_i32(&mut self) -> Result<i32, WasmRuntimeErrorKind> {
+        self.get_u32().map(|v| v as i32)
+    }
+
+    #[inline]
+    fn get_usize(&mut self) -> Result<usize, WasmRuntimeErrorKind> {
+        self.get_u32().map(|v| v as usize)
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn get_buffer<'a>(
+        &mut self,
+        memory: &'a WasmMemory,
+    ) -> Result<&'a mut [u8], WasmRuntimeErrorKind> {
+        let base = self.get_usize()?;
+        let len = self.get_usize()?;
+        unsafe { memory.slice_mut(base, len) }
+    }
+
+    #[inline]
+    fn get_string<'a>(&mut self, memory: &'a WasmMemory) -> Option<&'a str> {
+        let base = self.get_usize().ok()?;
+        let len = self.get_usize().ok()?;
+        memory
+            .slice(base, len)
+            .ok()
+            .and_then(|v| std::str::from_utf8(v).ok())
+    }
+
+    /// Gets a pair of signed integers such as a point or a size
+    #[inline]
+    fn get_point(&mut self) -> Result<(isize, isize), WasmRuntimeErrorKind> {
+        let x = self.get_i32()? as isize;
+        let y = self.get_i32()? as isize;
+        Ok((x, y))
+    }
+
+    #[inline]
+    fn get_color(&mut self) -> Result<Option<u32>, WasmRuntimeErrorKind> {
+        self.get_u32().map(canvas::unpack_color)
+    }
+
+    /// Gets the base address and dimensions of an 8bpp or 32bpp bitmap
+    fn get_bitmap(
+        &mut self,
+        memory: &WasmMemory,
+    ) -> Result<(usize, usize, usize), WasmRuntimeErrorKind> {
+        const SIZE_OF_BITMAP: usize = 20;
+        let base = self.get_usize()?;
+        let array = memory.slice(base, SIZE_OF_BITMAP)?;
+
+        let base = LE::read_u32(&array[0..4]) as usize;
+        let width = LE::read_u32(&array[8..12]) as usize;
+        let height = LE::read_u32(&array[12..16]) as usize;
+
+        Ok((base, width, height))
+    }
+
+    fn get_window<'a>(
+        &mut self,
+        rt: &'a mut HostRuntime,
+    ) -> Result<&'a mut HostWindow, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        rt.windows
+            .get_mut(&handle)
+            .ok_or(WasmRuntimeErrorKind::InvalidParameter)
+    }
+
+    fn get_file<'a>(
+        &mut self,
+        rt: &'a mut HostRuntime,
+    ) -> Result<&'a mut File, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.files.get_mut(handle) {
+            Some(Some(HostFile::File(file))) => Ok(file),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    /// Returns the file or the standard stream to read or write
+    fn get_stream<'a>(
+        &mut self,
+        rt: &'a mut HostRuntime,
+    ) -> Result<&'a mut HostFile, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.files.get_mut(handle) {
+            Some(Some(HostFile::Dir(_))) | Some(None) | None => {
+                Err(WasmRuntimeErrorKind::InvalidParameter)
+            }
+            Some(Some(stream)) => Ok(stream),
+        }
+    }
+
+    fn get_audio_context(&mut self, rt: &HostRuntime) -> Result<(), WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.audio.get(&handle) {
+            Some(HostAudio::Context) => Ok(()),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+
+    fn get_dir<'a>(
+        &mut self,
+        rt: &'a mut HostRuntime,
+    ) -> Result<&'a mut Peekable<fs::ReadDir>, WasmRuntimeErrorKind> {
+        let handle = self.get_usize()?;
+        match rt.files.get_mut(handle) {
+            Some(Some(HostFile::Dir(dir))) => Ok(dir),
+            _ => Err(WasmRuntimeErrorKind::InvalidParameter),
+        }
+    }
+}
+
+/// Audio objects of the application, the host has no audio output
+enum HostAudio {
+    Context,
+    Note,
+    Stream { sample_rate: u32, end: Instant },
+}
+
+/// Contents of the clipboard, which is private to the host process
+enum HostClipboard {
+    Text(String),
+    Bitmap {
+        width: usize,
+        height: usize,
+        pixels: Vec<u32>,
+    },
+}
+
+/// Named channel, the host process is the only process that can use it
+struct HostChannel {
+    capacity: usize,
+    queue: VecDeque<Vec<u8>>,
+    is_closed: bool,
+}
+
+/// Handle of a channel, only the handle of the owner has the name of the channel
+struct HostChannelHandle {
+    name: Option<String>,
+    channel: Rc<RefCell<HostChannel>>,
+}
+
+/// Files and directories opened by the application
+enum HostFile {
+    File(File),
+    Dir(Peekable<fs::ReadDir>),
+    /// Standard streams of the host process, which are open when the application starts
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+impl HostFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Stdin => io::stdin().read(buf),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(buf),
+            Self::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(buf).and_then(|_| stdout.flush())?;
+                Ok(buf.len())
+            }
+            Self::Stderr => io::stderr().write_all(buf).map(|_| buf.len()),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+}
+
+/// Debugger console on the standard input and output
+pub struct StdioDebugConsole;
+
+impl fmt::Write for StdioDebugConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(s.as_bytes()).map_err(|_| fmt::Error)?;
+        stdout.flush().map_err(|_| fmt::Error)
+    }
+}
+
+impl WasmDebugConsole for StdioDebugConsole {
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod canvas;
+pub mod host;
+pub mod spec;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,192 @@
+// wasm-run
+// Copyright(c) 2021 The MEG-OS Project
+
+use std::{
+    env,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+};
+use wasm::{cache::WasmCache, WasmDecodeErrorKind, WasmLoader, WasmModule};
+use wasm_run::{host::*, spec::*};
+
+fn usage() -> ! {
+    let mut args = env::args_os();
+    let arg = args.next().unwrap();
+    let path = Path::new(&arg);
+    let lpc = path.file_name().unwrap();
+    eprintln!("{} [OPTIONS] INPUT [ARGS...]", lpc.to_str().unwrap());
+    eprintln!("{} -wast [-verbose] SCRIPT...", lpc.to_str().unwrap());
+    eprintln!();
+    eprintln!("  -sandbox DIR   root directory of the files visible to the app");
+    eprintln!("  -env KEY=VALUE set an environment variable of the app");
+    eprintln!("  -r PATH        grant read access to PATH in the sandbox");
+    eprintln!("  -w PATH        grant read and write access to PATH in the sandbox");
+    eprintln!("                 (overrides the manifest, nothing is accessible without both)");
+    eprintln!("  -frames DIR    write window contents to DIR as PNG frames");
+    eprintln!("  -debug         run INPUT under the interactive debugger");
+    eprintln!("  -cache DIR     keep precompiled modules in DIR, outside the sandbox");
+    eprintln!("  -wast          run WebAssembly spec-test scripts");
+    process::exit(1);
+}
+
+fn main() {
+    let mut args = env::args();
+    let _ = args.next().unwrap();
+
+    let mut option = HostOption::default();
+    let mut wast_mode = false;
+    let mut verbose = false;
+    let mut path_input = None;
+    let mut cache_dir = None;
+    let mut caps = None;
+
+    while let Some(arg) = args.next() {
+        if arg.starts_with("-") {
+            match arg.as_str() {
+                "-sandbox" => match args.next() {
+                    Some(v) => option.sandbox = PathBuf::from(v),
+                    None => usage(),
+                },
+                "-env" => match args.next().as_ref().and_then(|v| v.split_once('=')) {
+                    Some((key, value)) if !key.is_empty() => {
+                        option.env.insert(key.to_owned(), value.to_owned());
+                    }
+                    _ => usage(),
+                },
+                "-r" | "-w" => match args.next() {
+                    Some(v) if v.starts_with('/') => {
+                        caps.get_or_insert_with(Vec::new).push(Capability {
+                            path: PathBuf::from(v),
+                            access: if arg == "-r" {
+                                Access::Read
+                            } else {
+                                Access::ReadWrite
+                            },
+                        })
+                    }
+                    _ => usage(),
+                },
+                "-frames" => match args.next() {
+                    Some(v) => option.frames = Some(PathBuf::from(v)),
+                    None => usage(),
+                },
+                "-cache" => match args.next() {
+                    Some(v) => cache_dir = Some(PathBuf::from(v)),
+                    None => usage(),
+                },
+                "-debug" => {
+                    option.debug = true;
+                }
+                "-wast" => {
+                    wast_mode = true;
+                }
+                "-verbose" => {
+                    verbose = true;
+                }
+                "--" => {
+                    path_input = args.next();
+                    break;
+                }
+                _ => panic!("unknown option: {}", arg),
+            }
+        } else {
+            path_input = Some(arg);
+            break;
+        }
+    }
+
+    let path_input = match path_input {
+        Some(v) => v,
+        None => usage(),
+    };
+
+    if wast_mode {
+        let mut has_failed = false;
+        for path in Some(path_input).into_iter().chain(args) {
+            let path = PathBuf::from(path);
+            let text = fs::read_to_string(&path).expect("cannot open file");
+            match SpecRunner::new(&path, &text, verbose).run() {
+                Ok(result) => {
+                    println!("{}: {}", path.display(), result);
+                    has_failed |= result.failed > 0;
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    has_failed = true;
+                }
+            }
+        }
+        process::exit(if has_failed { 1 } else { 0 });
+    }
+
+    option.args = Some(path_input.clone()).into_iter().chain(args).collect();
+
+    if option.sandbox.as_os_str().is_empty() {
+        option.sandbox = env::current_dir().expect("cannot get current directory");
+    }
+    if let Some(cache_dir) = cache_dir.as_ref() {
+        // The cached code is run without validation, so the app must not be able to rewrite it
+        let _ = fs::create_dir_all(cache_dir);
+        let is_inside = fs::canonicalize(cache_dir)
+            .and_then(|cache_dir| Ok(cache_dir.starts_with(fs::canonicalize(&option.sandbox)?)))
+            .unwrap_or(true);
+        if is_inside {
+            eprintln!("{}: cache must be outside the sandbox", cache_dir.display());
+            process::exit(1);
+        }
+    }
+
+    let mut blob = Vec::new();
+    let mut is = File::open(&path_input).expect("cannot open file");
+    is.read_to_end(&mut blob).expect("read file");
+
+    if let Some(manifest) = WasmLoader::custom_section(&blob, Capability::SECTION_NAME) {
+        match Capability::parse_manifest(manifest) {
+            Some(v) => {
+                caps.get_or_insert(v);
+            }
+            None => {
+                eprintln!("{}: load error: bad manifest", path_input);
+                process::exit(1);
+            }
+        }
+    }
+    // Without the options or the manifest, no files are accessible
+    option.caps = caps.unwrap_or_default();
+
+    let module = match load_module(&blob, cache_dir.as_deref()) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("{}: load error: {:?}", path_input, err);
+            process::exit(1);
+        }
+    };
+
+    let exit_code = HostRuntime::new(option).run(&module);
+    process::exit(exit_code);
+}
+
+/// Instantiates the module from the cache in `cache_dir` if it is usable,
+/// otherwise loads the module and rewrites the cache.
+fn load_module(blob: &[u8], cache_dir: Option<&Path>) -> Result<WasmModule, WasmDecodeErrorKind> {
+    let Some(cache_dir) = cache_dir else {
+        return WasmLoader::instantiate(blob, HostRuntime::resolve);
+    };
+    let path = cache_dir.join(WasmCache::file_name(blob));
+
+    if let Ok(cache) = fs::read(&path) {
+        // A broken cache is not fatal, the module is decoded from the blob again
+        if let Ok(v) = WasmCache::instantiate(&cache, blob, HostRuntime::resolve) {
+            return Ok(v);
+        }
+    }
+
+    let module = WasmLoader::instantiate(blob, HostRuntime::resolve)?;
+    let cache = WasmCache::encode(&module, blob);
+    if let Err(err) = fs::create_dir_all(cache_dir).and_then(|_| fs::write(&path, cache)) {
+        eprintln!("{}: cannot write cache: {}", path.display(), err);
+    }
+    Ok(module)
+}
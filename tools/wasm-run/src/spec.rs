@@ -0,0 +1,253 @@
+// WebAssembly spec-test (.wast) runner
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+use wasm::{intr::*, *};
+use wast::{
+    core::{NanPattern, WastArgCore, WastRetCore},
+    parser::{self, ParseBuffer},
+    QuoteWat, Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet, Wat,
+};
+
+/// Summary of a spec-test script
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpecResult {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl fmt::Display for SpecResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.passed, self.failed, self.skipped
+        )
+    }
+}
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip,
+}
+
+/// Runs the directives of spec-test scripts against the interpreter
+pub struct SpecRunner<'a> {
+    path: &'a Path,
+    text: &'a str,
+    instances: Vec<WasmModule>,
+    current: Option<usize>,
+    named: BTreeMap<String, usize>,
+    result: SpecResult,
+    verbose: bool,
+}
+
+impl<'a> SpecRunner<'a> {
+    pub fn new(path: &'a Path, text: &'a str, verbose: bool) -> Self {
+        Self {
+            path,
+            text,
+            instances: Vec::new(),
+            current: None,
+            named: BTreeMap::new(),
+            result: SpecResult::default(),
+            verbose,
+        }
+    }
+
+    /// Runs all directives in the script
+    pub fn run(mut self) -> Result<SpecResult, String> {
+        let buf = ParseBuffer::new(self.text).map_err(|err| self.format_error(err))?;
+        let wast = parser::parse::<Wast>(&buf).map_err(|err| self.format_error(err))?;
+
+        for directive in wast.directives {
+            let span = directive.span();
+            let outcome = match panic::catch_unwind(AssertUnwindSafe(|| self.directive(directive)))
+            {
+                Ok(v) => v,
+                Err(_) => Outcome::Fail("interpreter panicked".to_owned()),
+            };
+            match outcome {
+                Outcome::Pass => self.result.passed += 1,
+                Outcome::Skip => self.result.skipped += 1,
+                Outcome::Fail(reason) => {
+                    self.result.failed += 1;
+                    let (line, col) = span.linecol_in(self.text);
+                    println!(
+                        "{}:{}:{}: {}",
+                        self.path.display(),
+                        line + 1,
+                        col + 1,
+                        reason
+                    );
+                }
+            }
+        }
+
+        Ok(self.result)
+    }
+
+    fn format_error(&self, mut err: wast::Error) -> String {
+        err.set_path(self.path);
+        err.set_text(self.text);
+        err.to_string()
+    }
+
+    fn directive(&mut self, directive: WastDirective) -> Outcome {
+        match directive {
+            WastDirective::Wat(mut module) => {
+                let name = Self::module_name(&module);
+                self.current = None;
+                match Self::instantiate(&mut module) {
+                    Ok(module) => {
+                        let index = self.instances.len();
+                        self.instances.push(module);
+                        if let Some(name) = name {
+                            self.named.insert(name, index);
+                        }
+                        self.current = Some(index);
+                        Outcome::Pass
+                    }
+                    Err(err) => Outcome::Fail(err),
+                }
+            }
+            WastDirective::AssertMalformed { mut module, .. }
+            | WastDirective::AssertInvalid { mut module, .. } => {
+                match Self::instantiate(&mut module) {
+                    Ok(_) => Outcome::Fail("module was accepted".to_owned()),
+                    Err(_) => Outcome::Pass,
+                }
+            }
+            WastDirective::Invoke(invoke) => match self.invoke(&invoke) {
+                Some(Ok(_)) => Outcome::Pass,
+                Some(Err(err)) => Outcome::Fail(err),
+                None => Outcome::Skip,
+            },
+            WastDirective::AssertReturn { exec, results, .. } => {
+                let WastExecute::Invoke(invoke) = exec else {
+                    return Outcome::Skip;
+                };
+                match self.invoke(&invoke) {
+                    Some(Ok(actual)) => Self::check_results(actual, &results),
+                    Some(Err(err)) => Outcome::Fail(format!("{}: {}", invoke.name, err)),
+                    None => Outcome::Skip,
+                }
+            }
+            WastDirective::AssertTrap { exec, message, .. } => {
+                let WastExecute::Invoke(invoke) = exec else {
+                    return Outcome::Skip;
+                };
+                match self.invoke(&invoke) {
+                    Some(Ok(_)) => {
+                        Outcome::Fail(format!("{}: expected trap \"{}\"", invoke.name, message))
+                    }
+                    Some(Err(_)) => Outcome::Pass,
+                    None => Outcome::Skip,
+                }
+            }
+            // Exhausting the host stack would abort the runner itself
+            WastDirective::AssertExhaustion { .. } => Outcome::Skip,
+            _ => Outcome::Skip,
+        }
+    }
+
+    fn module_name(module: &QuoteWat) -> Option<String> {
+        match module {
+            QuoteWat::Wat(Wat::Module(module)) => module.id.map(|v| v.name().to_owned()),
+            _ => None,
+        }
+    }
+
+    fn instantiate(module: &mut QuoteWat) -> Result<WasmModule, String> {
+        let blob = module.encode().map_err(|err| err.to_string())?;
+        Self::load(&blob)
+    }
+
+    fn load(blob: &[u8]) -> Result<WasmModule, String> {
+        let module = WasmLoader::instantiate(blob, |mod_name, _name, _type_ref| match mod_name {
+            "spectest" => ImportResult::Ok(Self::spectest_print),
+            _ => ImportResult::NoModule,
+        })
+        .map_err(|err| format!("{:?}", err))?;
+
+        if let Ok(start) = module.entry_point() {
+            start
+                .invoke(&[])
+                .map_err(|err| format!("start: {:?}", err))?;
+        }
+
+        Ok(module)
+    }
+
+    /// `spectest.print*`
+    fn spectest_print(
+        _: &WasmModule,
+        _: &[WasmUnsafeValue],
+    ) -> Result<WasmValue, WasmRuntimeErrorKind> {
+        Ok(WasmValue::I32(0))
+    }
+
+    fn invoke(&self, invoke: &WastInvoke) -> Option<Result<Option<WasmValue>, String>> {
+        let module = match invoke.module {
+            Some(id) => self.named.get(id.name())?,
+            None => self.current.as_ref()?,
+        };
+        let module = self.instances.get(*module)?;
+
+        let mut args = Vec::with_capacity(invoke.args.len());
+        for arg in &invoke.args {
+            let value = match arg {
+                WastArg::Core(WastArgCore::I32(v)) => WasmValue::I32(*v),
+                WastArg::Core(WastArgCore::I64(v)) => WasmValue::I64(*v),
+                WastArg::Core(WastArgCore::F32(v)) => WasmValue::F32(f32::from_bits(v.bits)),
+                WastArg::Core(WastArgCore::F64(v)) => WasmValue::F64(f64::from_bits(v.bits)),
+                _ => return None,
+            };
+            args.push(value);
+        }
+
+        if self.verbose {
+            println!("invoke {}{:?}", invoke.name, args);
+        }
+
+        let function = match module.func(invoke.name) {
+            Ok(v) => v,
+            Err(err) => return Some(Err(format!("{}: {:?}", invoke.name, err))),
+        };
+        Some(function.invoke(&args).map_err(|err| format!("{:?}", err)))
+    }
+
+    fn check_results(actual: Option<WasmValue>, expected: &[WastRet]) -> Outcome {
+        let expected = match expected {
+            [] => None,
+            [WastRet::Core(v)] => Some(v),
+            _ => return Outcome::Skip,
+        };
+        let matches = match (actual, expected) {
+            (None, None) => true,
+            (Some(WasmValue::I32(a)), Some(WastRetCore::I32(b))) => a == *b,
+            (Some(WasmValue::I64(a)), Some(WastRetCore::I64(b))) => a == *b,
+            (Some(WasmValue::F32(a)), Some(WastRetCore::F32(b))) => match b {
+                NanPattern::CanonicalNan | NanPattern::ArithmeticNan => a.is_nan(),
+                NanPattern::Value(b) => a.to_bits() == b.bits,
+            },
+            (Some(WasmValue::F64(a)), Some(WastRetCore::F64(b))) => match b {
+                NanPattern::CanonicalNan | NanPattern::ArithmeticNan => a.is_nan(),
+                NanPattern::Value(b) => a.to_bits() == b.bits,
+            },
+            (_, Some(WastRetCore::V128(_))) => return Outcome::Skip,
+            _ => false,
+        };
+        if matches {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(format!("expected {:?}, but got {:?}", expected, actual))
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// test
+
+use crate::host::*;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+use wasm::{WasmLoader, WasmModule};
+use wast::{
+    parser::{self, ParseBuffer},
+    Wat,
+};
+
+/// Writes "Hello, world!" to `/hello.txt` and exits with 42
+const HELLO_WAT: &str = r#"
+(module
+    (import "megos-canary" "svc1" (func $svc1 (param i32 i32) (result i32)))
+    (import "megos-canary" "svc3" (func $svc3 (param i32 i32 i32 i32) (result i32)))
+    (memory 1)
+    (data (i32.const 16) "/hello.txt")
+    (data (i32.const 32) "Hello, world!")
+    (func (export "_start")
+        (local $fd i32)
+        ;; open(path, WRITE | TRUNC | CREAT)
+        (local.set $fd (call $svc3 (i32.const 107) (i32.const 16) (i32.const 10) (i32.const 0x62)))
+        (block $ok
+            (br_if $ok (i32.ge_s (local.get $fd) (i32.const 0)))
+            (drop (call $svc1 (i32.const 0) (i32.const 1)))
+        )
+        ;; write(fd, buf)
+        (drop (call $svc3 (i32.const 110) (local.get $fd) (i32.const 32) (i32.const 13)))
+        ;; close(fd)
+        (drop (call $svc1 (i32.const 108) (local.get $fd)))
+        ;; exit(42)
+        (drop (call $svc1 (i32.const 0) (i32.const 42)))
+    )
+)
+"#;
+
+fn load_wat(text: &str) -> WasmModule {
+    let buf = ParseBuffer::new(text).unwrap();
+    let mut wat = parser::parse::<Wat>(&buf).unwrap();
+    let blob = wat.encode().unwrap();
+    WasmLoader::instantiate(&blob, HostRuntime::resolve).unwrap()
+}
+
+fn make_sandbox(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("wasm-run-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Runs [`HELLO_WAT`] with the root of the sandbox granted with `access`
+fn run_hello_in(sandbox: &Path, access: Access) -> i32 {
+    let module = load_wat(HELLO_WAT);
+    let option = HostOption {
+        sandbox: sandbox.to_owned(),
+        args: vec!["hello".to_owned()],
+        caps: vec![Capability {
+            path: PathBuf::from("/"),
+            access,
+        }],
+        ..Default::default()
+    };
+    HostRuntime::new(option).run(&module)
+}
+
+#[test]
+fn run_hello() {
+    let sandbox = make_sandbox("hello");
+    let exit_code = run_hello_in(&sandbox, Access::ReadWrite);
+    let contents = fs::read_to_string(sandbox.join("hello.txt"));
+    let _ = fs::remove_dir_all(&sandbox);
+
+    assert_eq!(exit_code, 42);
+    assert_eq!(contents.unwrap(), "Hello, world!");
+}
+
+#[test]
+fn run_without_capability() {
+    let sandbox = make_sandbox("denied");
+    let exit_code = run_hello_in(&sandbox, Access::Read);
+    let exists = sandbox.join("hello.txt").exists();
+    let _ = fs::remove_dir_all(&sandbox);
+
+    assert_eq!(exit_code, 1);
+    assert!(!exists);
+}
+
+#[test]
+fn run_without_manifest() {
+    let sandbox = make_sandbox("default");
+    let module = load_wat(HELLO_WAT);
+    let option = HostOption {
+        sandbox: sandbox.clone(),
+        args: vec!["hello".to_owned()],
+        ..Default::default()
+    };
+    let exit_code = HostRuntime::new(option).run(&module);
+    let exists = sandbox.join("hello.txt").exists();
+    let _ = fs::remove_dir_all(&sandbox);
+
+    assert_eq!(exit_code, 1);
+    assert!(!exists);
+}
+
+#[test]
+fn open_truncates_existing_file() {
+    let sandbox = make_sandbox("truncate");
+    fs::write(
+        sandbox.join("hello.txt"),
+        "The previous contents are longer",
+    )
+    .unwrap();
+    let exit_code = run_hello_in(&sandbox, Access::ReadWrite);
+    let contents = fs::read_to_string(sandbox.join("hello.txt"));
+    let _ = fs::remove_dir_all(&sandbox);
+
+    assert_eq!(exit_code, 42);
+    assert_eq!(contents.unwrap(), "Hello, world!");
+}
@@ -0,0 +1,203 @@
+//! Scanner of WebAssembly instruction sequences
+
+use crate::wasm::*;
+
+/// An operand of the instruction that refers to a function
+#[derive(Debug, Clone, Copy)]
+pub struct WasmFuncRef {
+    /// Position of the operand in the stream
+    pub position: usize,
+    /// Length of the LEB128 encoded operand
+    pub len: usize,
+    /// Function index
+    pub index: u32,
+}
+
+pub struct WasmCodeScanner {
+    _phantom: (),
+}
+
+impl WasmCodeScanner {
+    /// Skips the local variable declarations at the beginning of a function body
+    pub fn skip_locals(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        let n_locals = stream.read_unsigned()?;
+        for _ in 0..n_locals {
+            stream.read_unsigned()?;
+            stream.read_val_type()?;
+        }
+        Ok(())
+    }
+
+    /// Scans an expression up to the `end` that terminates it,
+    /// and calls `f` for each operand that refers to a function.
+    pub fn scan_expr<F>(stream: &mut Leb128Stream, mut f: F) -> Result<(), WasmDecodeErrorType>
+    where
+        F: FnMut(WasmFuncRef),
+    {
+        let mut depth = 0usize;
+        loop {
+            let opcode = stream.read_byte()?;
+            match opcode {
+                // unreachable, nop, else, return, drop, select
+                0x00 | 0x01 | 0x05 | 0x0F | 0x1A | 0x1B => (),
+                // block, loop, if
+                0x02..=0x04 => {
+                    stream.read_signed()?;
+                    depth += 1;
+                }
+                // end
+                0x0B => {
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                    depth -= 1;
+                }
+                // br, br_if
+                0x0C | 0x0D => {
+                    stream.read_unsigned()?;
+                }
+                // br_table
+                0x0E => {
+                    let n_labels = stream.read_unsigned()?;
+                    for _ in 0..=n_labels {
+                        stream.read_unsigned()?;
+                    }
+                }
+                // call, return_call, ref.func
+                0x10 | 0x12 | 0xD2 => {
+                    let position = stream.position();
+                    let index = stream.read_unsigned()? as u32;
+                    f(WasmFuncRef {
+                        position,
+                        len: stream.position() - position,
+                        index,
+                    });
+                }
+                // call_indirect, return_call_indirect
+                0x11 | 0x13 => {
+                    stream.read_unsigned()?;
+                    stream.read_unsigned()?;
+                }
+                // select t*
+                0x1C => {
+                    let n_types = stream.read_unsigned()?;
+                    for _ in 0..n_types {
+                        stream.read_val_type()?;
+                    }
+                }
+                // local.*, global.*, table.get, table.set
+                0x20..=0x26 => {
+                    stream.read_unsigned()?;
+                }
+                // load, store
+                0x28..=0x3E => Self::skip_memarg(stream)?,
+                // memory.size, memory.grow
+                0x3F | 0x40 => {
+                    stream.read_unsigned()?;
+                }
+                // i32.const, i64.const
+                0x41 | 0x42 => {
+                    stream.read_signed()?;
+                }
+                // f32.const
+                0x43 => {
+                    stream.get_bytes(4)?;
+                }
+                // f64.const
+                0x44 => {
+                    stream.get_bytes(8)?;
+                }
+                // numeric instructions
+                0x45..=0xC4 => (),
+                // ref.null
+                0xD0 => {
+                    stream.read_byte()?;
+                }
+                // ref.is_null
+                0xD1 => (),
+                0xFC => Self::skip_prefix_fc(stream)?,
+                0xFD => Self::skip_prefix_fd(stream)?,
+                0xFE => Self::skip_prefix_fe(stream)?,
+                _ => return Err(WasmDecodeErrorType::InvalidBytecode),
+            }
+        }
+    }
+
+    /// Copies an expression from `stream` to `vec`, replacing each function index with the result of `f`.
+    pub fn rewrite_expr<F>(
+        stream: &mut Leb128Stream,
+        vec: &mut Vec<u8>,
+        mut f: F,
+    ) -> Result<(), WasmDecodeErrorType>
+    where
+        F: FnMut(u32) -> Result<u32, WasmDecodeErrorType>,
+    {
+        let start = stream.position();
+        let mut func_refs = Vec::new();
+        Self::scan_expr(stream, |v| func_refs.push(v))?;
+        let end = stream.position();
+
+        stream.set_position(start);
+        let blob = stream.get_bytes(end - start)?;
+        let mut cursor = 0;
+        for func_ref in func_refs {
+            let position = func_ref.position - start;
+            vec.extend_from_slice(&blob[cursor..position]);
+            Leb128Stream::write_unsigned(vec, f(func_ref.index)? as u64);
+            cursor = position + func_ref.len;
+        }
+        vec.extend_from_slice(&blob[cursor..]);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn skip_memarg(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        stream.read_unsigned()?;
+        stream.read_unsigned()?;
+        Ok(())
+    }
+
+    /// Miscellaneous instructions such as saturating truncation and bulk memory operations
+    fn skip_prefix_fc(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        let n_operands = match stream.read_unsigned()? {
+            0..=7 => 0,
+            // data.drop, memory.fill, elem.drop, table.grow, table.size, table.fill
+            9 | 11 | 13 | 15 | 16 | 17 => 1,
+            // memory.init, memory.copy, table.init, table.copy
+            8 | 10 | 12 | 14 => 2,
+            _ => return Err(WasmDecodeErrorType::InvalidBytecode),
+        };
+        for _ in 0..n_operands {
+            stream.read_unsigned()?;
+        }
+        Ok(())
+    }
+
+    /// SIMD instructions
+    fn skip_prefix_fd(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        match stream.read_unsigned()? {
+            // v128.load*, v128.store
+            0..=11 | 92 | 93 => Self::skip_memarg(stream),
+            // v128.const, i8x16.shuffle
+            12 | 13 => stream.get_bytes(16).map(|_| ()),
+            // extract_lane, replace_lane
+            21..=34 => stream.read_byte().map(|_| ()),
+            // v128.load*_lane, v128.store*_lane
+            84..=91 => {
+                Self::skip_memarg(stream)?;
+                stream.read_byte().map(|_| ())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Atomic instructions
+    fn skip_prefix_fe(stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        match stream.read_unsigned()? {
+            // atomic.fence
+            3 => stream.read_byte().map(|_| ()),
+            _ => Self::skip_memarg(stream),
+        }
+    }
+}
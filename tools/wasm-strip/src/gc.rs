@@ -0,0 +1,344 @@
+//! Removal of unused functions
+
+use crate::{code::*, wasm::*};
+
+/// Removes functions that cannot be reached from the exports, the start function and the tables.
+pub struct WasmFunctionGc {
+    /// New index of each function, or `None` if the function will be removed
+    func_map: Vec<Option<u32>>,
+    n_imported: u32,
+    n_removed: usize,
+}
+
+impl WasmFunctionGc {
+    pub fn analyze(sections: &[WasmSection]) -> Result<Self, WasmDecodeErrorType> {
+        let mut n_funcs = 0;
+        let mut n_imported = 0;
+        let mut roots = Vec::new();
+        let mut calls = Vec::new();
+
+        for section in sections {
+            let mut stream = section.stream();
+            match section.section_type() {
+                WasmSectionType::Import => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let import = WasmImport::from_stream(&mut stream)?;
+                        if let WasmImportDesc::Function(_) = import.desc {
+                            n_funcs += 1;
+                            n_imported += 1;
+                            calls.push(Vec::new());
+                        }
+                    }
+                }
+                WasmSectionType::Function => {
+                    n_funcs += stream.read_unsigned()? as usize;
+                }
+                WasmSectionType::Global => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        stream.read_val_type()?;
+                        stream.read_byte()?;
+                        WasmCodeScanner::scan_expr(&mut stream, |v| roots.push(v.index))?;
+                    }
+                }
+                WasmSectionType::Export => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let export = WasmExport::from_stream(&mut stream)?;
+                        if export.kind == WasmExportKind::Function {
+                            roots.push(export.index);
+                        }
+                    }
+                }
+                WasmSectionType::Start => {
+                    roots.push(stream.read_unsigned()? as u32);
+                }
+                WasmSectionType::Element => {
+                    // Any function in the tables may be called indirectly
+                    Self::rewrite_elements(&mut stream, &mut Vec::new(), |v| {
+                        roots.push(v);
+                        Ok(v)
+                    })?;
+                }
+                WasmSectionType::Code => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let mut body = Leb128Stream::from_slice(stream.read_bytes()?);
+                        WasmCodeScanner::skip_locals(&mut body)?;
+                        let mut callees = Vec::new();
+                        WasmCodeScanner::scan_expr(&mut body, |v| callees.push(v.index))?;
+                        calls.push(callees);
+                    }
+                }
+                _ => (),
+            }
+        }
+        if calls.len() != n_funcs {
+            return Err(WasmDecodeErrorType::InvalidParameter);
+        }
+
+        let mut is_live = vec![false; n_funcs];
+        while let Some(index) = roots.pop() {
+            let index = index as usize;
+            match is_live.get_mut(index) {
+                Some(true) => (),
+                Some(v) => {
+                    *v = true;
+                    roots.extend_from_slice(&calls[index]);
+                }
+                None => return Err(WasmDecodeErrorType::InvalidParameter),
+            }
+        }
+
+        let mut func_map = Vec::with_capacity(n_funcs);
+        let mut n_live = 0;
+        for is_live in is_live {
+            if is_live {
+                func_map.push(Some(n_live));
+                n_live += 1;
+            } else {
+                func_map.push(None);
+            }
+        }
+
+        Ok(Self {
+            func_map,
+            n_imported,
+            n_removed: n_funcs - n_live as usize,
+        })
+    }
+
+    /// Returns the number of functions to be removed
+    #[inline]
+    pub const fn n_removed(&self) -> usize {
+        self.n_removed
+    }
+
+    /// Returns whether the function will be kept
+    #[inline]
+    pub fn is_live(&self, index: u32) -> bool {
+        self.new_index(index).is_some()
+    }
+
+    #[inline]
+    fn new_index(&self, index: u32) -> Option<u32> {
+        self.func_map.get(index as usize).copied().flatten()
+    }
+
+    /// Returns the new index of the function that is referenced by live code,
+    /// or an error if the module refers to a function that the analysis did not see.
+    #[inline]
+    fn map_live(&self, index: u32) -> Result<u32, WasmDecodeErrorType> {
+        self.new_index(index)
+            .ok_or(WasmDecodeErrorType::InvalidParameter)
+    }
+
+    /// Returns the new contents of the section, or `None` if it does not need to be changed.
+    pub fn rewrite(&self, section: &WasmSection) -> Result<Option<Vec<u8>>, WasmDecodeErrorType> {
+        if self.n_removed == 0 {
+            return Ok(None);
+        }
+
+        let mut stream = section.stream();
+        let mut vec = Vec::with_capacity(stream.len());
+        match section.section_type() {
+            WasmSectionType::Import => {
+                let mut items = Vec::new();
+                let mut func_index = 0;
+                let n_items = stream.read_unsigned()?;
+                for _ in 0..n_items {
+                    let position = stream.position();
+                    let import = WasmImport::from_stream(&mut stream)?;
+                    let is_live = match import.desc {
+                        WasmImportDesc::Function(_) => {
+                            func_index += 1;
+                            self.is_live(func_index - 1)
+                        }
+                        _ => true,
+                    };
+                    let len = stream.position() - position;
+                    stream.set_position(position);
+                    let blob = stream.get_bytes(len)?;
+                    if is_live {
+                        items.push(blob);
+                    }
+                }
+                Leb128Stream::write_unsigned(&mut vec, items.len() as u64);
+                for item in items {
+                    vec.extend_from_slice(item);
+                }
+            }
+            WasmSectionType::Function => {
+                let base = self.n_imported;
+                let mut items = Vec::new();
+                let n_items = stream.read_unsigned()? as u32;
+                for index in 0..n_items {
+                    let type_index = stream.read_unsigned()?;
+                    if self.is_live(base + index) {
+                        items.push(type_index);
+                    }
+                }
+                Leb128Stream::write_unsigned(&mut vec, items.len() as u64);
+                for type_index in items {
+                    Leb128Stream::write_unsigned(&mut vec, type_index);
+                }
+            }
+            WasmSectionType::Global => {
+                let n_items = stream.read_unsigned()?;
+                Leb128Stream::write_unsigned(&mut vec, n_items);
+                for _ in 0..n_items {
+                    vec.extend_from_slice(stream.get_bytes(2)?);
+                    WasmCodeScanner::rewrite_expr(&mut stream, &mut vec, |v| self.map_live(v))?;
+                }
+            }
+            WasmSectionType::Export => {
+                let n_items = stream.read_unsigned()?;
+                Leb128Stream::write_unsigned(&mut vec, n_items);
+                for _ in 0..n_items {
+                    let mut export = WasmExport::from_stream(&mut stream)?;
+                    if export.kind == WasmExportKind::Function {
+                        export.index = self.map_live(export.index)?;
+                    }
+                    export.write_to_vec(&mut vec);
+                }
+            }
+            WasmSectionType::Start => {
+                let index = self.map_live(stream.read_unsigned()? as u32)?;
+                Leb128Stream::write_unsigned(&mut vec, index as u64);
+            }
+            WasmSectionType::Element => {
+                Self::rewrite_elements(&mut stream, &mut vec, |v| self.map_live(v))?;
+            }
+            WasmSectionType::Code => {
+                let base = self.n_imported;
+                let mut items = Vec::new();
+                let n_items = stream.read_unsigned()? as u32;
+                for index in 0..n_items {
+                    let mut body = Leb128Stream::from_slice(stream.read_bytes()?);
+                    if !self.is_live(base + index) {
+                        continue;
+                    }
+                    WasmCodeScanner::skip_locals(&mut body)?;
+                    let locals_len = body.position();
+                    body.reset();
+                    let mut item = body.get_bytes(locals_len)?.to_vec();
+                    WasmCodeScanner::rewrite_expr(&mut body, &mut item, |v| self.map_live(v))?;
+                    items.push(item);
+                }
+                Leb128Stream::write_unsigned(&mut vec, items.len() as u64);
+                for item in items {
+                    Leb128Stream::write_unsigned(&mut vec, item.len() as u64);
+                    vec.extend_from_slice(&item);
+                }
+            }
+            WasmSectionType::Custom => {
+                if section.custom_section_name().as_deref() != Some("name") {
+                    return Ok(None);
+                }
+                self.rewrite_names(&mut stream, &mut vec)?;
+            }
+            _ => return Ok(None),
+        }
+
+        Ok(Some(vec))
+    }
+
+    /// Copies the element section, replacing each function index with the result of `f`.
+    fn rewrite_elements<F>(
+        stream: &mut Leb128Stream,
+        vec: &mut Vec<u8>,
+        mut f: F,
+    ) -> Result<(), WasmDecodeErrorType>
+    where
+        F: FnMut(u32) -> Result<u32, WasmDecodeErrorType>,
+    {
+        let n_items = stream.read_unsigned()?;
+        Leb128Stream::write_unsigned(vec, n_items);
+        for _ in 0..n_items {
+            let flags = stream.read_unsigned()?;
+            Leb128Stream::write_unsigned(vec, flags);
+            let is_passive = (flags & 1) != 0;
+            let has_table_index = (flags & 2) != 0;
+            let uses_expr = (flags & 4) != 0;
+
+            if !is_passive {
+                if has_table_index {
+                    let table_index = stream.read_unsigned()?;
+                    Leb128Stream::write_unsigned(vec, table_index);
+                }
+                WasmCodeScanner::rewrite_expr(stream, vec, &mut f)?;
+            }
+            if is_passive || has_table_index {
+                // elemkind or reftype
+                vec.push(stream.read_byte()?);
+            }
+
+            let n_elements = stream.read_unsigned()?;
+            Leb128Stream::write_unsigned(vec, n_elements);
+            for _ in 0..n_elements {
+                if uses_expr {
+                    WasmCodeScanner::rewrite_expr(stream, vec, &mut f)?;
+                } else {
+                    let index = f(stream.read_unsigned()? as u32)?;
+                    Leb128Stream::write_unsigned(vec, index as u64);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the name section, dropping the names of removed functions.
+    fn rewrite_names(
+        &self,
+        stream: &mut Leb128Stream,
+        vec: &mut Vec<u8>,
+    ) -> Result<(), WasmDecodeErrorType> {
+        let name = stream.get_string()?;
+        Leb128Stream::write_unsigned(vec, name.len() as u64);
+        vec.extend_from_slice(name.as_bytes());
+
+        while !stream.is_eof() {
+            let name_id = stream.read_byte()?;
+            let blob = stream.read_bytes()?;
+            let mut subsection = Leb128Stream::from_slice(blob);
+            let mut payload = Vec::with_capacity(blob.len());
+            match name_id {
+                // function names, local names and label names are keyed by function index
+                1..=3 => {
+                    let mut items = Vec::new();
+                    let n_items = subsection.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let index = subsection.read_unsigned()? as u32;
+                        let position = subsection.position();
+                        if name_id == 1 {
+                            subsection.get_string()?;
+                        } else {
+                            let n_names = subsection.read_unsigned()?;
+                            for _ in 0..n_names {
+                                subsection.read_unsigned()?;
+                                subsection.get_string()?;
+                            }
+                        }
+                        let len = subsection.position() - position;
+                        subsection.set_position(position);
+                        let item = subsection.get_bytes(len)?;
+                        if let Some(index) = self.new_index(index) {
+                            items.push((index, item));
+                        }
+                    }
+                    Leb128Stream::write_unsigned(&mut payload, items.len() as u64);
+                    for (index, item) in items {
+                        Leb128Stream::write_unsigned(&mut payload, index as u64);
+                        payload.extend_from_slice(item);
+                    }
+                }
+                _ => payload.extend_from_slice(blob),
+            }
+            vec.push(name_id);
+            Leb128Stream::write_unsigned(vec, payload.len() as u64);
+            vec.extend_from_slice(&payload);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+//! Summary of WebAssembly modules
+
+use crate::{code::*, wasm::*};
+use std::{collections::BTreeMap, fmt};
+
+/// Summary of the sections of a module
+pub struct WasmModuleInfo {
+    pub sections: Vec<WasmSectionInfo>,
+    pub types: Vec<WasmType>,
+    pub imports: Vec<WasmImport>,
+    /// Type indices of the functions defined in the module
+    pub functions: Vec<u32>,
+    pub tables: Vec<(WasmValType, WasmLimit)>,
+    pub memories: Vec<WasmLimit>,
+    pub globals: Vec<(WasmValType, bool)>,
+    pub exports: Vec<WasmExport>,
+    pub start: Option<u32>,
+    /// Sizes of the function bodies in the code section
+    pub code_sizes: Vec<usize>,
+    pub func_names: BTreeMap<u32, String>,
+}
+
+/// Header of a section
+pub struct WasmSectionInfo {
+    pub section_type: WasmSectionType,
+    pub name: Option<String>,
+    pub file_position: usize,
+    pub size: usize,
+    pub count: Option<u64>,
+}
+
+impl WasmModuleInfo {
+    /// Namespace of the system calls provided by the OS
+    pub const SYSTEM_MODULE: &'static str = "megos-canary";
+
+    pub fn from_sections(sections: &[WasmSection]) -> Result<Self, WasmDecodeErrorType> {
+        let mut info = Self {
+            sections: Vec::new(),
+            types: Vec::new(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            exports: Vec::new(),
+            start: None,
+            code_sizes: Vec::new(),
+            func_names: BTreeMap::new(),
+        };
+
+        for section in sections {
+            let mut stream = section.stream();
+            let count = match section.section_type() {
+                WasmSectionType::Custom | WasmSectionType::Start => None,
+                _ => Some(stream.cloned().read_unsigned()?),
+            };
+            info.sections.push(WasmSectionInfo {
+                section_type: section.section_type(),
+                name: section.custom_section_name(),
+                file_position: WasmMiniLoader::file_header().len() + section.file_position(),
+                size: section.stream_size(),
+                count,
+            });
+
+            match section.section_type() {
+                WasmSectionType::Type => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.types.push(WasmType::from_stream(&mut stream)?);
+                    }
+                }
+                WasmSectionType::Import => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.imports.push(WasmImport::from_stream(&mut stream)?);
+                    }
+                }
+                WasmSectionType::Function => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.functions.push(stream.read_unsigned()? as u32);
+                    }
+                }
+                WasmSectionType::Table => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let ref_type = stream.read_val_type()?;
+                        info.tables.push((ref_type, stream.read_limit()?));
+                    }
+                }
+                WasmSectionType::Memory => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.memories.push(stream.read_limit()?);
+                    }
+                }
+                WasmSectionType::Global => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        let val_type = stream.read_val_type()?;
+                        let is_mutable = stream.read_byte()? != 0;
+                        WasmCodeScanner::scan_expr(&mut stream, |_| {})?;
+                        info.globals.push((val_type, is_mutable));
+                    }
+                }
+                WasmSectionType::Export => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.exports.push(WasmExport::from_stream(&mut stream)?);
+                    }
+                }
+                WasmSectionType::Start => {
+                    info.start = Some(stream.read_unsigned()? as u32);
+                }
+                WasmSectionType::Code => {
+                    let n_items = stream.read_unsigned()?;
+                    for _ in 0..n_items {
+                        info.code_sizes.push(stream.read_bytes()?.len());
+                    }
+                }
+                WasmSectionType::Custom
+                    if section.custom_section_name().as_deref() == Some("name") =>
+                {
+                    info.read_names(&mut stream)?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(info)
+    }
+
+    fn read_names(&mut self, stream: &mut Leb128Stream) -> Result<(), WasmDecodeErrorType> {
+        stream.get_string()?;
+        while !stream.is_eof() {
+            let name_id = stream.read_byte()?;
+            let blob = stream.read_bytes()?;
+            if name_id != 1 {
+                continue;
+            }
+            let mut stream = Leb128Stream::from_slice(blob);
+            let n_items = stream.read_unsigned()?;
+            for _ in 0..n_items {
+                let index = stream.read_unsigned()? as u32;
+                let name = stream.get_string()?.to_string();
+                self.func_names.insert(index, name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of imported functions
+    pub fn n_imported_funcs(&self) -> usize {
+        self.imports
+            .iter()
+            .filter(|v| matches!(v.desc, WasmImportDesc::Function(_)))
+            .count()
+    }
+
+    /// Returns the name of the function from the name section or the export section.
+    pub fn func_name(&self, index: u32) -> Option<&str> {
+        self.func_names.get(&index).map(|v| v.as_str()).or_else(|| {
+            self.exports
+                .iter()
+                .find(|v| v.kind == WasmExportKind::Function && v.index == index)
+                .map(|v| v.name.as_str())
+        })
+    }
+
+    /// Returns imports that are not provided by the OS
+    pub fn foreign_imports(&self) -> impl Iterator<Item = &WasmImport> {
+        self.imports
+            .iter()
+            .filter(|v| v.module != Self::SYSTEM_MODULE)
+    }
+
+    fn write_func_name<W: fmt::Write>(&self, f: &mut W, index: u32) -> fmt::Result {
+        match self.func_name(index) {
+            Some(name) => write!(f, " <{}>", name),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the headers and the details of the sections, like `wasm-objdump -x`
+    pub fn write_dump<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        writeln!(f, "Sections:")?;
+        for section in &self.sections {
+            write!(
+                f,
+                "{:>9} start={:#010x} end={:#010x} (size={:#010x})",
+                format!("{:?}", section.section_type),
+                section.file_position,
+                section.file_position + section.size,
+                section.size,
+            )?;
+            if let Some(count) = section.count {
+                write!(f, " count: {}", count)?;
+            }
+            if let Some(name) = section.name.as_ref() {
+                write!(f, " \"{}\"", name)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Section Details:")?;
+
+        if !self.types.is_empty() {
+            writeln!(f, "Type[{}]:", self.types.len())?;
+            for (index, func_type) in self.types.iter().enumerate() {
+                writeln!(f, " - type[{}] {}", index, func_type)?;
+            }
+        }
+
+        if !self.imports.is_empty() {
+            writeln!(f, "Import[{}]:", self.imports.len())?;
+            let mut func_index = 0;
+            for import in &self.imports {
+                match import.desc {
+                    WasmImportDesc::Function(type_index) => {
+                        write!(f, " - func[{}] sig={}", func_index, type_index)?;
+                        self.write_func_name(f, func_index)?;
+                        func_index += 1;
+                    }
+                    WasmImportDesc::Table(ref_type, limit) => {
+                        write!(f, " - table type={} {}", ref_type, limit)?
+                    }
+                    WasmImportDesc::Memory(limit) => write!(f, " - memory pages: {}", limit)?,
+                    WasmImportDesc::Global(val_type, is_mutable) => {
+                        write!(f, " - global {} mutable={}", val_type, is_mutable as usize)?
+                    }
+                }
+                writeln!(f, " <- {}.{}", import.module, import.name)?;
+            }
+        }
+
+        if !self.functions.is_empty() {
+            writeln!(f, "Function[{}]:", self.functions.len())?;
+            let base = self.n_imported_funcs() as u32;
+            for (index, type_index) in self.functions.iter().enumerate() {
+                let func_index = base + index as u32;
+                write!(f, " - func[{}] sig={}", func_index, type_index)?;
+                self.write_func_name(f, func_index)?;
+                writeln!(f)?;
+            }
+        }
+
+        if !self.tables.is_empty() {
+            writeln!(f, "Table[{}]:", self.tables.len())?;
+            for (index, (ref_type, limit)) in self.tables.iter().enumerate() {
+                writeln!(f, " - table[{}] type={} {}", index, ref_type, limit)?;
+            }
+        }
+
+        if !self.memories.is_empty() {
+            writeln!(f, "Memory[{}]:", self.memories.len())?;
+            for (index, limit) in self.memories.iter().enumerate() {
+                writeln!(f, " - memory[{}] pages: {}", index, limit)?;
+            }
+        }
+
+        if !self.globals.is_empty() {
+            writeln!(f, "Global[{}]:", self.globals.len())?;
+            for (index, (val_type, is_mutable)) in self.globals.iter().enumerate() {
+                writeln!(
+                    f,
+                    " - global[{}] {} mutable={}",
+                    index, val_type, *is_mutable as usize
+                )?;
+            }
+        }
+
+        if !self.exports.is_empty() {
+            writeln!(f, "Export[{}]:", self.exports.len())?;
+            for export in &self.exports {
+                let kind = match export.kind {
+                    WasmExportKind::Function => "func",
+                    WasmExportKind::Table => "table",
+                    WasmExportKind::Memory => "memory",
+                    WasmExportKind::Global => "global",
+                };
+                writeln!(f, " - {}[{}] -> \"{}\"", kind, export.index, export.name)?;
+            }
+        }
+
+        if let Some(start) = self.start {
+            write!(f, "Start:\n - start function: {}", start)?;
+            self.write_func_name(f, start)?;
+            writeln!(f)?;
+        }
+
+        if !self.code_sizes.is_empty() {
+            writeln!(f, "Code[{}]:", self.code_sizes.len())?;
+            let base = self.n_imported_funcs() as u32;
+            for (index, size) in self.code_sizes.iter().enumerate() {
+                let func_index = base + index as u32;
+                write!(f, " - func[{}] size={}", func_index, size)?;
+                self.write_func_name(f, func_index)?;
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the code sizes of the functions in descending order
+    pub fn write_sizes<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        let base = self.n_imported_funcs() as u32;
+        let mut sizes = self
+            .code_sizes
+            .iter()
+            .enumerate()
+            .map(|(index, size)| (base + index as u32, *size))
+            .collect::<Vec<_>>();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let total = sizes.iter().map(|v| v.1).sum::<usize>();
+        for (func_index, size) in sizes {
+            write!(
+                f,
+                "{:8} {:6.2}% func[{}]",
+                size,
+                (100.0 * size as f64) / total.max(1) as f64,
+                func_index
+            )?;
+            self.write_func_name(f, func_index)?;
+            writeln!(f)?;
+        }
+        writeln!(f, "{:8} total", total)
+    }
+}
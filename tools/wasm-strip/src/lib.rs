@@ -1,2 +1,9 @@
 extern crate alloc;
+pub mod code;
+pub mod gc;
+pub mod info;
+pub mod strip;
 pub mod wasm;
+
+#[cfg(test)]
+mod tests;
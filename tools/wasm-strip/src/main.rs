@@ -9,7 +9,7 @@ use std::{
     path::Path,
     process,
 };
-use wasm_strip::wasm::*;
+use wasm_strip::{gc::*, info::*, strip::*, wasm::*};
 
 fn usage() -> ! {
     let mut args = env::args_os();
@@ -17,6 +17,19 @@ fn usage() -> ! {
     let path = Path::new(&arg);
     let lpc = path.file_name().unwrap();
     eprintln!("{} [OPTIONS] INPUT OUTPUT", lpc.to_str().unwrap());
+    eprintln!();
+    eprintln!("  -strip NAME      drop the custom section NAME");
+    eprintln!("  -strip-all       drop all custom sections");
+    eprintln!("  -strip-export    drop the export section");
+    eprintln!("  -preserve NAME   keep the custom section NAME");
+    eprintln!("  -gc              remove functions unreachable from exports, start and tables");
+    eprintln!(
+        "  -verify-imports  fail if any import is not provided by {}",
+        WasmModuleInfo::SYSTEM_MODULE
+    );
+    eprintln!("  -dump            print the sections of INPUT and exit");
+    eprintln!("  -sizes           print the code size of each function of INPUT and exit");
+    eprintln!("  -overwrite       write OUTPUT even if nothing was stripped");
     process::exit(1);
 }
 
@@ -24,12 +37,13 @@ fn main() {
     let mut args = env::args();
     let _ = args.next().unwrap();
 
-    let mut strip_all = false;
+    let mut option = WasmStripOption::default();
     let mut will_overwrite = false;
-    let mut strip_names = Vec::new();
-    let mut preserved_names = Vec::new();
     let mut path_input = None;
-    let mut strip_export = false;
+    let mut gc = false;
+    let mut verify_imports = false;
+    let mut dump = false;
+    let mut sizes = false;
 
     while let Some(arg) = args.next() {
         if arg.starts_with("-") {
@@ -38,17 +52,29 @@ fn main() {
                     will_overwrite = true;
                 }
                 "-strip-all" => {
-                    strip_all = true;
+                    option.strip_all = true;
                 }
                 "-strip-export" => {
-                    strip_export = true;
+                    option.strip_export = true;
+                }
+                "-gc" => {
+                    gc = true;
+                }
+                "-verify-imports" => {
+                    verify_imports = true;
+                }
+                "-dump" => {
+                    dump = true;
+                }
+                "-sizes" => {
+                    sizes = true;
                 }
                 "-strip" => match args.next() {
-                    Some(v) => strip_names.push(v),
+                    Some(v) => option.strip_names.push(v),
                     None => usage(),
                 },
                 "-preserve" => match args.next() {
-                    Some(v) => preserved_names.push(v),
+                    Some(v) => option.preserved_names.push(v),
                     None => usage(),
                 },
                 "--" => {
@@ -87,25 +113,50 @@ fn main() {
         }
         let sections = WasmMiniLoader::load_sections(ib.as_slice()).unwrap();
 
+        if dump || sizes {
+            let info = WasmModuleInfo::from_sections(&sections).unwrap();
+            let mut s = String::new();
+            if dump {
+                info.write_dump(&mut s).unwrap();
+            }
+            if sizes {
+                if dump {
+                    s.push('\n');
+                }
+                info.write_sizes(&mut s).unwrap();
+            }
+            print!("{}", s);
+            if verify_imports && !verify(&info) {
+                process::exit(1);
+            }
+            return;
+        }
+
+        let gc = if gc {
+            let gc = WasmFunctionGc::analyze(&sections).expect("cannot analyze functions");
+            if gc.n_removed() > 0 {
+                println!("REMOVED {} unused functions", gc.n_removed());
+            }
+            Some(gc)
+        } else {
+            None
+        };
+
         let mut ob = Vec::with_capacity(org_size);
         ob.extend_from_slice(&WasmMiniLoader::file_header());
 
         for (index, section) in sections.iter().enumerate() {
-            let preserved = match section.section_type() {
-                WasmSectionType::Export => !strip_export,
-                WasmSectionType::Custom => match section.custom_section_name() {
-                    Some(name) => {
-                        preserved_names.binary_search(&name).is_ok()
-                            || !(strip_all
-                                || strip_names.binary_search(&name).is_ok()
-                                || name.starts_with("."))
+            if option.is_preserved(section) {
+                let payload = match gc.as_ref() {
+                    Some(gc) => gc.rewrite(section).expect("cannot rewrite section"),
+                    None => None,
+                };
+                match payload {
+                    Some(payload) => {
+                        WasmSection::write_payload(&mut ob, section.section_type(), &payload)
                     }
-                    None => false,
-                },
-                _ => true,
-            };
-            if preserved {
-                section.write_to_vec(&mut ob);
+                    None => section.write_to_vec(&mut ob),
+                }
             } else {
                 println!(
                     "DROPPED section #{} {} ({:?} {}) file: {}, {}",
@@ -121,6 +172,14 @@ fn main() {
 
         let out_size = ob.len();
 
+        if verify_imports {
+            let sections = WasmMiniLoader::load_sections(ob.as_slice()).unwrap();
+            let info = WasmModuleInfo::from_sections(&sections).unwrap();
+            if !verify(&info) {
+                process::exit(1);
+            }
+        }
+
         if !will_overwrite && is_same_file && org_size <= out_size {
             println!("There is no more data in the file that can be stripped.");
         } else {
@@ -137,3 +196,18 @@ fn main() {
         }
     }
 }
+
+/// Reports imports that are not provided by the OS
+fn verify(info: &WasmModuleInfo) -> bool {
+    let mut is_valid = true;
+    for import in info.foreign_imports() {
+        eprintln!(
+            "error: unresolved import {}.{}, not provided by {}",
+            import.module,
+            import.name,
+            WasmModuleInfo::SYSTEM_MODULE
+        );
+        is_valid = false;
+    }
+    is_valid
+}
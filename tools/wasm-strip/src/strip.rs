@@ -0,0 +1,35 @@
+//! Selection of the sections to be dropped
+
+use crate::wasm::*;
+
+/// Options that select the sections to be dropped
+#[derive(Debug, Default)]
+pub struct WasmStripOption {
+    /// Drop all custom sections but the preserved ones
+    pub strip_all: bool,
+    /// Drop the export section
+    pub strip_export: bool,
+    /// Names of the custom sections to be dropped
+    pub strip_names: Vec<String>,
+    /// Names of the custom sections to be kept in any case
+    pub preserved_names: Vec<String>,
+}
+
+impl WasmStripOption {
+    /// Returns whether the section is kept in the output
+    pub fn is_preserved(&self, section: &WasmSection) -> bool {
+        match section.section_type() {
+            WasmSectionType::Export => !self.strip_export,
+            WasmSectionType::Custom => match section.custom_section_name() {
+                Some(name) => {
+                    self.preserved_names.contains(&name)
+                        || !(self.strip_all
+                            || self.strip_names.contains(&name)
+                            || name.starts_with("."))
+                }
+                None => false,
+            },
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// test
+
+use crate::{gc::*, info::*, strip::*, wasm::*};
+use wasm::{
+    intr::WasmInvocation, ImportResult, WasmLoader, WasmModule, WasmRuntimeErrorKind,
+    WasmUnsafeValue, WasmValue,
+};
+use wast::{
+    parser::{self, ParseBuffer},
+    Wat,
+};
+
+/// `_start` returns 42 through an import, a direct call and an indirect call,
+/// while `$dead`, `$dead_callee` and `$unused` are unreachable.
+const GC_WAT: &str = r#"
+(module
+    (import "megos-canary" "svc0" (func $svc0 (param i32) (result i32)))
+    (import "env" "unused" (func $unused (param i32) (result i32)))
+    (type $t (func (result i32)))
+    (table 1 funcref)
+    (elem (i32.const 0) $indirect)
+    (func $dead (result i32) (call $dead_callee))
+    (func $dead_callee (result i32) (call $unused (i32.const 1)))
+    (func $answer (result i32) (i32.const 30))
+    (func $indirect (result i32) (i32.const 2))
+    (func $start (export "_start") (result i32)
+        (i32.add
+            (i32.add (call $answer) (call_indirect (type $t) (i32.const 0)))
+            (call $svc0 (i32.const 10))
+        )
+    )
+)
+"#;
+
+fn encode_wat(text: &str) -> Vec<u8> {
+    let buf = ParseBuffer::new(text).unwrap();
+    let mut wat = parser::parse::<Wat>(&buf).unwrap();
+    wat.encode().unwrap()
+}
+
+/// Runs the module through the function GC like `wasm-strip -gc`
+fn gc(blob: &[u8]) -> (WasmFunctionGc, Vec<u8>) {
+    let sections = WasmMiniLoader::load_sections(blob).unwrap();
+    let gc = WasmFunctionGc::analyze(&sections).unwrap();
+    let mut vec = WasmMiniLoader::file_header().to_vec();
+    for section in &sections {
+        match gc.rewrite(section).unwrap() {
+            Some(payload) => WasmSection::write_payload(&mut vec, section.section_type(), &payload),
+            None => section.write_to_vec(&mut vec),
+        }
+    }
+    (gc, vec)
+}
+
+fn svc0(_: &WasmModule, args: &[WasmUnsafeValue]) -> Result<WasmValue, WasmRuntimeErrorKind> {
+    Ok(WasmValue::I32(unsafe { args[0].get_i32() }))
+}
+
+fn instantiate(blob: &[u8]) -> WasmModule {
+    WasmLoader::instantiate(blob, |mod_name, name, _| match (mod_name, name) {
+        ("megos-canary", "svc0") => ImportResult::Ok(svc0),
+        ("env", "unused") => ImportResult::Ok(svc0),
+        _ => ImportResult::NoMethod,
+    })
+    .unwrap()
+}
+
+fn module_info(blob: &[u8]) -> WasmModuleInfo {
+    let sections = WasmMiniLoader::load_sections(blob).unwrap();
+    WasmModuleInfo::from_sections(&sections).unwrap()
+}
+
+#[test]
+fn gc_removes_unreachable_functions() {
+    let blob = encode_wat(GC_WAT);
+    let (gc, stripped) = gc(&blob);
+    assert_eq!(gc.n_removed(), 3);
+    assert!(stripped.len() < blob.len());
+
+    let info = module_info(&stripped);
+    assert_eq!(info.imports.len(), 1);
+    assert_eq!(info.n_imported_funcs(), 1);
+    assert_eq!(info.functions.len(), 3);
+    let names = info.func_names.values().cloned().collect::<Vec<_>>();
+    assert_eq!(names, ["svc0", "answer", "indirect", "start"]);
+
+    // The rewritten indices still point to the same functions
+    let module = instantiate(&stripped);
+    let result = module.func("_start").unwrap().invoke(&[]).unwrap();
+    assert_eq!(result.unwrap().get_i32().unwrap(), 42);
+}
+
+#[test]
+fn gc_keeps_everything_reachable() {
+    let blob = encode_wat(
+        r#"(module
+            (func $a (export "a") (call $b))
+            (func $b)
+            (start $b)
+        )"#,
+    );
+    let (gc, stripped) = gc(&blob);
+    assert_eq!(gc.n_removed(), 0);
+    assert_eq!(stripped, blob);
+    instantiate(&stripped);
+}
+
+#[test]
+fn gc_rejects_unknown_function() {
+    let blob = encode_wat(r#"(module (func (export "a")) (func))"#);
+    let sections = WasmMiniLoader::load_sections(&blob).unwrap();
+    let gc = WasmFunctionGc::analyze(&sections).unwrap();
+    assert_eq!(gc.n_removed(), 1);
+
+    // The export refers to the function that has been found dead
+    let other = encode_wat(r#"(module (func) (func (export "a")))"#);
+    let sections = WasmMiniLoader::load_sections(&other).unwrap();
+    let export = sections
+        .iter()
+        .find(|v| v.section_type() == WasmSectionType::Export)
+        .unwrap();
+    assert!(gc.rewrite(export).is_err());
+
+    // Calls to the functions out of range
+    let mut blob = encode_wat(r#"(module (func (export "a") (call 0)))"#);
+    let position = blob.windows(2).position(|v| v == [0x10, 0x00]).unwrap();
+    blob[position + 1] = 0x05;
+    let sections = WasmMiniLoader::load_sections(&blob).unwrap();
+    assert!(WasmFunctionGc::analyze(&sections).is_err());
+}
+
+#[test]
+fn dump_and_verify_imports() {
+    let blob = encode_wat(GC_WAT);
+    let info = module_info(&blob);
+
+    let mut dump = String::new();
+    info.write_dump(&mut dump).unwrap();
+    assert!(dump.contains("megos-canary"));
+    assert!(dump.contains("_start"));
+
+    let foreign = info
+        .foreign_imports()
+        .map(|v| format!("{}.{}", v.module, v.name))
+        .collect::<Vec<_>>();
+    assert_eq!(foreign, ["env.unused"]);
+
+    // The unused import is removed by the GC, so the stripped module passes the verification
+    let (_, stripped) = gc(&blob);
+    assert_eq!(module_info(&stripped).foreign_imports().count(), 0);
+}
+
+/// Strips the module with the options that the Makefile uses for the apps,
+/// `-preserve name -preserve megos.caps -strip-all -gc`
+fn strip_app(blob: &[u8]) -> Vec<u8> {
+    let option = WasmStripOption {
+        strip_all: true,
+        preserved_names: vec!["name".to_owned(), "megos.caps".to_owned()],
+        ..Default::default()
+    };
+    let sections = WasmMiniLoader::load_sections(blob).unwrap();
+    let gc = WasmFunctionGc::analyze(&sections).unwrap();
+    let mut vec = WasmMiniLoader::file_header().to_vec();
+    for section in sections.iter().filter(|v| option.is_preserved(v)) {
+        match gc.rewrite(section).unwrap() {
+            Some(payload) => WasmSection::write_payload(&mut vec, section.section_type(), &payload),
+            None => section.write_to_vec(&mut vec),
+        }
+    }
+    vec
+}
+
+#[test]
+fn strip_all_keeps_caps() {
+    let blob = encode_wat(
+        r#"(module
+            (@custom "megos.caps" "rw /home\nr /etc\n")
+            (@custom "producers" "rustc")
+            (func $unused)
+            (func (export "_start"))
+        )"#,
+    );
+    let stripped = strip_app(&blob);
+
+    let sections = WasmMiniLoader::load_sections(&stripped).unwrap();
+    let names = sections
+        .iter()
+        .filter_map(|v| v.custom_section_name())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"name".to_owned()));
+    assert!(!names.contains(&"producers".to_owned()));
+    assert_eq!(
+        WasmLoader::custom_section(&stripped, "megos.caps"),
+        Some(b"rw /home\nr /etc\n".as_slice())
+    );
+}
+
+#[test]
+fn strip_names() {
+    let blob = encode_wat(r#"(module (@custom "a" "") (@custom "b" "") (@custom ".c" ""))"#);
+    let sections = WasmMiniLoader::load_sections(&blob).unwrap();
+    let option = WasmStripOption {
+        strip_names: vec!["a".to_owned()],
+        ..Default::default()
+    };
+    let names = sections
+        .iter()
+        .filter(|v| option.is_preserved(v))
+        .filter_map(|v| v.custom_section_name())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"b".to_owned()));
+    assert!(!names.contains(&"a".to_owned()));
+    assert!(!names.contains(&".c".to_owned()));
+}
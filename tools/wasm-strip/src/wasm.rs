@@ -1,6 +1,7 @@
 //! WemAssembly mini library (expr)
 
 use byteorder::*;
+use core::fmt;
 use core::mem::transmute;
 use core::str;
 
@@ -76,7 +77,7 @@ impl<'a> Leb128Stream<'a> {
 }
 
 #[allow(dead_code)]
-impl Leb128Stream<'_> {
+impl<'a> Leb128Stream<'a> {
     /// Returns to the origin of the stream
     #[inline]
     pub fn reset(&mut self) {
@@ -117,7 +118,7 @@ impl Leb128Stream<'_> {
     }
 
     /// Returns a slice of the specified number of bytes from the stream
-    pub fn get_bytes(&mut self, size: usize) -> Result<&[u8], WasmDecodeErrorType> {
+    pub fn get_bytes(&mut self, size: usize) -> Result<&'a [u8], WasmDecodeErrorType> {
         let limit = self.blob.len();
         if self.position <= limit && size <= limit && self.position + size <= limit {
             let offset = self.position;
@@ -130,7 +131,7 @@ impl Leb128Stream<'_> {
 
     /// Reads multiple bytes from the stream
     #[inline]
-    pub fn read_bytes(&mut self) -> Result<&[u8], WasmDecodeErrorType> {
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], WasmDecodeErrorType> {
         self.read_unsigned()
             .and_then(move |size| self.get_bytes(size as usize))
     }
@@ -184,7 +185,7 @@ impl Leb128Stream<'_> {
 
     /// Reads the UTF-8 encoded string from the stream
     #[inline]
-    pub fn get_string(&mut self) -> Result<&str, WasmDecodeErrorType> {
+    pub fn get_string(&mut self) -> Result<&'a str, WasmDecodeErrorType> {
         self.read_bytes()
             .and_then(|v| str::from_utf8(v).map_err(|_| WasmDecodeErrorType::UnexpectedToken))
     }
@@ -204,6 +205,25 @@ impl Leb128Stream<'_> {
         Ok(Some((section_type.into(), start, length)))
     }
 
+    /// Reads the limits of tables and memories
+    pub fn read_limit(&mut self) -> Result<WasmLimit, WasmDecodeErrorType> {
+        let flags = self.read_unsigned()?;
+        let min = self.read_unsigned()?;
+        let max = if (flags & 1) != 0 {
+            Some(self.read_unsigned()?)
+        } else {
+            None
+        };
+        Ok(WasmLimit { min, max })
+    }
+
+    /// Reads a value type
+    #[inline]
+    pub fn read_val_type(&mut self) -> Result<WasmValType, WasmDecodeErrorType> {
+        self.read_byte()
+            .and_then(|v| WasmValType::from_u8(v).ok_or(WasmDecodeErrorType::InvalidType))
+    }
+
     pub fn write_unsigned(vec: &mut Vec<u8>, value: u64) {
         let mut value = value;
         loop {
@@ -242,6 +262,12 @@ impl WasmSection<'_> {
         self.stream.len()
     }
 
+    /// Returns the contents of the section as a new stream
+    #[inline]
+    pub fn stream(&self) -> Leb128Stream<'_> {
+        Leb128Stream::from_slice(self.stream.blob)
+    }
+
     #[inline]
     pub fn custom_section_name(&self) -> Option<String> {
         if self.section_type != WasmSectionType::Custom {
@@ -252,10 +278,16 @@ impl WasmSection<'_> {
         blob.get_string().map(|v| v.to_string()).ok()
     }
 
+    #[inline]
     pub fn write_to_vec(&self, vec: &mut Vec<u8>) {
-        vec.push(self.section_type() as u8);
-        Leb128Stream::write_unsigned(vec, self.stream_size() as u64);
-        vec.extend_from_slice(self.stream.blob);
+        Self::write_payload(vec, self.section_type(), self.stream.blob);
+    }
+
+    /// Writes a section that has the specified contents
+    pub fn write_payload(vec: &mut Vec<u8>, section_type: WasmSectionType, payload: &[u8]) {
+        vec.push(section_type as u8);
+        Leb128Stream::write_unsigned(vec, payload.len() as u64);
+        vec.extend_from_slice(payload);
     }
 }
 
@@ -275,6 +307,7 @@ pub enum WasmSectionType {
     Element,
     Code,
     Data,
+    DataCount,
 }
 
 impl From<u8> for WasmSectionType {
@@ -292,11 +325,198 @@ impl From<u8> for WasmSectionType {
             9 => WasmSectionType::Element,
             10 => WasmSectionType::Code,
             11 => WasmSectionType::Data,
+            12 => WasmSectionType::DataCount,
             _ => WasmSectionType::Custom,
         }
     }
 }
 
+/// WebAssembly value types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmValType {
+    I32 = 0x7F,
+    I64 = 0x7E,
+    F32 = 0x7D,
+    F64 = 0x7C,
+    V128 = 0x7B,
+    FuncRef = 0x70,
+    ExternRef = 0x6F,
+}
+
+impl WasmValType {
+    #[inline]
+    pub const fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0x7F => Some(Self::I32),
+            0x7E => Some(Self::I64),
+            0x7D => Some(Self::F32),
+            0x7C => Some(Self::F64),
+            0x7B => Some(Self::V128),
+            0x70 => Some(Self::FuncRef),
+            0x6F => Some(Self::ExternRef),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WasmValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match *self {
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::V128 => "v128",
+            Self::FuncRef => "funcref",
+            Self::ExternRef => "externref",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Function signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmType {
+    pub params: Vec<WasmValType>,
+    pub results: Vec<WasmValType>,
+}
+
+impl WasmType {
+    pub fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        if stream.read_byte()? != 0x60 {
+            return Err(WasmDecodeErrorType::UnexpectedToken);
+        }
+        let n_params = stream.read_unsigned()? as usize;
+        let params = (0..n_params)
+            .map(|_| stream.read_val_type())
+            .collect::<Result<Vec<_>, _>>()?;
+        let n_results = stream.read_unsigned()? as usize;
+        let results = (0..n_results)
+            .map(|_| stream.read_val_type())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { params, results })
+    }
+}
+
+impl fmt::Display for WasmType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for (index, param) in self.params.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        f.write_str(") -> ")?;
+        match self.results.as_slice() {
+            [] => f.write_str("nil"),
+            [result] => write!(f, "{}", result),
+            results => {
+                f.write_str("(")?;
+                for (index, result) in results.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", result)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Limits of tables and memories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmLimit {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl fmt::Display for WasmLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "initial={}", self.min)?;
+        if let Some(max) = self.max {
+            write!(f, " max={}", max)?;
+        }
+        Ok(())
+    }
+}
+
+/// Import entry
+#[derive(Debug, Clone)]
+pub struct WasmImport {
+    pub module: String,
+    pub name: String,
+    pub desc: WasmImportDesc,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WasmImportDesc {
+    Function(u32),
+    Table(WasmValType, WasmLimit),
+    Memory(WasmLimit),
+    Global(WasmValType, bool),
+}
+
+impl WasmImport {
+    pub fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        let module = stream.get_string()?.to_string();
+        let name = stream.get_string()?.to_string();
+        let desc = match stream.read_byte()? {
+            0 => WasmImportDesc::Function(stream.read_unsigned()? as u32),
+            1 => {
+                let ref_type = stream.read_val_type()?;
+                WasmImportDesc::Table(ref_type, stream.read_limit()?)
+            }
+            2 => WasmImportDesc::Memory(stream.read_limit()?),
+            3 => {
+                let val_type = stream.read_val_type()?;
+                WasmImportDesc::Global(val_type, stream.read_byte()? != 0)
+            }
+            _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+        };
+        Ok(Self { module, name, desc })
+    }
+}
+
+/// Export entry
+#[derive(Debug, Clone)]
+pub struct WasmExport {
+    pub name: String,
+    pub kind: WasmExportKind,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmExportKind {
+    Function = 0,
+    Table,
+    Memory,
+    Global,
+}
+
+impl WasmExport {
+    pub fn from_stream(stream: &mut Leb128Stream) -> Result<Self, WasmDecodeErrorType> {
+        let name = stream.get_string()?.to_string();
+        let kind = match stream.read_byte()? {
+            0 => WasmExportKind::Function,
+            1 => WasmExportKind::Table,
+            2 => WasmExportKind::Memory,
+            3 => WasmExportKind::Global,
+            _ => return Err(WasmDecodeErrorType::UnexpectedToken),
+        };
+        let index = stream.read_unsigned()? as u32;
+        Ok(Self { name, kind, index })
+    }
+
+    pub fn write_to_vec(&self, vec: &mut Vec<u8>) {
+        Leb128Stream::write_unsigned(vec, self.name.len() as u64);
+        vec.extend_from_slice(self.name.as_bytes());
+        vec.push(self.kind as u8);
+        Leb128Stream::write_unsigned(vec, self.index as u64);
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum WasmDecodeErrorType {
     UnexpectedEof,